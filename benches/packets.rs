@@ -0,0 +1,11 @@
+// Times ray packets against tracing the same camera rays one at a time.
+// Run with cargo bench.
+use feoray::bench::{glass_grid, packets};
+
+fn main() {
+    for n in [4, 16] {
+        let (packed, single) = packets(&format!("glass-grid-{n}"), &glass_grid(n));
+        println!("{packed}");
+        println!("{single}");
+    }
+}
@@ -0,0 +1,118 @@
+use crate::core::{Intersections, RayPacket, RenderSettings, LANES};
+use crate::scene::Scene;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// How long a scene took to render, and how many rays it traced.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BenchResult {
+    pub name: String,
+    pub pixels: usize,
+    pub rays: u64,
+    pub elapsed: Duration
+}
+
+impl BenchResult {
+    pub fn rays_per_second(&self) -> f64 {
+        let secs = self.elapsed.as_secs_f64();
+        if secs > 0.0 {
+            self.rays as f64 / secs
+        } else {
+            0.0
+        }
+    }
+}
+
+impl fmt::Display for BenchResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:<14} {:>8} px {:>10} rays {:>8.3} s {:>8.3} Mrays/s",
+            self.name,
+            self.pixels,
+            self.rays,
+            self.elapsed.as_secs_f64(),
+            self.rays_per_second() / 1.0e6
+        )
+    }
+}
+
+/// Renders a scene with the given settings and times it. The time covers the
+/// render only, not building the scene.
+pub fn run(name: &str, scene: Scene, settings: &RenderSettings) -> BenchResult {
+    let Scene { camera, world } = scene;
+    let mut rays = 0;
+    let start = Instant::now();
+    camera.render_with_progress(world, settings, |progress| rays = progress.rays);
+
+    BenchResult {
+        name: name.to_string(),
+        pixels: camera.hsize * camera.vsize,
+        rays,
+        elapsed: start.elapsed()
+    }
+}
+
+/// Intersects every camera ray of a scene with its world, in packets of LANES
+/// rays and then one ray at a time, and times both, nothing else, to see what
+/// packets are worth. Only spheres and planes are done lane by lane, so scenes
+/// made of them show the difference best.
+pub fn packets(name: &str, scene: &Scene) -> (BenchResult, BenchResult) {
+    let Scene { camera, world } = scene;
+    let rays: Vec<_> = (0..camera.vsize)
+        .flat_map(|py| (0..camera.hsize).map(move |px| (px, py)))
+        .map(|(px, py)| camera.ray_for_pixel(px, py))
+        .collect();
+    let result = |kind: &str, elapsed| BenchResult {
+        name: format!("{name} ({kind})"),
+        pixels: rays.len(),
+        rays: rays.len() as u64,
+        elapsed
+    };
+
+    let mut xs: [Intersections; LANES] = Default::default();
+    let start = Instant::now();
+    for chunk in rays.chunks(LANES) {
+        world.intersect_packet(&RayPacket::new(chunk), &mut xs);
+    }
+    let packed = result("packets", start.elapsed());
+
+    let start = Instant::now();
+    for ray in &rays {
+        std::hint::black_box(world.intersect(ray));
+    }
+    let single = result("single rays", start.elapsed());
+
+    (packed, single)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bench::cornell_box;
+
+    #[test]
+    fn every_ray_is_counted() {
+        let Scene { camera, world } = cornell_box();
+        let camera = camera.preview(16);
+        let settings = RenderSettings::for_world(&world).with_threads(2);
+        let pixels = camera.hsize * camera.vsize;
+        let result = run("cornell-box", Scene { camera, world }, &settings);
+
+        assert_eq!(result.pixels, pixels);
+        // A camera ray and at least one more ray for every pixel, as they all hit a wall.
+        assert!(result.rays >= 2 * pixels as u64);
+        assert!(result.to_string().starts_with("cornell-box"));
+    }
+
+    #[test]
+    fn packets_and_single_rays_trace_the_same_rays() {
+        let Scene { camera, world } = cornell_box();
+        let scene = Scene { camera: camera.preview(16), world };
+        let (packed, single) = packets("cornell-box", &scene);
+
+        assert_eq!(packed.rays, (scene.camera.hsize * scene.camera.vsize) as u64);
+        assert_eq!(packed.rays, single.rays);
+        assert!(packed.to_string().starts_with("cornell-box (packets)"));
+    }
+}
@@ -0,0 +1,167 @@
+use crate::Float;
+use crate::core::{Camera, Canvas, RenderSettings, World};
+#[cfg(feature = "image")]
+use image::ImageResult;
+use std::path::Path;
+
+/// Renders a range of frames from an animated camera and world, writing each
+/// one to a numbered image file.
+///
+/// Frame n is rendered at time n / fps, so objects keyed at 0.0 and 1.0 with
+/// 24 fps take 24 frames to complete their movement. The camera's shutter is
+/// relative to the start of each frame.
+///
+/// # Example
+///
+/// ```ignore
+/// let anim = Animation::new(1, 48, 24.0)
+///     .with_output("renders", "turntable", "png");
+///
+/// // renders/turntable_0001.png ... renders/turntable_0048.png
+/// anim.render(&cam, &world, &settings).unwrap();
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Animation {
+    pub first_frame: usize,
+    pub last_frame: usize,
+    pub fps: Float,
+    pub directory: String,
+    pub prefix: String,
+    pub extension: String
+}
+
+impl Animation {
+    /// Frames from first_frame to last_frame inclusive, written to
+    /// frame_0001.png etc. in the working directory.
+    pub fn new(first_frame: usize, last_frame: usize, fps: Float) -> Self {
+        Animation {
+            first_frame,
+            last_frame,
+            fps,
+            directory: String::from("."),
+            prefix: String::from("frame"),
+            extension: String::from("png")
+        }
+    }
+
+    /// Changes where frames are written and what they are called. The image
+    /// format is deduced from the extension.
+    pub fn with_output(mut self, directory: &str, prefix: &str, extension: &str) -> Self {
+        self.directory = directory.to_string();
+        self.prefix = prefix.to_string();
+        self.extension = extension.to_string();
+
+        self
+    }
+
+    /// Number of frames in the sequence.
+    pub fn frame_count(&self) -> usize {
+        (self.last_frame + 1).saturating_sub(self.first_frame)
+    }
+
+    /// Moment in time at which a frame starts.
+    pub fn frame_time(&self, frame: usize) -> Float {
+        frame as Float / self.fps
+    }
+
+    /// File name for a frame, numbered to at least four digits.
+    pub fn frame_path(&self, frame: usize) -> String {
+        let name = format!("{}_{:04}.{}", self.prefix, frame, self.extension);
+        Path::new(&self.directory).join(name).to_string_lossy().into_owned()
+    }
+
+    /// Renders a single frame without writing it.
+    pub fn render_frame(&self, camera: &Camera, world: &World, settings: &RenderSettings, frame: usize) -> Canvas {
+        let time = self.frame_time(frame);
+        let camera = camera
+            .at_time(time)
+            .with_shutter(time + camera.shutter_open, time + camera.shutter_close);
+
+        camera.render_with(world.clone(), settings)
+    }
+
+    #[cfg(feature = "image")]
+    /// Renders every frame in turn and writes it out, stopping at the first
+    /// frame that can't be saved. Returns the paths of the written frames.
+    pub fn render(&self, camera: &Camera, world: &World, settings: &RenderSettings) -> ImageResult<Vec<String>> {
+        let mut paths = vec![];
+        for frame in self.first_frame..=self.last_frame {
+            let path = self.frame_path(frame);
+            self.render_frame(camera, world, settings, frame).export(&path)?;
+            paths.push(path);
+        }
+
+        Ok(paths)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{point, Colour, Transform};
+    use crate::lights::PointLight;
+    use crate::materials::Material;
+    use crate::primitives::Object;
+    use nalgebra::Matrix4;
+    use crate::consts::PI;
+
+    #[test]
+    fn frames_are_numbered_and_timed() {
+        let anim = Animation::new(1, 48, 24.0)
+            .with_output("renders", "turntable", "png");
+
+        assert_eq!(anim.frame_count(), 48);
+        assert_eq!(anim.frame_time(12), 0.5);
+        assert!(anim.frame_path(7).ends_with("turntable_0007.png"));
+        assert!(anim.frame_path(7).starts_with("renders"));
+    }
+
+    #[test]
+    fn objects_are_evaluated_per_frame() {
+        let cam = Camera::new(1, 1, PI/6.0)
+            .with_transform(Matrix4::translate(0.0, 0.0, -5.0));
+        let ball = Object::new_sphere()
+            .with_material(Material::null().with_ambient(1.0))
+            .with_motion(Matrix4::translate(4.0, 0.0, 0.0));
+        let w = World::default()
+            .with_light(PointLight::new(Colour::white(), point(0.0, 0.0, -5.0)))
+            .with_object(ball);
+        let anim = Animation::new(0, 2, 2.0);
+        let settings = RenderSettings::default();
+
+        assert_eq!(anim.render_frame(&cam, &w, &settings, 0).read_pix(0, 0), Colour::white());
+        assert_eq!(anim.render_frame(&cam, &w, &settings, 2).read_pix(0, 0), Colour::black());
+    }
+
+    #[test]
+    fn cameras_are_evaluated_per_frame() {
+        let cam = Camera::new(1, 1, PI/6.0)
+            .with_transform_at(0.0, Matrix4::translate(0.0, 0.0, -5.0))
+            .with_transform_at(1.0, Matrix4::translate(4.0, 0.0, -5.0));
+        let ball = Object::new_sphere()
+            .with_material(Material::null().with_ambient(1.0));
+        let w = World::default()
+            .with_light(PointLight::new(Colour::white(), point(0.0, 0.0, -5.0)))
+            .with_object(ball);
+        let anim = Animation::new(0, 1, 1.0);
+        let settings = RenderSettings::default();
+
+        assert_eq!(anim.render_frame(&cam, &w, &settings, 0).read_pix(0, 0), Colour::white());
+        assert_eq!(anim.render_frame(&cam, &w, &settings, 1).read_pix(0, 0), Colour::black());
+    }
+
+    #[test]
+    #[cfg(feature = "image")]
+    fn frames_are_written_to_numbered_files() {
+        let dir = std::env::temp_dir().join("feoray_animation_test");
+        std::fs::create_dir_all(&dir).unwrap();
+        let anim = Animation::new(3, 4, 24.0)
+            .with_output(dir.to_str().unwrap(), "frame", "png");
+        let paths = anim.render(&Camera::new(2, 2, PI/2.0), &World::default(), &RenderSettings::default()).unwrap();
+
+        assert_eq!(paths.len(), 2);
+        assert!(paths.iter().all(|p| Path::new(p).exists()));
+        assert!(paths[1].ends_with("frame_0004.png"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
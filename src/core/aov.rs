@@ -0,0 +1,277 @@
+use crate::to_f32;
+use crate::core::{Camera, Canvas, Colour, Intersections, RenderSettings, World};
+use std::collections::BTreeMap;
+#[cfg(feature = "image")]
+use image::ImageResult;
+#[cfg(feature = "image")]
+use std::path::Path;
+
+/// Auxiliary buffers (AOVs) from a render, each a canvas the size of the
+/// image. They're found from one ray through the centre of each pixel, and
+/// hold raw values rather than colours to look at, so save them as EXR to keep
+/// them intact. Pixels where nothing was hit are black in every pass.
+#[derive(Debug, PartialEq)]
+pub struct Aovs {
+    /// Distance from the camera to the nearest hit, in each channel.
+    pub depth: Canvas,
+    /// World space surface normal, with x, y and z as red, green and blue,
+    /// from -1.0 to 1.0.
+    pub normal: Canvas,
+    /// Surface colour before lighting, from the material's pattern.
+    pub albedo: Canvas,
+    /// Colour standing for the ID of the object that was hit (see
+    /// World::object_id() and id_colour()).
+    pub object_id: Canvas,
+    /// Share of the lights shaded at each hit that were blocked, from 0.0
+    /// (fully lit) to 1.0 (fully shadowed).
+    pub shadow: Canvas,
+    /// Names of the named objects seen in the object ID pass, by ID, so the
+    /// pass can be read back without the scene to hand.
+    pub names: BTreeMap<u32, String>
+}
+
+impl Aovs {
+    /// Each pass with its name, for saving or looping over.
+    pub fn passes(&self) -> [(&'static str, &Canvas); 5] {
+        [
+            ("depth", &self.depth),
+            ("normal", &self.normal),
+            ("albedo", &self.albedo),
+            ("object_id", &self.object_id),
+            ("shadow", &self.shadow)
+        ]
+    }
+
+    #[cfg(feature = "image")]
+    /// Saves each pass next to path, with the pass name added to the file name,
+    /// so "out/frame.exr" gives "out/frame_depth.exr", "out/frame_normal.exr"
+    /// and so on. The format comes from the extension, as for Canvas::export().
+    pub fn export(&self, path: &str) -> ImageResult<()> {
+        let path = Path::new(path);
+        let stem = path.file_stem().map_or_else(String::new, |s| s.to_string_lossy().into_owned());
+        let ext = path.extension().map_or_else(String::new, |e| format!(".{}", e.to_string_lossy()));
+        for (name, canvas) in self.passes() {
+            let file = path.with_file_name(format!("{}_{}{}", stem, name, ext));
+            canvas.export(&file.to_string_lossy())?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Camera {
+    /// Renders the auxiliary buffers for a scene: depth, normals, albedo, object
+    /// IDs and a shadow mask, for compositing or for tracking down odd shading.
+    pub fn render_aovs(&self, world: &World) -> Aovs {
+        let blank = || Canvas::new(self.hsize, self.vsize, Colour::black());
+        let mut aovs = Aovs { depth: blank(), normal: blank(), albedo: blank(), object_id: blank(), shadow: blank(), names: BTreeMap::new() };
+        let mut xs = Intersections::default();
+        for y in 0..self.vsize {
+            for x in 0..self.hsize {
+                if !self.covers(x, y) {
+                    continue;
+                }
+                let ray = self.ray_for_pixel(x, y).with_time(self.shutter_open);
+                world.intersect_into(&ray, &mut xs);
+                // The ID comes from the hit, which points into the world,
+                // rather than from comps, which may hold an animated copy.
+                let (comps, id) = match xs.hit_index() {
+                    Some(i) => (world.prepare_computations(&xs, i, &ray), world.object_id(xs[i].object)),
+                    None => continue
+                };
+                let depth = to_f32(comps.t * ray.direction.magnitude());
+                let n = comps.normal_vec;
+                let lights = world.lights_to_shade(&comps);
+                let blocked: f32 = lights.iter()
+                    .zip(world.shadows_into(&lights, &comps, &mut xs))
+                    .filter(|(_, shadowed)| *shadowed)
+                    .map(|((_, weight), _)| weight)
+                    .sum();
+                let total: f32 = lights.iter().map(|(_, weight)| weight).sum();
+
+                aovs.depth.write_pix(x, y, Colour::grey(depth));
+                aovs.normal.write_pix(x, y, Colour::new(to_f32(n.x), to_f32(n.y), to_f32(n.z)));
+                aovs.albedo.write_pix(x, y, comps.pattern_colour());
+                if let Some(id) = id {
+                    aovs.object_id.write_pix(x, y, id_colour(id));
+                    if let Some(name) = &comps.object.name {
+                        aovs.names.entry(id).or_insert_with(|| name.clone());
+                    }
+                }
+                aovs.shadow.write_pix(x, y, Colour::grey(if total > 0.0 { blocked / total } else { 0.0 }));
+            }
+        }
+
+        aovs
+    }
+}
+
+impl Camera {
+    /// Mask of one object, by its ID: white where the object is seen, black
+    /// elsewhere, and grey along its edges in proportion to the share of each
+    /// pixel's samples that hit it. Objects in front of it cut it out.
+    pub fn render_mask(&self, world: &World, settings: &RenderSettings, id: u32) -> Canvas {
+        self.coverage(world, settings, Some(id))
+            .remove(&id)
+            .unwrap_or_else(|| Canvas::new(self.hsize, self.vsize, Colour::black()))
+    }
+
+    /// Mask of every object seen, as for render_mask(), from a single render.
+    /// Sorted by ID.
+    pub fn render_masks(&self, world: &World, settings: &RenderSettings) -> Vec<(u32, Canvas)> {
+        self.coverage(world, settings, None).into_iter().collect()
+    }
+
+    // Share of each pixel's samples hitting each object (or just the one asked
+    // for), with the same subpixel rays as a render with these settings.
+    fn coverage(&self, world: &World, settings: &RenderSettings, only: Option<u32>) -> BTreeMap<u32, Canvas> {
+        let mut masks = BTreeMap::new();
+        let sampler = settings.seeded_sampler();
+        let mut xs = Intersections::default();
+        for y in 0..self.vsize {
+            for x in 0..self.hsize {
+                if !self.covers(x, y) {
+                    continue;
+                }
+                let offsets = sampler.samples_2d(settings.samples, (y * self.hsize + x) as u64);
+                let share = 1.0 / offsets.len() as f32;
+                for (ox, oy) in offsets {
+                    let ray = self.ray_for_subpixel(x, y, ox, oy).with_time(self.shutter_open);
+                    world.intersect_into(&ray, &mut xs);
+                    let id = match xs.hit().and_then(|hit| world.object_id(hit.object)) {
+                        Some(id) if only.map_or(true, |only| only == id) => id,
+                        _ => continue
+                    };
+                    let mask = masks.entry(id).or_insert_with(|| Canvas::new(self.hsize, self.vsize, Colour::black()));
+                    mask[(x, y)] += Colour::grey(share);
+                }
+            }
+        }
+
+        masks
+    }
+}
+
+/// Bright, well spread colour standing for an object ID. The same ID always
+/// gives the same colour, and neighbouring IDs look nothing alike.
+pub fn id_colour(id: u32) -> Colour {
+    // Hashes the ID so consecutive IDs land far apart.
+    let mut h = (id as u64 + 1).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    h ^= h >> 31;
+    let channel = |shift: u32| 0.2 + 0.8 * ((h >> shift) & 0xff) as f32 / 255.0;
+
+    Colour::new(channel(0), channel(8), channel(16))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{point, vector, Transform};
+    use crate::consts::PI;
+    use crate::lights::PointLight;
+    use crate::primitives::Object;
+    use nalgebra::Matrix4;
+
+    fn camera() -> Camera {
+        Camera::new(11, 11, PI / 2.0)
+            .with_transform(Matrix4::view_transform(point(0.0, 0.0, -5.0), point(0.0, 0.0, 0.0), vector(0.0, 1.0, 0.0)))
+    }
+
+    #[test]
+    fn passes_describe_the_nearest_hit() {
+        let aovs = camera().render_aovs(&World::default_world());
+
+        assert_eq!(aovs.depth.read_pix(5, 5), Colour::grey(4.0));
+        assert_eq!(aovs.normal.read_pix(5, 5), Colour::new(0.0, 0.0, -1.0));
+        assert_eq!(aovs.albedo.read_pix(5, 5), Colour::new(0.8, 1.0, 0.6));
+        assert_eq!(aovs.object_id.read_pix(5, 5), id_colour(0));
+        assert_eq!(aovs.shadow.read_pix(5, 5), Colour::black());
+        for (_, pass) in aovs.passes() {
+            assert_eq!(pass.read_pix(0, 0), Colour::black());
+        }
+    }
+
+    #[test]
+    fn shadow_mask_shows_blocked_lights() {
+        // Two lights above a floor, one of them behind a ball.
+        let w = World::default()
+            .with_light(PointLight::new(Colour::white(), point(0.0, 10.0, 0.0)))
+            .with_light(PointLight::new(Colour::white(), point(0.0, 10.0, -10.0)))
+            .with_object(Object::new_plane().with_transform(Matrix4::translate(0.0, -1.0, 0.0)))
+            .with_object(Object::new_sphere().with_transform(Matrix4::translate(0.0, 1.0, 0.0)));
+        let cam = Camera::new(1, 1, PI / 4.0)
+            .with_transform(Matrix4::view_transform(point(0.0, 1.0, -3.0), point(0.0, -1.0, 0.0), vector(0.0, 1.0, 0.0)));
+        let aovs = cam.render_aovs(&w);
+
+        assert_eq!(aovs.shadow.read_pix(0, 0), Colour::grey(0.5));
+        assert_eq!(aovs.object_id.read_pix(0, 0), id_colour(0));
+    }
+
+    #[test]
+    fn objects_keep_their_own_ids() {
+        let mut w = World::default_world();
+        w.objects[1].with_id(7);
+        let aovs = camera().render_aovs(&w);
+
+        // The smaller sphere, inside the larger one, is never seen.
+        assert_eq!(aovs.object_id.read_pix(5, 5), id_colour(0));
+        assert_eq!(w.object_id(&w.objects[1]), Some(7));
+        assert_eq!(w.object_id(&Object::new_plane()), None);
+    }
+
+    #[test]
+    fn identical_objects_get_their_own_ids() {
+        let w = World::default()
+            .with_object(Object::new_sphere())
+            .with_object(Object::new_sphere());
+
+        assert_eq!(w.object_id(&w.objects[0]), Some(0));
+        assert_eq!(w.object_id(&w.objects[1]), Some(1));
+        assert_eq!(w.object_id(&w.objects[1].clone()), None);
+    }
+
+    #[test]
+    fn named_objects_are_listed_with_their_ids() {
+        let mut w = World::default_world();
+        w.objects[0].with_name("outer");
+        w.objects[1].with_name("inner");
+        let aovs = camera().render_aovs(&w);
+
+        // Only objects that were seen are listed.
+        assert_eq!(aovs.names.into_iter().collect::<Vec<_>>(), vec![(0, String::from("outer"))]);
+    }
+
+    #[test]
+    fn masks_pick_out_one_object() {
+        let w = World::default()
+            .with_object(Object::new_sphere().with_id(3))
+            .with_object(Object::new_plane().with_transform(Matrix4::translate(0.0, -1.0, 0.0)).with_id(9));
+        let settings = RenderSettings::default().with_samples(4);
+        let mask = camera().render_mask(&w, &settings, 3);
+        let masks = camera().render_masks(&w, &settings);
+
+        assert_eq!(mask.read_pix(5, 5), Colour::white());
+        assert_eq!(mask.read_pix(0, 0), Colour::black());
+        assert!(mask.pixels.iter().any(|c| c.r > 0.0 && c.r < 1.0));
+        assert_eq!(masks.iter().map(|(id, _)| *id).collect::<Vec<_>>(), vec![3, 9]);
+        assert_eq!(masks[0].1, mask);
+        assert_eq!(masks[1].1.read_pix(5, 10), Colour::white());
+    }
+
+    #[test]
+    fn id_colours_are_stable_and_distinct() {
+        assert_eq!(id_colour(3), id_colour(3));
+        assert_ne!(id_colour(0), id_colour(1));
+    }
+
+    #[test]
+    #[cfg(feature = "image")]
+    fn passes_are_saved_by_name() {
+        let dir = std::env::temp_dir().join("feoray-aovs");
+        std::fs::create_dir_all(&dir).unwrap();
+        camera().render_aovs(&World::default_world()).export(&dir.join("frame.exr").to_string_lossy()).unwrap();
+
+        assert!(dir.join("frame_depth.exr").exists());
+        assert!(dir.join("frame_shadow.exr").exists());
+    }
+}
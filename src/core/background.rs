@@ -0,0 +1,178 @@
+use crate::Float;
+use crate::core::{Canvas, Colour};
+use crate::materials::texture::sample;
+use crate::materials::{Filter, Wrap};
+use nalgebra::Vector4;
+use crate::consts::PI;
+use std::sync::Arc;
+
+/// What a ray sees when it misses everything in the world.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Background {
+    /// The same colour in every direction.
+    Solid(Colour),
+    /// Sky blending from the horizon colour straight ahead to the zenith colour
+    /// overhead. Anything below the horizon gets the horizon colour.
+    Gradient { horizon: Colour, zenith: Colour },
+    /// Equirectangular image wrapped around the whole scene, laid out the same
+    /// way as an equirectangular camera render, so one can be used as the
+    /// environment of another.
+    Environment(Arc<Canvas>),
+    /// Skybox of six square images, one for each face of a cube around the
+    /// scene, in the order +x, -x, +y, -y, +z, -z. Faces are laid out as
+    /// they're seen from inside the cube, with the top of the side faces
+    /// towards +y, the top of the +y face towards -z and the top of the -y
+    /// face towards +z. Colours are blended between pixels.
+    CubeMap(Arc<[Canvas; 6]>)
+}
+
+impl Background {
+    /// Vertical sky gradient.
+    pub fn gradient(horizon: Colour, zenith: Colour) -> Self {
+        Background::Gradient { horizon, zenith }
+    }
+
+    /// Environment map from an equirectangular image. An empty image shows
+    /// black, as an empty texture does.
+    pub fn environment(image: Canvas) -> Self {
+        Background::Environment(Arc::new(image))
+    }
+
+    /// Skybox from six cube faces, in the order +x, -x, +y, -y, +z, -z.
+    pub fn cube_map(faces: [Canvas; 6]) -> Self {
+        Background::CubeMap(Arc::new(faces))
+    }
+
+    /// Colour seen looking along a direction.
+    pub fn colour_at(&self, direction: &Vector4<Float>) -> Colour {
+        let d = direction.normalize();
+        match self {
+            Background::Solid(colour) => *colour,
+            Background::Gradient { horizon, zenith } => {
+                let t = d.y.max(0.0);
+                *horizon * (1.0 - t) + *zenith * t
+            },
+            Background::Environment(image) if image.width == 0 || image.height == 0 => Colour::black(),
+            Background::Environment(image) => {
+                let longitude = (-d.x).atan2(-d.z);
+                let latitude = d.y.clamp(-1.0, 1.0).asin();
+                let u = longitude / (2.0 * PI) + 0.5;
+                let v = 0.5 - latitude / PI;
+                let x = ((u * image.width as Float) as usize).min(image.width - 1);
+                let y = ((v * image.height as Float) as usize).min(image.height - 1);
+                image.read_pix(x, y)
+            },
+            Background::CubeMap(faces) => {
+                let (face, s, t) = cube_face(&d);
+                // Clamping keeps the blend from reaching across to the far
+                // side of the face at its edges.
+                sample(&faces[face], s, 1.0 - t, Wrap::Clamp, Filter::Bilinear)
+            }
+        }
+    }
+}
+
+// Face of a cube map seen along a direction, with how far across it is (s)
+// and down it (t), from 0.0 to 1.0.
+fn cube_face(d: &Vector4<Float>) -> (usize, Float, Float) {
+    let (ax, ay, az) = (d.x.abs(), d.y.abs(), d.z.abs());
+    let (face, sc, tc, ma) = if ax >= ay && ax >= az {
+        if d.x > 0.0 { (0, -d.z, -d.y, ax) } else { (1, d.z, -d.y, ax) }
+    } else if ay >= az {
+        if d.y > 0.0 { (2, d.x, d.z, ay) } else { (3, d.x, -d.z, ay) }
+    } else if d.z > 0.0 {
+        (4, d.x, -d.y, az)
+    } else {
+        (5, -d.x, -d.y, az)
+    };
+
+    (face, (sc / ma + 1.0) / 2.0, (tc / ma + 1.0) / 2.0)
+}
+
+impl Default for Background {
+    fn default() -> Self {
+        Background::Solid(Colour::black())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::vector;
+
+    #[test]
+    fn default_background_is_black() {
+        assert_eq!(Background::default().colour_at(&vector(0.0, 1.0, 0.0)), Colour::black());
+    }
+
+    #[test]
+    fn gradient_blends_from_horizon_to_zenith() {
+        let sky = Background::gradient(Colour::white(), Colour::blue());
+
+        assert_eq!(sky.colour_at(&vector(0.0, 0.0, 1.0)), Colour::white());
+        assert_eq!(sky.colour_at(&vector(0.0, 3.0, 0.0)), Colour::blue());
+        assert_eq!(sky.colour_at(&vector(0.0, 1.0, 1.0)).to_5dp(), Colour::new(0.29289, 0.29289, 1.0));
+        assert_eq!(sky.colour_at(&vector(0.0, -1.0, 0.0)), Colour::white());
+    }
+
+    #[test]
+    fn environment_maps_wrap_around_the_scene() {
+        let mut image = Canvas::new(4, 2, Colour::black());
+        image.write_pix(2, 0, Colour::red());
+        image.write_pix(0, 1, Colour::green());
+        let env = Background::environment(image);
+
+        // Straight ahead (-z) is the middle of the image, behind is the edge.
+        assert_eq!(env.colour_at(&vector(0.0, 0.5, -1.0)), Colour::red());
+        assert_eq!(env.colour_at(&vector(0.01, -0.5, 1.0)), Colour::green());
+    }
+
+    // Faces of one colour each, with the top left pixel of the +y face white.
+    fn skybox() -> Background {
+        let colours = [Colour::red(), Colour::cyan(), Colour::green(), Colour::magenta(), Colour::blue(), Colour::yellow()];
+        let mut faces = colours.map(|c| Canvas::new(4, 4, c));
+        faces[2].write_pix(0, 0, Colour::white());
+        Background::cube_map(faces)
+    }
+
+    #[test]
+    fn cube_maps_show_the_face_they_look_at() {
+        let sky = skybox();
+
+        assert_eq!(sky.colour_at(&vector(2.0, 0.5, -0.3)), Colour::red());
+        assert_eq!(sky.colour_at(&vector(-1.0, 0.0, 0.0)), Colour::cyan());
+        assert_eq!(sky.colour_at(&vector(0.0, -1.0, 0.2)), Colour::magenta());
+        assert_eq!(sky.colour_at(&vector(0.1, 0.2, 1.0)), Colour::blue());
+        assert_eq!(sky.colour_at(&vector(0.0, 0.0, -1.0)), Colour::yellow());
+        // Top left of the top face is towards -x and -z.
+        assert_eq!(sky.colour_at(&vector(-0.9, 1.0, -0.9)), Colour::white());
+        assert_eq!(sky.colour_at(&vector(0.9, 1.0, 0.9)), Colour::green());
+    }
+
+    #[test]
+    fn reflections_pick_up_the_skybox() {
+        use crate::core::{point, Ray, World};
+        use crate::lights::PointLight;
+        use crate::materials::Material;
+        use crate::primitives::Object;
+
+        let mirror = Object::new_sphere().with_material(Material::null().with_reflectivity(1.0));
+        let w = World::default()
+            .with_light(PointLight::new(Colour::white(), point(0.0, 10.0, -10.0)))
+            .with_object(mirror)
+            .with_background(skybox());
+        let ray = Ray::new(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+
+        // Looking at +z, the mirror reflects the sky behind the camera.
+        assert_eq!(w.colour_at(&ray, 5), Colour::yellow());
+        assert_eq!(w.colour_at(&ray, 0), Colour::black());
+    }
+
+    #[test]
+    fn empty_environment_maps_are_black() {
+        let sky = Background::environment(Canvas::new(0, 4, Colour::white()));
+
+        assert_eq!(sky.colour_at(&vector(0.0, 0.0, 1.0)), Colour::black());
+        assert_eq!(Background::environment(Canvas::new(4, 0, Colour::white())).colour_at(&vector(1.0, 0.0, 0.0)), Colour::black());
+    }
+}
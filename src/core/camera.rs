@@ -1,28 +1,107 @@
-use crate::core::{canvas, point, Canvas, Ray, World};
-use nalgebra::Matrix4;
+use crate::{to_f32, Float, FeorayError};
+use crate::core::{canvas, point, vector, CameraPath, Canvas, Colour, Integrator, Intersections, Keyframes, Progress, Ray, RayPacket, RenderSettings, Rng, Transform, World, LANES};
+use crate::core::matrix::inverse;
+use crate::core::progress::{take_rays, ProgressTracker};
+use nalgebra::{Matrix4, Vector4};
+use crate::consts::PI;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+
+// Offsets for the RNG streams used for shutter times and path tracing, to keep
+// them independent of the sampler streams and of each other.
+const SHUTTER_STREAM: u64 = 1 << 40;
+const PATH_STREAM: u64 = 2 << 40;
+
+/// How rays are fanned out from the camera.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Projection {
+    /// Standard pinhole camera. Field of view spans the longest side of the canvas.
+    Perspective,
+    /// Equidistant fisheye. Field of view spans the diameter of the image circle,
+    /// so use PI for a 180° dome. Pixels outside the circle are left black.
+    Fisheye,
+    /// Full 360° x 180° panorama, suitable for environment maps and VR. Field of
+    /// view is ignored.
+    Equirectangular
+}
+
+/// A rectangular bucket of pixels, from (x0, y0) up to but not including (x1, y1).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tile {
+    pub x0: usize,
+    pub y0: usize,
+    pub x1: usize,
+    pub y1: usize
+}
+
+impl Tile {
+    pub fn new(x0: usize, y0: usize, x1: usize, y1: usize) -> Self {
+        Tile { x0, y0, x1, y1 }
+    }
+
+    /// Number of pixels in the tile.
+    pub fn len(&self) -> usize {
+        (self.x1 - self.x0) * (self.y1 - self.y0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Makes the camera space ray for a pixel, given its column and row and the
+/// offset into it, from (0.0, 0.0) at its top left corner to (1.0, 1.0).
+pub type RayGenerator = fn(usize, usize, (Float, Float)) -> Ray;
 
 #[derive(Debug, Clone, Copy)]
 pub struct Camera {
     pub hsize: usize,
     pub vsize: usize,
-    pub fov: f64,
-    pub px_size: f64,
-    pub transform: Matrix4<f64>,
-    half_width: f64,
-    half_height:f64
+    pub fov: Float,
+    pub px_size: Float,
+    pub transform: Matrix4<Float>,
+    pub inverse_transform: Matrix4<Float>,
+    pub projection: Projection,
+    pub shutter_open: Float,
+    pub shutter_close: Float,
+    pub keyframes: Keyframes,
+    pub path: Option<CameraPath>,
+    /// Brightness of the image, in stops, applied before gamma. Each stop up
+    /// doubles the light, and 0.0 leaves it as it is.
+    pub exposure: Float,
+    /// Radial lens distortion coefficients (k1, k2), as in the Brown-Conrady
+    /// model. Positive bows straight lines out like a wide lens (barrel), and
+    /// negative pinches them in (pincushion).
+    pub distortion: (Float, Float),
+    /// How much of the natural cos⁴ falloff of a real lens darkens the edges
+    /// of the frame, from 0.0 for none to 1.0 for all of it.
+    pub vignetting: Float,
+    /// Lens shift, moving the frame right and up by these shares of its width
+    /// and height without turning the camera, so verticals stay parallel.
+    pub shift: (Float, Float),
+    /// Lens tilt about the camera's x axis and swing about its y axis, in
+    /// radians, turning the plane of focus away from square on.
+    pub tilt: (Float, Float),
+    /// Replaces the projection, for ones feoray doesn't have. See
+    /// with_ray_generator().
+    pub ray_generator: Option<RayGenerator>,
+    origin: Vector4<Float>,
+    pub(crate) half_width: Float,
+    half_height:Float
 }
 
 impl Camera {
     /// Initialise new camera.
-    pub fn new(hsize: usize, vsize: usize, fov: f64) -> Self {
+    pub fn new(hsize: usize, vsize: usize, fov: Float) -> Self {
         let half_view = (fov/2.0).tan();
-        let aspect = hsize as f64/vsize as f64;
+        let aspect = hsize as Float/vsize as Float;
         let (half_width, half_height) = if aspect >= 1.0 {
             (half_view, half_view / aspect)
         } else {
             (half_view * aspect, half_view)
         };
-        let px_size = (half_width * 2.0) / hsize as f64;
+        let px_size = (half_width * 2.0) / hsize as Float;
         let transform = Matrix4::identity();
         Self {
             hsize,
@@ -30,53 +109,700 @@ impl Camera {
             fov,
             px_size,
             transform,
+            inverse_transform: transform,
+            projection: Projection::Perspective,
+            shutter_open: 0.0,
+            shutter_close: 0.0,
+            keyframes: Keyframes::new(),
+            path: None,
+            exposure: 0.0,
+            distortion: (0.0, 0.0),
+            vignetting: 0.0,
+            shift: (0.0, 0.0),
+            tilt: (0.0, 0.0),
+            ray_generator: None,
+            origin: point(0.0, 0.0, 0.0),
             half_width,
             half_height
         }
     }
 
-    /// Creates a single ray for the specified pixel.
+    /// Creates a single ray through the centre of the specified pixel.
     pub fn ray_for_pixel(&self, px: usize, py: usize) -> Ray {
-        let world_x = self.half_width - (px as f64 + 0.5) * self.px_size;
-        let world_y = self.half_height - (py as f64 + 0.5) * self.px_size;
-        let mut pixel = self.transform.try_inverse().unwrap() * point(world_x, world_y, -1.0);
-        let mut origin = self.transform.try_inverse().unwrap() * point(0.0, 0.0, 0.0);
+        self.ray_for_subpixel(px, py, 0.5, 0.5)
+    }
+
+    /// Creates a ray through the specified pixel, offset from its top left
+    /// corner by (ox, oy), where both offsets are between 0.0 and 1.0.
+    pub fn ray_for_subpixel(&self, px: usize, py: usize, ox: Float, oy: Float) -> Ray {
+        if let Some(generate) = self.ray_generator {
+            let mut ray = generate(px, py, (ox, oy)).transform(self.inverse_transform);
+            ray.direction = ray.direction.normalize();
+            return ray;
+        }
+        let (fx, fy) = (px as Float + ox, py as Float + oy);
+        match self.projection {
+            Projection::Perspective => self.perspective_ray(fx, fy),
+            Projection::Fisheye => self.camera_ray(self.fisheye_direction(fx, fy)),
+            Projection::Equirectangular => self.camera_ray(self.equirect_direction(fx, fy))
+        }
+    }
+
+    /// Angle, in radians, a single pixel spans near the centre of the image.
+    /// Camera rays widen by this much per unit of distance, which is how big a
+    /// patch of a pattern each of them sees.
+    pub fn pixel_spread(&self) -> Float {
+        match self.projection {
+            Projection::Perspective => self.px_size,
+            Projection::Fisheye => self.fov / self.hsize.min(self.vsize) as Float,
+            Projection::Equirectangular => 2.0 * PI / self.hsize as Float
+        }
+    }
+
+    // Width of camera rays' cones, if the settings filter patterns.
+    fn ray_spread(&self, settings: &RenderSettings) -> Float {
+        if settings.pattern_filtering { self.pixel_spread() } else { 0.0 }
+    }
+
+    /// Tests if the pixel falls within the area covered by the projection.
+    pub fn covers(&self, px: usize, py: usize) -> bool {
+        match self.projection {
+            Projection::Fisheye => {
+                let (dx, dy) = self.fisheye_offset(px as Float + 0.5, py as Float + 0.5);
+                dx.powi(2) + dy.powi(2) <= 1.0
+            },
+            _ => true
+        }
+    }
+
+    fn perspective_ray(&self, fx: Float, fy: Float) -> Ray {
+        let (world_x, world_y) = self.film_point(fx, fy);
+        let mut pixel = self.inverse_transform * point(world_x, world_y, -1.0);
         pixel.w = 1.0; // on second thought, assigning the correction may be easier on memory than to_point()
-        origin.w = 1.0;
-        let direction = (pixel - origin).normalize();
+        let direction = (pixel - self.origin).normalize();
+
+        Ray::new(self.origin, direction)
+    }
+
+    // Where a point on the image is on the film, one unit in front of the
+    // camera, after the lens has been shifted and has distorted it.
+    fn film_point(&self, fx: Float, fy: Float) -> (Float, Float) {
+        // Camera space x is to the left of the image.
+        let x = self.half_width * (1.0 - 2.0 * self.shift.0) - fx * self.px_size;
+        let y = self.half_height * (1.0 + 2.0 * self.shift.1) - fy * self.px_size;
+        let (k1, k2) = self.distortion;
+        if k1 == 0.0 && k2 == 0.0 {
+            return (x, y);
+        }
+        // Radius is 1.0 at the middle of the longer side of the frame.
+        let half = self.half_width.max(self.half_height);
+        let r2 = (x * x + y * y) / (half * half);
+        let scale = 1.0 + k1 * r2 + k2 * r2 * r2;
+
+        (x * scale, y * scale)
+    }
+
+    /// How much of the light through a point on the image, as for
+    /// ray_for_subpixel(), makes it past the vignetting. Always 1.0 for
+    /// projections other than perspective, and with a ray generator.
+    pub fn vignette_at(&self, fx: Float, fy: Float) -> f32 {
+        if self.vignetting == 0.0 || self.projection != Projection::Perspective || self.ray_generator.is_some() {
+            return 1.0;
+        }
+        let (x, y) = self.film_point(fx, fy);
+        // The film is a unit away, so this is the cosine of the angle off axis.
+        let cos = 1.0 / (x * x + y * y + 1.0).sqrt();
+
+        to_f32(1.0 - self.vignetting * (1.0 - cos.powi(4)))
+    }
+
+    // Converts a direction in camera space into a world space ray.
+    fn camera_ray(&self, direction: Vector4<Float>) -> Ray {
+        let mut direction = self.inverse_transform * direction;
+        direction.w = 0.0;
+
+        Ray::new(self.origin, direction.normalize())
+    }
+
+    // Pixel offset from the centre of the image circle, where the circle has a
+    // radius of 1.0. Positive x is to the left, to match the perspective camera.
+    fn fisheye_offset(&self, fx: Float, fy: Float) -> (Float, Float) {
+        let radius = self.hsize.min(self.vsize) as Float / 2.0;
+        let dx = (self.hsize as Float / 2.0 - fx) / radius;
+        let dy = (self.vsize as Float / 2.0 - fy) / radius;
+        (dx, dy)
+    }
 
-        Ray::new(origin, direction)
+    fn fisheye_direction(&self, fx: Float, fy: Float) -> Vector4<Float> {
+        let (dx, dy) = self.fisheye_offset(fx, fy);
+        let r = (dx.powi(2) + dy.powi(2)).sqrt();
+        if r == 0.0 {
+            return vector(0.0, 0.0, -1.0);
+        }
+        let theta = r * self.fov / 2.0;
+        vector(dx / r * theta.sin(), dy / r * theta.sin(), -theta.cos())
+    }
+
+    fn equirect_direction(&self, fx: Float, fy: Float) -> Vector4<Float> {
+        let longitude = (fx / self.hsize as Float - 0.5) * 2.0 * PI;
+        let latitude = (0.5 - fy / self.vsize as Float) * PI;
+        vector(
+            -longitude.sin() * latitude.cos(),
+            latitude.sin(),
+            -longitude.cos() * latitude.cos()
+        )
     }
 
     /// Routine to render a scene to a canvas. Canvas can then be exported to
     /// an image file.
     pub fn render(&self, world: World) -> Canvas {
-        let mut canvas = canvas(self.hsize, self.vsize);
-        for y in 0..self.vsize {
-            for x in 0..self.hsize {
-                let ray = self.ray_for_pixel(x, y);
-                let colour = world.colour_at(&ray, 1);
-                canvas.write_pix(x, y, colour);
+        let settings = RenderSettings::for_world(&world);
+        self.render_with(world, &settings)
+    }
+
+    /// Renders a scene using the given render settings.
+    pub fn render_with(&self, world: World, settings: &RenderSettings) -> Canvas {
+        self.render_with_progress(world, settings, |_| {})
+    }
+
+    /// Same as render_with(), but returns an error rather than panicking if a
+    /// pixel is traced that doesn't fit on the canvas.
+    pub fn try_render_with(&self, world: World, settings: &RenderSettings) -> Result<Canvas, FeorayError> {
+        self.try_render_live(world, settings, |_, _, _| {})
+    }
+
+    /// Same as render_with(), but calls on_progress after every completed scanline
+    /// (or tile) so long renders can drive a progress bar.
+    /// 
+    /// # Example
+    /// 
+    /// ```ignore
+    /// let canvas = cam.render_with_progress(world, &settings, |p| {
+    ///     println!("{:.1}% - ETA {:?}", p.fraction() * 100.0, p.eta());
+    /// });
+    /// ```
+    pub fn render_with_progress<F>(&self, world: World, settings: &RenderSettings, mut on_progress: F) -> Canvas
+    where
+        F: FnMut(&Progress)
+    {
+        self.render_live(world, settings, |_, _, progress| on_progress(progress))
+    }
+
+    /// Same as render_with_progress(), but also hands over the canvas and the
+    /// tile that has just been finished, so a front end (e.g. a preview window)
+    /// can show the image as it is built up. Pixels outside finished tiles are
+    /// still black.
+    pub fn render_live<F>(&self, world: World, settings: &RenderSettings, on_tile: F) -> Canvas
+    where
+        F: FnMut(&Canvas, Tile, &Progress)
+    {
+        self.try_render_live(world, settings, on_tile).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Same as render_live(), but returns an error rather than panicking if a
+    /// pixel is traced that doesn't fit on the canvas.
+    pub fn try_render_live<F>(&self, mut world: World, settings: &RenderSettings, on_tile: F) -> Result<Canvas, FeorayError>
+    where
+        F: FnMut(&Canvas, Tile, &Progress)
+    {
+        let tiles = self.prepare(&mut world, settings);
+        self.render_tiles(&world, &tiles, settings, on_tile)
+    }
+
+    // Applies the settings that change the world rather than how it's traced,
+    // and returns the tiles (or scanlines) to render, in order.
+    pub(crate) fn prepare(&self, world: &mut World, settings: &RenderSettings) -> Vec<Tile> {
+        if !settings.shadows {
+            for object in &mut world.objects {
+                object.umbra = false;
+            }
+        }
+        if let Some(epsilon) = settings.epsilon {
+            world.epsilon = epsilon;
+        }
+        if let Some(slope_bias) = settings.slope_bias {
+            world.slope_bias = slope_bias;
+        }
+        world.reseed(settings.seed);
+        match settings.tile_size {
+            Some(tile_size) => self.tiles(tile_size),
+            None => (0..self.vsize)
+                .map(|y| Tile::new(0, y, self.hsize, y + 1))
+                .collect()
+        }
+    }
+
+    /// Splits the canvas into square tiles, working left to right, top to bottom.
+    /// Tiles along the right and bottom edges are clipped to fit the canvas.
+    pub fn tiles(&self, tile_size: usize) -> Vec<Tile> {
+        let tile_size = tile_size.max(1);
+        let mut tiles = vec![];
+        for y0 in (0..self.vsize).step_by(tile_size) {
+            for x0 in (0..self.hsize).step_by(tile_size) {
+                let x1 = (x0 + tile_size).min(self.hsize);
+                let y1 = (y0 + tile_size).min(self.vsize);
+                tiles.push(Tile::new(x0, y0, x1, y1));
             }
         }
 
+        tiles
+    }
+
+    /// Renders a scene one tile (bucket) at a time rather than one scanline at a
+    /// time. Output is identical to render().
+    pub fn render_tiled(&self, world: World, tile_size: usize) -> Canvas {
+        self.render_tiled_with_progress(world, tile_size, |_| {})
+    }
+
+    /// Same as render_tiled(), but calls on_progress after every completed tile.
+    pub fn render_tiled_with_progress<F>(&self, world: World, tile_size: usize, on_progress: F) -> Canvas
+    where
+        F: FnMut(&Progress)
+    {
+        let settings = RenderSettings::for_world(&world).with_tile_size(tile_size);
+        self.render_with_progress(world, &settings, on_progress)
+    }
+
+    // Common render loop. With one thread tiles are rendered in order. Otherwise
+    // workers take the next free tile and send back the finished pixels, so the
+    // canvas and the callback stay on this thread. A pixel that doesn't fit on
+    // the canvas stops the render with an error.
+    fn render_tiles<F>(&self, world: &World, tiles: &[Tile], settings: &RenderSettings, mut on_tile: F) -> Result<Canvas, FeorayError>
+    where
+        F: FnMut(&Canvas, Tile, &Progress)
+    {
+        let mut canvas = canvas(self.hsize, self.vsize).with_encoding(settings.encoding);
+        let mut tracker = ProgressTracker::new(self.hsize * self.vsize);
+        take_rays();
+        let threads = settings.thread_count().min(tiles.len()).max(1);
+        if threads == 1 {
+            for tile in tiles {
+                self.render_tile(world, *tile, &mut canvas, settings)?;
+                on_tile(&canvas, *tile, &tracker.advance(tile.len(), take_rays()));
+            }
+
+            return Ok(canvas);
+        }
+
+        let next = AtomicUsize::new(0);
+        let (tx, rx) = mpsc::channel();
+        thread::scope(|s| -> Result<(), FeorayError> {
+            for _ in 0..threads {
+                let tx = tx.clone();
+                let next = &next;
+                s.spawn(move || loop {
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+                    if i >= tiles.len() {
+                        break;
+                    }
+                    let pixels = self.trace_tile(world, tiles[i], settings);
+                    if tx.send((tiles[i], pixels, take_rays())).is_err() {
+                        break;
+                    }
+                });
+            }
+            drop(tx);
+
+            for (tile, pixels, rays) in rx {
+                // Dropping the receiver stops the workers at their next tile.
+                write_tile(&mut canvas, tile, &pixels, settings.alpha)?;
+                on_tile(&canvas, tile, &tracker.advance(tile.len(), rays));
+            }
+
+            Ok(())
+        })?;
+
+        Ok(canvas)
+    }
+
+    /// Renders only the region from (x0, y0) up to but not including (x1, y1).
+    /// Framing is identical to a full render, but the returned canvas is cropped
+    /// to the size of the region. The region is clipped to fit the camera.
+    /// 
+    /// # Example
+    /// 
+    /// ```ignore
+    /// // Check the lighting on the middle of a 700 x 350 render
+    /// let crop = cam.render_region(world, 300, 125, 400, 225);
+    /// 
+    /// assert_eq!((crop.width, crop.height), (100, 100));
+    /// ```
+    pub fn render_region(&self, world: World, x0: usize, y0: usize, x1: usize, y1: usize) -> Canvas {
+        let settings = RenderSettings::for_world(&world);
+        let x1 = x1.min(self.hsize);
+        let y1 = y1.min(self.vsize);
+        let x0 = x0.min(x1);
+        let y0 = y0.min(y1);
+        let mut canvas = canvas(x1 - x0, y1 - y0);
+        let mut xs = Default::default();
+        for y in y0..y1 {
+            self.trace_span(&world, y, x0, x1, &settings, &mut xs, |x, colour, _| canvas.write_pix(x - x0, y - y0, colour));
+        }
+
         canvas
     }
 
+    /// Renders a single tile directly into the canvas, along with its alpha if
+    /// the settings ask for it. Returns an error if the tile doesn't fit on the
+    /// canvas, leaving whatever of it did fit.
+    pub fn render_tile(&self, world: &World, tile: Tile, canvas: &mut Canvas, settings: &RenderSettings) -> Result<(), FeorayError> {
+        if !tile.is_empty() {
+            canvas.check_bounds(tile.x1 - 1, tile.y1 - 1)?;
+        }
+        let mut xs = Default::default();
+        for y in tile.y0..tile.y1 {
+            self.trace_span(world, y, tile.x0, tile.x1, settings, &mut xs, |x, colour, alpha| {
+                canvas.write_pix(x, y, colour);
+                if settings.alpha {
+                    canvas.write_alpha(x, y, alpha);
+                }
+            });
+        }
+
+        Ok(())
+    }
+
+    // Traces every pixel in a tile, returned row by row with their opacity.
+    fn trace_tile(&self, world: &World, tile: Tile, settings: &RenderSettings) -> Vec<(Colour, f32)> {
+        let mut pixels = Vec::with_capacity(tile.len());
+        let mut xs = Default::default();
+        for y in tile.y0..tile.y1 {
+            self.trace_span(world, y, tile.x0, tile.x1, settings, &mut xs, |_, colour, alpha| pixels.push((colour, alpha)));
+        }
+
+        pixels
+    }
+
+    // Traces the pixels along row y from x0 up to x1, handing each one's colour
+    // and opacity to put() in order. The Whitted integrator traces LANES neighbouring
+    // pixels at a time, with their camera rays intersected as packets. Paths
+    // soon head off in different directions, so are traced a pixel at a time.
+    #[allow(clippy::too_many_arguments)]
+    fn trace_span<'a, F>(&self, world: &'a World, y: usize, x0: usize, x1: usize, settings: &RenderSettings, xs: &mut [Intersections<'a>; LANES], mut put: F)
+    where
+        F: FnMut(usize, Colour, f32)
+    {
+        if settings.integrator != Integrator::Whitted {
+            for x in x0..x1 {
+                let (colour, alpha) = self.pixel_colour(world, x, y, settings, &mut xs[0]);
+                put(x, colour, alpha);
+            }
+            return;
+        }
+        for start in (x0..x1).step_by(LANES) {
+            let end = (start + LANES).min(x1);
+            for (x, (colour, alpha)) in (start..end).zip(self.packet_colours(world, start, end, y, settings, xs)) {
+                put(x, colour, alpha);
+            }
+        }
+    }
+
+    // Same as pixel_colour() for each of the (up to LANES) pixels from x0 up to
+    // x1 along row y, using the Whitted integrator. Each sample's camera rays
+    // for all the pixels are intersected together.
+    fn packet_colours<'a>(&self, world: &'a World, x0: usize, x1: usize, y: usize, settings: &RenderSettings, xs: &mut [Intersections<'a>; LANES]) -> [(Colour, f32); LANES] {
+        let mut colours = [uncovered(settings); LANES];
+        let covered: Vec<usize> = (x0..x1).filter(|x| self.covers(*x, y)).collect();
+        let sampler = settings.seeded_sampler();
+        let spread = self.ray_spread(settings);
+        let mut pixels: Vec<(Vec<(Float, Float)>, Rng)> = covered.iter()
+            .map(|x| {
+                let stream = (y * self.hsize + x) as u64;
+                (sampler.samples_2d(settings.samples, stream), settings.rng(SHUTTER_STREAM + stream))
+            })
+            .collect();
+        let n = pixels.first().map_or(0, |(offsets, _)| offsets.len());
+        let mut totals = [Colour::black(); LANES];
+        let mut hits = [0; LANES];
+        for i in 0..n {
+            let rays: Vec<Ray> = covered.iter()
+                .zip(pixels.iter_mut())
+                .map(|(x, (offsets, rng))| {
+                    let time = self.shutter_time(i, n, rng);
+                    let (ox, oy) = offsets[i];
+                    self.ray_for_subpixel(*x, y, ox, oy).with_time(time).with_cone(0.0, spread)
+                })
+                .collect();
+            world.intersect_packet(&RayPacket::new(&rays), xs);
+            for (l, ray) in rays.iter().enumerate() {
+                if !settings.alpha || xs[l].hit_index().is_some() {
+                    let (ox, oy) = pixels[l].0[i];
+                    let vignette = self.vignette_at(covered[l] as Float + ox, y as Float + oy);
+                    totals[l] += settings.clamp(world.colour_of_hits(ray, settings.depth(), settings.background, &mut xs[l])) * vignette;
+                    hits[l] += 1;
+                }
+            }
+        }
+        for (l, x) in covered.iter().enumerate() {
+            colours[x - x0] = coverage(totals[l] * self.exposure_scale(), hits[l], n);
+        }
+
+        colours
+    }
+
+    // Traces the colour of a single pixel, averaging the sampler's subpixel rays.
+    // Each pixel is its own sampler stream, so threads and tiles never change the result.
+    // Intersections are found in xs, which is reused from pixel to pixel. Also
+    // returns the pixel's opacity, which is 1.0 unless the settings ask for alpha.
+    fn pixel_colour<'a>(&self, world: &'a World, x: usize, y: usize, settings: &RenderSettings, xs: &mut Intersections<'a>) -> (Colour, f32) {
+        if !self.covers(x, y) {
+            return uncovered(settings);
+        }
+        let stream = (y * self.hsize + x) as u64;
+        let spread = self.ray_spread(settings);
+        let offsets = settings.seeded_sampler().samples_2d(settings.samples, stream);
+        let mut rng = settings.rng(SHUTTER_STREAM + stream);
+        let mut paths = settings.rng(PATH_STREAM + stream);
+        let mut colour = Colour::black();
+        let mut hits = 0;
+        for (i, (ox, oy)) in offsets.iter().enumerate() {
+            let time = self.shutter_time(i, offsets.len(), &mut rng);
+            let ray = self.ray_for_subpixel(x, y, *ox, *oy).with_time(time).with_cone(0.0, spread);
+            if settings.alpha && !world.intersect_any(&ray, Float::INFINITY) {
+                continue;
+            }
+            hits += 1;
+            colour += match settings.integrator {
+                Integrator::Whitted => settings.clamp(world.colour_at_into(&ray, settings.depth(), settings.background, xs)),
+                Integrator::PathTracer => world.path_colour_into(&ray, settings, &mut paths, xs)
+            } * self.vignette_at(x as Float + ox, y as Float + oy);
+        }
+
+        coverage(colour * self.exposure_scale(), hits, offsets.len())
+    }
+
+    // Spreads samples evenly over the exposure, jittered within each time slot.
+    fn shutter_time(&self, i: usize, n: usize, rng: &mut Rng) -> Float {
+        if self.shutter_close <= self.shutter_open {
+            return self.shutter_open;
+        }
+        let slot = (i as Float + rng.next_float()) / n as Float;
+        self.shutter_open + (self.shutter_close - self.shutter_open) * slot
+    }
+
+    /// Opens the shutter between two moments in time, so moving objects are
+    /// smeared across the frame. Object motion runs from 0.0 to 1.0, so a
+    /// shutter of (0.0, 1.0) captures the whole movement. Use several samples per
+    /// pixel, otherwise the blur will be very noisy.
+    pub fn with_shutter(&mut self, open: Float, close: Float) -> Self {
+        self.shutter_open = open;
+        self.shutter_close = close;
+
+        *self
+    }
+
+    /// Brightens (or darkens, if negative) the image by this many stops, so a
+    /// dim interior or a sunlit exterior can be shot without rescaling every
+    /// light.
+    pub fn with_exposure(&mut self, stops: Float) -> Self {
+        self.exposure = stops;
+
+        *self
+    }
+
+    /// Sets the exposure the way a real camera is set, from its f-number,
+    /// shutter time in seconds and ISO, for scenes whose light colours are in
+    /// physical units. A surface as bright as 1.2 × 2^EV100 comes out white,
+    /// where EV100 is the exposure value at ISO 100, e.g. around 30,000 at
+    /// f/16, 1/100 s and ISO 100 for a sunny day.
+    pub fn with_photographic_exposure(&mut self, f_number: Float, shutter: Float, iso: Float) -> Self {
+        let ev100 = (f_number * f_number / shutter * 100.0 / iso).log2();
+        self.exposure = -(ev100 + (1.2 as Float).log2());
+
+        *self
+    }
+
+    /// Factor the exposure scales each pixel's light by.
+    pub fn exposure_scale(&self) -> f32 {
+        to_f32((2.0 as Float).powf(self.exposure))
+    }
+
+    /// Distorts the image the way a real lens does, with the radial
+    /// coefficients k1 and k2. Positive values give barrel distortion and
+    /// negative pincushion. Only affects the perspective projection.
+    pub fn with_distortion(&mut self, k1: Float, k2: Float) -> Self {
+        self.distortion = (k1, k2);
+
+        *self
+    }
+
+    /// Darkens the edges of the frame by the given share of a real lens's
+    /// cos⁴ falloff, for a more photographic look. Only affects the
+    /// perspective projection.
+    pub fn with_vignetting(&mut self, amount: Float) -> Self {
+        self.vignetting = amount;
+
+        *self
+    }
+
+    /// Shifts the lens, moving the frame right and up by these shares of its
+    /// width and height. Looking level and shifting up takes in a tall
+    /// building with its sides still upright, rather than tilting the camera
+    /// and having them lean in. Only affects the perspective projection.
+    pub fn with_shift(&mut self, x: Float, y: Float) -> Self {
+        self.shift = (x, y);
+
+        *self
+    }
+
+    /// Tilts the lens by the given angles about the camera's x axis and
+    /// swings it about its y axis, turning the plane of focus (Scheimpflug),
+    /// as for faking a miniature. The view itself is unchanged, so this only
+    /// matters to depth of field.
+    pub fn with_tilt(&mut self, tilt: Float, swing: Float) -> Self {
+        self.tilt = (tilt, swing);
+
+        *self
+    }
+
+    /// Normal of the plane of focus in world space, facing the camera. Points
+    /// straight back along the view unless the lens is tilted.
+    pub fn focus_plane_normal(&self) -> Vector4<Float> {
+        let (tilt, swing) = self.tilt;
+        let normal = Matrix4::rot_y(swing) * Matrix4::rot_x(tilt) * vector(0.0, 0.0, 1.0);
+        let mut normal = self.inverse_transform * normal;
+        normal.w = 0.0;
+
+        normal.normalize()
+    }
+
+    /// Selects the projection used to generate rays. Defaults to perspective.
+    pub fn with_projection(&mut self, projection: Projection) -> Self {
+        self.projection = projection;
+
+        *self
+    }
+
+    /// Makes camera rays with the given function instead of the projection, for
+    /// exotic ones like panini, cube faces or lightfield grids. Rays are in
+    /// camera space, looking down -z with +x to the left of the image, and are
+    /// moved into place by the camera's transform. Lens shift, distortion and
+    /// vignetting are up to the generator, and pattern filtering still uses
+    /// the projection's pixel_spread().
+    pub fn with_ray_generator(&mut self, generator: RayGenerator) -> Self {
+        self.ray_generator = Some(generator);
+
+        *self
+    }
+
     /// Applies a transform directly to the camera. The only transform that should be
-    /// applied is view_transform().
-    pub fn with_transform(&mut self, transform: Matrix4<f64>) -> Self {
+    /// applied is view_transform(). Panics if the transform can't be inverted,
+    /// as when looking from a point to itself. See try_with_transform().
+    pub fn with_transform(&mut self, transform: impl Into<Matrix4<Float>>) -> Self {
+        self.try_with_transform(transform).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Same as with_transform(), but returns an error, leaving the camera as it
+    /// was, if the transform can't be inverted.
+    pub fn try_with_transform(&mut self, transform: impl Into<Matrix4<Float>>) -> Result<Self, FeorayError> {
+        let transform = transform.into();
+        self.inverse_transform = inverse(&transform).ok_or(FeorayError::NonInvertible("camera"))?;
         self.transform = transform;
+        self.origin = self.inverse_transform * point(0.0, 0.0, 0.0);
+        self.origin.w = 1.0;
+
+        Ok(*self)
+    }
+
+    /// Keys the camera's transform at a moment in time, for animation. Keys are
+    /// interpolated like those on objects.
+    pub fn with_transform_at(&mut self, time: Float, transform: Matrix4<Float>) -> Self {
+        self.keyframes.insert(time, transform);
+
+        *self
+    }
+
+    /// Moves the camera along a path for animation. The path takes precedence
+    /// over any keyframes.
+    pub fn animated(&mut self, path: CameraPath) -> Self {
+        self.path = Some(path);
 
         *self
     }
+
+    /// Copy of the camera at a fraction of the resolution, covering exactly the
+    /// same view. Pair with RenderSettings::preview() for quick test renders.
+    pub fn preview(&self, scale: usize) -> Self {
+        let scale = scale.max(1);
+        let hsize = (self.hsize / scale).max(1);
+        let vsize = (self.vsize / scale).max(1);
+        let sized = Camera::new(hsize, vsize, self.fov);
+        let mut camera = *self;
+        camera.hsize = hsize;
+        camera.vsize = vsize;
+        camera.px_size = sized.px_size;
+        camera.half_width = sized.half_width;
+        camera.half_height = sized.half_height;
+
+        camera
+    }
+
+    /// Whether the camera's transform changes over time.
+    pub fn is_animated(&self) -> bool {
+        self.path.is_some() || !self.keyframes.is_empty()
+    }
+
+    /// Returns the camera's transform at a moment in time.
+    pub fn transform_at(&self, time: Float) -> Matrix4<Float> {
+        self.path
+            .and_then(|path| path.transform_at(time))
+            .or_else(|| self.keyframes.sample(time))
+            .unwrap_or(self.transform)
+    }
+
+    /// Static copy of the camera as it is at a moment in time.
+    pub fn at_time(&self, time: Float) -> Self {
+        let mut camera = *self;
+        camera.keyframes = Keyframes::new();
+        camera.path = None;
+        camera.with_transform(self.transform_at(time))
+    }
+}
+
+// Copies a finished tile's pixels into the canvas, with their opacity if the
+// render has an alpha channel.
+fn write_tile(canvas: &mut Canvas, tile: Tile, pixels: &[(Colour, f32)], alpha: bool) -> Result<(), FeorayError> {
+    let width = tile.x1 - tile.x0;
+    for (i, (colour, a)) in pixels.iter().enumerate() {
+        let (x, y) = (tile.x0 + i % width, tile.y0 + i / width);
+        canvas.try_write_pix(x, y, *colour)?;
+        if alpha {
+            canvas.try_write_alpha(x, y, *a)?;
+        }
+    }
+
+    Ok(())
+}
+
+// Colour and opacity of pixels outside the camera's image (e.g. beyond a
+// fisheye's circle).
+fn uncovered(settings: &RenderSettings) -> (Colour, f32) {
+    if settings.alpha {
+        (Colour::black(), 0.0)
+    } else {
+        (settings.background.unwrap_or(Colour::black()), 1.0)
+    }
+}
+
+// Final colour and opacity of a pixel from the total of its samples that hit
+// something, out of n. Without alpha every sample counts as a hit.
+fn coverage(total: Colour, hits: usize, n: usize) -> (Colour, f32) {
+    if hits == 0 {
+        return (Colour::black(), 0.0);
+    }
+
+    (total / hits as f32, hits as f32 / n as f32)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::core::{vector, Colour, Transform, Tuple, World};
-    use std::f64::consts::PI;
+    use crate::core::{Background, Encoding, Sampler, SamplerKind, Tuple};
+    use crate::lights::PointLight;
+    use crate::materials::{Material, Pattern};
+    use crate::primitives::Object;
+    use crate::EPSILON;
+    use assert_approx_eq::assert_approx_eq;
 
     #[test]
     fn constructing_a_camera() {
@@ -89,6 +815,22 @@ mod tests {
         assert_eq!(cam.vsize, 120);
         assert_eq!(cam.fov, PI/2.0);
         assert_eq!(cam.transform, Matrix4::identity());
+        assert_eq!(cam.inverse_transform, Matrix4::identity());
+    }
+
+    #[test]
+    fn tiles_that_dont_fit_the_canvas_are_errors() {
+        let cam = Camera::new(8, 8, PI/2.0);
+        let w = World::default_world();
+        let settings = RenderSettings::for_world(&w);
+        let mut small = canvas(4, 4);
+
+        assert!(cam.render_tile(&w, Tile::new(0, 0, 4, 4), &mut small, &settings).is_ok());
+        assert!(matches!(
+            cam.render_tile(&w, Tile::new(4, 0, 8, 4), &mut small, &settings),
+            Err(FeorayError::OutOfBounds { x: 7, y: 3, .. })
+        ));
+        assert_eq!(cam.try_render_with(w.clone(), &settings.with_threads(2)).unwrap(), cam.render_with(w, &settings));
     }
 
     #[test]
@@ -129,9 +871,10 @@ mod tests {
         let t = Matrix4::rot_y(PI/4.0) * Matrix4::translate(0.0, -2.0, 5.0);
         cam.with_transform(t);
         let r = cam.ray_for_pixel(100, 50);
-        let irr_no = 2.0f64.sqrt() / 2.0;
+        let irr_no = Float::sqrt(2.0) / 2.0;
 
-        assert_eq!(r.origin, point(0.0, 2.0, -5.0));
+        assert_eq!(cam.inverse_transform, t.try_inverse().unwrap());
+        assert!(r.origin.approx_eq(&point(0.0, 2.0, -5.0)));
         assert_eq!(r.direction.to_5dp(), vector(irr_no, 0.0, -irr_no).to_5dp());
     }
 
@@ -145,6 +888,545 @@ mod tests {
         cam.with_transform(Matrix4::view_transform(from, to, up));
         let image = cam.render(w);
 
-        assert_eq!(image.read_pix(5, 5).to_5dp(), Colour::new(0.38066, 0.47583, 0.2855));
+        assert!(image.read_pix(5, 5).approx_eq(&Colour::new(0.38066, 0.47583, 0.2855)));
+    }
+
+    #[test]
+    fn fisheye_ray_through_centre_of_canvas() {
+        let cam = Camera::new(101, 101, PI)
+            .with_projection(Projection::Fisheye);
+        let r = cam.ray_for_pixel(50, 50);
+
+        assert_eq!(r.origin, point(0.0, 0.0, 0.0));
+        assert_eq!(r.direction.to_5dp(), vector(0.0, 0.0, -1.0));
+    }
+
+    #[test]
+    fn fisheye_ray_at_edge_of_image_circle_is_perpendicular() {
+        let cam = Camera::new(200, 99, PI)
+            .with_projection(Projection::Fisheye);
+        let r = cam.ray_for_pixel(50, 49);
+
+        assert!(cam.covers(50, 49));
+        assert!(!cam.covers(0, 49));
+        assert_eq!(r.direction.to_5dp(), vector(1.0, 0.0, 0.0).to_5dp());
+    }
+
+    #[test]
+    fn equirectangular_ray_through_centre_of_canvas() {
+        let cam = Camera::new(201, 101, PI/2.0)
+            .with_projection(Projection::Equirectangular);
+        let r = cam.ray_for_pixel(100, 50);
+
+        assert!(cam.covers(0, 0));
+        assert_eq!(r.direction.to_5dp(), vector(0.0, 0.0, -1.0).to_5dp());
+    }
+
+    #[test]
+    fn equirectangular_ray_at_left_edge_looks_behind() {
+        let cam = Camera::new(4, 2, PI/2.0)
+            .with_projection(Projection::Equirectangular);
+        let left = cam.ray_for_pixel(1, 0);
+        let right = cam.ray_for_pixel(2, 0);
+
+        assert!(left.direction.x > 0.0);
+        assert!(right.direction.x < 0.0);
+        assert!(left.direction.y > 0.0);
+    }
+
+    #[test]
+    fn tiles_cover_the_whole_canvas() {
+        let cam = Camera::new(10, 5, PI/2.0);
+        let tiles = cam.tiles(4);
+
+        assert_eq!(tiles.len(), 6);
+        assert_eq!(tiles[0], Tile::new(0, 0, 4, 4));
+        assert_eq!(tiles[2], Tile::new(8, 0, 10, 4));
+        assert_eq!(tiles[5], Tile::new(8, 4, 10, 5));
+        assert_eq!(tiles.iter().map(|t| t.len()).sum::<usize>(), 50);
+    }
+
+    #[test]
+    fn tiled_render_matches_scanline_render() {
+        let mut cam = Camera::new(11, 11, PI/2.0);
+        let from = point(0.0, 0.0, -5.0);
+        let to = point(0.0, 0.0, 0.0);
+        let up = vector(0.0, 1.0, 0.0);
+        cam.with_transform(Matrix4::view_transform(from, to, up));
+        let image = cam.render(World::default_world());
+        let tiled = cam.render_tiled(World::default_world(), 3);
+
+        assert_eq!(tiled, image);
+    }
+
+    #[test]
+    fn progress_is_reported_for_every_scanline() {
+        let cam = Camera::new(4, 3, PI/2.0);
+        let mut reports = vec![];
+        let settings = RenderSettings::default();
+        cam.render_with_progress(World::default_world(), &settings, |p| reports.push(p.pixels_done));
+
+        assert_eq!(reports, vec![4, 8, 12]);
+    }
+
+    #[test]
+    fn live_renders_show_each_finished_tile() {
+        let cam = Camera::new(5, 4, PI/2.0)
+            .with_transform(Matrix4::translate(0.0, 0.0, -5.0));
+        let settings = RenderSettings::default().with_tile_size(2).with_threads(2);
+        let mut seen = vec![];
+        let image = cam.render_live(World::default_world(), &settings, |canvas, tile, _| {
+            seen.push((tile, canvas.read_pix(tile.x1 - 1, tile.y1 - 1)));
+        });
+
+        assert_eq!(seen.len(), cam.tiles(2).len());
+        for (tile, colour) in seen {
+            assert_eq!(colour, image.read_pix(tile.x1 - 1, tile.y1 - 1));
+        }
+    }
+
+    #[test]
+    fn progress_is_reported_for_every_tile() {
+        let cam = Camera::new(4, 3, PI/2.0);
+        let mut last = None;
+        let mut count = 0;
+        cam.render_tiled_with_progress(World::default_world(), 2, |p| {
+            count += 1;
+            last = Some(*p);
+        });
+
+        assert_eq!(count, 4);
+        assert!(last.unwrap().is_complete());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn rendering_region_keeps_framing() {
+        let w = World::default_world();
+        let mut cam = Camera::new(11, 11, PI/2.0);
+        let from = point(0.0, 0.0, -5.0);
+        let to = point(0.0, 0.0, 0.0);
+        let up = vector(0.0, 1.0, 0.0);
+        cam.with_transform(Matrix4::view_transform(from, to, up));
+        let crop = cam.render_region(w, 4, 4, 7, 20);
+
+        assert_eq!((crop.width, crop.height), (3, 7));
+        assert!(crop.read_pix(1, 1).approx_eq(&Colour::new(0.38066, 0.47583, 0.2855)));
+    }
+
+    #[test]
+    fn rendering_respects_world_recursion_limit() {
+        let mirror = Material::default().with_reflectivity(1.0);
+        let lower = Object::new_plane()
+            .with_material(mirror)
+            .with_transform(Matrix4::translate(0.0, -1.0, 0.0));
+        let upper = Object::new_plane()
+            .with_material(mirror)
+            .with_transform(Matrix4::translate(0.0, 1.0, 0.0));
+        let light = PointLight::new(Colour::white(), point(0.0, 0.0, 0.0));
+        let from = point(0.0, 0.0, 0.0);
+        let to = point(0.0, 1.0, 1.0);
+        let up = vector(0.0, 1.0, 0.0);
+        let cam = Camera::new(1, 1, PI/2.0)
+            .with_transform(Matrix4::view_transform(from, to, up));
+        let shallow = World::default()
+            .with_light(light.clone())
+            .with_object(lower.clone())
+            .with_object(upper.clone())
+            .with_recursions(1);
+        let deep = World::default()
+            .with_light(light.clone())
+            .with_object(lower)
+            .with_object(upper)
+            .with_recursions(4);
+
+        assert!(cam.render(deep).read_pix(0, 0).r > cam.render(shallow).read_pix(0, 0).r);
+    }
+
+    #[test]
+    fn threaded_render_matches_single_threaded_render() {
+        let mut cam = Camera::new(11, 11, PI/2.0);
+        let from = point(0.0, 0.0, -5.0);
+        let to = point(0.0, 0.0, 0.0);
+        let up = vector(0.0, 1.0, 0.0);
+        cam.with_transform(Matrix4::view_transform(from, to, up));
+        let single = cam.render(World::default_world());
+        let settings = RenderSettings::default().with_threads(4).with_tile_size(3);
+        let threaded = cam.render_with(World::default_world(), &settings);
+
+        assert_eq!(threaded, single);
+    }
+
+    #[test]
+    fn background_colour_fills_misses() {
+        let cam = Camera::new(3, 3, PI/2.0);
+        let settings = RenderSettings::default().with_background(Colour::blue());
+        let image = cam.render_with(World::default(), &settings);
+
+        assert_eq!(image.read_pix(1, 1), Colour::blue());
+    }
+
+    #[test]
+    fn misses_are_transparent_with_alpha() {
+        let cam = Camera::new(1, 1, PI/2.0)
+            .with_transform(Matrix4::translate(0.0, 0.0, -5.0));
+        let w = World::default()
+            .with_background(Background::Solid(Colour::red()))
+            .with_light(PointLight::new(Colour::white(), point(0.0, 0.0, 5.0)))
+            .with_object(Object::new_sphere()
+                .with_material(Material::null().with_ambient(1.0))
+                .with_transform(Matrix4::translate(1.25, 1.25, 0.0)));
+        for integrator in [Integrator::Whitted, Integrator::PathTracer] {
+            let settings = RenderSettings::default().with_samples(16).with_integrator(integrator);
+            let opaque = cam.render_with(w.clone(), &settings);
+            let image = cam.render_with(w.clone(), &settings.with_alpha(true));
+
+            assert!(!opaque.has_alpha());
+            assert_eq!(image.read_alpha(0, 0), 1.0 / 16.0);
+            assert_ne!(image.read_pix(0, 0), opaque.read_pix(0, 0));
+        }
+        let image = cam.render_with(w, &RenderSettings::default().with_samples(16).with_alpha(true));
+
+        // Only the samples that hit the ball are averaged.
+        assert_eq!(image.read_pix(0, 0), Colour::white());
+        let empty = cam.render_with(World::default(), &RenderSettings::default().with_alpha(true).with_threads(2));
+
+        assert_eq!(empty.read_alpha(0, 0), 0.0);
+    }
+
+    #[test]
+    fn world_background_is_used_unless_overridden() {
+        let cam = Camera::new(3, 3, PI/2.0);
+        let w = World::default().with_background(Background::Solid(Colour::red()));
+        let sky = cam.render_with(w.clone(), &RenderSettings::default());
+        let matte = cam.render_with(w, &RenderSettings::default().with_background(Colour::blue()));
+
+        assert_eq!(sky.read_pix(1, 1), Colour::red());
+        assert_eq!(matte.read_pix(1, 1), Colour::blue());
+    }
+
+    #[test]
+    fn supersampling_averages_subpixel_rays() {
+        let cam = Camera::new(1, 1, PI/2.0)
+            .with_transform(Matrix4::translate(0.0, 0.0, -5.0));
+        let w = World::default()
+            .with_light(PointLight::new(Colour::white(), point(0.0, 0.0, 5.0)))
+            .with_object(Object::new_sphere()
+                .with_material(Material::null().with_ambient(1.0))
+                .with_transform(Matrix4::translate(1.25, 1.25, 0.0)));
+        let one = cam.render_with(w, &RenderSettings::default());
+        let w = World::default()
+            .with_light(PointLight::new(Colour::white(), point(0.0, 0.0, 5.0)))
+            .with_object(Object::new_sphere()
+                .with_material(Material::null().with_ambient(1.0))
+                .with_transform(Matrix4::translate(1.25, 1.25, 0.0)));
+        let many = cam.render_with(w, &RenderSettings::default().with_samples(16));
+
+        assert_eq!(one.read_pix(0, 0), Colour::black());
+        assert_eq!(many.read_pix(0, 0), Colour::grey(1.0 / 16.0));
+    }
+
+    #[test]
+    fn stochastic_samplers_are_reproducible_across_threads() {
+        let mut cam = Camera::new(11, 11, PI/2.0);
+        let from = point(0.0, 0.0, -5.0);
+        let to = point(0.0, 0.0, 0.0);
+        let up = vector(0.0, 1.0, 0.0);
+        cam.with_transform(Matrix4::view_transform(from, to, up));
+        let sampler = Sampler::new(SamplerKind::Stratified);
+        let settings = RenderSettings::default().with_samples(4).with_sampler(sampler).with_seed(11);
+        let single = cam.render_with(World::default_world(), &settings);
+        let threaded = cam.render_with(World::default_world(), &settings.with_threads(3).with_tile_size(4));
+
+        assert_eq!(threaded, single);
+    }
+
+    #[test]
+    fn render_seed_controls_random_sampling() {
+        let mut cam = Camera::new(11, 11, PI/2.0);
+        let from = point(0.0, 0.0, -5.0);
+        let to = point(0.0, 0.0, 0.0);
+        let up = vector(0.0, 1.0, 0.0);
+        cam.with_transform(Matrix4::view_transform(from, to, up));
+        let settings = RenderSettings::default()
+            .with_samples(2)
+            .with_sampler(Sampler::new(SamplerKind::Random));
+        let a = cam.render_with(World::default_world(), &settings.with_seed(1));
+        let b = cam.render_with(World::default_world(), &settings.with_seed(1));
+        let c = cam.render_with(World::default_world(), &settings.with_seed(2));
+
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+    }
+
+    #[test]
+    fn closed_shutter_renders_a_frozen_frame() {
+        let cam = Camera::new(1, 1, PI/2.0);
+        let mut rng = Rng::new(0);
+
+        assert_eq!(cam.shutter_time(0, 4, &mut rng), 0.0);
+    }
+
+    #[test]
+    fn packet_traced_pixels_match_single_rays() {
+        let mut cam = Camera::new(7, 5, PI/2.0);
+        cam.with_transform(Matrix4::view_transform(point(0.0, 1.0, -5.0), point(0.0, 0.0, 0.0), vector(0.0, 1.0, 0.0)));
+        let w = World::default_world()
+            .with_object(Object::new_plane().with_transform(Matrix4::translate(0.0, -1.0, 0.0)));
+        let settings = RenderSettings::for_world(&w).with_samples(4);
+        let image = cam.render_with(w.clone(), &settings);
+        let mut xs = Intersections::default();
+        for y in 0..5 {
+            for x in 0..7 {
+                let (single, _) = cam.pixel_colour(&w, x, y, &settings, &mut xs);
+
+                assert_eq!(image.read_pix(x, y).to_5dp(), single.to_5dp());
+            }
+        }
+    }
+
+    #[test]
+    fn shutter_times_are_stratified_over_the_exposure() {
+        let cam = Camera::new(1, 1, PI/2.0)
+            .with_shutter(0.0, 1.0);
+        let mut rng = Rng::new(0);
+        for i in 0..4 {
+            let t = cam.shutter_time(i, 4, &mut rng);
+            assert!(t >= i as Float / 4.0 && t < (i + 1) as Float / 4.0);
+        }
+    }
+
+    #[test]
+    fn moving_objects_are_blurred() {
+        let cam = Camera::new(1, 1, PI/6.0)
+            .with_transform(Matrix4::translate(0.0, 0.0, -5.0))
+            .with_shutter(0.0, 1.0);
+        let ball = Object::new_sphere()
+            .with_material(Material::null().with_ambient(1.0))
+            .with_transform(Matrix4::uscale(2.0))
+            .with_motion(Matrix4::translate(4.0, 0.0, 0.0) * Matrix4::uscale(2.0));
+        let w = World::default()
+            .with_light(PointLight::new(Colour::white(), point(0.0, 0.0, 5.0)))
+            .with_object(ball);
+        let c = cam.render_with(w, &RenderSettings::default().with_samples(16)).read_pix(0, 0);
+
+        assert!(c.r > 0.0 && c.r < 1.0);
+    }
+
+    #[test]
+    fn animated_camera_follows_its_path() {
+        let path = CameraPath::dolly(point(0.0, 0.0, 8.0), point(0.0, 0.0, 4.0), point(0.0, 0.0, 0.0), 0.0, 1.0);
+        let cam = Camera::new(1, 1, PI/2.0)
+            .with_transform_at(0.0, Matrix4::identity())
+            .animated(path);
+        let frozen = cam.at_time(0.5);
+
+        assert!(cam.is_animated());
+        assert!(!frozen.is_animated());
+        assert_eq!(frozen.transform, Matrix4::translate(0.0, 0.0, -6.0));
+        assert_eq!(frozen.ray_for_pixel(0, 0).origin, point(0.0, 0.0, 6.0));
+    }
+
+    #[test]
+    fn path_traced_renders_are_reproducible() {
+        let cam = Camera::new(4, 4, PI/3.0)
+            .with_transform(Matrix4::view_transform(point(0.0, 0.0, -5.0), point(0.0, 0.0, 0.0), vector(0.0, 1.0, 0.0)));
+        let settings = RenderSettings::default()
+            .with_integrator(Integrator::PathTracer)
+            .with_samples(4)
+            .with_seed(9);
+        let a = cam.render_with(World::default_world(), &settings);
+        let b = cam.render_with(World::default_world(), &settings.with_threads(2));
+
+        assert_eq!(a, b);
+        assert_ne!(a.read_pix(2, 2), Colour::black());
+    }
+
+    #[test]
+    fn preview_camera_covers_the_same_view() {
+        fn straight_ahead(_: usize, _: usize, _: (Float, Float)) -> Ray {
+            Ray::new(point(0.0, 0.0, 0.0), vector(0.0, 0.0, -1.0))
+        }
+        let cam = Camera::new(200, 100, PI/2.0)
+            .with_transform(Matrix4::translate(0.0, -2.0, 5.0))
+            .with_shutter(0.0, 1.0)
+            .with_exposure(1.5)
+            .with_shift(0.1, -0.2)
+            .with_distortion(0.05, 0.01)
+            .with_vignetting(0.5);
+        let preview = cam.preview(4);
+
+        assert_eq!((preview.hsize, preview.vsize), (50, 25));
+        assert_eq!(preview.transform, cam.transform);
+        assert_eq!(preview.shutter_close, 1.0);
+        assert_eq!(preview.exposure, 1.5);
+        assert_eq!(preview.shift, (0.1, -0.2));
+        assert_eq!(preview.distortion, (0.05, 0.01));
+        assert_eq!(preview.vignetting, 0.5);
+        assert!(preview.ray_generator.is_none());
+        let mut generated = cam;
+        assert!(generated.with_ray_generator(straight_ahead).preview(4).ray_generator.is_some());
+        assert!((preview.px_size - cam.px_size * 4.0).abs() < EPSILON);
+        assert_eq!(preview.ray_for_subpixel(0, 0, 0.0, 0.0).direction.to_5dp(),
+            cam.ray_for_subpixel(0, 0, 0.0, 0.0).direction.to_5dp());
+    }
+
+    #[test]
+    fn gamma_is_left_to_the_canvas_encoding() {
+        let cam = Camera::new(5, 5, PI/3.0)
+            .with_transform(Matrix4::view_transform(point(0.0, 0.0, -5.0), point(0.0, 0.0, 0.0), vector(0.0, 1.0, 0.0)));
+        let w = World::default_world();
+        let settings = RenderSettings::for_world(&w);
+        let linear = cam.render_with(w.clone(), &settings);
+        let encoded = cam.render_with(w, &settings.with_gamma(2.2));
+
+        assert_eq!(encoded.pixels, linear.pixels);
+        assert_eq!(encoded.encoding, Encoding::Gamma(2.2));
+    }
+
+    #[test]
+    fn render_settings_can_replace_the_world_epsilon() {
+        let cam = Camera::new(5, 5, PI/3.0)
+            .with_transform(Matrix4::view_transform(point(0.0, 0.0, -5.0), point(0.0, 0.0, 0.0), vector(0.0, 1.0, 0.0)));
+        let w = World::default_world();
+        let settings = RenderSettings::for_world(&w);
+        let wide = cam.render_with(w.clone(), &settings.with_epsilon(0.5));
+
+        assert_eq!(wide, cam.render_with(w.clone().with_epsilon(0.5), &settings));
+        assert_ne!(wide, cam.render_with(w, &settings));
+    }
+
+    #[test]
+    fn rendering_without_shadows() {
+        let cam = Camera::new(1, 1, PI/6.0)
+            .with_transform(Matrix4::view_transform(point(0.0, 1.0, -5.0), point(0.0, 0.0, 0.0), vector(0.0, 1.0, 0.0)));
+        let w = World::default()
+            .with_light(PointLight::new(Colour::white(), point(0.0, 10.0, 0.0)))
+            .with_object(Object::new_plane())
+            .with_object(Object::new_sphere().with_transform(Matrix4::translate(0.0, 2.0, 0.0)));
+        let shadowed = cam.render_with(w.clone(), &RenderSettings::default()).read_pix(0, 0);
+        let lit = cam.render_with(w, &RenderSettings::default().with_shadows(false)).read_pix(0, 0);
+
+        assert!(lit.r > shadowed.r);
+    }
+
+    #[test]
+    fn distant_patterns_are_filtered_over_each_pixel() {
+        let cam = Camera::new(1, 1, PI/2.0)
+            .with_transform(Matrix4::view_transform(point(0.5, 100.0, 0.5), point(0.5, 0.0, 0.5), vector(0.0, 0.0, 1.0)));
+        let w = World::default()
+            .with_light(PointLight::new(Colour::white(), point(0.0, 200.0, 0.0)))
+            .with_object(Object::new_plane()
+                .with_material(Material::null().with_ambient(1.0)
+                    .with_pattern(Pattern::new_checkers(Colour::white(), Colour::black()))));
+        let filtered = cam.render_with(w.clone(), &RenderSettings::default()).read_pix(0, 0);
+        let sharp = cam.render_with(w, &RenderSettings::default().with_pattern_filtering(false)).read_pix(0, 0);
+
+        assert!((filtered.r - 0.5).abs() < 0.01);
+        assert_eq!(sharp, Colour::white());
+        assert_eq!(cam.pixel_spread(), cam.px_size);
+    }
+
+    #[test]
+    fn singular_camera_transforms_are_refused() {
+        let mut cam = Camera::new(10, 10, PI/2.0);
+        let looking_up = Matrix4::view_transform(point(0.0, 0.0, 0.0), point(0.0, 1.0, 0.0), vector(0.0, 1.0, 0.0));
+
+        assert!(matches!(cam.try_with_transform(looking_up), Err(FeorayError::NonInvertible("camera"))));
+        assert_eq!(cam.inverse_transform, Matrix4::identity());
+        // Looking from a point to itself gives NaNs rather than a singular matrix.
+        let nowhere = Matrix4::view_transform(point(0.0, 0.0, -5.0), point(0.0, 0.0, -5.0), vector(0.0, 1.0, 0.0));
+        assert!(matches!(cam.try_with_transform(nowhere), Err(FeorayError::NonInvertible("camera"))));
+        assert_eq!(cam.inverse_transform, Matrix4::identity());
+    }
+
+    #[test]
+    fn exposure_scales_the_light_in_stops() {
+        let camera = Camera::new(11, 11, PI/2.0)
+            .with_transform(Matrix4::view_transform(point(0.0, 0.0, -5.0), point(0.0, 0.0, 0.0), vector(0.0, 1.0, 0.0)));
+        let plain = camera.render(World::default_world()).read_pix(5, 5);
+        let mut exposed = camera;
+        let brighter = exposed.with_exposure(1.0).render(World::default_world()).read_pix(5, 5);
+
+        assert_eq!(brighter, plain * 2.0);
+        assert_eq!(exposed.with_photographic_exposure(1.0, 1.0, 100.0).exposure_scale(), 1.0 / 1.2);
+        assert_eq!(exposed.with_photographic_exposure(4.0, 1.0, 1600.0).exposure, -(1.2 as Float).log2());
+    }
+
+    #[test]
+    fn lens_distortion_bends_rays_away_from_the_centre() {
+        let plain = Camera::new(201, 101, PI / 2.0);
+        let barrel = Camera::new(201, 101, PI / 2.0).with_distortion(0.2, 0.0);
+        let pincushion = Camera::new(201, 101, PI / 2.0).with_distortion(-0.2, 0.0);
+        let off_axis = |cam: &Camera| -cam.ray_for_pixel(0, 0).direction.z;
+
+        assert_eq!(barrel.ray_for_pixel(100, 50).direction, plain.ray_for_pixel(100, 50).direction);
+        assert!(off_axis(&barrel) < off_axis(&plain));
+        assert!(off_axis(&pincushion) > off_axis(&plain));
+    }
+
+    #[test]
+    fn vignetting_darkens_the_edges_of_the_frame() {
+        let cam = Camera::new(201, 101, PI / 2.0).with_vignetting(1.0);
+        let corner = to_f32(cam.ray_for_subpixel(0, 0, 0.0, 0.0).direction.z);
+
+        assert_eq!(cam.vignette_at(100.5, 50.5), 1.0);
+        assert_approx_eq!(cam.vignette_at(0.0, 0.0), corner.powi(4), 1e-5);
+        let mut half = cam;
+        assert_approx_eq!(half.with_vignetting(0.5).vignette_at(0.0, 0.0), 1.0 - 0.5 * (1.0 - corner.powi(4)), 1e-5);
+        assert_eq!(Camera::new(201, 101, PI / 2.0).vignette_at(0.0, 0.0), 1.0);
+
+        let world = World::default_world();
+        let view = Matrix4::view_transform(point(0.0, 0.0, -5.0), point(0.0, 0.0, 0.0), vector(0.0, 1.0, 0.0));
+        let plain = Camera::new(11, 11, PI / 2.0).with_transform(view).render(world.clone());
+        let dimmed = Camera::new(11, 11, PI / 2.0).with_transform(view).with_vignetting(1.0).render(world);
+        assert_eq!(dimmed.read_pix(5, 5), plain.read_pix(5, 5));
+        assert!(dimmed.read_pix(4, 5).r < plain.read_pix(4, 5).r);
+    }
+
+    #[test]
+    fn shifting_the_lens_moves_the_frame_without_turning_the_camera() {
+        let plain = Camera::new(201, 101, PI / 2.0);
+        let raised = Camera::new(201, 101, PI / 2.0).with_shift(0.0, 0.5);
+        let right = Camera::new(201, 101, PI / 2.0).with_shift(0.5, 0.0);
+
+        assert_eq!(raised.ray_for_pixel(100, 50).direction.to_5dp(), plain.ray_for_subpixel(100, 0, 0.5, 0.0).direction.to_5dp());
+        assert_eq!(right.ray_for_pixel(100, 50).direction.to_5dp(), plain.ray_for_subpixel(201, 50, 0.0, 0.5).direction.to_5dp());
+        // Columns of pixels still look along one vertical plane.
+        let (top, bottom) = (raised.ray_for_pixel(100, 0).direction, raised.ray_for_pixel(100, 100).direction);
+        assert!(top.x.abs() < EPSILON && bottom.x.abs() < EPSILON);
+    }
+
+    #[test]
+    fn tilting_the_lens_turns_the_plane_of_focus() {
+        let view = Matrix4::view_transform(point(0.0, 0.0, -5.0), point(0.0, 0.0, 0.0), vector(0.0, 1.0, 0.0));
+        let cam = Camera::new(201, 101, PI / 2.0).with_transform(view);
+        let mut tilted = cam;
+        tilted.with_tilt(PI / 2.0, 0.0);
+
+        assert_eq!(cam.focus_plane_normal().to_5dp(), vector(0.0, 0.0, -1.0));
+        assert_eq!(tilted.focus_plane_normal().to_5dp(), vector(0.0, -1.0, 0.0));
+        assert_eq!(tilted.ray_for_pixel(20, 30), cam.ray_for_pixel(20, 30));
+    }
+
+    #[test]
+    fn ray_generators_replace_the_projection() {
+        // Orthographic, two units across an 11 x 11 image.
+        fn orthographic(px: usize, py: usize, (ox, oy): (Float, Float)) -> Ray {
+            let x = 1.0 - (px as Float + ox) * 2.0 / 11.0;
+            let y = 1.0 - (py as Float + oy) * 2.0 / 11.0;
+            Ray::new(point(x, y, 0.0), vector(0.0, 0.0, -2.0))
+        }
+        let mut cam = Camera::new(11, 11, PI/2.0);
+        cam.with_transform(Matrix4::view_transform(point(0.0, 0.0, -5.0), point(0.0, 0.0, 0.0), vector(0.0, 1.0, 0.0)));
+        cam.with_ray_generator(orthographic).with_vignetting(1.0);
+        let r = cam.ray_for_subpixel(0, 0, 0.0, 0.0);
+
+        assert_eq!(r.origin.to_5dp(), point(-1.0, 1.0, -5.0));
+        assert_eq!(r.direction, vector(0.0, 0.0, 1.0));
+        assert_eq!(cam.vignette_at(0.0, 0.0), 1.0);
+        let image = cam.render(World::default_world());
+        assert!(image.read_pix(5, 5).approx_eq(&Colour::new(0.38066, 0.47583, 0.2855)));
+        // Parallel rays still hit the sphere near the corner.
+        assert_ne!(image.read_pix(2, 2), Colour::black());
+    }
+}
@@ -0,0 +1,230 @@
+use crate::{Float, FeorayError};
+use crate::core::{point, vector, Transform};
+use nalgebra::{Matrix4, Vector4};
+use crate::consts::PI;
+
+/// Maximum number of points on a camera path. Cameras are Copy, so the path
+/// has a fixed size.
+pub const MAX_PATH_POINTS: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct PathPoint {
+    time: Float,
+    position: Vector4<Float>,
+    target: Vector4<Float>
+}
+
+/// Smooth camera move through a series of positions, each paired with the
+/// point the camera looks at. Positions and targets are interpolated with a
+/// Catmull-Rom spline, so the camera passes through every point.
+///
+/// # Example
+///
+/// ```ignore
+/// // Two second orbit around the origin
+/// let path = CameraPath::orbit(point(0.0, 1.0, 0.0), 5.0, 1.5, 0.0, 2.0);
+/// let cam = Camera::new(700, 350, PI / 3.0).animated(path);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CameraPath {
+    points: [PathPoint; MAX_PATH_POINTS],
+    len: usize,
+    pub up: Vector4<Float>
+}
+
+impl CameraPath {
+    /// Creates an empty path with +y as up.
+    pub fn new() -> Self {
+        let empty = PathPoint {
+            time: 0.0,
+            position: point(0.0, 0.0, 0.0),
+            target: point(0.0, 0.0, 0.0)
+        };
+        CameraPath { points: [empty; MAX_PATH_POINTS], len: 0, up: vector(0.0, 1.0, 0.0) }
+    }
+
+    /// Adds a point on the path, replacing any point already at that time.
+    /// Panics if the path is full.
+    pub fn with_point(self, time: Float, position: Vector4<Float>, target: Vector4<Float>) -> Self {
+        self.try_with_point(time, position, target).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Same as with_point(), but returns an error rather than panicking if the
+    /// path is full.
+    pub fn try_with_point(mut self, time: Float, position: Vector4<Float>, target: Vector4<Float>) -> Result<Self, FeorayError> {
+        let new = PathPoint { time, position, target };
+        let points = &mut self.points[..self.len];
+        if let Some(p) = points.iter_mut().find(|p| p.time == time) {
+            *p = new;
+            return Ok(self);
+        }
+        if self.len == MAX_PATH_POINTS {
+            return Err(FeorayError::TooMany { what: "points per camera path", max: MAX_PATH_POINTS });
+        }
+        let index = points.iter().position(|p| p.time > time).unwrap_or(self.len);
+        self.points.copy_within(index..self.len, index + 1);
+        self.points[index] = new;
+        self.len += 1;
+
+        Ok(self)
+    }
+
+    /// Changes which way is up for the camera.
+    pub fn with_up(mut self, up: Vector4<Float>) -> Self {
+        self.up = up;
+
+        self
+    }
+
+    /// Circles the camera once around a centre point, at a fixed radius and
+    /// height above it, always looking at the centre.
+    pub fn orbit(centre: Vector4<Float>, radius: Float, height: Float, start: Float, end: Float) -> Self {
+        let steps = 12;
+        (0..=steps).fold(CameraPath::new(), |path, i| {
+            let s = i as Float / steps as Float;
+            // The last point must land exactly on the first to close the loop
+            let angle = 2.0 * PI * (i % steps) as Float / steps as Float;
+            let position = centre + vector(radius * angle.sin(), height, -radius * angle.cos());
+            path.with_point(start + (end - start) * s, position, centre)
+        })
+    }
+
+    /// Moves the camera in a straight line while it looks at a fixed target.
+    pub fn dolly(from: Vector4<Float>, to: Vector4<Float>, target: Vector4<Float>, start: Float, end: Float) -> Self {
+        CameraPath::new()
+            .with_point(start, from, target)
+            .with_point(end, to, target)
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Camera position at a moment in time.
+    pub fn position_at(&self, time: Float) -> Vector4<Float> {
+        self.spline(time, |p| p.position)
+    }
+
+    /// Point the camera looks at, at a moment in time.
+    pub fn target_at(&self, time: Float) -> Vector4<Float> {
+        self.spline(time, |p| p.target)
+    }
+
+    /// View transform at a moment in time. None if the path is empty.
+    pub fn transform_at(&self, time: Float) -> Option<Matrix4<Float>> {
+        if self.is_empty() {
+            return None;
+        }
+
+        Some(Matrix4::view_transform(self.position_at(time), self.target_at(time), self.up))
+    }
+
+    // Cubic Hermite interpolation with Catmull-Rom tangents scaled for uneven
+    // spacing in time. The path is held at its ends, and wraps around smoothly
+    // if it ends where it started.
+    fn spline<F>(&self, time: Float, f: F) -> Vector4<Float>
+    where
+        F: Fn(&PathPoint) -> Vector4<Float>
+    {
+        let points = &self.points[..self.len];
+        let (first, last) = (points[0], points[self.len - 1]);
+        if time <= first.time {
+            return f(&first);
+        }
+        if time >= last.time {
+            return f(&last);
+        }
+        let i = points.iter().position(|p| p.time > time).unwrap() - 1;
+        let (p1, p2) = (points[i], points[i + 1]);
+        let closed = self.len > 2 && first.position == last.position && first.target == last.target;
+        let period = last.time - first.time;
+        let p0 = match i {
+            0 if closed => PathPoint { time: points[self.len - 2].time - period, ..points[self.len - 2] },
+            0 => p1,
+            _ => points[i - 1]
+        };
+        let p3 = match points.get(i + 2) {
+            Some(p) => *p,
+            None if closed => PathPoint { time: points[1].time + period, ..points[1] },
+            None => p2
+        };
+        let dt = p2.time - p1.time;
+        let m1 = (f(&p2) - f(&p0)) * (dt / (p2.time - p0.time));
+        let m2 = (f(&p3) - f(&p1)) * (dt / (p3.time - p1.time));
+
+        let s = (time - p1.time) / dt;
+        let (s2, s3) = (s * s, s * s * s);
+        f(&p1) * (2.0 * s3 - 3.0 * s2 + 1.0)
+            + m1 * (s3 - 2.0 * s2 + s)
+            + f(&p2) * (-2.0 * s3 + 3.0 * s2)
+            + m2 * (s3 - s2)
+    }
+}
+
+impl Default for CameraPath {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::Tuple;
+
+    #[test]
+    fn path_passes_through_its_points() {
+        let path = CameraPath::new()
+            .with_point(0.0, point(0.0, 0.0, -5.0), point(0.0, 0.0, 0.0))
+            .with_point(1.0, point(5.0, 0.0, 0.0), point(0.0, 1.0, 0.0))
+            .with_point(2.0, point(0.0, 0.0, 5.0), point(0.0, 0.0, 0.0));
+
+        assert_eq!(path.position_at(1.0), point(5.0, 0.0, 0.0));
+        assert_eq!(path.target_at(1.0), point(0.0, 1.0, 0.0));
+        assert_eq!(path.position_at(-1.0), point(0.0, 0.0, -5.0));
+        assert_eq!(path.position_at(3.0), point(0.0, 0.0, 5.0));
+    }
+
+    #[test]
+    fn dolly_moves_in_a_straight_line() {
+        let path = CameraPath::dolly(point(0.0, 1.0, -10.0), point(0.0, 1.0, -2.0), point(0.0, 0.0, 0.0), 0.0, 4.0);
+
+        assert_eq!(path.position_at(1.0), point(0.0, 1.0, -8.0));
+        assert_eq!(path.target_at(3.0), point(0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn orbit_keeps_its_distance_from_the_centre() {
+        let centre = point(1.0, 0.0, 0.0);
+        let path = CameraPath::orbit(centre, 5.0, 2.0, 0.0, 1.0);
+        for i in 0..=20 {
+            let p = path.position_at(i as Float / 20.0);
+            let r = (p.x - centre.x).hypot(p.z - centre.z);
+            assert!((r - 5.0).abs() < 0.01);
+            assert!((p.y - 2.0).abs() < 1e-9);
+        }
+        assert_eq!(path.position_at(0.25).to_5dp(), point(6.0, 2.0, 0.0));
+    }
+
+    #[test]
+    fn path_transform_looks_at_the_target() {
+        let path = CameraPath::dolly(point(0.0, 0.0, 8.0), point(0.0, 0.0, 4.0), point(0.0, 0.0, 0.0), 0.0, 1.0);
+
+        assert_eq!(path.transform_at(0.0), Some(Matrix4::translate(0.0, 0.0, -8.0)));
+        assert_eq!(CameraPath::new().transform_at(0.0), None);
+    }
+
+    #[test]
+    fn full_paths_refuse_new_points() {
+        let target = point(0.0, 0.0, 0.0);
+        let path = (0..MAX_PATH_POINTS)
+            .fold(CameraPath::new(), |path, i| path.with_point(i as Float, point(0.0, 0.0, -5.0), target));
+
+        assert!(path.try_with_point(99.0, point(0.0, 0.0, -5.0), target).is_err());
+        assert!(path.try_with_point(0.0, point(0.0, 1.0, -5.0), target).is_ok());
+    }
+}
@@ -1,6 +1,20 @@
-use crate::core::Colour;
-use image::{ImageBuffer, ImageResult, Rgb};
+use crate::FeorayError;
+use crate::core::{Colour, Encoding};
+#[cfg(feature = "image")]
+use image::codecs::hdr::{HdrDecoder, HdrEncoder};
+#[cfg(feature = "image")]
+use image::{ImageBuffer, ImageFormat, ImageResult, Rgb, Rgba};
+use std::fs;
+#[cfg(feature = "image")]
+use std::fs::File;
+use std::io;
+#[cfg(feature = "image")]
+use std::io::{BufReader, BufWriter};
 use std::ops::{Index, IndexMut};
+use std::path::Path;
+
+// Longest line allowed in a PPM file.
+const PPM_LINE_LEN: usize = 70;
 
 // Max size is 18.44 x 18.44 exapixels
 #[derive(Debug, PartialEq)]
@@ -8,6 +22,11 @@ pub struct Canvas {
     pub width: usize,
     pub height: usize,
     pub pixels: Vec<Colour>,
+    /// Opacity of each pixel, from 0.0 (transparent) to 1.0, if the canvas has
+    /// an alpha channel. Colours aren't premultiplied by it. None is opaque.
+    pub alpha: Option<Vec<f32>>,
+    /// How colours are encoded when saved to an eight bit image.
+    pub encoding: Encoding
 }
 
 impl Canvas {
@@ -27,8 +46,51 @@ impl Canvas {
         Canvas {
             width,
             height,
-            pixels: vec![colour; width * height]
+            pixels: vec![colour; width * height],
+            alpha: None,
+            encoding: Encoding::Linear
+        }
+    }
+
+    /// Sets how colours are encoded on export, e.g. Encoding::Srgb so renders
+    /// look the same as they do in other renderers.
+    pub fn with_encoding(mut self, encoding: Encoding) -> Self {
+        self.encoding = encoding;
+
+        self
+    }
+
+    /// Colour of the selected pixel as it's saved to an eight bit image, with
+    /// the canvas's encoding applied.
+    pub fn scaled_pix(&self, x: usize, y: usize) -> (u8, u8, u8) {
+        self.read_pix(x, y).encode(self.encoding).scale()
+    }
+
+    #[cfg(feature = "image")]
+    /// Loads an image file into a canvas, e.g. a reference render to diff
+    /// against or a plate to composite a render over. Channels are read as they
+    /// are stored, so eight bit images come in between 0.0 and 1.0, and EXR and
+    /// HDR images keep their full range. Images with alpha keep it.
+    pub fn from_image<P: AsRef<Path>>(path: P) -> ImageResult<Canvas> {
+        let path = path.as_ref();
+        if extension(path).as_deref() == Some("hdr") {
+            // image::open() would convert it to eight bits.
+            let decoder = HdrDecoder::new(BufReader::new(File::open(path)?))?;
+            let (width, height) = (decoder.metadata().width as usize, decoder.metadata().height as usize);
+            let mut canvas = Canvas::new(width, height, Colour::black());
+            canvas.pixels = decoder.read_image_hdr()?.iter().map(|p| Colour::new(p[0], p[1], p[2])).collect();
+            return Ok(canvas);
         }
+        let img = image::open(path)?;
+        let has_alpha = img.color().has_alpha();
+        let img = img.to_rgba32f();
+        let mut canvas = Canvas::new(img.width() as usize, img.height() as usize, Colour::black());
+        canvas.pixels = img.pixels().map(|p| Colour::new(p[0], p[1], p[2])).collect();
+        if has_alpha {
+            canvas.alpha = Some(img.pixels().map(|p| p[3]).collect());
+        }
+
+        Ok(canvas)
     }
 
     /// Returns the colour of the selected pixel
@@ -59,28 +121,244 @@ impl Canvas {
         
     }
 
+    /// Same as read_pix(), but returns an error for a pixel off the canvas.
+    pub fn try_read_pix(&self, x: usize, y: usize) -> Result<Colour, FeorayError> {
+        self.check_bounds(x, y)?;
+
+        Ok(self[(x, y)])
+    }
+
+    /// Same as write_pix(), but returns an error, leaving the canvas as it was,
+    /// for a pixel off the canvas.
+    pub fn try_write_pix(&mut self, x: usize, y: usize, colour: Colour) -> Result<(), FeorayError> {
+        self.check_bounds(x, y)?;
+        self[(x, y)] = colour;
+
+        Ok(())
+    }
+
+    /// Same as write_pix(), but a pixel off the canvas is moved onto its
+    /// nearest edge, for drawing where an odd pixel in the wrong place beats
+    /// stopping.
+    pub fn write_pix_clamped(&mut self, x: usize, y: usize, colour: Colour) {
+        let (x, y) = (x.min(self.width.saturating_sub(1)), y.min(self.height.saturating_sub(1)));
+        if self.check_bounds(x, y).is_ok() {
+            self[(x, y)] = colour;
+        }
+    }
+
+    pub(crate) fn check_bounds(&self, x: usize, y: usize) -> Result<(), FeorayError> {
+        if x < self.width && y < self.height {
+            Ok(())
+        } else {
+            Err(FeorayError::OutOfBounds { x, y, width: self.width, height: self.height })
+        }
+    }
+
+    // Position of a pixel in the pixel list. Panics for a pixel off the canvas,
+    // rather than quietly landing on the next row.
+    fn index_of(&self, x: usize, y: usize) -> usize {
+        if let Err(err) = self.check_bounds(x, y) {
+            panic!("{}", err);
+        }
+
+        self.width * y + x
+    }
+
+    /// Returns the opacity of the selected pixel. Pixels of canvases without an
+    /// alpha channel are all opaque.
+    pub fn read_alpha(&self, x: usize, y: usize) -> f32 {
+        let idx = self.index_of(x, y);
+        self.alpha.as_ref().map_or(1.0, |alpha| alpha[idx])
+    }
+
+    /// Overwrites the opacity of the selected pixel, adding an alpha channel
+    /// to the canvas if it hasn't got one.
+    pub fn write_alpha(&mut self, x: usize, y: usize, alpha: f32) {
+        let idx = self.index_of(x, y);
+        self.alpha.get_or_insert_with(|| vec![1.0; self.pixels.len()])[idx] = alpha.clamp(0.0, 1.0);
+    }
+
+    /// Same as write_alpha(), but returns an error, leaving the canvas as it
+    /// was, for a pixel off the canvas.
+    pub fn try_write_alpha(&mut self, x: usize, y: usize, alpha: f32) -> Result<(), FeorayError> {
+        self.check_bounds(x, y)?;
+        self.write_alpha(x, y, alpha);
+
+        Ok(())
+    }
+
+    pub fn has_alpha(&self) -> bool {
+        self.alpha.is_some()
+    }
+
+    #[cfg(feature = "image")]
+    /// Exports the canvas to an OpenEXR file, keeping the full floating point
+    /// colours rather than clamping them to eight bits, so the render can be
+    /// tone mapped and graded elsewhere. Alpha is kept if the canvas has it.
+    pub fn export_exr<P: AsRef<Path>>(&self, path: P) -> ImageResult<()> {
+        let (width, height) = (self.width as u32, self.height as u32);
+        if self.has_alpha() {
+            let img: ImageBuffer<Rgba<f32>, Vec<f32>> = ImageBuffer::from_fn(width, height, |x, y| {
+                let (x, y) = (x as usize, y as usize);
+                let c = self.read_pix(x, y);
+                Rgba([c.r, c.g, c.b, self.read_alpha(x, y)])
+            });
+            return img.save_with_format(path, ImageFormat::OpenExr);
+        }
+        let img: ImageBuffer<Rgb<f32>, Vec<f32>> = ImageBuffer::from_fn(width, height, |x, y| {
+            let c = self.read_pix(x as usize, y as usize);
+            Rgb([c.r, c.g, c.b])
+        });
+
+        img.save_with_format(path, ImageFormat::OpenExr)
+    }
+
+    #[cfg(feature = "image")]
+    /// Exports the canvas to a Radiance HDR (RGBE) file, keeping colours
+    /// brighter than white. Negative channels are clamped to zero, and alpha is
+    /// left out.
+    pub fn export_hdr<P: AsRef<Path>>(&self, path: P) -> ImageResult<()> {
+        let pixels: Vec<Rgb<f32>> = self.pixels.iter()
+            .map(|c| Rgb([c.r.max(0.0), c.g.max(0.0), c.b.max(0.0)]))
+            .collect();
+
+        HdrEncoder::new(BufWriter::new(File::create(path)?)).encode(&pixels, self.width, self.height)
+    }
+
+    /// Plain text (P3) PPM image of the canvas, as in the book: a header, then
+    /// each row's channels from 0 to 255, on lines of no more than 70
+    /// characters. Channels are rounded rather than truncated, so 0.5 becomes
+    /// 128. The canvas's encoding is applied, and alpha is left out.
+    /// 
+    /// # Example
+    /// 
+    /// ```ignore
+    /// let c = Canvas::new(5, 3, Colour::black());
+    /// 
+    /// assert!(c.to_ppm().starts_with("P3\n5 3\n255\n"));
+    /// ```
+    pub fn to_ppm(&self) -> String {
+        let mut ppm = format!("P3\n{} {}\n255\n", self.width, self.height);
+        for row in self.pixels.chunks(self.width.max(1)) {
+            let mut line = String::new();
+            for colour in row {
+                let colour = colour.encode(self.encoding);
+                for channel in [colour.r, colour.g, colour.b] {
+                    let value = ((channel.clamp(0.0, 1.0) * 255.0).round() as u8).to_string();
+                    if !line.is_empty() && line.len() + 1 + value.len() > PPM_LINE_LEN {
+                        ppm.push_str(&line);
+                        ppm.push('\n');
+                        line.clear();
+                    }
+                    if !line.is_empty() {
+                        line.push(' ');
+                    }
+                    line.push_str(&value);
+                }
+            }
+            ppm.push_str(&line);
+            ppm.push('\n');
+        }
+
+        ppm
+    }
+
+    /// Writes the canvas to a plain text PPM file, without going through the
+    /// `image` crate.
+    pub fn export_ppm<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        fs::write(path, self.to_ppm())
+    }
+
+    /// Eight bit RGBA bytes of the canvas, row by row from the top, with the
+    /// canvas's encoding applied. Laid out the same as a browser's ImageData,
+    /// so a render can be shown without saving it anywhere. Opaque canvases
+    /// get an alpha of 255.
+    pub fn to_rgba_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.width * self.height * 4);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let (r, g, b) = self.scaled_pix(x, y);
+                let (a, _, _) = Colour::grey(self.read_alpha(x, y)).scale();
+                bytes.extend([r, g, b, a]);
+            }
+        }
+
+        bytes
+    }
+
+    /// Lays this canvas over another of the same size, blending each pixel by
+    /// its opacity. The result takes on the other canvas's alpha, so laying a
+    /// transparent render over an opaque plate gives an opaque image.
+    /// 
+    /// # Example
+    /// 
+    /// ```ignore
+    /// let render = cam.render_with(world, &RenderSettings::default().with_alpha(true));
+    /// let plate = Canvas::new(render.width, render.height, Colour::blue());
+    /// render.over(&plate).export("composite.png").unwrap();
+    /// ```
+    pub fn over(&self, under: &Canvas) -> Canvas {
+        let mut out = Canvas::new(self.width, self.height, Colour::black()).with_encoding(self.encoding);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let (a, b) = (self.read_alpha(x, y), under.read_alpha(x, y));
+                let alpha = a + b * (1.0 - a);
+                let colour = if alpha > 0.0 {
+                    (self.read_pix(x, y) * a + under.read_pix(x, y) * (b * (1.0 - a))) / alpha
+                } else {
+                    Colour::black()
+                };
+                out.write_pix(x, y, colour);
+                if under.has_alpha() {
+                    out.write_alpha(x, y, alpha);
+                }
+            }
+        }
+
+        out
+    }
+
+    #[cfg(feature = "image")]
     /// Exports the canvas to a recognisable image format.
     /// Uses the `image` crate, which does all the heavy lifting.
     /// Can export to many popular image formats, where format is automatically deduced from the path.
     /// 
     /// Default formats are: bmp, gif, ico, jpg, jpeg, pam, png, ppm, tiff, tga
-    /// Formats that don't work: hdr, pbm, pgm, webp
+    /// High dynamic range formats: exr, hdr (see export_exr() and export_hdr())
+    /// Formats that don't work: pbm, pgm, webp
     /// 
     /// # Example
     /// 
     /// ```ignore
-    /// use image::{ImageBuffer, ImageResult, Rgb};
+    /// use image::{ImageBuffer, ImageResult, Rgb, Rgba};
     /// 
     /// let c = Canvas::new(10, 20, Colour::black());
     /// c.write_pix(2, 3, Colour::red());
     /// c.export("image.jpg").unwrap();
     /// ```
+    /// 
+    /// Canvases with an alpha channel are saved with it, as RGBA, for formats
+    /// like png that can hold one.
     pub fn export(&self, path: &str) -> ImageResult<()> {
+        match extension(Path::new(path)).as_deref() {
+            Some("exr") => return self.export_exr(path),
+            Some("hdr") => return self.export_hdr(path),
+            _ => {}
+        }
+        if self.has_alpha() {
+            let img = ImageBuffer::from_fn(self.width as u32, self.height as u32, |x, y| {
+                let (x, y) = (x as usize, y as usize);
+                let (r, g, b) = self.scaled_pix(x, y);
+                let (a, _, _) = Colour::grey(self.read_alpha(x, y)).scale();
+                Rgba([r, g, b, a])
+            });
+            return img.save(path);
+        }
         let mut img = ImageBuffer::new(self.width as u32, self.height as u32);
 
         for (x, y, pixel) in img.enumerate_pixels_mut() {
-            let colour = &self.read_pix(x as usize, y as usize);
-            let (r, g, b) = colour.scale();
+            let (r, g, b) = self.scaled_pix(x as usize, y as usize);
             *pixel = Rgb([r, g, b]);
         }
 
@@ -93,19 +371,24 @@ impl Index<(usize, usize)> for Canvas {
 
     // Returns the colour of a pixel at location on canvas[(x, y)]
     fn index(&self, (col, row): (usize, usize)) -> &Self::Output {
-        let idx = (self.width * row) + col;
-        &self.pixels[idx]
+        &self.pixels[self.index_of(col, row)]
     }
 }
 
 impl IndexMut<(usize, usize)> for Canvas {
     // Changes the colour of a pixel at location on canvas[(x, y)]
     fn index_mut(&mut self, (col, row): (usize, usize)) -> &mut Colour {
-        let idx = (self.width * row) + col;
+        let idx = self.index_of(col, row);
         &mut self.pixels[idx]
     }
 }
 
+#[cfg(feature = "image")]
+// Lower case extension of a path, to pick the image format by.
+fn extension(path: &Path) -> Option<String> {
+    path.extension().and_then(|ext| ext.to_str()).map(str::to_ascii_lowercase)
+}
+
 pub fn canvas(width: usize, height: usize) -> Canvas {
     Canvas::new(width, height, Colour::black())
 }
@@ -140,6 +423,214 @@ mod tests {
         assert_eq!(c.read_pix(2, 3), Colour::red());
     }
 
+    #[test]
+    fn pixels_off_the_canvas_are_errors() {
+        let mut c = canvas(10, 20);
+
+        // Would otherwise land on the next row.
+        assert!(matches!(c.try_write_pix(10, 0, Colour::red()), Err(FeorayError::OutOfBounds { x: 10, y: 0, .. })));
+        assert!(c.try_read_pix(0, 20).is_err());
+        assert_eq!(c.read_pix(0, 1), Colour::black());
+        c.try_write_pix(9, 19, Colour::red()).unwrap();
+        assert_eq!(c.try_read_pix(9, 19).unwrap(), Colour::red());
+    }
+
+    #[test]
+    fn clamped_writes_land_on_the_nearest_edge() {
+        let mut c = canvas(4, 3);
+        c.write_pix_clamped(7, 1, Colour::red());
+        c.write_pix_clamped(2, 9, Colour::green());
+
+        assert_eq!(c.read_pix(3, 1), Colour::red());
+        assert_eq!(c.read_pix(2, 2), Colour::green());
+        assert_eq!(c.read_pix(0, 2), Colour::black());
+    }
+
+    #[test]
+    #[should_panic(expected = "outside the 4 x 3 canvas")]
+    fn writing_off_the_end_of_a_row_panics() {
+        canvas(4, 3).write_pix(4, 0, Colour::red());
+    }
+
+    #[test]
+    fn canvases_are_opaque_until_given_alpha() {
+        let mut c = canvas(4, 2);
+
+        assert!(!c.has_alpha());
+        assert_eq!(c.read_alpha(1, 1), 1.0);
+        c.write_alpha(1, 1, 0.25);
+        assert!(c.has_alpha());
+        assert_eq!(c.read_alpha(1, 1), 0.25);
+        assert_eq!(c.read_alpha(0, 1), 1.0);
+    }
+
+    #[test]
+    fn transparent_pixels_show_what_is_under_them() {
+        let mut top = Canvas::new(3, 1, Colour::red());
+        top.write_alpha(0, 0, 0.0);
+        top.write_alpha(1, 0, 0.5);
+        let out = top.over(&Canvas::new(3, 1, Colour::blue()));
+
+        assert!(!out.has_alpha());
+        assert_eq!(out.read_pix(0, 0), Colour::blue());
+        assert_eq!(out.read_pix(1, 0), Colour::new(0.5, 0.0, 0.5));
+        assert_eq!(out.read_pix(2, 0), Colour::red());
+    }
+
+    #[cfg(feature = "image")]
+    #[test]
+    fn alpha_is_exported_as_rgba() {
+        let mut c = Canvas::new(2, 1, Colour::red());
+        c.write_alpha(1, 0, 0.0);
+        let path = std::env::temp_dir().join("feoray-canvas-alpha.png");
+        c.export(&path.to_string_lossy()).unwrap();
+        let img = image::open(&path).unwrap();
+
+        assert!(img.color().has_alpha());
+        let img = img.to_rgba8();
+        assert_eq!(img.get_pixel(0, 0), &Rgba([255, 0, 0, 255]));
+        assert_eq!(img.get_pixel(1, 0)[3], 0);
+    }
+
+    #[cfg(feature = "image")]
+    #[test]
+    fn exports_apply_the_encoding() {
+        let c = Canvas::new(1, 1, Colour::grey(0.5)).with_encoding(Encoding::Srgb);
+        let path = std::env::temp_dir().join("feoray-canvas-srgb.png");
+        c.export(&path.to_string_lossy()).unwrap();
+
+        assert_eq!(canvas(1, 1).scaled_pix(0, 0), (0, 0, 0));
+        assert_eq!(c.scaled_pix(0, 0), (187, 187, 187));
+        assert_eq!(image::open(&path).unwrap().to_rgb8().get_pixel(0, 0), &Rgb([187, 187, 187]));
+        assert!(c.to_ppm().ends_with("\n188 188 188\n"));
+    }
+
+    #[cfg(feature = "image")]
+    #[test]
+    fn exr_keeps_colours_brighter_than_white() {
+        let mut c = Canvas::new(2, 1, Colour::new(2.5, 0.5, 0.0));
+        c.write_pix(1, 0, Colour::new(0.25, 10.0, 1.0));
+        let path = std::env::temp_dir().join("feoray-canvas.exr");
+        c.export(&path.to_string_lossy()).unwrap();
+        let img = image::open(&path).unwrap().to_rgb32f();
+
+        assert_eq!(img.get_pixel(0, 0), &Rgb([2.5, 0.5, 0.0]));
+        assert_eq!(img.get_pixel(1, 0), &Rgb([0.25, 10.0, 1.0]));
+    }
+
+    #[cfg(feature = "image")]
+    #[test]
+    fn exr_keeps_alpha() {
+        let mut c = Canvas::new(1, 1, Colour::white());
+        c.write_alpha(0, 0, 0.5);
+        let path = std::env::temp_dir().join("feoray-canvas-alpha.exr");
+        c.export_exr(&path).unwrap();
+        let img = image::open(&path).unwrap();
+
+        assert!(img.color().has_alpha());
+        assert_eq!(img.to_rgba32f().get_pixel(0, 0), &Rgba([1.0, 1.0, 1.0, 0.5]));
+    }
+
+    #[cfg(feature = "image")]
+    #[test]
+    fn hdr_keeps_colours_brighter_than_white() {
+        let c = Canvas::new(3, 2, Colour::new(4.0, 1.0, 0.5));
+        let path = std::env::temp_dir().join("feoray-canvas.hdr");
+        c.export(&path.to_string_lossy()).unwrap();
+        // Opening it as an image would convert it to eight bits.
+        let decoder = image::codecs::hdr::HdrDecoder::new(io::BufReader::new(File::open(&path).unwrap())).unwrap();
+        let meta = decoder.metadata();
+        let pixels = decoder.read_image_hdr().unwrap();
+
+        assert_eq!((meta.width, meta.height), (3, 2));
+        assert_eq!(pixels[5], Rgb([4.0, 1.0, 0.5]));
+    }
+
+    #[cfg(feature = "image")]
+    #[test]
+    fn images_load_back_into_canvases() {
+        let mut c = Canvas::new(2, 1, Colour::new(1.0, 0.2, 0.0));
+        c.write_alpha(1, 0, 0.0);
+        let path = std::env::temp_dir().join("feoray-canvas-load.png");
+        c.export(&path.to_string_lossy()).unwrap();
+        let loaded = Canvas::from_image(&path).unwrap();
+
+        assert_eq!((loaded.width, loaded.height), (2, 1));
+        assert_eq!(loaded.read_pix(0, 0).to_5dp(), Colour::new(1.0, 0.2, 0.0));
+        assert_eq!(loaded.read_alpha(1, 0), 0.0);
+        assert_eq!(loaded.diff(&c).1.above_threshold, 0);
+    }
+
+    #[cfg(feature = "image")]
+    #[test]
+    fn high_dynamic_range_images_load_unclamped() {
+        let c = Canvas::new(2, 2, Colour::new(4.0, 1.0, 0.5));
+        let dir = std::env::temp_dir();
+        c.export_exr(dir.join("feoray-canvas-load.exr")).unwrap();
+        c.export_hdr(dir.join("feoray-canvas-load.hdr")).unwrap();
+
+        assert_eq!(Canvas::from_image(dir.join("feoray-canvas-load.exr")).unwrap(), c);
+        assert_eq!(Canvas::from_image(dir.join("feoray-canvas-load.hdr")).unwrap(), c);
+        assert!(Canvas::from_image(dir.join("feoray-missing.png")).is_err());
+    }
+
+    #[test]
+    fn rgba_bytes_are_laid_out_row_by_row() {
+        let mut c = Canvas::new(2, 2, Colour::grey(0.5)).with_encoding(Encoding::Srgb);
+        c.write_pix(1, 0, Colour::new(0.0, 0.0, 0.5));
+        c.write_alpha(0, 1, 0.0);
+        let bytes = c.to_rgba_bytes();
+
+        assert_eq!(bytes.len(), 16);
+        assert_eq!(&bytes[..8], &[187, 187, 187, 255, 0, 0, 187, 255]);
+        assert_eq!(bytes[11], 0);
+        assert_eq!(canvas(1, 1).to_rgba_bytes(), vec![0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn ppm_header() {
+        let ppm = canvas(5, 3).to_ppm();
+
+        assert_eq!(ppm.lines().take(3).collect::<Vec<_>>(), vec!["P3", "5 3", "255"]);
+    }
+
+    #[test]
+    fn ppm_pixel_data() {
+        let mut c = canvas(5, 3);
+        c.write_pix(0, 0, Colour::new(1.5, 0.0, 0.0));
+        c.write_pix(2, 1, Colour::new(0.0, 0.5, 0.0));
+        c.write_pix(4, 2, Colour::new(-0.5, 0.0, 1.0));
+        let ppm = c.to_ppm();
+
+        assert_eq!(ppm.lines().skip(3).collect::<Vec<_>>(), vec![
+            "255 0 0 0 0 0 0 0 0 0 0 0 0 0 0",
+            "0 0 0 0 0 0 0 128 0 0 0 0 0 0 0",
+            "0 0 0 0 0 0 0 0 0 0 0 0 0 0 255"
+        ]);
+    }
+
+    #[test]
+    fn ppm_splits_long_lines() {
+        let ppm = Canvas::new(10, 2, Colour::new(1.0, 0.8, 0.6)).to_ppm();
+
+        assert_eq!(ppm.lines().skip(3).collect::<Vec<_>>(), vec![
+            "255 204 153 255 204 153 255 204 153 255 204 153 255 204 153 255 204",
+            "153 255 204 153 255 204 153 255 204 153 255 204 153",
+            "255 204 153 255 204 153 255 204 153 255 204 153 255 204 153 255 204",
+            "153 255 204 153 255 204 153 255 204 153 255 204 153"
+        ]);
+    }
+
+    #[test]
+    fn ppm_ends_with_a_newline() {
+        let c = canvas(5, 3);
+        let path = std::env::temp_dir().join("feoray-canvas.ppm");
+        c.export_ppm(&path).unwrap();
+
+        assert!(c.to_ppm().ends_with('\n'));
+        assert_eq!(fs::read_to_string(path).unwrap(), c.to_ppm());
+    }
+
     /*#[test]
     fn write_blank_canvas() {
         let cnvs = canvas(5, 3);
@@ -1,4 +1,5 @@
 #![allow(unused)]
+use crate::{to_f32, Float, EPSILON};
 use std::ops::{Add, Div, Mul, Sub, AddAssign};
 
 #[derive(Debug, Clone, Copy, PartialOrd)]
@@ -23,6 +24,118 @@ impl Colour {
         Colour { r, g, b }
     }
 
+    /// Colour from eight bit channels, as given by most design tools.
+    /// 
+    /// # Example
+    /// 
+    /// ```ignore
+    /// assert_eq!(Colour::from_u8(255, 0, 51), Colour::new(1.0, 0.0, 0.2));
+    /// ```
+    pub fn from_u8(r: u8, g: u8, b: u8) -> Self {
+        Colour::new(r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0)
+    }
+
+    /// Colour from a hex string like "#ffaa00", "ffaa00" or the short form
+    /// "#fa0". None if the string isn't a colour.
+    /// 
+    /// # Example
+    /// 
+    /// ```ignore
+    /// assert_eq!(Colour::from_hex("#ff0033"), Some(Colour::new(1.0, 0.0, 0.2)));
+    /// ```
+    pub fn from_hex(hex: &str) -> Option<Self> {
+        let hex = hex.strip_prefix('#').unwrap_or(hex);
+        if !hex.is_ascii() {
+            return None;
+        }
+        let channel = |digits: &str| u8::from_str_radix(digits, 16).ok();
+        match hex.len() {
+            6 => Some(Colour::from_u8(channel(&hex[0..2])?, channel(&hex[2..4])?, channel(&hex[4..6])?)),
+            3 => {
+                let short = |i: usize| channel(&hex[i..i + 1]).map(|c| c * 17);
+                Some(Colour::from_u8(short(0)?, short(1)?, short(2)?))
+            },
+            _ => None
+        }
+    }
+
+    /// Hex string of the colour, like "#ffaa00". Channels are clamped between
+    /// 0.0 and 1.0 and rounded to the nearest eight bit value, so colours made
+    /// with from_hex() come back unchanged.
+    pub fn to_hex(&self) -> String {
+        let channel = |c: f32| (c.clamp(0.0, 1.0) * 255.0).round() as u8;
+        format!("#{:02x}{:02x}{:02x}", channel(self.r), channel(self.g), channel(self.b))
+    }
+
+    /// Colour from a hue in degrees (wrapping round past 360), saturation
+    /// and value, both from 0.0 to 1.0. Handy for spreading colours evenly
+    /// round the colour wheel.
+    /// 
+    /// # Example
+    /// 
+    /// ```ignore
+    /// let rainbow: Vec<Colour> = (0..6).map(|i| Colour::from_hsv(i as f32 * 60.0, 1.0, 1.0)).collect();
+    /// ```
+    pub fn from_hsv(h: f32, s: f32, v: f32) -> Self {
+        let chroma = v * s;
+        Colour::from_hue(h, chroma, v - chroma)
+    }
+
+    /// Hue in degrees (from 0.0 up to 360.0), saturation and value of the
+    /// colour. Greys have a hue of 0.0.
+    pub fn to_hsv(&self) -> (f32, f32, f32) {
+        let (hue, max, chroma) = self.hue();
+        let s = if max > 0.0 { chroma / max } else { 0.0 };
+        (hue, s, max)
+    }
+
+    /// Colour from a hue in degrees, saturation and lightness, both from 0.0
+    /// to 1.0. A lightness of 0.5 gives the most vivid colours.
+    pub fn from_hsl(h: f32, s: f32, l: f32) -> Self {
+        let chroma = (1.0 - (2.0 * l - 1.0).abs()) * s;
+        Colour::from_hue(h, chroma, l - chroma / 2.0)
+    }
+
+    /// Hue in degrees (from 0.0 up to 360.0), saturation and lightness of the
+    /// colour.
+    pub fn to_hsl(&self) -> (f32, f32, f32) {
+        let (hue, max, chroma) = self.hue();
+        let l = max - chroma / 2.0;
+        let s = if l > 0.0 && l < 1.0 { chroma / (1.0 - (2.0 * l - 1.0).abs()) } else { 0.0 };
+        (hue, s, l)
+    }
+
+    // Colour of a hue with the given chroma, lifted by m in every channel.
+    fn from_hue(h: f32, chroma: f32, m: f32) -> Self {
+        let h = h.rem_euclid(360.0) / 60.0;
+        let x = chroma * (1.0 - (h % 2.0 - 1.0).abs());
+        let (r, g, b) = match h as u32 {
+            0 => (chroma, x, 0.0),
+            1 => (x, chroma, 0.0),
+            2 => (0.0, chroma, x),
+            3 => (0.0, x, chroma),
+            4 => (x, 0.0, chroma),
+            _ => (chroma, 0.0, x)
+        };
+        Colour::new(r + m, g + m, b + m)
+    }
+
+    // Hue in degrees, brightest channel and chroma of the colour.
+    fn hue(&self) -> (f32, f32, f32) {
+        let max = self.r.max(self.g).max(self.b);
+        let chroma = max - self.r.min(self.g).min(self.b);
+        let hue = if chroma == 0.0 {
+            0.0
+        } else if max == self.r {
+            ((self.g - self.b) / chroma).rem_euclid(6.0)
+        } else if max == self.g {
+            (self.b - self.r) / chroma + 2.0
+        } else {
+            (self.r - self.g) / chroma + 4.0
+        };
+        (hue * 60.0, max, chroma)
+    }
+
     /// Scales and converts f32 colours to u8.
     /// Clamps min and max values between 0.0 and 1.0 before converting.
     /// Always gives values between 0 and 255.
@@ -82,11 +195,101 @@ impl Colour {
         Colour { r: 0.0, g: 0.0, b: 0.0 }
     }
 
-    /// Greyscale colour as a fractional percentage
+    /// Predefined colour, as in CSS
+    pub fn orange() -> Self {
+        Colour::from_u8(255, 165, 0)
+    }
+
+    /// Predefined colour, as in CSS
+    pub fn purple() -> Self {
+        Colour::from_u8(128, 0, 128)
+    }
+
+    /// Predefined colour, as in CSS
+    pub fn brown() -> Self {
+        Colour::from_u8(165, 42, 42)
+    }
+
+    /// Predefined colour, as in CSS
+    pub fn pink() -> Self {
+        Colour::from_u8(255, 192, 203)
+    }
+
+    /// Greyscale colour as a fractional percentage, in linear light: grey(0.5)
+    /// reflects half as much light as white. Exported with Encoding::Srgb it
+    /// looks lighter than half way, as screens expect colours to be encoded.
     pub fn grey(s: f32) -> Self {
         Colour { r: s, g: s, b: s }
     }
 
+    /// Light grey, grey(0.75) in linear light.
+    pub fn light_grey() -> Self {
+        Colour::grey(0.75)
+    }
+
+    /// Dark grey, grey(0.25) in linear light.
+    pub fn dark_grey() -> Self {
+        Colour::grey(0.25)
+    }
+
+    /// One of the 148 CSS named colours (most of which are X11 colours too),
+    /// like "cornflowerblue" or "Dark Orange". Case and spaces are ignored.
+    /// The values are used as they are, the same as from_hex().
+    pub fn named(name: &str) -> Option<Self> {
+        let name: String = name.chars().filter(|c| !c.is_whitespace()).collect::<String>().to_ascii_lowercase();
+        NAMED.binary_search_by(|(n, _)| n.cmp(&name.as_str()))
+            .ok()
+            .map(|i| {
+                let rgb = NAMED[i].1;
+                Colour::from_u8((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8)
+            })
+    }
+
+    /// Applies gamma correction to each channel. Negative channels are clamped to
+    /// 0.0 first. A gamma of 1.0 returns the colour unchanged.
+    pub fn gamma_correct(&self, gamma: f32) -> Self {
+        if gamma == 1.0 {
+            return *self;
+        }
+        let inv = 1.0 / gamma;
+        Colour {
+            r: self.r.max(0.0).powf(inv),
+            g: self.g.max(0.0).powf(inv),
+            b: self.b.max(0.0).powf(inv)
+        }
+    }
+
+    /// Converts a linear colour to sRGB, the encoding most images and screens
+    /// expect, using the standard curve rather than a plain gamma of 2.2.
+    /// Negative channels are clamped to 0.0 first.
+    pub fn to_srgb(&self) -> Self {
+        Colour {
+            r: srgb_channel(self.r),
+            g: srgb_channel(self.g),
+            b: srgb_channel(self.b)
+        }
+    }
+
+    /// Encodes a linear colour for saving to an eight bit image.
+    pub fn encode(&self, encoding: Encoding) -> Self {
+        match encoding {
+            Encoding::Linear => *self,
+            Encoding::Srgb => self.to_srgb(),
+            Encoding::Gamma(gamma) => self.gamma_correct(gamma)
+        }
+    }
+
+    /// Scales the colour down, keeping its hue, so no channel is brighter than
+    /// max. Colours already within the limit are returned unchanged.
+    pub fn clamp_to(&self, max: f32) -> Self {
+        let brightest = self.r.max(self.g).max(self.b);
+        if brightest <= max {
+            return *self;
+        }
+
+        *self * (max / brightest)
+    }
+
     /// Rounds a Colour to 5dp. Only useful for tests.
     pub fn to_5dp(&self) -> Self {
         let r = (self.r * 100000.0).round() / 100000.0;
@@ -94,6 +297,14 @@ impl Colour {
         let b = (self.b * 100000.0).round() / 100000.0;
         Colour { r, g, b }
     }
+
+    /// Whether every channel is within EPSILON of another colour's, as colours
+    /// worked out from geometry are no more precise than Float. Only useful
+    /// for tests.
+    pub fn approx_eq(&self, other: &Colour) -> bool {
+        let diff = *self - *other;
+        diff.r.abs().max(diff.g.abs()).max(diff.b.abs()) < to_f32(EPSILON)
+    }
 }
 
 impl Add for Colour {
@@ -164,10 +375,12 @@ impl Mul<f32> for Colour {
     }
 }
 
-impl Mul<f64> for Colour {
+// With the f32 feature, Float is f32 and this is the impl above.
+#[cfg(not(feature = "f32"))]
+impl Mul<Float> for Colour {
     type Output = Colour;
 
-    fn mul(self, rhs: f64) -> Self::Output {
+    fn mul(self, rhs: Float) -> Self::Output {
         Colour {
             r: self.r * rhs as f32,
             g: self.g * rhs as f32,
@@ -188,16 +401,77 @@ pub fn colour(r: f32, g: f32, b: f32) -> Colour {
     Colour::new(r, g, b)
 }
 
-fn scale_channel(channel: f32) -> u8 {
-    let channel = if channel < 0.0 {
-        0.0
-    } else if channel > 1.0 {
-        1.0
+/// How linear colours are encoded when a canvas is saved to an eight bit
+/// image. High dynamic range formats are always saved linear.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Encoding {
+    /// Saved as they are, which looks too dark on most screens.
+    #[default]
+    Linear,
+    /// Standard sRGB curve.
+    Srgb,
+    /// Plain power curve with the given gamma, e.g. 2.2.
+    Gamma(f32)
+}
+
+// CSS named colours, sorted by name for searching.
+const NAMED: [(&str, u32); 148] = [
+    ("aliceblue", 0xf0f8ff), ("antiquewhite", 0xfaebd7), ("aqua", 0x00ffff),
+    ("aquamarine", 0x7fffd4), ("azure", 0xf0ffff), ("beige", 0xf5f5dc), ("bisque", 0xffe4c4),
+    ("black", 0x000000), ("blanchedalmond", 0xffebcd), ("blue", 0x0000ff), ("blueviolet", 0x8a2be2),
+    ("brown", 0xa52a2a), ("burlywood", 0xdeb887), ("cadetblue", 0x5f9ea0), ("chartreuse", 0x7fff00),
+    ("chocolate", 0xd2691e), ("coral", 0xff7f50), ("cornflowerblue", 0x6495ed),
+    ("cornsilk", 0xfff8dc), ("crimson", 0xdc143c), ("cyan", 0x00ffff), ("darkblue", 0x00008b),
+    ("darkcyan", 0x008b8b), ("darkgoldenrod", 0xb8860b), ("darkgray", 0xa9a9a9),
+    ("darkgreen", 0x006400), ("darkgrey", 0xa9a9a9), ("darkkhaki", 0xbdb76b),
+    ("darkmagenta", 0x8b008b), ("darkolivegreen", 0x556b2f), ("darkorange", 0xff8c00),
+    ("darkorchid", 0x9932cc), ("darkred", 0x8b0000), ("darksalmon", 0xe9967a),
+    ("darkseagreen", 0x8fbc8f), ("darkslateblue", 0x483d8b), ("darkslategray", 0x2f4f4f),
+    ("darkslategrey", 0x2f4f4f), ("darkturquoise", 0x00ced1), ("darkviolet", 0x9400d3),
+    ("deeppink", 0xff1493), ("deepskyblue", 0x00bfff), ("dimgray", 0x696969), ("dimgrey", 0x696969),
+    ("dodgerblue", 0x1e90ff), ("firebrick", 0xb22222), ("floralwhite", 0xfffaf0),
+    ("forestgreen", 0x228b22), ("fuchsia", 0xff00ff), ("gainsboro", 0xdcdcdc),
+    ("ghostwhite", 0xf8f8ff), ("gold", 0xffd700), ("goldenrod", 0xdaa520), ("gray", 0x808080),
+    ("green", 0x008000), ("greenyellow", 0xadff2f), ("grey", 0x808080), ("honeydew", 0xf0fff0),
+    ("hotpink", 0xff69b4), ("indianred", 0xcd5c5c), ("indigo", 0x4b0082), ("ivory", 0xfffff0),
+    ("khaki", 0xf0e68c), ("lavender", 0xe6e6fa), ("lavenderblush", 0xfff0f5),
+    ("lawngreen", 0x7cfc00), ("lemonchiffon", 0xfffacd), ("lightblue", 0xadd8e6),
+    ("lightcoral", 0xf08080), ("lightcyan", 0xe0ffff), ("lightgoldenrodyellow", 0xfafad2),
+    ("lightgray", 0xd3d3d3), ("lightgreen", 0x90ee90), ("lightgrey", 0xd3d3d3),
+    ("lightpink", 0xffb6c1), ("lightsalmon", 0xffa07a), ("lightseagreen", 0x20b2aa),
+    ("lightskyblue", 0x87cefa), ("lightslategray", 0x778899), ("lightslategrey", 0x778899),
+    ("lightsteelblue", 0xb0c4de), ("lightyellow", 0xffffe0), ("lime", 0x00ff00),
+    ("limegreen", 0x32cd32), ("linen", 0xfaf0e6), ("magenta", 0xff00ff), ("maroon", 0x800000),
+    ("mediumaquamarine", 0x66cdaa), ("mediumblue", 0x0000cd), ("mediumorchid", 0xba55d3),
+    ("mediumpurple", 0x9370db), ("mediumseagreen", 0x3cb371), ("mediumslateblue", 0x7b68ee),
+    ("mediumspringgreen", 0x00fa9a), ("mediumturquoise", 0x48d1cc), ("mediumvioletred", 0xc71585),
+    ("midnightblue", 0x191970), ("mintcream", 0xf5fffa), ("mistyrose", 0xffe4e1),
+    ("moccasin", 0xffe4b5), ("navajowhite", 0xffdead), ("navy", 0x000080), ("oldlace", 0xfdf5e6),
+    ("olive", 0x808000), ("olivedrab", 0x6b8e23), ("orange", 0xffa500), ("orangered", 0xff4500),
+    ("orchid", 0xda70d6), ("palegoldenrod", 0xeee8aa), ("palegreen", 0x98fb98),
+    ("paleturquoise", 0xafeeee), ("palevioletred", 0xdb7093), ("papayawhip", 0xffefd5),
+    ("peachpuff", 0xffdab9), ("peru", 0xcd853f), ("pink", 0xffc0cb), ("plum", 0xdda0dd),
+    ("powderblue", 0xb0e0e6), ("purple", 0x800080), ("rebeccapurple", 0x663399), ("red", 0xff0000),
+    ("rosybrown", 0xbc8f8f), ("royalblue", 0x4169e1), ("saddlebrown", 0x8b4513),
+    ("salmon", 0xfa8072), ("sandybrown", 0xf4a460), ("seagreen", 0x2e8b57), ("seashell", 0xfff5ee),
+    ("sienna", 0xa0522d), ("silver", 0xc0c0c0), ("skyblue", 0x87ceeb), ("slateblue", 0x6a5acd),
+    ("slategray", 0x708090), ("slategrey", 0x708090), ("snow", 0xfffafa), ("springgreen", 0x00ff7f),
+    ("steelblue", 0x4682b4), ("tan", 0xd2b48c), ("teal", 0x008080), ("thistle", 0xd8bfd8),
+    ("tomato", 0xff6347), ("turquoise", 0x40e0d0), ("violet", 0xee82ee), ("wheat", 0xf5deb3),
+    ("white", 0xffffff), ("whitesmoke", 0xf5f5f5), ("yellow", 0xffff00), ("yellowgreen", 0x9acd32),
+];
+
+fn srgb_channel(channel: f32) -> f32 {
+    let c = channel.max(0.0);
+    if c <= 0.003_130_8 {
+        c * 12.92
     } else {
-        channel
-    };
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
 
-    (channel * 255.0) as u8
+fn scale_channel(channel: f32) -> u8 {
+    (channel.clamp(0.0, 1.0) * 255.0) as u8
 }
 
 #[cfg(test)]
@@ -259,4 +533,92 @@ mod tests {
 
         assert_eq!(c.scale(), (0, 102, 255));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn linear_colours_encoded_as_srgb() {
+        let c = Colour::new(0.0, 0.002, 0.5).to_srgb();
+
+        assert_eq!(c.r, 0.0);
+        assert_approx_eq!(c.g, 0.02584);
+        assert_approx_eq!(c.b, 0.73536, 1e-5);
+        assert_eq!(Colour::white().to_srgb().to_5dp(), Colour::white());
+        assert_eq!(Colour::grey(0.5).encode(Encoding::Gamma(2.0)), Colour::grey(0.5f32.sqrt()));
+        assert_eq!(Colour::grey(0.5).encode(Encoding::Linear), Colour::grey(0.5));
+    }
+
+    #[test]
+    fn colours_from_eight_bit_channels() {
+        assert_eq!(Colour::from_u8(255, 0, 51), Colour::new(1.0, 0.0, 0.2));
+    }
+
+    #[test]
+    fn colours_from_hex_strings() {
+        assert_eq!(Colour::from_hex("#ff0033"), Some(Colour::new(1.0, 0.0, 0.2)));
+        assert_eq!(Colour::from_hex("FF0033"), Colour::from_hex("#ff0033"));
+        assert_eq!(Colour::from_hex("#f03"), Colour::from_hex("#ff0033"));
+        assert_eq!(Colour::from_hex("#ff00"), None);
+        assert_eq!(Colour::from_hex("#gg0033"), None);
+        assert_eq!(Colour::from_hex("#ff00é"), None);
+    }
+
+    #[test]
+    fn colours_to_hex_strings() {
+        assert_eq!(Colour::new(1.5, 0.0, 0.2).to_hex(), "#ff0033");
+        assert_eq!(Colour::from_hex("#a1b2c3").unwrap().to_hex(), "#a1b2c3");
+    }
+
+    #[test]
+    fn colours_from_hsv() {
+        assert_eq!(Colour::from_hsv(0.0, 1.0, 1.0), Colour::red());
+        assert_eq!(Colour::from_hsv(120.0, 1.0, 1.0), Colour::green());
+        assert_eq!(Colour::from_hsv(600.0, 1.0, 1.0), Colour::new(0.0, 0.0, 1.0));
+        assert_eq!(Colour::from_hsv(-60.0, 1.0, 0.5), Colour::new(0.5, 0.0, 0.5));
+        assert_eq!(Colour::from_hsv(90.0, 0.0, 0.25), Colour::grey(0.25));
+    }
+
+    #[test]
+    fn colours_to_hsv_and_back() {
+        let c = Colour::new(0.2, 0.6, 0.4);
+        let (h, s, v) = c.to_hsv();
+
+        assert_approx_eq!(h, 150.0, 1e-4);
+        assert_approx_eq!(s, 2.0 / 3.0);
+        assert_approx_eq!(v, 0.6);
+        assert_eq!(Colour::from_hsv(h, s, v).to_5dp(), c);
+    }
+
+    #[test]
+    fn colours_to_hsl_and_back() {
+        assert_eq!(Colour::from_hsl(240.0, 1.0, 0.5), Colour::new(0.0, 0.0, 1.0));
+        assert_eq!(Colour::from_hsl(0.0, 1.0, 1.0), Colour::white());
+        let c = Colour::new(0.9, 0.3, 0.5);
+        let (h, s, l) = c.to_hsl();
+
+        assert_approx_eq!(l, 0.6);
+        assert_eq!(Colour::from_hsl(h, s, l).to_5dp(), c);
+    }
+
+    #[test]
+    fn predefined_colours_are_full_strength() {
+        assert_eq!(Colour::blue(), Colour::new(0.0, 0.0, 1.0));
+        assert_eq!(Colour::red() + Colour::green() + Colour::blue(), Colour::white());
+        assert_eq!(Colour::cyan(), Colour::green() + Colour::blue());
+        assert_eq!(Colour::magenta(), Colour::red() + Colour::blue());
+        assert_eq!(Colour::yellow(), Colour::red() + Colour::green());
+    }
+
+    #[test]
+    fn colours_by_name() {
+        assert_eq!(Colour::named("orange"), Some(Colour::orange()));
+        assert_eq!(Colour::named("Rebecca Purple"), Colour::from_hex("#663399"));
+        assert_eq!(Colour::named("white"), Some(Colour::white()));
+        assert_eq!(Colour::named("blurple"), None);
+        assert!(NAMED.windows(2).all(|w| w[0].0 < w[1].0));
+    }
+
+    #[test]
+    fn clamping_keeps_the_hue() {
+        assert_eq!(Colour::new(4.0, 2.0, 0.0).clamp_to(1.0), Colour::new(1.0, 0.5, 0.0));
+        assert_eq!(Colour::new(0.5, 0.2, 0.1).clamp_to(1.0), Colour::new(0.5, 0.2, 0.1));
+    }
+}
@@ -0,0 +1,196 @@
+use crate::core::{Aovs, Canvas, Colour};
+
+// B3 spline weights for the five taps either side of, and at, the centre.
+const KERNEL: [f32; 5] = [1.0 / 16.0, 1.0 / 4.0, 3.0 / 8.0, 1.0 / 4.0, 1.0 / 16.0];
+
+/// Edge-aware (à-trous) filter for cleaning up the noise in renders with few
+/// samples per pixel. Each pass blurs with a wider spaced kernel, but pixels
+/// only take from neighbours with a similar colour, normal and depth, so edges
+/// and the boundaries between objects stay sharp. Works best with path traced
+/// or soft shadowed renders, guided by their AOVs.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Denoiser {
+    /// Number of passes. Each one doubles the spacing of the kernel, so five
+    /// passes reach 31 pixels across.
+    pub iterations: usize,
+    /// How different colours can be and still be blended. Higher blurs more.
+    pub colour_sigma: f32,
+    /// How sharply a change in normal stops the blur. Higher keeps more edges.
+    pub normal_power: f32,
+    /// How different depths can be, relative to the depth, and still be blended.
+    pub depth_sigma: f32
+}
+
+impl Denoiser {
+    pub fn with_iterations(mut self, iterations: usize) -> Self {
+        self.iterations = iterations;
+
+        self
+    }
+
+    pub fn with_colour_sigma(mut self, colour_sigma: f32) -> Self {
+        self.colour_sigma = colour_sigma;
+
+        self
+    }
+
+    pub fn with_normal_power(mut self, normal_power: f32) -> Self {
+        self.normal_power = normal_power;
+
+        self
+    }
+
+    pub fn with_depth_sigma(mut self, depth_sigma: f32) -> Self {
+        self.depth_sigma = depth_sigma;
+
+        self
+    }
+
+    /// Denoised copy of the canvas, guided by the normal and depth passes of
+    /// the same view, which must be the same size.
+    pub fn apply(&self, canvas: &Canvas, aux: &Aovs) -> Canvas {
+        let (width, height) = (canvas.width, canvas.height);
+        let mut pixels = canvas.pixels.clone();
+        for i in 0..self.iterations {
+            let step = 1 << i;
+            // Noise gets smaller every pass, so colours have to be closer.
+            let colour_sigma = self.colour_sigma / (1 << i) as f32;
+            let mut out = vec![Colour::black(); pixels.len()];
+            for y in 0..height {
+                for x in 0..width {
+                    let p = y * width + x;
+                    let mut total = Colour::black();
+                    let mut weights = 0.0;
+                    for (ky, hy) in KERNEL.iter().enumerate() {
+                        for (kx, hx) in KERNEL.iter().enumerate() {
+                            let qx = x as isize + (kx as isize - 2) * step;
+                            let qy = y as isize + (ky as isize - 2) * step;
+                            if qx < 0 || qy < 0 || qx >= width as isize || qy >= height as isize {
+                                continue;
+                            }
+                            let q = qy as usize * width + qx as usize;
+                            let w = hx * hy * self.similarity(p, q, &pixels, aux, colour_sigma);
+                            total += pixels[q] * w;
+                            weights += w;
+                        }
+                    }
+                    out[p] = if weights > 0.0 { total / weights } else { pixels[p] };
+                }
+            }
+            pixels = out;
+        }
+        let mut out = Canvas::new(width, height, Colour::black()).with_encoding(canvas.encoding);
+        out.pixels = pixels;
+        out.alpha.clone_from(&canvas.alpha);
+
+        out
+    }
+
+    // How much pixel q should count towards pixel p, from 0.0 to 1.0.
+    fn similarity(&self, p: usize, q: usize, pixels: &[Colour], aux: &Aovs, colour_sigma: f32) -> f32 {
+        let (zp, zq) = (aux.depth.pixels[p].r, aux.depth.pixels[q].r);
+        // Pixels where nothing was hit only blend with each other.
+        match (zp > 0.0, zq > 0.0) {
+            (false, false) => return 1.0,
+            (true, true) => {},
+            _ => return 0.0
+        }
+        let (np, nq) = (aux.normal.pixels[p], aux.normal.pixels[q]);
+        let normal = (np.r * nq.r + np.g * nq.g + np.b * nq.b).max(0.0).powf(self.normal_power);
+        let depth = (-(zp - zq).abs() / (self.depth_sigma * zp)).exp();
+        let d = pixels[p] - pixels[q];
+        let colour = (-(d.r * d.r + d.g * d.g + d.b * d.b) / (colour_sigma * colour_sigma)).exp();
+
+        normal * depth * colour
+    }
+}
+
+impl Default for Denoiser {
+    fn default() -> Self {
+        Denoiser { iterations: 5, colour_sigma: 0.5, normal_power: 64.0, depth_sigma: 0.05 }
+    }
+}
+
+impl Canvas {
+    /// Denoised copy of the canvas with the default Denoiser, guided by the
+    /// auxiliary buffers rendered for the same view with Camera::render_aovs().
+    /// 
+    /// # Example
+    /// 
+    /// ```ignore
+    /// let settings = RenderSettings::for_world(&world).with_integrator(Integrator::PathTracer);
+    /// let noisy = cam.render_with(world.clone(), &settings);
+    /// let clean = noisy.denoise(&cam.render_aovs(&world));
+    /// ```
+    pub fn denoise(&self, aux: &Aovs) -> Canvas {
+        Denoiser::default().apply(self, aux)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::to_f32;
+    use crate::core::{point, vector, Camera, Rng, Transform, World};
+    use crate::consts::PI;
+    use crate::primitives::Object;
+    use nalgebra::Matrix4;
+
+    // Camera looking at a floor with a sphere sitting on it.
+    fn scene() -> (Camera, World) {
+        let cam = Camera::new(32, 32, PI / 3.0)
+            .with_transform(Matrix4::view_transform(point(0.0, 1.5, -5.0), point(0.0, 0.5, 0.0), vector(0.0, 1.0, 0.0)));
+        let w = World::default()
+            .with_object(Object::new_plane())
+            .with_object(Object::new_sphere().with_transform(Matrix4::translate(0.0, 1.0, 0.0)));
+        (cam, w)
+    }
+
+    fn variance(pixels: &[Colour]) -> f32 {
+        let mean = pixels.iter().map(|c| c.r).sum::<f32>() / pixels.len() as f32;
+        pixels.iter().map(|c| (c.r - mean).powi(2)).sum::<f32>() / pixels.len() as f32
+    }
+
+    #[test]
+    fn noise_is_smoothed_out() {
+        let (cam, w) = scene();
+        let aux = cam.render_aovs(&w);
+        let mut rng = Rng::new(5);
+        let mut noisy = Canvas::new(32, 32, Colour::black());
+        for p in &mut noisy.pixels {
+            *p = Colour::grey(0.5 + (to_f32(rng.next_float()) - 0.5) * 0.4);
+        }
+        let clean = noisy.denoise(&aux);
+
+        assert!(variance(&clean.pixels) < variance(&noisy.pixels) / 4.0);
+    }
+
+    #[test]
+    fn edges_between_objects_stay_sharp() {
+        let (cam, w) = scene();
+        let aux = cam.render_aovs(&w);
+        // Sphere white, floor black, and the sky above the floor grey.
+        let ids = &aux.object_id;
+        let mut image = Canvas::new(32, 32, Colour::grey(0.5));
+        for (i, p) in image.pixels.iter_mut().enumerate() {
+            if aux.depth.pixels[i].r > 0.0 {
+                *p = if ids.pixels[i] == aux.object_id.pixels[16 * 32 + 16] { Colour::white() } else { Colour::black() };
+            }
+        }
+        let clean = image.denoise(&aux);
+        let worst = clean.pixels.iter()
+            .zip(&image.pixels)
+            .map(|(a, b)| (a.r - b.r).abs())
+            .fold(0.0, f32::max);
+
+        assert!(worst < 0.05);
+    }
+
+    #[test]
+    fn no_iterations_leaves_the_canvas_alone() {
+        let (cam, w) = scene();
+        let image = cam.render(w.clone());
+
+        assert_eq!(Denoiser::default().with_iterations(0).apply(&image, &cam.render_aovs(&w)), image);
+    }
+}
@@ -0,0 +1,143 @@
+use crate::{to_f64, Float};
+use crate::core::Rng;
+use nalgebra::Vector4;
+
+/// Kind of bounce a ray takes off a surface.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bounce {
+    Reflection,
+    Refraction,
+    Diffuse
+}
+
+/// Bounces a ray has left before it's cut off: in total, and of each kind on
+/// its own. Glass stacks need plenty of refractions, for example, without also
+/// letting mirrors bounce light back and forth forever. Past a given number of
+/// bounces, rays can also be cut short at random (Russian roulette), which
+/// saves tracing paths that would add little to the image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RayDepth {
+    pub total: u8,
+    pub reflection: u8,
+    pub refraction: u8,
+    pub diffuse: u8,
+    /// Bounces after which rays are cut short at random. None never does.
+    pub roulette: Option<u8>,
+    /// Bounces taken so far.
+    pub bounces: u8
+}
+
+impl RayDepth {
+    /// The same limit for every kind of bounce, so only the total counts.
+    pub fn new(total: u8) -> Self {
+        RayDepth { total, reflection: total, refraction: total, diffuse: total, roulette: None, bounces: 0 }
+    }
+
+    pub fn with_reflection(mut self, reflection: u8) -> Self {
+        self.reflection = reflection;
+
+        self
+    }
+
+    pub fn with_refraction(mut self, refraction: u8) -> Self {
+        self.refraction = refraction;
+
+        self
+    }
+
+    pub fn with_diffuse(mut self, diffuse: u8) -> Self {
+        self.diffuse = diffuse;
+
+        self
+    }
+
+    /// Cuts rays short at random once they've taken this many bounces.
+    pub fn with_roulette(mut self, after: u8) -> Self {
+        self.roulette = Some(after);
+
+        self
+    }
+
+    /// Bounces left after one more of the given kind, or None if the ray can't
+    /// go any further.
+    pub fn bounce(&self, kind: Bounce) -> Option<RayDepth> {
+        let left = match kind {
+            Bounce::Reflection => self.reflection,
+            Bounce::Refraction => self.refraction,
+            Bounce::Diffuse => self.diffuse
+        };
+        if self.total == 0 || left == 0 {
+            return None;
+        }
+        let mut next = *self;
+        next.total -= 1;
+        next.bounces = next.bounces.saturating_add(1);
+        match kind {
+            Bounce::Reflection => next.reflection -= 1,
+            Bounce::Refraction => next.refraction -= 1,
+            Bounce::Diffuse => next.diffuse -= 1
+        }
+
+        Some(next)
+    }
+
+    /// Whether rays this deep are subject to Russian roulette.
+    pub fn in_roulette(&self) -> bool {
+        matches!(self.roulette, Some(after) if self.bounces >= after)
+    }
+
+    /// Chance a ray carrying the given weight (reflectivity, transparency or
+    /// path throughput) survives the roulette. Dim rays are more likely to be
+    /// cut, and survivors are scaled up by one over this to make up for the
+    /// rest. Always 1.0 before roulette starts.
+    pub fn survival(&self, weight: f32) -> f32 {
+        if self.in_roulette() {
+            weight.clamp(0.05, 1.0)
+        } else {
+            1.0
+        }
+    }
+}
+
+impl From<u8> for RayDepth {
+    fn from(total: u8) -> Self {
+        RayDepth::new(total)
+    }
+}
+
+/// Random numbers seeded by a point on a surface, for the Whitted tracer to
+/// make random choices that are the same every time the scene is rendered.
+/// Each kind of choice uses its own seed, so they don't all go the same way.
+pub(crate) fn rng_at(p: Vector4<Float>, time: Float, seed: u64) -> Rng {
+    let bits = |x: Float| to_f64(x).to_bits();
+    let stream = bits(p.x) ^ bits(p.y).rotate_left(21) ^ bits(p.z).rotate_left(42) ^ bits(time);
+
+    Rng::for_stream(seed, stream)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bounces_use_up_the_depth() {
+        let d = RayDepth::new(2).with_refraction(1);
+        let once = d.bounce(Bounce::Refraction).unwrap();
+
+        assert_eq!(once.total, 1);
+        assert_eq!(once.bounces, 1);
+        assert_eq!(once.bounce(Bounce::Refraction), None);
+        assert_eq!(once.bounce(Bounce::Reflection).unwrap().total, 0);
+        assert_eq!(RayDepth::new(0).bounce(Bounce::Diffuse), None);
+    }
+
+    #[test]
+    fn roulette_starts_after_its_depth() {
+        let d = RayDepth::new(5).with_roulette(1);
+
+        assert_eq!(d.survival(0.5), 1.0);
+        assert_eq!(d.bounce(Bounce::Diffuse).unwrap().survival(0.5), 0.5);
+        assert_eq!(d.bounce(Bounce::Diffuse).unwrap().survival(0.0), 0.05);
+        assert_eq!(RayDepth::new(5).bounce(Bounce::Diffuse).unwrap().survival(0.5), 1.0);
+    }
+}
@@ -0,0 +1,105 @@
+use crate::core::{Canvas, Colour};
+
+// Differences no bigger than a step of an eight bit image aren't counted.
+const THRESHOLD: f32 = 1.0 / 255.0;
+
+/// How far apart two canvases are, from Canvas::diff().
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DiffStats {
+    pub pixels: usize,
+    /// Largest difference in any channel of any pixel.
+    pub max_error: f32,
+    /// Root mean square difference over every channel of every pixel.
+    pub rmse: f32,
+    /// Pixels with a channel further off than the threshold.
+    pub above_threshold: usize,
+    pub threshold: f32
+}
+
+impl DiffStats {
+    /// Whether the canvases match to within the threshold everywhere.
+    pub fn matches(&self) -> bool {
+        self.above_threshold == 0
+    }
+}
+
+impl Canvas {
+    /// Compares two canvases of the same size, for regression tests and before
+    /// and after comparisons. Returns an image of the differences, with each
+    /// channel scaled so the largest difference is white, and a summary of
+    /// them. Pixels more than one eight bit step apart are counted as
+    /// differing; see diff_above() to pick another threshold.
+    /// 
+    /// # Panics
+    /// 
+    /// If the canvases aren't the same size.
+    /// 
+    /// # Example
+    /// 
+    /// ```ignore
+    /// let (image, stats) = before.diff(&after);
+    /// println!("{} pixels changed, rmse {}", stats.above_threshold, stats.rmse);
+    /// image.export("diff.png").unwrap();
+    /// ```
+    pub fn diff(&self, other: &Canvas) -> (Canvas, DiffStats) {
+        self.diff_above(other, THRESHOLD)
+    }
+
+    /// Same as diff(), counting pixels with a channel differing by more than
+    /// threshold.
+    pub fn diff_above(&self, other: &Canvas, threshold: f32) -> (Canvas, DiffStats) {
+        assert_eq!((self.width, self.height), (other.width, other.height), "canvases must be the same size");
+        let errors: Vec<Colour> = self.pixels.iter()
+            .zip(&other.pixels)
+            .map(|(a, b)| Colour::new((a.r - b.r).abs(), (a.g - b.g).abs(), (a.b - b.b).abs()))
+            .collect();
+        let max_error = errors.iter().map(|e| e.r.max(e.g).max(e.b)).fold(0.0, f32::max);
+        let squares: f32 = errors.iter().map(|e| e.r * e.r + e.g * e.g + e.b * e.b).sum();
+        let rmse = (squares / (3 * errors.len()).max(1) as f32).sqrt();
+        let above_threshold = errors.iter().filter(|e| e.r.max(e.g).max(e.b) > threshold).count();
+        let mut image = Canvas::new(self.width, self.height, Colour::black());
+        if max_error > 0.0 {
+            image.pixels = errors.iter().map(|e| *e / max_error).collect();
+        }
+
+        (image, DiffStats { pixels: errors.len(), max_error, rmse, above_threshold, threshold })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn identical_canvases_match() {
+        let c = Canvas::new(3, 2, Colour::grey(0.5));
+        let (image, stats) = c.diff(&Canvas::new(3, 2, Colour::grey(0.5)));
+
+        assert!(stats.matches());
+        assert_eq!((stats.max_error, stats.rmse, stats.pixels), (0.0, 0.0, 6));
+        assert_eq!(image.pixels, vec![Colour::black(); 6]);
+    }
+
+    #[test]
+    fn differences_are_measured_and_drawn() {
+        let a = Canvas::new(2, 2, Colour::black());
+        let mut b = Canvas::new(2, 2, Colour::black());
+        b.write_pix(1, 0, Colour::new(0.5, 0.0, 0.0));
+        b.write_pix(0, 1, Colour::new(0.0, 0.001, 0.0));
+        let (image, stats) = a.diff(&b);
+
+        assert_eq!(stats.max_error, 0.5);
+        assert_eq!(stats.above_threshold, 1);
+        assert_approx_eq!(stats.rmse, ((0.25 + 0.000001) / 12.0f32).sqrt());
+        assert_eq!(image.read_pix(1, 0), Colour::red());
+        assert_eq!(image.read_pix(0, 0), Colour::black());
+        assert_eq!(a.diff_above(&b, 0.0).1.above_threshold, 2);
+    }
+
+    #[test]
+    #[should_panic]
+    fn canvases_must_be_the_same_size() {
+        Canvas::new(2, 2, Colour::black()).diff(&Canvas::new(2, 3, Colour::black()));
+    }
+}
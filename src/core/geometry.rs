@@ -0,0 +1,168 @@
+use crate::Float;
+use crate::core::{Ray, Tuple};
+use nalgebra::Vector4;
+
+// Shared geometry for primitives and acceleration structures, so each shape
+// doesn't need its own copy. Points and vectors are the usual Vector4s.
+
+/// Point on the segment from a to b nearest to p.
+pub fn closest_point_on_segment(p: Vector4<Float>, a: Vector4<Float>, b: Vector4<Float>) -> Vector4<Float> {
+    let ab = b - a;
+    let length2 = ab.dot(&ab);
+    if length2 == 0.0 {
+        return a;
+    }
+    let t = ((p - a).dot(&ab) / length2).clamp(0.0, 1.0);
+
+    a + ab * t
+}
+
+/// Point on the triangle abc, edges and inside, nearest to p.
+pub fn closest_point_on_triangle(p: Vector4<Float>, a: Vector4<Float>, b: Vector4<Float>, c: Vector4<Float>) -> Vector4<Float> {
+    // Works out which corner, edge or face p is closest to from where it sits
+    // against each of them, as in Ericson's Real-Time Collision Detection.
+    let (ab, ac, ap) = (b - a, c - a, p - a);
+    let (d1, d2) = (ab.dot(&ap), ac.dot(&ap));
+    if d1 <= 0.0 && d2 <= 0.0 {
+        return a;
+    }
+    let bp = p - b;
+    let (d3, d4) = (ab.dot(&bp), ac.dot(&bp));
+    if d3 >= 0.0 && d4 <= d3 {
+        return b;
+    }
+    let vc = d1 * d4 - d3 * d2;
+    if vc <= 0.0 && d1 >= 0.0 && d3 <= 0.0 {
+        return a + ab * (d1 / (d1 - d3));
+    }
+    let cp = p - c;
+    let (d5, d6) = (ab.dot(&cp), ac.dot(&cp));
+    if d6 >= 0.0 && d5 <= d6 {
+        return c;
+    }
+    let vb = d5 * d2 - d1 * d6;
+    if vb <= 0.0 && d2 >= 0.0 && d6 <= 0.0 {
+        return a + ac * (d2 / (d2 - d6));
+    }
+    let va = d3 * d6 - d5 * d4;
+    if va <= 0.0 && d4 - d3 >= 0.0 && d5 - d6 >= 0.0 {
+        return b + (c - b) * ((d4 - d3) / ((d4 - d3) + (d5 - d6)));
+    }
+    let total = va + vb + vc;
+    if total == 0.0 {
+        // A triangle with no area, so it's one of the edges after all.
+        return [closest_point_on_segment(p, a, b), closest_point_on_segment(p, b, c)]
+            .into_iter()
+            .min_by(|x, y| (x - p).magnitude().total_cmp(&(y - p).magnitude()))
+            .unwrap_or(a);
+    }
+
+    a + ab * (vb / total) + ac * (vc / total)
+}
+
+/// Area of the triangle abc.
+pub fn triangle_area(a: Vector4<Float>, b: Vector4<Float>, c: Vector4<Float>) -> Float {
+    (b - a).xprod(&(c - a)).magnitude() / 2.0
+}
+
+/// Barycentric coordinates (u, v, w) of p in the triangle abc, so that p is
+/// u * a + v * b + w * c. They add up to 1 and are all between 0 and 1 for
+/// points inside. Points off the triangle's plane are projected onto it, and a
+/// triangle with no area gives None.
+pub fn barycentric(p: Vector4<Float>, a: Vector4<Float>, b: Vector4<Float>, c: Vector4<Float>) -> Option<(Float, Float, Float)> {
+    let (ab, ac, ap) = (b - a, c - a, p - a);
+    let (d00, d01, d11) = (ab.dot(&ab), ab.dot(&ac), ac.dot(&ac));
+    let (d20, d21) = (ap.dot(&ab), ap.dot(&ac));
+    let denom = d00 * d11 - d01 * d01;
+    if denom == 0.0 {
+        return None;
+    }
+    let v = (d11 * d20 - d01 * d21) / denom;
+    let w = (d00 * d21 - d01 * d20) / denom;
+
+    Some((1.0 - v - w, v, w))
+}
+
+/// Distances along a ray where it goes into and comes out of the axis-aligned
+/// box from min to max, by the slab test, or None if it misses. The entry is
+/// negative when the ray starts inside the box, and a box entirely behind the
+/// ray is a miss.
+pub fn ray_aabb(ray: &Ray, min: Vector4<Float>, max: Vector4<Float>) -> Option<(Float, Float)> {
+    let (mut t0, mut t1) = (Float::NEG_INFINITY, Float::INFINITY);
+    for axis in 0..3 {
+        let (origin, direction) = (ray.origin[axis], ray.direction[axis]);
+        // A ray parallel to a slab is inside it all the way along, or never.
+        if direction == 0.0 {
+            if origin < min[axis] || origin > max[axis] {
+                return None;
+            }
+            continue;
+        }
+        let near = (min[axis] - origin) / direction;
+        let far = (max[axis] - origin) / direction;
+        t0 = t0.max(near.min(far));
+        t1 = t1.min(near.max(far));
+    }
+
+    (t0 <= t1 && t1 >= 0.0).then_some((t0, t1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{point, vector};
+
+    fn triangle() -> (Vector4<Float>, Vector4<Float>, Vector4<Float>) {
+        (point(0.0, 0.0, 0.0), point(2.0, 0.0, 0.0), point(0.0, 2.0, 0.0))
+    }
+
+    #[test]
+    fn closest_points_on_a_segment_stay_between_its_ends() {
+        let (a, b) = (point(0.0, 0.0, 0.0), point(2.0, 0.0, 0.0));
+
+        assert_eq!(closest_point_on_segment(point(1.0, 3.0, 0.0), a, b), point(1.0, 0.0, 0.0));
+        assert_eq!(closest_point_on_segment(point(-1.0, 1.0, 0.0), a, b), a);
+        assert_eq!(closest_point_on_segment(point(5.0, 0.0, 1.0), a, b), b);
+        assert_eq!(closest_point_on_segment(point(5.0, 0.0, 1.0), a, a), a);
+    }
+
+    #[test]
+    fn closest_points_on_a_triangle() {
+        let (a, b, c) = triangle();
+
+        assert_eq!(closest_point_on_triangle(point(0.5, 0.5, 3.0), a, b, c), point(0.5, 0.5, 0.0));
+        assert_eq!(closest_point_on_triangle(point(-1.0, -1.0, 0.0), a, b, c), a);
+        assert_eq!(closest_point_on_triangle(point(3.0, -1.0, 0.0), a, b, c), b);
+        assert_eq!(closest_point_on_triangle(point(1.0, -1.0, 0.0), a, b, c), point(1.0, 0.0, 0.0));
+        assert_eq!(closest_point_on_triangle(point(-1.0, 1.0, 0.0), a, b, c), point(0.0, 1.0, 0.0));
+        assert_eq!(closest_point_on_triangle(point(2.0, 2.0, 0.0), a, b, c), point(1.0, 1.0, 0.0));
+        assert_eq!(closest_point_on_triangle(point(1.0, 1.0, 0.0), a, b, a), point(1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn triangle_areas_and_barycentric_coordinates() {
+        let (a, b, c) = triangle();
+
+        assert_eq!(triangle_area(a, b, c), 2.0);
+        assert_eq!(barycentric(a, a, b, c), Some((1.0, 0.0, 0.0)));
+        assert_eq!(barycentric(point(1.0, 1.0, 0.0), a, b, c), Some((0.0, 0.5, 0.5)));
+        assert_eq!(barycentric(point(0.5, 0.5, 4.0), a, b, c), Some((0.5, 0.25, 0.25)));
+        assert_eq!(barycentric(point(1.0, 0.0, 0.0), a, b, point(4.0, 0.0, 0.0)), None);
+    }
+
+    #[test]
+    fn rays_against_boxes() {
+        let (min, max) = (point(-1.0, -1.0, -1.0), point(1.0, 1.0, 1.0));
+        let hit = Ray::new(point(0.5, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+        let inside = Ray::new(point(0.0, 0.0, 0.0), vector(0.0, 1.0, 0.0));
+        let miss = Ray::new(point(2.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+        let behind = Ray::new(point(0.0, 0.0, 5.0), vector(0.0, 0.0, 1.0));
+        let grazing = Ray::new(point(1.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+
+        assert_eq!(ray_aabb(&hit, min, max), Some((4.0, 6.0)));
+        assert_eq!(ray_aabb(&inside, min, max), Some((-1.0, 1.0)));
+        assert_eq!(ray_aabb(&miss, min, max), None);
+        assert_eq!(ray_aabb(&behind, min, max), None);
+        assert_eq!(ray_aabb(&grazing, min, max), Some((4.0, 6.0)));
+    }
+}
@@ -0,0 +1,210 @@
+use crate::core::{Camera, Canvas, RenderSettings, World};
+use image::{ImageBuffer, ImageError, Rgb, RgbImage};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// Check of a render against a stored reference (golden) image, so refactors
+/// can be shown not to change what gets drawn. Images are compared as they're
+/// saved, eight bits per channel, and a small tolerance absorbs rounding. If
+/// the reference doesn't exist yet, the render is saved as the reference.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Golden {
+    pub reference: PathBuf,
+    /// Largest difference allowed in any channel, out of 255.
+    pub tolerance: u8,
+    /// Where to save an image of the differences when the check fails.
+    pub diff: Option<PathBuf>
+}
+
+impl Golden {
+    pub fn new<P: AsRef<Path>>(reference: P) -> Self {
+        Golden { reference: reference.as_ref().to_path_buf(), tolerance: 1, diff: None }
+    }
+
+    pub fn with_tolerance(mut self, tolerance: u8) -> Self {
+        self.tolerance = tolerance;
+
+        self
+    }
+
+    /// Saves the differences to an image when the check fails. Each channel
+    /// is scaled so the largest difference is white.
+    pub fn with_diff<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.diff = Some(path.as_ref().to_path_buf());
+
+        self
+    }
+
+    /// Renders the scene and checks the result. The settings carry the seed,
+    /// so the same scene and settings always render the same image, however
+    /// many threads are used.
+    pub fn check_render(&self, camera: &Camera, world: World, settings: &RenderSettings) -> Result<GoldenReport, GoldenError> {
+        self.check(&camera.render_with(world, settings))
+    }
+
+    /// Checks a canvas against the reference image.
+    pub fn check(&self, canvas: &Canvas) -> Result<GoldenReport, GoldenError> {
+        let image = to_image(canvas);
+        let pixels = canvas.width * canvas.height;
+        if !self.reference.exists() {
+            image.save(&self.reference)?;
+            return Ok(GoldenReport { pixels, mismatched: 0, max_error: 0, created: true });
+        }
+        let reference = image::open(&self.reference)?.to_rgb8();
+        if reference.dimensions() != image.dimensions() {
+            let (width, height) = reference.dimensions();
+            return Err(GoldenError::Size {
+                expected: (width as usize, height as usize),
+                found: (canvas.width, canvas.height)
+            });
+        }
+        let errors: Vec<[u8; 3]> = image.pixels()
+            .zip(reference.pixels())
+            .map(|(a, b)| [0, 1, 2].map(|c| a[c].abs_diff(b[c])))
+            .collect();
+        let max_error = errors.iter().flatten().copied().max().unwrap_or(0);
+        let mismatched = errors.iter().filter(|e| e.iter().any(|c| *c > self.tolerance)).count();
+        if let (Some(path), true) = (&self.diff, mismatched > 0) {
+            let scale = |e: u8| (e as u32 * 255 / max_error as u32) as u8;
+            let diff: RgbImage = ImageBuffer::from_fn(image.width(), image.height(), |x, y| {
+                let e = errors[(y * image.width() + x) as usize];
+                Rgb(e.map(scale))
+            });
+            diff.save(path)?;
+        }
+
+        Ok(GoldenReport { pixels, mismatched, max_error, created: false })
+    }
+}
+
+fn to_image(canvas: &Canvas) -> RgbImage {
+    ImageBuffer::from_fn(canvas.width as u32, canvas.height as u32, |x, y| {
+        let (r, g, b) = canvas.scaled_pix(x as usize, y as usize);
+        Rgb([r, g, b])
+    })
+}
+
+/// Outcome of a golden image check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GoldenReport {
+    pub pixels: usize,
+    /// Pixels with a channel further off than the tolerance.
+    pub mismatched: usize,
+    /// Largest difference in any channel, out of 255.
+    pub max_error: u8,
+    /// Whether there was no reference, so this render became it.
+    pub created: bool
+}
+
+impl GoldenReport {
+    pub fn passed(&self) -> bool {
+        self.mismatched == 0
+    }
+}
+
+/// Everything that can stop a golden image check.
+#[derive(Debug)]
+pub enum GoldenError {
+    /// The reference or diff image couldn't be read or written.
+    Image(ImageError),
+    /// The render isn't the same size as the reference.
+    Size { expected: (usize, usize), found: (usize, usize) }
+}
+
+impl fmt::Display for GoldenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GoldenError::Image(err) => write!(f, "unable to use reference image: {}", err),
+            GoldenError::Size { expected, found } => write!(
+                f,
+                "render is {} x {}, but the reference is {} x {}",
+                found.0, found.1, expected.0, expected.1
+            )
+        }
+    }
+}
+
+impl std::error::Error for GoldenError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            GoldenError::Image(err) => Some(err),
+            _ => None
+        }
+    }
+}
+
+impl From<ImageError> for GoldenError {
+    fn from(err: ImageError) -> Self {
+        GoldenError::Image(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{point, vector, Colour, Transform};
+    use crate::consts::PI;
+    use nalgebra::Matrix4;
+    use std::fs;
+
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join("feoray-golden");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        let _ = fs::remove_file(&path);
+
+        path
+    }
+
+    fn camera() -> Camera {
+        Camera::new(11, 11, PI / 2.0)
+            .with_transform(Matrix4::view_transform(point(0.0, 0.0, -5.0), point(0.0, 0.0, 0.0), vector(0.0, 1.0, 0.0)))
+    }
+
+    #[test]
+    fn missing_references_are_created_then_matched() {
+        let golden = Golden::new(scratch("created.png"));
+        let w = World::default_world();
+        let settings = RenderSettings::for_world(&w);
+        let first = golden.check_render(&camera(), w.clone(), &settings).unwrap();
+        let second = golden.check_render(&camera(), w, &settings.with_threads(3)).unwrap();
+
+        assert!(first.created && first.passed());
+        assert!(!second.created && second.passed());
+        assert_eq!(second.max_error, 0);
+    }
+
+    #[test]
+    fn changed_pixels_fail_and_are_drawn_in_the_diff() {
+        let diff = scratch("changed_diff.png");
+        let golden = Golden::new(scratch("changed.png")).with_diff(&diff);
+        let mut canvas = camera().render(World::default_world());
+        golden.check(&canvas).unwrap();
+        canvas.write_pix(5, 5, Colour::red());
+        let report = golden.check(&canvas).unwrap();
+
+        assert!(!report.passed());
+        assert_eq!(report.mismatched, 1);
+        let diff = image::open(diff).unwrap().to_rgb8();
+        assert_eq!(diff.get_pixel(0, 0), &Rgb([0, 0, 0]));
+        assert_ne!(diff.get_pixel(5, 5), &Rgb([0, 0, 0]));
+    }
+
+    #[test]
+    fn small_differences_are_tolerated() {
+        let golden = Golden::new(scratch("tolerated.png")).with_tolerance(3);
+        golden.check(&Canvas::new(2, 2, Colour::grey(0.5))).unwrap();
+        let report = golden.check(&Canvas::new(2, 2, Colour::grey(0.505))).unwrap();
+
+        assert!(report.passed());
+        assert!(report.max_error > 0);
+    }
+
+    #[test]
+    fn renders_must_match_the_reference_size() {
+        let golden = Golden::new(scratch("sized.png"));
+        golden.check(&Canvas::new(2, 2, Colour::black())).unwrap();
+
+        assert!(matches!(golden.check(&Canvas::new(3, 2, Colour::black())), Err(GoldenError::Size { .. })));
+    }
+}
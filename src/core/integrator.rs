@@ -0,0 +1,240 @@
+use crate::{to_f32, Float};
+use crate::core::world::pick_light;
+use crate::core::{cosine_direction, Bounce, Colour, Frame, Intersections, PreCompData, Ray, RayKind, RenderSettings, Rng, World};
+use crate::materials::Material;
+use crate::lights::PointLight;
+use nalgebra::Vector4;
+
+/// How the colour seen along a camera ray is worked out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Integrator {
+    /// Classic recursive ray tracer: direct lighting, perfect reflections and
+    /// refractions, with ambient standing in for all other bounced light.
+    #[default]
+    Whitted,
+    /// Monte Carlo path tracer. Light bounces off diffuse surfaces too, so
+    /// colours bleed onto nearby objects. Each sample follows a single random
+    /// path, so use plenty of samples per pixel to keep the noise down.
+    /// Ambient is ignored, as bounced light takes its place.
+    PathTracer
+}
+
+impl World {
+    /// Colour seen along a ray, found by following one random path through the
+    /// scene, for as many bounces as the settings' depth limits allow. At every hit one
+    /// light, picked at random with the brightest favoured, is sampled
+    /// directly. Averaging many paths converges on the full global illumination
+    /// solution. A ray that misses everything straight away sees the settings'
+    /// background instead of the world's, if there is one, and light that has
+    /// bounced is clamped to the settings' max_radiance.
+    pub fn path_colour(&self, ray: &Ray, settings: &RenderSettings, rng: &mut Rng) -> Colour {
+        self.path_colour_into(ray, settings, rng, &mut Intersections::default())
+    }
+
+    /// Same as path_colour(), using a reusable buffer for the intersections.
+    pub fn path_colour_into<'a>(&'a self, ray: &Ray, settings: &RenderSettings, rng: &mut Rng, xs: &mut Intersections<'a>) -> Colour {
+        let mut ray = *ray;
+        let mut throughput = Colour::white();
+        let mut radiance = Colour::black();
+        let mut depth = settings.depth();
+        loop {
+            // Light arriving after a bounce is where fireflies come from.
+            let bounced = depth.bounces > 0;
+            let clamp = |colour: Colour| if bounced { settings.clamp(colour) } else { colour };
+            self.intersect_into(&ray, xs);
+            let hit = xs.hit_index();
+            let distance = hit.map_or(Float::INFINITY, |i| xs[i].t);
+            let (scattered, transmittance) = self.volume_light(&ray, distance);
+            radiance += clamp(throughput * scattered);
+            throughput = throughput * transmittance;
+            let comps = match hit {
+                Some(i) => self.prepare_computations(xs, i, &ray),
+                None => {
+                    let sky = match settings.background {
+                        Some(colour) if !bounced => colour,
+                        _ => self.background.colour_at(&ray.direction)
+                    };
+                    radiance += clamp(throughput * sky);
+                    break;
+                }
+            };
+            radiance += clamp(throughput * self.sample_light(&comps, rng, xs));
+            if depth.total == 0 {
+                break;
+            }
+            let (next, weight, kind) = match sample_bounce(&comps, rng) {
+                Some(bounce) => bounce,
+                None => break
+            };
+            depth = match depth.bounce(kind) {
+                Some(depth) => depth,
+                None => break
+            };
+            ray = next;
+            throughput = throughput * weight;
+            let survival = depth.survival(throughput.r.max(throughput.g).max(throughput.b));
+            if survival < 1.0 {
+                if rng.next_float() >= survival as Float {
+                    break;
+                }
+                throughput = throughput / survival;
+            }
+        }
+
+        radiance
+    }
+
+    // Direct light from one light picked at random, favouring the lights most
+    // likely to contribute, and scaled by one over its chance of being picked so
+    // that on average every light is accounted for. Light focused by glass
+    // can't be found this way, so comes from the photon map if there is one.
+    fn sample_light<'a>(&'a self, comps: &PreCompData, rng: &mut Rng, xs: &mut Intersections<'a>) -> Colour {
+        let weights = self.light_weights(comps);
+        let total: Float = weights.iter().sum();
+        if total <= 0.0 {
+            return self.caustic_at(comps);
+        }
+        let i = pick_light(&weights, total, rng.next_float());
+        let light = &self.lights[i];
+        let dimmed = PointLight::new(light.colour_at(comps.over_pos) * self.volume_transmittance(comps.over_pos, light.position, comps.time), light.position);
+        let material = Material { ambient: 0.0, ..comps.object.material };
+        let shadowed = self.is_shadowed_into(dimmed.position, comps.over_pos, comps.time, xs);
+        let direct = material.lighting_with(comps.pattern_colour(), &dimmed, comps.over_pos, comps.eye_vec, comps.normal_vec, shadowed);
+
+        direct * (total / weights[i]) as f32 + self.caustic_at(comps)
+    }
+}
+
+// Picks how the path carries on from a hit: a diffuse bounce, a mirror
+// reflection or a refraction, chosen in proportion to how much each contributes.
+// Returns the new ray, the weight to apply to everything it sees, and the kind
+// of bounce it took.
+fn sample_bounce(comps: &PreCompData, rng: &mut Rng) -> Option<(Ray, Colour, Bounce)> {
+    let material = comps.object.material;
+    let albedo = comps.pattern_colour() * material.diffuse;
+    let (mut reflective, mut transparent) = (material.reflectivity, material.transparency);
+    if reflective > 0.0 && transparent > 0.0 {
+        let reflectance = to_f32(comps.schlick());
+        reflective *= reflectance;
+        transparent *= 1.0 - reflectance;
+    }
+    let diffuse = (albedo.r + albedo.g + albedo.b) / 3.0;
+    let total = diffuse + reflective + transparent;
+    if total <= 0.0 {
+        return None;
+    }
+    let choice = to_f32(rng.next_float()) * total;
+    if choice < diffuse {
+        let direction = cosine_hemisphere(comps.normal_vec, rng);
+        let ray = Ray::new(comps.over_pos, direction).with_time(comps.time).with_kind(RayKind::Diffuse);
+        Some((ray, albedo * (total / diffuse), Bounce::Diffuse))
+    } else if choice < diffuse + reflective {
+        let ray = Ray::new(comps.over_pos, comps.reflect_vec).with_time(comps.time).with_kind(RayKind::Reflection);
+        Some((ray, Colour::white() * total, Bounce::Reflection))
+    } else {
+        // Total internal reflection sends all of the light back inside.
+        let ray = match comps.refract_vec() {
+            Some(direction) => Ray::new(comps.under_pos, direction),
+            None => Ray::new(comps.under_pos, comps.reflect_vec)
+        };
+        Some((ray.with_time(comps.time).with_kind(RayKind::Refraction), Colour::white() * total, Bounce::Refraction))
+    }
+}
+
+// Random direction about the normal, more likely towards the normal than
+// away from it, which matches how a diffuse surface scatters light.
+fn cosine_hemisphere(normal: Vector4<Float>, rng: &mut Rng) -> Vector4<Float> {
+    let (u1, u2) = (rng.next_float(), rng.next_float());
+
+    Frame::new(normal).to_world(cosine_direction(u1, u2))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{point, vector, Background, Transform};
+    use crate::consts::PI;
+    use crate::primitives::Object;
+    use crate::EPSILON;
+    use nalgebra::Matrix4;
+
+    #[test]
+    fn diffuse_bounces_stay_above_the_surface() {
+        let mut rng = Rng::new(3);
+        let normal = vector(0.0, 0.0, 1.0);
+        for _ in 0..100 {
+            let d = cosine_hemisphere(normal, &mut rng);
+
+            assert!(d.dot(&normal) >= 0.0);
+            assert!((d.magnitude() - 1.0).abs() < EPSILON);
+        }
+    }
+
+    #[test]
+    fn paths_that_miss_see_the_background() {
+        let w = World::default().with_background(Background::Solid(Colour::blue()));
+        let r = Ray::new(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+        let mut rng = Rng::new(0);
+        let settings = RenderSettings::default();
+
+        assert_eq!(w.path_colour(&r, &settings, &mut rng), Colour::blue());
+        assert_eq!(w.path_colour(&r, &settings.with_background(Colour::red()), &mut rng), Colour::red());
+    }
+
+    #[test]
+    fn direct_light_matches_the_whitted_integrator() {
+        let m = Material::default().with_ambient(0.0);
+        let w = World::default()
+            .with_light(PointLight::new(Colour::white(), point(-10.0, 10.0, -10.0)))
+            .with_object(Object::new_sphere().with_material(m));
+        let r = Ray::new(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+        let mut rng = Rng::new(0);
+        let settings = RenderSettings::default().with_max_depth(0);
+
+        assert_eq!(w.path_colour(&r, &settings, &mut rng), w.colour_at(&r, 0));
+    }
+
+    #[test]
+    fn bounced_light_can_be_clamped() {
+        // A mirror facing a brightly lit ball, so all the light seen has bounced.
+        let mirror = Object::new_plane()
+            .with_material(Material::null().with_reflectivity(1.0));
+        let ball = Object::new_sphere()
+            .with_transform(Matrix4::translate(0.0, 3.0, 3.0));
+        let w = World::default()
+            .with_light(PointLight::new(Colour::white() * 20.0, point(0.0, 1.5, 1.5)))
+            .with_object(mirror)
+            .with_object(ball);
+        let r = Ray::new(point(0.0, 1.0, -1.0), vector(0.0, -1.0, 1.0).normalize());
+        let settings = RenderSettings::default().with_max_depth(1);
+        let bright = w.path_colour(&r, &settings, &mut Rng::new(0));
+        let clamped = w.path_colour(&r, &settings.with_max_radiance(1.0), &mut Rng::new(0));
+
+        assert!(bright.r > 1.0);
+        assert_eq!(clamped, bright.clamp_to(1.0));
+    }
+
+    #[test]
+    fn colour_bleeds_between_diffuse_surfaces() {
+        // A white floor next to a red wall, lit from above the floor.
+        let wall = Object::new_plane()
+            .with_transform(Matrix4::translate(1.0, 0.0, 0.0) * Matrix4::rot_z(PI / 2.0))
+            .with_material(Material::default().with_colour(Colour::red()).with_specular(0.0));
+        let floor = Object::new_plane()
+            .with_material(Material::default().with_specular(0.0));
+        let w = World::default()
+            .with_light(PointLight::new(Colour::white(), point(0.0, 5.0, 0.0)))
+            .with_object(wall)
+            .with_object(floor);
+        let r = Ray::new(point(0.0, 1.0, -1.0), vector(0.0, -1.0, 1.0).normalize());
+        let mut rng = Rng::new(1);
+        let mut total = Colour::black();
+        for _ in 0..200 {
+            total += w.path_colour(&r, &RenderSettings::default().with_max_depth(2), &mut rng);
+        }
+        let c = total / 200.0;
+
+        assert!(c.r > c.g && c.r > c.b);
+        assert!((c.g - c.b).abs() < 1e-3);
+    }
+}
@@ -1,25 +1,36 @@
-use crate::EPSILON;
+use crate::{Float, EPSILON};
 use crate::core::{PreCompData, Ray, Tuple};
 use crate::primitives::Object;
+use std::borrow::Cow;
 use std::cmp::Ordering;
 use std::ops::Index;
+use std::ptr;
 use std::slice::Iter;
 
-#[derive(Debug, Clone, Copy, PartialOrd)]
-pub struct Intersection {
-    pub t: f64,
-    pub object: Object
+/// Where along a ray it hits an object. The object is borrowed from wherever
+/// it lives (usually the world), rather than copied into every hit.
+#[derive(Debug, Clone, Copy)]
+pub struct Intersection<'a> {
+    pub t: Float,
+    pub object: &'a Object,
+    /// Where on the shape's surface it was hit, for shapes that know this
+    /// when they find the hit, such as triangles. None works it out later from
+    /// the point, if it's needed.
+    pub uv: Option<(Float, Float)>,
+    /// Anything else a shape keeps about the hit to shade it later, e.g.
+    /// which face or triangle it was. 0 for shapes that don't need it.
+    pub data: usize
 }
 
-impl PartialEq for Intersection {
+impl PartialEq for Intersection<'_> {
     fn eq(&self, other: &Self) -> bool {
         (self.t - other.t).abs() < EPSILON
     }
 }
 
-impl Eq for Intersection {}
+impl Eq for Intersection<'_> {}
 
-impl Ord for Intersection {
+impl Ord for Intersection<'_> {
     fn cmp(&self, other: &Self) -> Ordering {
         let diff = self.t - other.t;
         if diff.abs() < EPSILON {
@@ -32,104 +43,202 @@ impl Ord for Intersection {
     }
 }
 
-impl Intersection {
-    pub fn new(t: f64, object: Object) -> Self {
+impl PartialOrd for Intersection<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<'a> Intersection<'a> {
+    pub fn new(t: Float, object: &'a Object) -> Self {
         Intersection {
             t,
-            object
+            object,
+            uv: None,
+            data: 0
         }
     }
+
+    /// Records where on the surface the hit is.
+    pub fn with_uv(mut self, u: Float, v: Float) -> Self {
+        self.uv = Some((u, v));
+
+        self
+    }
+
+    /// Records shape-specific data about the hit.
+    pub fn with_data(mut self, data: usize) -> Self {
+        self.data = data;
+
+        self
+    }
 }
 
 #[derive(Debug, Clone)]
-pub struct Intersections {
-    pub intrsc: Vec<Intersection>
+pub struct Intersections<'a> {
+    pub intrsc: Vec<Intersection<'a>>
 }
 
-impl Intersections {
-    pub fn new(mut intrsc: Vec<Intersection>) -> Self {
+impl<'a> Intersections<'a> {
+    pub fn new(mut intrsc: Vec<Intersection<'a>>) -> Self {
         intrsc.sort();
         Intersections { intrsc }
     }
 
-    pub fn hit(&self) -> Option<&Intersection> {
-        self.intrsc.iter().find(|i| i.t >= 0.0)
+    /// Empty list with room for the given number of hits, e.g. two per object
+    /// in the world, so tracing doesn't have to grow it.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Intersections { intrsc: Vec::with_capacity(capacity) }
+    }
+
+    /// Empties the list, keeping its memory for the next ray.
+    pub fn clear(&mut self) {
+        self.intrsc.clear();
+    }
+
+    /// Adds the hits from another list, keeping them all in order.
+    pub fn merge(&mut self, other: Intersections<'a>) {
+        self.extend_sorted(other.intrsc);
+    }
+
+    /// Adds hits that are already in order among themselves, e.g. one
+    /// object's, merging them in with the rest.
+    pub fn extend_sorted<I: IntoIterator<Item = Intersection<'a>>>(&mut self, hits: I) {
+        let start = self.len();
+        self.intrsc.extend(hits);
+        merge_run(&mut self.intrsc, start);
+    }
+
+    /// Adds an object's hits along the ray, leaving out any beyond its t_max.
+    pub fn add_hits(&mut self, object: &'a Object, ray: &Ray) {
+        let start = self.len();
+        object.intersect_into(ray, &mut self.intrsc);
+        // Each object's hits come nearest first, so anything out of range
+        // is at the end, and the rest only has to be merged in.
+        while self.len() > start && matches!(self.intrsc.last(), Some(i) if i.t > ray.t_max) {
+            self.intrsc.pop();
+        }
+        merge_run(&mut self.intrsc, start);
+    }
+
+    /// Keeps only the hits the predicate is true for, still in order.
+    pub fn retain<F: FnMut(&Intersection<'a>) -> bool>(&mut self, f: F) {
+        self.intrsc.retain(f);
+    }
+
+    /// Hits in front of the ray's origin, nearest first.
+    pub fn hits(&self) -> impl Iterator<Item = &Intersection<'a>> {
+        self.intrsc.iter().filter(|i| i.t >= 0.0)
+    }
+
+    pub fn hit(&self) -> Option<&Intersection<'a>> {
+        self.hits().next()
     }
 
     pub fn len(&self) -> usize {
         self.intrsc.len()
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.intrsc.is_empty()
+    }
+
     pub fn hit_index(&self) -> Option<usize> {
         self.intrsc.iter().position(|i| i.t >= 0.0)
     }
 
-    pub fn iter(&self) -> Iter<Intersection> {
+    pub fn iter(&self) -> Iter<'_, Intersection<'a>> {
         self.intrsc.iter()
     }
 
     // Reminder to refactor later
-    pub fn prepare_computations(&self, index: usize, ray: &Ray) -> PreCompData {
-        let mut containers = Vec::<Object>::new();
-        let mut n1 = None;
-        let mut n2 = None;
-
-        for i in 0..self.len() {
-            let is_hit = i == index;
-            
-            if is_hit {
-                if containers.is_empty() {
-                    n1 = Some(1.0);
-                } else {
-                    n1 = Some(containers.last().unwrap().material.ior);
-                }
-            }
+    pub fn prepare_computations(&self, index: usize, ray: &Ray) -> PreCompData<'a> {
+        self.prepare_computations_with(index, ray, EPSILON)
+    }
 
-            // Kinda pukey 🤮 but it works
-            let mut found = false;
-            let mut cnt_idx = 0;
-            for (j, obj) in containers.iter().enumerate() {
-                if obj == &self[i].object {
-                    found = true;
-                    cnt_idx = j;
-                    break;
-                }
-            }
-            if found {
-                containers.remove(cnt_idx);
-            } else {
-                containers.push(self[i].object);
-            }
+    /// Same as prepare_computations(), but with over_pos and under_pos offset
+    /// from the surface by the given epsilon, scaled up for hits far from the
+    /// origin where floating point is coarser.
+    pub fn prepare_computations_with(&self, index: usize, ray: &Ray, epsilon: Float) -> PreCompData<'a> {
+        self.prepare_computations_biased(index, ray, epsilon, 0.0)
+    }
+
+    /// Same as prepare_computations_with(), but with slope added to the
+    /// offset for every unit of the tangent of the angle the surface is seen
+    /// at, so grazing hits, like far off parts of a floor, are pushed further
+    /// out. The tangent is capped at 100.
+    pub fn prepare_computations_biased(&self, index: usize, ray: &Ray, epsilon: Float, slope: Float) -> PreCompData<'a> {
+        let mut indices = (1.0, 1.0);
+        self.walk_containers(index + 1, |n1, n2| indices = (n1, n2));
+
+        self.prepare_hit(index, ray, epsilon, slope, indices)
+    }
 
-            if is_hit {
-                if containers.is_empty() {
-                    n2 = Some(1.0);
-                } else {
-                    n2 = Some(containers.last().unwrap().material.ior);
-                }
-                break;
+    /// Computations for every hit along the ray, as from prepare_computations(),
+    /// with what the ray is inside worked out in a single pass rather than
+    /// again from the start for each hit.
+    pub fn prepare_all(&self, ray: &Ray, epsilon: Float) -> Vec<PreCompData<'a>> {
+        self.refractive_indices()
+            .into_iter()
+            .enumerate()
+            .map(|(i, indices)| self.prepare_hit(i, ray, epsilon, 0.0, indices))
+            .collect()
+    }
+
+    /// Refractive indices either side of every hit, in order: n1 for the
+    /// material the ray is leaving and n2 for the one it's entering.
+    pub fn refractive_indices(&self) -> Vec<(f32, f32)> {
+        let mut indices = Vec::with_capacity(self.len());
+        self.walk_containers(self.len(), |n1, n2| indices.push((n1, n2)));
+
+        indices
+    }
+
+    // Follows the ray through its first count hits, keeping track of which
+    // objects it's inside, and passes each hit's n1 and n2 to f.
+    fn walk_containers<F: FnMut(f32, f32)>(&self, count: usize, mut f: F) {
+        let mut containers = Vec::<&Object>::new();
+        let ior = |containers: &[&Object]| containers.last().map_or(1.0, |o| o.material.ior);
+        for hit in self.iter().take(count) {
+            let n1 = ior(&containers);
+            match containers.iter().position(|o| ptr::eq(*o, hit.object)) {
+                Some(j) => {
+                    containers.remove(j);
+                },
+                None => containers.push(hit.object)
             }
+            f(n1, ior(&containers));
         }
+    }
 
+    // Computations for a hit, with n1 and n2 already known.
+    fn prepare_hit(&self, index: usize, ray: &Ray, epsilon: Float, slope: Float, (n1, n2): (f32, f32)) -> PreCompData<'a> {
         let intersection = self[index];
+        // Moving objects are shaded as they are at the time of the ray.
+        let object = if intersection.object.is_animated() {
+            Cow::Owned(intersection.object.at_time(ray.time))
+        } else {
+            Cow::Borrowed(intersection.object)
+        };
         let pos = ray.position(intersection.t);
         let eye_vec = -ray.direction;
-        let mut normal_vec = intersection.object.normal_at(pos);
+        let mut normal_vec = object.normal_at(pos);
         let inside = if normal_vec.dot(&eye_vec) < 0.0 {
             normal_vec = -normal_vec;
             true
         } else {
             false
         };
-        let n1 = n1.unwrap_or(1.0);
-        let n2 = n2.unwrap_or(1.0);
-        let over_pos = pos + normal_vec * EPSILON;
-        let under_pos = pos - normal_vec * EPSILON;
+        let cos = normal_vec.dot(&eye_vec) / eye_vec.magnitude();
+        let tan = (1.0 - cos * cos).max(0.0).sqrt() / cos.max(0.01);
+        let offset = (epsilon + slope * tan) * pos.xyz().amax().max(1.0);
+        let over_pos = pos + normal_vec * offset;
+        let under_pos = pos - normal_vec * offset;
         let reflect_vec = ray.direction.reflect(normal_vec);
 
         PreCompData::new(
             intersection.t,
-            intersection.object,
+            object,
             pos,
             over_pos,
             under_pos,
@@ -140,17 +249,33 @@ impl Intersections {
             reflect_vec,
             inside
         )
+        .with_time(ray.time)
+        .with_cone(ray.width_at(intersection.t), ray.spread)
+        .with_hit_data(intersection.uv, intersection.data)
     }
 }
 
-impl Default for Intersections {
+// Merges the sorted run of hits from start onwards into the sorted hits before
+// it. Runs are short, so each hit is walked back into place rather than sorting
+// the whole list again. Equal hits keep their order.
+fn merge_run(hits: &mut [Intersection], start: usize) {
+    for i in start..hits.len() {
+        let mut j = i;
+        while j > 0 && hits[j - 1].t > hits[j].t {
+            hits.swap(j - 1, j);
+            j -= 1;
+        }
+    }
+}
+
+impl Default for Intersections<'_> {
     fn default() -> Self {
         Self::new(vec![])
     }
 }
 
-impl Index<usize> for Intersections {
-    type Output = Intersection;
+impl<'a> Index<usize> for Intersections<'a> {
+    type Output = Intersection<'a>;
 
     fn index(&self, i: usize) -> &Self::Output {
         &self.intrsc[i]
@@ -167,20 +292,18 @@ mod tests {
     #[test]
     fn intersection_encapsulates_t_and_object() {
         let s = Object::new_sphere();
-        let i = Intersection::new(3.5, s);
+        let i = Intersection::new(3.5, &s);
 
         assert_eq!(i.t, 3.5);
-        assert_eq!(i.object, s);
+        assert_eq!(i.object, &s);
     }
 
     #[test]
     fn aggregating_intersections() {
         let s = Object::new_sphere();
-        let i1 = Intersection::new(1.0, s);
-        let i2 = Intersection::new(2.0, s);
-        let mut intrsc = vec![];
-        intrsc.push(i1);
-        intrsc.push(i2);
+        let i1 = Intersection::new(1.0, &s);
+        let i2 = Intersection::new(2.0, &s);
+        let intrsc = vec![i1, i2];
         let xs = Intersections::new(intrsc);
 
         assert_eq!(xs.len(), 2);
@@ -198,16 +321,16 @@ mod tests {
         let xs = s.intersect(&r);
 
         assert_eq!(xs.len(), 2);
-        assert_eq!(xs[0].object, s);
-        assert_eq!(xs[1].object, s);
+        assert_eq!(xs[0].object, &s);
+        assert_eq!(xs[1].object, &s);
     }
 
     #[test]
     fn the_hit_when_all_ints_have_pos_t() {
         let s = Object::new_sphere();
-        let i1 = Intersection::new(1.0, s);
-        let i2 = Intersection::new(2.0, s);
-        let xs = Intersections::new(vec![i1.clone(), i2]);
+        let i1 = Intersection::new(1.0, &s);
+        let i2 = Intersection::new(2.0, &s);
+        let xs = Intersections::new(vec![i1, i2]);
 
         assert_eq!(xs.hit().unwrap(), &i1);
     }
@@ -215,9 +338,9 @@ mod tests {
     #[test]
     fn the_hit_when_some_ints_have_neg_t() {
         let s = Object::new_sphere();
-        let i1 = Intersection::new(-1.0, s);
-        let i2 = Intersection::new(1.0, s);
-        let xs = Intersections::new(vec![i1, i2.clone()]);
+        let i1 = Intersection::new(-1.0, &s);
+        let i2 = Intersection::new(1.0, &s);
+        let xs = Intersections::new(vec![i1, i2]);
 
         assert_eq!(xs.hit().unwrap(), &i2);
     }
@@ -225,9 +348,9 @@ mod tests {
     #[test]
     fn the_hit_when_all_ints_have_neg_t() {
         let s = Object::new_sphere();
-        let i1 = Intersection::new(-2.0, s);
-        let i2 = Intersection::new(-1.0, s);
-        let xs = Intersections::new(vec![i1, i2.clone()]);
+        let i1 = Intersection::new(-2.0, &s);
+        let i2 = Intersection::new(-1.0, &s);
+        let xs = Intersections::new(vec![i1, i2]);
 
         assert_eq!(xs.hit(), None);
     }
@@ -235,11 +358,11 @@ mod tests {
     #[test]
     fn the_hit_always_the_lowest_pos_t() {
         let s = Object::new_sphere();
-        let i1 = Intersection::new(5.0, s);
-        let i2 = Intersection::new(7.0, s);
-        let i3 = Intersection::new(-3.0, s);
-        let i4 = Intersection::new(2.0, s);
-        let xs = Intersections::new(vec![i1, i2, i3, i4.clone()]);
+        let i1 = Intersection::new(5.0, &s);
+        let i2 = Intersection::new(7.0, &s);
+        let i3 = Intersection::new(-3.0, &s);
+        let i4 = Intersection::new(2.0, &s);
+        let xs = Intersections::new(vec![i1, i2, i3, i4]);
 
         assert_eq!(xs.hit().unwrap(), &i4);
     }
@@ -248,12 +371,12 @@ mod tests {
     fn precomputing_state_of_intersection() {
         let r = Ray::new(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
         let s = Object::new_sphere();
-        let int = Intersection::new(4.0, s);
+        let int = Intersection::new(4.0, &s);
         let ints = Intersections::new(vec![int]);
         let comps = ints.prepare_computations(0, &r);
 
         assert_eq!(comps.t, int.t);
-        assert_eq!(comps.object, int.object);
+        assert_eq!(*comps.object, *int.object);
         assert_eq!(comps.pos, point(0.0, 0.0, -1.0));
         assert_eq!(comps.eye_vec, vector(0.0, 0.0, -1.0));
         assert_eq!(comps.normal_vec, vector(0.0, 0.0, -1.0));
@@ -263,24 +386,24 @@ mod tests {
     fn hit_when_intersection_occurs_outside() {
         let r = Ray::new(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
         let s = Object::new_sphere();
-        let int = Intersection::new(4.0, s);
+        let int = Intersection::new(4.0, &s);
         let ints = Intersections::new(vec![int]);
         let comps = ints.prepare_computations(0, &r);
 
-        assert_eq!(comps.inside, false);
+        assert!(!comps.inside);
     }
 
     #[test]
     fn hit_when_intersection_occurs_inside() {
         let r = Ray::new(point(0.0, 0.0, 0.0), vector(0.0, 0.0, 1.0));
         let s = Object::new_sphere();
-        let int = Intersection::new(1.0, s);
+        let int = Intersection::new(1.0, &s);
         let ints = Intersections::new(vec![int]);
         let comps = ints.prepare_computations(0, &r);
 
         assert_eq!(comps.pos, point(0.0, 0.0, 1.0));
         assert_eq!(comps.eye_vec, vector(0.0, 0.0, -1.0));
-        assert_eq!(comps.inside, true);
+        assert!(comps.inside);
         assert_eq!(comps.normal_vec, vector(0.0, 0.0, -1.0));
     }
 
@@ -289,7 +412,7 @@ mod tests {
         let r = Ray::new(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
         let s = Object::new_sphere()
             .with_transform(Matrix4::translate(0.0, 0.0, 1.0));
-        let int = Intersection::new(5.0, s);
+        let int = Intersection::new(5.0, &s);
         let ints = Intersections::new(vec![int]);
         let comps = ints.prepare_computations(0, &r);
 
@@ -300,9 +423,9 @@ mod tests {
     #[test]
     fn precomputing_reflection_vector() {
         let s = Object::new_plane();
-        let irr_no = 2.0f64.sqrt() / 2.0;
+        let irr_no = Float::sqrt(2.0) / 2.0;
         let r = Ray::new(point(0.0, 1.0, -1.0), vector(0.0, -irr_no, irr_no));
-        let int = Intersection::new(2.0f64.sqrt(), s);
+        let int = Intersection::new(Float::sqrt(2.0), &s);
         let ints = Intersections::new(vec![int]);
         let comps = ints.prepare_computations(0, &r);
 
@@ -321,12 +444,12 @@ mod tests {
             .with_material(Material::default().with_transparency(1.0).with_ior(2.5));
         let ray = Ray::new(point(0.0, 0.0, -4.0), vector(0.0, 0.0, 1.0));
         let xs = Intersections::new(vec![
-            Intersection::new(2.0, a),
-            Intersection::new(2.75, b),
-            Intersection::new(3.25, c),
-            Intersection::new(4.75, b),
-            Intersection::new(5.25, c),
-            Intersection::new(6.0, a)
+            Intersection::new(2.0, &a),
+            Intersection::new(2.75, &b),
+            Intersection::new(3.25, &c),
+            Intersection::new(4.75, &b),
+            Intersection::new(5.25, &c),
+            Intersection::new(6.0, &a)
         ]);
 
         assert_eq!(xs.prepare_computations(0, &ray).n1, 1.0);
@@ -343,17 +466,127 @@ mod tests {
         assert_eq!(xs.prepare_computations(5, &ray).n2, 1.0);
     }
 
+    #[test]
+    fn refractive_indices_are_found_for_every_hit_at_once() {
+        let a = Object::glass_orb()
+            .with_transform(Matrix4::uscale(2.0));
+        let b = Object::glass_orb()
+            .with_transform(Matrix4::translate(0.0, 0.0, -0.25))
+            .with_material(Material::default().with_transparency(1.0).with_ior(2.0));
+        let ray = Ray::new(point(0.0, 0.0, -4.0), vector(0.0, 0.0, 1.0));
+        let xs = Intersections::new(vec![
+            Intersection::new(2.0, &a),
+            Intersection::new(2.75, &b),
+            Intersection::new(4.75, &b),
+            Intersection::new(6.0, &a)
+        ]);
+        let all = xs.prepare_all(&ray, EPSILON);
+
+        assert_eq!(xs.refractive_indices(), vec![(1.0, 1.5), (1.5, 2.0), (2.0, 1.5), (1.5, 1.0)]);
+        assert_eq!(all.len(), 4);
+        for (i, comps) in all.iter().enumerate() {
+            let one = xs.prepare_computations(i, &ray);
+            assert_eq!((comps.n1, comps.n2, comps.over_pos), (one.n1, one.n2, one.over_pos));
+        }
+    }
+
     #[test]
     fn under_point_is_offset_below_surface() {
         let ray = Ray::new(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
         let shape = Object::glass_orb()
             .with_transform(Matrix4::translate(0.0, 0.0, 1.0));
         let xs = Intersections::new(vec![
-            Intersection::new(5.0, shape)
+            Intersection::new(5.0, &shape)
         ]);
         let comps = xs.prepare_computations(0, &ray);
 
         assert!(comps.under_pos.z > EPSILON/2.0);
         assert!(comps.pos.z < comps.under_pos.z);
     }
+
+    #[test]
+    fn hits_borrow_the_objects_they_hit() {
+        let w = crate::core::World::default_world();
+        let xs = w.intersect(&Ray::new(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0)));
+
+        assert!(ptr::eq(xs[0].object, &w.objects[0]));
+        assert!(ptr::eq(xs[1].object, &w.objects[1]));
+    }
+
+    #[test]
+    fn moving_objects_are_shaded_where_they_are_at_the_time_of_the_ray() {
+        let s = Object::new_sphere().with_motion(Matrix4::translate(2.0, 0.0, 0.0));
+        let ray = Ray::new(point(2.0, 0.0, -5.0), vector(0.0, 0.0, 1.0)).with_time(1.0);
+        let xs = s.intersect(&ray);
+        let comps = xs.prepare_computations(0, &ray);
+
+        assert!(ptr::eq(xs[0].object, &s));
+        assert_eq!(comps.normal_vec.to_5dp(), vector(0.0, 0.0, -1.0));
+        assert!(!comps.object.is_animated());
+    }
+
+    #[test]
+    fn offsets_grow_with_distance_from_the_origin() {
+        let ray = Ray::new(point(0.0, 0.0, 1000.0 - 5.0), vector(0.0, 0.0, 1.0));
+        let shape = Object::new_sphere().with_transform(Matrix4::translate(0.0, 0.0, 1000.0));
+        let xs = Intersections::new(vec![Intersection::new(4.0, &shape)]);
+        let wide = xs.prepare_computations_with(0, &ray, 0.01);
+        let comps = xs.prepare_computations(0, &ray);
+
+        assert!(((wide.pos.z - wide.over_pos.z) / (0.01 * 999.0) - 1.0).abs() < 0.01);
+        assert!(((comps.pos.z - comps.over_pos.z) / (EPSILON * 999.0) - 1.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn slope_bias_pushes_grazing_hits_further_out() {
+        let floor = Object::new_plane();
+        let xs = Intersections::new(vec![Intersection::new(1.0, &floor)]);
+        let above = Ray::new(point(0.0, 1.0, 0.0), vector(0.0, -1.0, 0.0));
+        let skimming = Ray::new(point(0.0, 0.1, -1.0), vector(0.0, -0.1, 1.0));
+
+        assert!((xs.prepare_computations_biased(0, &above, 0.01, 0.5).over_pos.y - 0.01).abs() < EPSILON);
+        assert!((xs.prepare_computations_biased(0, &skimming, 0.01, 0.5).over_pos.y - 5.01).abs() < 0.001);
+        assert_eq!(xs.prepare_computations_biased(0, &skimming, 0.01, 0.0).over_pos,
+            xs.prepare_computations_with(0, &skimming, 0.01).over_pos);
+    }
+
+    #[test]
+    fn merging_intersections_keeps_them_in_order() {
+        let s = Object::new_sphere();
+        let mut xs = Intersections::with_capacity(8);
+        xs.merge(Intersections::new(vec![Intersection::new(-1.0, &s), Intersection::new(3.0, &s)]));
+        xs.merge(Intersections::new(vec![Intersection::new(2.0, &s), Intersection::new(5.0, &s)]));
+
+        assert!(xs.intrsc.capacity() >= 8);
+        assert_eq!(xs.iter().map(|i| i.t).collect::<Vec<_>>(), vec![-1.0, 2.0, 3.0, 5.0]);
+        assert_eq!(xs.hits().map(|i| i.t).collect::<Vec<_>>(), vec![2.0, 3.0, 5.0]);
+        xs.retain(|i| i.t != 3.0);
+        assert_eq!(xs.iter().map(|i| i.t).collect::<Vec<_>>(), vec![-1.0, 2.0, 5.0]);
+    }
+
+    #[test]
+    fn adding_an_objects_hits_respects_the_rays_range() {
+        let near = Object::new_sphere();
+        let far = Object::new_sphere().with_transform(Matrix4::translate(0.0, 0.0, 1.5));
+        let ray = Ray::new(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0)).with_t_max(6.0);
+        let mut xs = Intersections::default();
+        xs.add_hits(&near, &ray);
+        xs.add_hits(&far, &ray);
+
+        assert_eq!(xs.iter().map(|i| i.t).collect::<Vec<_>>(), vec![4.0, 5.5, 6.0]);
+        assert!(ptr::eq(xs[1].object, &far));
+    }
+
+    #[test]
+    fn hits_carry_uv_and_shape_data_into_their_computations() {
+        let s = Object::new_sphere();
+        let ray = Ray::new(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+        let i = Intersection::new(4.0, &s).with_uv(0.25, 0.75).with_data(3);
+        let comps = Intersections::new(vec![i]).prepare_computations(0, &ray);
+
+        assert_eq!(Intersection::new(4.0, &s).uv, None);
+        assert_eq!((i.uv, i.data), (Some((0.25, 0.75)), 3));
+        assert_eq!((comps.uv, comps.data), (Some((0.25, 0.75)), 3));
+        assert_eq!(s.intersect(&ray)[0].data, 0);
+    }
 }
\ No newline at end of file
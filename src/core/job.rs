@@ -0,0 +1,133 @@
+use crate::FeorayError;
+use crate::core::{canvas, Camera, Canvas, Progress, RenderSettings, Tile, World};
+use crate::core::progress::{take_rays, ProgressTracker};
+
+/// A render that's done a few tiles at a time, on the calling thread, rather
+/// than all at once. Lets a front end that can't block or spawn threads, like
+/// a browser page, render a chunk per animation frame and show the canvas as
+/// it fills in. The finished canvas is the same as from render_with().
+///
+/// # Example
+///
+/// ```ignore
+/// let mut job = cam.start_render(world, &RenderSettings::default().with_tile_size(16));
+/// while !job.is_done() {
+///     let progress = job.step(4);
+///     draw(job.canvas().to_rgba_bytes(), progress.fraction());
+/// }
+/// ```
+pub struct RenderJob {
+    camera: Camera,
+    world: World,
+    settings: RenderSettings,
+    tiles: Vec<Tile>,
+    next: usize,
+    canvas: Canvas,
+    tracker: ProgressTracker,
+    progress: Progress
+}
+
+impl RenderJob {
+    /// Renders up to the given number of tiles (or scanlines, without a tile
+    /// size), and returns how far along the render is.
+    pub fn step(&mut self, tiles: usize) -> Progress {
+        self.try_step(tiles).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Same as step(), but returns an error rather than panicking if a pixel
+    /// is traced that doesn't fit on the canvas.
+    pub fn try_step(&mut self, tiles: usize) -> Result<Progress, FeorayError> {
+        let end = (self.next + tiles).min(self.tiles.len());
+        while self.next < end {
+            let tile = self.tiles[self.next];
+            self.camera.render_tile(&self.world, tile, &mut self.canvas, &self.settings)?;
+            self.progress = self.tracker.advance(tile.len(), take_rays());
+            self.next += 1;
+        }
+
+        Ok(self.progress)
+    }
+
+    /// Renders whatever is left, and hands over the canvas.
+    pub fn finish(mut self) -> Canvas {
+        self.step(self.tiles.len());
+
+        self.canvas
+    }
+
+    /// The canvas so far. Pixels in tiles that haven't been rendered yet are
+    /// black.
+    pub fn canvas(&self) -> &Canvas {
+        &self.canvas
+    }
+
+    pub fn progress(&self) -> Progress {
+        self.progress
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.next >= self.tiles.len()
+    }
+}
+
+impl Camera {
+    /// Sets up a render to be done bit by bit with RenderJob::step(). Threads
+    /// in the settings are ignored.
+    pub fn start_render(&self, mut world: World, settings: &RenderSettings) -> RenderJob {
+        let tiles = self.prepare(&mut world, settings);
+        let mut tracker = ProgressTracker::new(self.hsize * self.vsize);
+        let progress = tracker.advance(0, 0);
+        take_rays();
+
+        RenderJob {
+            camera: *self,
+            world,
+            settings: *settings,
+            tiles,
+            next: 0,
+            canvas: canvas(self.hsize, self.vsize),
+            tracker,
+            progress
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{Colour, Transform};
+    use crate::consts::PI;
+    use nalgebra::Matrix4;
+
+    fn camera() -> Camera {
+        Camera::new(7, 5, PI / 2.0).with_transform(Matrix4::translate(0.0, 0.0, -5.0))
+    }
+
+    #[test]
+    fn stepped_renders_match_full_renders() {
+        let settings = RenderSettings::default().with_tile_size(3);
+        let mut job = camera().start_render(World::default_world(), &settings);
+        let mut done = vec![];
+        while !job.is_done() {
+            done.push(job.step(2).pixels_done);
+        }
+
+        assert_eq!(done, vec![18, 27, 35]);
+        assert!(job.progress().is_complete());
+        assert_eq!(job.finish(), camera().render_with(World::default_world(), &settings));
+    }
+
+    #[test]
+    fn unfinished_jobs_show_the_tiles_done_so_far() {
+        let settings = RenderSettings::default();
+        let mut job = camera().start_render(World::default_world(), &settings);
+        let full = camera().render_with(World::default_world(), &settings);
+
+        assert_eq!(job.progress().pixels_done, 0);
+        job.step(3);
+        assert_eq!(job.canvas().read_pix(3, 2), full.read_pix(3, 2));
+        assert_eq!(job.canvas().read_pix(3, 3), Colour::black());
+        assert_eq!(job.step(0).pixels_done, 21);
+        assert_eq!(job.finish(), full);
+    }
+}
@@ -0,0 +1,147 @@
+use crate::{Float, FeorayError};
+use crate::core::interpolate;
+use nalgebra::Matrix4;
+
+/// Maximum number of keyframes an animated channel can hold. Cameras are Copy,
+/// and share the channel type with objects, so it has a fixed size.
+pub const MAX_KEYFRAMES: usize = 8;
+
+/// Keyframed transform channel. Keys are kept sorted by time, and the
+/// transform between two keys is interpolated with interpolate(), so spinning
+/// objects don't shear.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub struct Keyframes {
+    keys: [(Float, Matrix4<Float>); MAX_KEYFRAMES],
+    len: usize
+}
+
+impl Keyframes {
+    /// Creates an empty channel.
+    pub fn new() -> Self {
+        Keyframes { keys: [(0.0, Matrix4::identity()); MAX_KEYFRAMES], len: 0 }
+    }
+
+    /// Sets the transform at a moment in time, replacing any key already there.
+    /// Panics if the channel is full.
+    pub fn insert(&mut self, time: Float, transform: Matrix4<Float>) {
+        self.try_insert(time, transform).unwrap_or_else(|err| panic!("{}", err));
+    }
+
+    /// Same as insert(), but returns an error rather than panicking if the
+    /// channel is full.
+    pub fn try_insert(&mut self, time: Float, transform: Matrix4<Float>) -> Result<(), FeorayError> {
+        let keys = &mut self.keys[..self.len];
+        if let Some(key) = keys.iter_mut().find(|(t, _)| *t == time) {
+            key.1 = transform;
+            return Ok(());
+        }
+        if self.len == MAX_KEYFRAMES {
+            return Err(FeorayError::TooMany { what: "keyframes per channel", max: MAX_KEYFRAMES });
+        }
+        let index = keys.iter().position(|(t, _)| *t > time).unwrap_or(self.len);
+        self.keys.copy_within(index..self.len, index + 1);
+        self.keys[index] = (time, transform);
+        self.len += 1;
+
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Keys in time order.
+    pub fn keys(&self) -> &[(Float, Matrix4<Float>)] {
+        &self.keys[..self.len]
+    }
+
+    /// Transform at a moment in time, held at the first and last keys outside
+    /// the keyed range. None if the channel is empty.
+    pub fn sample(&self, time: Float) -> Option<Matrix4<Float>> {
+        let keys = self.keys();
+        let (first, last) = (keys.first()?, keys.last()?);
+        if time <= first.0 {
+            return Some(first.1);
+        }
+        if time >= last.0 {
+            return Some(last.1);
+        }
+        let next = keys.iter().position(|(t, _)| *t > time).unwrap();
+        let (t0, m0) = keys[next - 1];
+        let (t1, m1) = keys[next];
+        let s = (time - t0) / (t1 - t0);
+
+        Some(interpolate(&m0, &m1, s))
+    }
+}
+
+impl Default for Keyframes {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{Test, Transform};
+    use crate::consts::PI;
+
+    #[test]
+    fn empty_channel_has_no_transform() {
+        let k = Keyframes::new();
+
+        assert!(k.is_empty());
+        assert_eq!(k.sample(0.5), None);
+    }
+
+    #[test]
+    fn keys_are_kept_in_time_order() {
+        let mut k = Keyframes::new();
+        k.insert(2.0, Matrix4::translate(2.0, 0.0, 0.0));
+        k.insert(0.0, Matrix4::identity());
+        k.insert(1.0, Matrix4::translate(1.0, 0.0, 0.0));
+        k.insert(1.0, Matrix4::translate(5.0, 0.0, 0.0));
+        let times: Vec<Float> = k.keys().iter().map(|(t, _)| *t).collect();
+
+        assert_eq!(times, vec![0.0, 1.0, 2.0]);
+        assert_eq!(k.keys()[1].1, Matrix4::translate(5.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn sampling_between_and_beyond_keys() {
+        let mut k = Keyframes::new();
+        k.insert(1.0, Matrix4::translate(2.0, 0.0, 0.0));
+        k.insert(3.0, Matrix4::translate(6.0, 0.0, 0.0));
+
+        assert_eq!(k.sample(0.0), Some(Matrix4::translate(2.0, 0.0, 0.0)));
+        assert_eq!(k.sample(2.0), Some(Matrix4::translate(4.0, 0.0, 0.0)));
+        assert_eq!(k.sample(9.0), Some(Matrix4::translate(6.0, 0.0, 0.0)));
+    }
+
+    #[test]
+    fn spinning_between_keys_does_not_squash() {
+        let mut k = Keyframes::new();
+        k.insert(0.0, Matrix4::identity());
+        k.insert(1.0, Matrix4::rot_z(PI / 2.0));
+
+        assert_eq!(k.sample(0.5).unwrap().to_5dp(), Matrix4::rot_z(PI / 4.0).to_5dp());
+    }
+
+    #[test]
+    fn full_channels_refuse_new_keys() {
+        let mut k = Keyframes::new();
+        for i in 0..MAX_KEYFRAMES {
+            k.insert(i as Float, Matrix4::identity());
+        }
+
+        assert!(k.try_insert(99.0, Matrix4::identity()).is_err());
+        // Replacing a key still works.
+        assert!(k.try_insert(0.0, Matrix4::translate(1.0, 0.0, 0.0)).is_ok());
+        assert_eq!(k.len(), MAX_KEYFRAMES);
+    }
+}
@@ -1,13 +1,14 @@
+use crate::Float;
 use nalgebra::Matrix4;
 // Previous iterations of matrix.rs (i.e. pre-nalgebra refactoring) can be
 // found in the archive folder. This file only contains tests now.
 
 // Only for tests
 pub trait Test {
-    fn to_5dp(&self) -> Matrix4<f64>;
+    fn to_5dp(&self) -> Matrix4<Float>;
 }
 
-impl Test for Matrix4<f64> {
+impl Test for Matrix4<Float> {
     fn to_5dp(&self) -> Self {
         let mut res = Matrix4::zeros();
         for i in 0..4 {
@@ -19,10 +20,23 @@ impl Test for Matrix4<f64> {
     }
 }
 
+/// Inverts a transform, or gives None if it can't be. Unlike try_inverse(),
+/// transforms with a NaN or infinite entry are refused too, as from a
+/// view_transform() looking from a point to itself, rather than inverted into
+/// more NaNs.
+pub(crate) fn inverse(m: &Matrix4<Float>) -> Option<Matrix4<Float>> {
+    if m.iter().any(|x| !x.is_finite()) {
+        return None;
+    }
+
+    m.try_inverse().filter(|inv| inv.iter().all(|x| x.is_finite()))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use nalgebra::{Matrix2, Matrix3, Vector4};
+    use crate::EPSILON;
     use assert_approx_eq::assert_approx_eq;
 
     #[test]
@@ -259,7 +273,7 @@ mod tests {
 
     #[test]
     fn determinant_of_4x4() {
-        let m = Matrix4::new(
+        let m = Matrix4::<Float>::new(
             -2.0, -8.0, 3.0, 5.0,
             -3.0, 1.0, 7.0, 3.0,
             1.0, 2.0, -9.0, 6.0,
@@ -271,7 +285,7 @@ mod tests {
         //assert_eq!(m.cofactor(0, 2), 210.0);
         //assert_eq!(m.cofactor(0, 3), 51.0);
         //assert_eq!(m.determinant(), -4071.0);
-        assert_approx_eq!(m.determinant() as f64, -4071.0);
+        assert_approx_eq!(m.determinant(), -4071.0, EPSILON);
     }
 
     #[test]
@@ -284,7 +298,7 @@ mod tests {
         );
 
         assert_eq!(m.determinant(), -2120.0);
-        assert_eq!(m.is_invertible(), true);
+        assert!(m.is_invertible());
     }
 
     #[test]
@@ -297,7 +311,18 @@ mod tests {
         );
 
         assert_eq!(m.determinant(), 0.0);
-        assert_eq!(m.is_invertible(), false);
+        assert!(!m.is_invertible());
+        assert_eq!(inverse(&m), None);
+    }
+
+    #[test]
+    fn transforms_with_nans_are_not_inverted() {
+        let mut m = Matrix4::identity();
+        assert_eq!(inverse(&m), Some(m));
+        m[(0, 3)] = Float::NAN;
+        assert_eq!(inverse(&m), None);
+        m[(0, 3)] = Float::INFINITY;
+        assert_eq!(inverse(&m), None);
     }
 
     // Manually verified. Test result has greater precision than expected result.
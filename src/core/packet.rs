@@ -0,0 +1,220 @@
+use crate::{Float, EPSILON};
+use crate::core::progress::count_rays;
+use crate::core::{point, vector, Intersection, Intersections, Ray, World};
+use crate::primitives::{Object, Primitive};
+use nalgebra::Matrix4;
+use wide::CmpGe;
+
+/// Number of rays traced together in a packet.
+pub const LANES: usize = 4;
+
+// One value for each ray in a packet, worked on with SIMD instructions.
+#[cfg(not(feature = "f32"))]
+type Lanes = wide::f64x4;
+#[cfg(feature = "f32")]
+type Lanes = wide::f32x4;
+
+/// Up to four rays traced together. Their origins and directions are laid out
+/// component by component in SIMD vectors (from the wide crate), so each step
+/// of intersecting a sphere or plane is done for every ray at once. Works best
+/// with rays heading the same way, like camera rays through neighbouring
+/// pixels, or shadow rays from one point. `cargo bench` compares packets with
+/// tracing the same rays one at a time.
+#[derive(Debug, Clone, Copy)]
+pub struct RayPacket {
+    rays: [Ray; LANES],
+    len: usize
+}
+
+impl RayPacket {
+    /// Packs up to LANES rays. Any more are left out.
+    pub fn new(rays: &[Ray]) -> Self {
+        let len = rays.len().min(LANES);
+        // Spare lanes are filled with copies of the first ray, and ignored.
+        let spare = rays.first().copied().unwrap_or_else(|| Ray::new(point(0.0, 0.0, 0.0), vector(0.0, 0.0, 1.0)));
+        let mut packet = RayPacket { rays: [spare; LANES], len };
+        packet.rays[..len].copy_from_slice(&rays[..len]);
+
+        packet
+    }
+
+    pub fn rays(&self) -> &[Ray] {
+        &self.rays[..self.len]
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // Each lane's hits with the object, nearest first, with NaN for a miss. None
+    // for objects that have to be intersected one ray at a time.
+    fn hits(&self, object: &Object) -> Option<[[Float; 2]; LANES]> {
+        if object.is_animated() {
+            return None;
+        }
+        let (o, d) = (self.local(&object.inverse_transform, 1.0), self.local(&object.inverse_transform, 0.0));
+        let miss = Lanes::splat(Float::NAN);
+        let (near, far) = match object.shape {
+            Primitive::Sphere(sphere) if sphere.is_whole() => {
+                let a = dot(d, d);
+                let b = dot(o, d) * 2.0;
+                let c = dot(o, o) - 1.0;
+                let disc = b * b - a * c * 4.0;
+                // As roots::quadratic(), without the branches. Misses take the
+                // square root of a negative, so come out NaN.
+                let q = (b + disc.sqrt().copysign(b)) * -0.5;
+                let (t0, t1) = (q / a, c / q);
+                (t0.min(t1), t0.max(t1))
+            },
+            Primitive::Plane() => {
+                let length = dot(d, d).sqrt();
+                let crosses = d[1].abs().cmp_ge(length * EPSILON);
+                (crosses.blend(-o[1] / d[1], miss), miss)
+            },
+            _ => return None
+        };
+        let (near, far) = (near.to_array(), far.to_array());
+
+        Some(std::array::from_fn(|l| [near[l], far[l]]))
+    }
+
+    // Origins (w = 1.0) or directions (w = 0.0) of every lane, transformed by m,
+    // as x, y and z lanes.
+    fn local(&self, m: &Matrix4<Float>, w: Float) -> [Lanes; 3] {
+        let v = |ray: &Ray| if w == 0.0 { ray.direction } else { ray.origin };
+        let [x, y, z] = [0, 1, 2].map(|i| Lanes::new(self.rays.map(|ray| v(&ray)[i])));
+
+        [0, 1, 2].map(|row| {
+            x * m[(row, 0)] + y * m[(row, 1)] + z * m[(row, 2)] + Lanes::splat(m[(row, 3)] * w)
+        })
+    }
+}
+
+fn dot(a: [Lanes; 3], b: [Lanes; 3]) -> Lanes {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+impl World {
+    /// Intersects every ray in the packet with the world, the same as calling
+    /// intersect_into() for each ray, with ray i's hits going into `xs[i]`.
+    pub fn intersect_packet<'a>(&'a self, packet: &RayPacket, xs: &mut [Intersections<'a>; LANES]) {
+        count_rays(packet.len() as u64);
+        xs.iter_mut().for_each(Intersections::clear);
+        let rays = packet.rays();
+        for o in self.objects.iter().filter(|o| !o.is_volume()) {
+            let hits = packet.hits(o);
+            for (l, ray) in rays.iter().enumerate().filter(|(_, ray)| o.is_visible_to(ray.kind)) {
+                match hits {
+                    Some(hits) => xs[l].extend_sorted(hits[l].iter()
+                        .filter(|t| !t.is_nan() && **t <= ray.t_max)
+                        .map(|&t| Intersection::new(t, o))),
+                    None => xs[l].add_hits(o, ray)
+                }
+            }
+        }
+    }
+
+    /// Which rays in the packet hit something they can see before their t_max,
+    /// the same as calling intersect_any() for each ray. Lanes past the end of
+    /// the packet are false.
+    pub fn intersect_any_packet<'a>(&'a self, packet: &RayPacket, xs: &mut Intersections<'a>) -> [bool; LANES] {
+        count_rays(packet.len() as u64);
+        let rays = packet.rays();
+        let mut blocked = [false; LANES];
+        for o in self.objects.iter().filter(|o| !o.is_volume()) {
+            if blocked[..rays.len()].iter().all(|b| *b) {
+                break;
+            }
+            let hits = packet.hits(o);
+            for (l, ray) in rays.iter().enumerate() {
+                if blocked[l] || !o.is_visible_to(ray.kind) {
+                    continue;
+                }
+                let in_range = |t: Float| t >= 0.0 && t < ray.t_max;
+                blocked[l] = match hits {
+                    Some(hits) => hits[l].iter().any(|t| in_range(*t)),
+                    None => {
+                        xs.clear();
+                        xs.add_hits(o, ray);
+                        xs.hits().any(|i| in_range(i.t))
+                    }
+                };
+            }
+        }
+
+        blocked
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{RayKind, Transform};
+    use crate::consts::PI;
+
+    fn fan() -> Vec<Ray> {
+        (0..LANES)
+            .map(|i| Ray::new(point(0.0, 0.5, -5.0), vector(i as Float * 0.1 - 0.15, -0.1, 1.0).normalize()))
+            .collect()
+    }
+
+    fn scene() -> World {
+        let mut w = World::default_world()
+            .with_object(Object::new_plane().with_transform(Matrix4::translate(0.0, -1.0, 0.0)))
+            .with_object(Object::new_test_shape().with_transform(Matrix4::translate(0.0, 0.0, 3.0)))
+            .with_object(Object::new_sphere()
+                .with_transform(Matrix4::translate(0.0, 0.0, 6.0))
+                .with_transform_at(1.0, Matrix4::translate(1.0, 0.0, 6.0) * Matrix4::rot_y(PI)));
+        w.objects[1].hide_from(RayKind::Camera);
+        w
+    }
+
+    #[test]
+    fn packets_find_the_same_hits_as_single_rays() {
+        let w = scene();
+        let rays = fan();
+        let mut xs: [Intersections; LANES] = Default::default();
+        w.intersect_packet(&RayPacket::new(&rays), &mut xs);
+        for (ray, hits) in rays.iter().zip(&xs) {
+            let expected = w.intersect(ray);
+
+            assert_eq!(hits.len(), expected.len());
+            for (a, b) in hits.iter().zip(expected.iter()) {
+                assert!((a.t - b.t).abs() < 1e-9);
+                assert_eq!(a.object, b.object);
+            }
+        }
+    }
+
+    #[test]
+    fn short_packets_leave_the_other_lanes_empty() {
+        let rays = fan();
+        let packet = RayPacket::new(&rays[..2]);
+        let mut xs: [Intersections; LANES] = Default::default();
+        let w = scene();
+        w.intersect_packet(&packet, &mut xs);
+
+        assert_eq!(packet.len(), 2);
+        assert!(!xs[1].is_empty());
+        assert!(xs[2].is_empty() && xs[3].is_empty());
+    }
+
+    #[test]
+    fn packets_of_shadow_rays_stop_at_their_t_max() {
+        let w = scene();
+        let rays: Vec<Ray> = fan().iter()
+            .enumerate()
+            .map(|(i, r)| r.with_t_max(i as Float * 3.0).with_kind(RayKind::Shadow))
+            .collect();
+        let blocked = w.intersect_any_packet(&RayPacket::new(&rays), &mut Intersections::default());
+        for (ray, b) in rays.iter().zip(blocked) {
+            assert_eq!(b, w.intersect_any(ray, ray.t_max));
+        }
+
+        assert_eq!(blocked, [false, false, true, true]);
+    }
+}
@@ -0,0 +1,269 @@
+use crate::{to_f32, Float};
+use crate::core::{point, vector, Colour, Frame, Ray, RayKind, Rng, World};
+use crate::primitives::{Object, Primitive};
+use nalgebra::Vector4;
+use std::collections::HashMap;
+use crate::consts::PI;
+
+// Most specular bounces a photon is followed through before it's given up on.
+const MAX_BOUNCES: usize = 8;
+
+/// Packet of light that has been focused by glass or mirrors onto a surface.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Photon {
+    pub position: Vector4<Float>,
+    /// Direction the photon was travelling when it landed.
+    pub direction: Vector4<Float>,
+    pub power: Colour
+}
+
+/// Caustic photon map. Photons are fired from every light at the reflective
+/// and transparent spheres in the world, followed through each reflection and
+/// refraction, and stored where they land on a diffuse surface. The density of
+/// photons around a point then gives the light focused onto it, which ordinary
+/// shadow rays can't find.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PhotonMap {
+    photons: Vec<Photon>,
+    photons_per_light: usize,
+    radius: Float,
+    cells: HashMap<(i64, i64, i64), Vec<usize>>
+}
+
+impl PhotonMap {
+    /// Fires the given number of photons from each light, and gathers them
+    /// within radius of each shaded point. More photons allow a smaller radius,
+    /// giving sharper caustics. The same world always gives the same map.
+    pub fn build(world: &World, photons_per_light: usize, radius: Float) -> Self {
+        let mut map = PhotonMap { photons: vec![], photons_per_light, radius, cells: HashMap::new() };
+        let targets: Vec<(Vector4<Float>, Float)> = world.objects.iter()
+            .filter(|o| o.material.reflectivity > 0.0 || o.material.transparency > 0.0)
+            .filter_map(bounding_sphere)
+            .collect();
+        if targets.is_empty() || photons_per_light == 0 {
+            return map;
+        }
+        let per_target = (photons_per_light / targets.len()).max(1);
+        for (i, light) in world.lights.iter().enumerate() {
+            let mut rng = Rng::for_stream(0, i as u64);
+            for (centre, bound) in &targets {
+                let axis = *centre - light.position;
+                let distance = axis.magnitude();
+                // Cone of directions from the light that can reach the sphere.
+                let cos_max = if distance > *bound {
+                    (1.0 - (bound / distance).powi(2)).sqrt()
+                } else {
+                    -1.0
+                };
+                let solid_angle = 2.0 * PI * (1.0 - cos_max);
+                let power = light.colour * (solid_angle / per_target as Float);
+                for _ in 0..per_target {
+                    let direction = cone_direction(axis / distance, cos_max, &mut rng);
+                    let ray = Ray::new(light.position, direction);
+                    // Masks are read a unit out from the light, which is
+                    // exact for projections thrown from the light itself.
+                    let power = power * light.mask_at(light.position + direction);
+                    if let Some(photon) = trace_photon(world, ray, power, &mut rng) {
+                        map.store(photon);
+                    }
+                }
+            }
+        }
+
+        map
+    }
+
+    pub fn len(&self) -> usize {
+        self.photons.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.photons.is_empty()
+    }
+
+    pub fn photons(&self) -> &[Photon] {
+        &self.photons
+    }
+
+    pub fn photons_per_light(&self) -> usize {
+        self.photons_per_light
+    }
+
+    pub fn radius(&self) -> Float {
+        self.radius
+    }
+
+    /// Focused light arriving at a point on a surface facing along normal.
+    /// Photons that landed on the far side of the surface, or on a different
+    /// surface close by, are ignored.
+    pub fn irradiance_at(&self, position: Vector4<Float>, normal: Vector4<Float>) -> Colour {
+        let (x, y, z) = self.cell(position);
+        let mut total = Colour::black();
+        for cell in neighbours(x, y, z) {
+            for &i in self.cells.get(&cell).into_iter().flatten() {
+                let photon = &self.photons[i];
+                let offset = photon.position - position;
+                if offset.magnitude() < self.radius
+                    && offset.dot(&normal).abs() < self.radius * 0.25
+                    && photon.direction.dot(&normal) < 0.0
+                {
+                    total += photon.power;
+                }
+            }
+        }
+
+        total / to_f32(PI * self.radius * self.radius)
+    }
+
+    fn cell(&self, p: Vector4<Float>) -> (i64, i64, i64) {
+        let size = self.radius.max(Float::EPSILON);
+        ((p.x / size).floor() as i64, (p.y / size).floor() as i64, (p.z / size).floor() as i64)
+    }
+
+    fn store(&mut self, photon: Photon) {
+        let cell = self.cell(photon.position);
+        self.cells.entry(cell).or_default().push(self.photons.len());
+        self.photons.push(photon);
+    }
+}
+
+fn neighbours(x: i64, y: i64, z: i64) -> impl Iterator<Item = (i64, i64, i64)> {
+    (-1..=1).flat_map(move |dx| (-1..=1).flat_map(move |dy| (-1..=1).map(move |dz| (x + dx, y + dy, z + dz))))
+}
+
+// Centre and radius of a sphere in world space. Other shapes can't be aimed at.
+fn bounding_sphere(object: &Object) -> Option<(Vector4<Float>, Float)> {
+    match object.shape {
+        Primitive::Sphere(_) => {
+            let centre = object.transform * point(0.0, 0.0, 0.0);
+            let radius = [vector(1.0, 0.0, 0.0), vector(0.0, 1.0, 0.0), vector(0.0, 0.0, 1.0)]
+                .iter()
+                .map(|axis| (object.transform * axis).magnitude())
+                .fold(0.0, Float::max);
+            Some((centre, radius))
+        },
+        _ => None
+    }
+}
+
+// Uniformly distributed direction within cos_max of the axis.
+fn cone_direction(axis: Vector4<Float>, cos_max: Float, rng: &mut Rng) -> Vector4<Float> {
+    let cos_theta = 1.0 - rng.next_float() * (1.0 - cos_max);
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    let phi = 2.0 * PI * rng.next_float();
+
+    Frame::new(axis).to_world(vector(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta))
+}
+
+// Follows a photon through reflections and refractions until it lands on a
+// diffuse surface. Photons that hit a diffuse surface first aren't caustics,
+// and are dropped. Power is scaled by the square of the distance travelled, as
+// lights in this renderer don't fall off with distance.
+fn trace_photon(world: &World, mut ray: Ray, mut power: Colour, rng: &mut Rng) -> Option<Photon> {
+    let mut travelled = 0.0;
+    for bounce in 0..=MAX_BOUNCES {
+        let xs = world.intersect(&ray);
+        let i = xs.hit_index()?;
+        let comps = world.prepare_computations(&xs, i, &ray);
+        let material = comps.object.material;
+        travelled += xs[i].t * ray.direction.magnitude();
+        let (mut reflective, mut transparent) = (material.reflectivity, material.transparency);
+        if reflective <= 0.0 && transparent <= 0.0 {
+            if bounce == 0 || material.diffuse <= 0.0 {
+                return None;
+            }
+            return Some(Photon {
+                position: comps.pos,
+                direction: ray.direction.normalize(),
+                power: power * (travelled * travelled)
+            });
+        }
+        if reflective > 0.0 && transparent > 0.0 {
+            let reflectance = to_f32(comps.schlick());
+            reflective *= reflectance;
+            transparent *= 1.0 - reflectance;
+        }
+        // Absorbed photons are dropped, so the survivors keep their power.
+        let total = (reflective + transparent).max(1.0);
+        let choice = to_f32(rng.next_float()) * total;
+        let (direction, kind) = if choice < reflective {
+            (comps.reflect_vec, RayKind::Reflection)
+        } else if choice < reflective + transparent {
+            match comps.refract_vec() {
+                Some(direction) => (direction, RayKind::Refraction),
+                None => (comps.reflect_vec, RayKind::Refraction)
+            }
+        } else {
+            return None;
+        };
+        let origin = if direction.dot(&comps.normal_vec) < 0.0 { comps.under_pos } else { comps.over_pos };
+        ray = Ray::new(origin, direction).with_kind(kind);
+        power = power * total;
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::Transform;
+    use crate::lights::PointLight;
+    use crate::materials::Material;
+    use nalgebra::Matrix4;
+
+    fn glass_over_floor() -> World {
+        let floor = Object::new_plane()
+            .with_material(Material::default().with_specular(0.0));
+        let orb = Object::glass_orb()
+            .with_transform(Matrix4::translate(0.0, 2.0, 0.0));
+        World::default()
+            .with_light(PointLight::new(Colour::white(), point(0.0, 10.0, 0.0)))
+            .with_object(floor)
+            .with_object(orb)
+    }
+
+    #[test]
+    fn photons_land_beneath_a_glass_orb() {
+        let map = PhotonMap::build(&glass_over_floor(), 2000, 0.2);
+
+        assert!(!map.is_empty());
+        assert!(map.photons().iter().all(|p| p.position.y.abs() < 1e-3));
+    }
+
+    #[test]
+    fn glass_focuses_light_below_it() {
+        let map = PhotonMap::build(&glass_over_floor(), 5000, 0.2);
+        let up = vector(0.0, 1.0, 0.0);
+        let centre = map.irradiance_at(point(0.0, 0.0, 0.0), up);
+        let edge = map.irradiance_at(point(0.9, 0.0, 0.0), up);
+
+        // Brighter than the light would be without the orb in the way.
+        assert!(centre.r > 1.0);
+        assert!(centre.r > edge.r);
+        assert_eq!(map.irradiance_at(point(0.0, 0.0, 0.0), -up), Colour::black());
+    }
+
+    #[test]
+    fn worlds_without_glass_have_no_caustics() {
+        let w = World::default_world();
+
+        assert!(PhotonMap::build(&w, 1000, 0.1).is_empty());
+    }
+
+    #[test]
+    fn unfocused_light_matches_direct_lighting() {
+        // A flat sheet of glass lets light straight through.
+        let sheet = Object::new_sphere()
+            .with_transform(Matrix4::translate(0.0, 5.0, 0.0) * Matrix4::nuscale(4.0, 0.01, 4.0))
+            .with_material(Material::null().with_transparency(1.0));
+        let w = World::default()
+            .with_light(PointLight::new(Colour::white(), point(0.0, 10.0, 0.0)))
+            .with_object(Object::new_plane())
+            .with_object(sheet);
+        let map = PhotonMap::build(&w, 20000, 0.5);
+        let e = map.irradiance_at(point(0.0, 0.0, 0.0), vector(0.0, 1.0, 0.0));
+
+        assert!((e.r - 1.0).abs() < 0.2);
+    }
+}
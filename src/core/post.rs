@@ -0,0 +1,159 @@
+use crate::core::{Canvas, Colour};
+
+/// Image operation applied to a finished render, so it can be given a final
+/// look without an external editor. Ops work on linear colours, so apply them
+/// before the canvas is encoded for export.
+/// 
+/// # Example
+/// 
+/// ```ignore
+/// let canvas = cam.render(world).post_process(&[
+///     PostOp::Exposure(0.5),
+///     PostOp::Bloom { threshold: 1.0, radius: 4, strength: 0.5 },
+///     PostOp::Vignette(0.3)
+/// ]);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PostOp {
+    /// Brightens (or darkens, if negative) by this many stops.
+    Exposure(f32),
+    /// Spreads colours away from mid grey. 1.0 leaves them unchanged, less
+    /// flattens the image.
+    Contrast(f32),
+    /// Scales how far colours are from grey. 1.0 leaves them unchanged and 0.0
+    /// turns the image black and white.
+    Saturation(f32),
+    /// Darkens towards the edges, so the corners are this much darker.
+    Vignette(f32),
+    /// Light brighter than the threshold spills onto the pixels around it,
+    /// blurred over radius pixels and scaled by strength.
+    Bloom { threshold: f32, radius: usize, strength: f32 }
+}
+
+impl PostOp {
+    /// Applies the op to every pixel of the canvas. Alpha is left alone.
+    pub fn apply(&self, canvas: &mut Canvas) {
+        match *self {
+            PostOp::Exposure(stops) => {
+                let scale = 2f32.powf(stops);
+                canvas.pixels.iter_mut().for_each(|c| *c = *c * scale);
+            },
+            PostOp::Contrast(amount) => {
+                let mid = Colour::grey(0.5);
+                canvas.pixels.iter_mut().for_each(|c| *c = (*c - mid) * amount + mid);
+            },
+            PostOp::Saturation(amount) => {
+                for c in &mut canvas.pixels {
+                    let grey = Colour::grey(luminance(*c));
+                    *c = grey + (*c - grey) * amount;
+                }
+            },
+            PostOp::Vignette(strength) => {
+                let (cx, cy) = (canvas.width as f32 / 2.0, canvas.height as f32 / 2.0);
+                let corner = cx * cx + cy * cy;
+                for y in 0..canvas.height {
+                    for x in 0..canvas.width {
+                        let (dx, dy) = (x as f32 + 0.5 - cx, y as f32 + 0.5 - cy);
+                        let falloff = (1.0 - strength * (dx * dx + dy * dy) / corner).max(0.0);
+                        canvas[(x, y)] = canvas[(x, y)] * falloff;
+                    }
+                }
+            },
+            PostOp::Bloom { threshold, radius, strength } => {
+                let bright: Vec<Colour> = canvas.pixels.iter()
+                    .map(|c| Colour::new((c.r - threshold).max(0.0), (c.g - threshold).max(0.0), (c.b - threshold).max(0.0)))
+                    .collect();
+                let glow = blur(&blur(&bright, canvas.width, canvas.height, radius, true), canvas.width, canvas.height, radius, false);
+                for (c, g) in canvas.pixels.iter_mut().zip(glow) {
+                    *c += g * strength;
+                }
+            }
+        }
+    }
+}
+
+impl Canvas {
+    /// Copy of the canvas with each op applied in turn.
+    pub fn post_process(&self, ops: &[PostOp]) -> Canvas {
+        let mut out = Canvas::new(self.width, self.height, Colour::black()).with_encoding(self.encoding);
+        out.pixels.clone_from(&self.pixels);
+        out.alpha.clone_from(&self.alpha);
+        for op in ops {
+            op.apply(&mut out);
+        }
+
+        out
+    }
+}
+
+// Perceived brightness of a linear colour.
+fn luminance(c: Colour) -> f32 {
+    0.2126 * c.r + 0.7152 * c.g + 0.0722 * c.b
+}
+
+// Box blur along rows (or columns), with pixels off the edge left out.
+fn blur(pixels: &[Colour], width: usize, height: usize, radius: usize, rows: bool) -> Vec<Colour> {
+    let (len, lines) = if rows { (width, height) } else { (height, width) };
+    let at = |line: usize, i: usize| if rows { line * width + i } else { i * width + line };
+    let mut out = vec![Colour::black(); pixels.len()];
+    for line in 0..lines {
+        for i in 0..len {
+            let (start, end) = (i.saturating_sub(radius), (i + radius + 1).min(len));
+            let mut total = Colour::black();
+            for j in start..end {
+                total += pixels[at(line, j)];
+            }
+            out[at(line, i)] = total / (end - start) as f32;
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn exposure_is_in_stops() {
+        let c = Canvas::new(2, 2, Colour::grey(0.25)).post_process(&[PostOp::Exposure(1.0)]);
+
+        assert_eq!(c.read_pix(1, 1), Colour::grey(0.5));
+    }
+
+    #[test]
+    fn contrast_and_saturation_pivot_on_grey() {
+        let c = Canvas::new(1, 1, Colour::new(0.75, 0.5, 0.25));
+        let flat = c.post_process(&[PostOp::Contrast(0.0)]);
+        let boosted = c.post_process(&[PostOp::Contrast(2.0)]);
+        let mono = c.post_process(&[PostOp::Saturation(0.0)]).read_pix(0, 0);
+
+        assert_eq!(flat.read_pix(0, 0), Colour::grey(0.5));
+        assert_eq!(boosted.read_pix(0, 0), Colour::new(1.0, 0.5, 0.0));
+        assert_approx_eq!(mono.r, mono.b);
+        assert_approx_eq!(mono.g, luminance(Colour::new(0.75, 0.5, 0.25)));
+    }
+
+    #[test]
+    fn vignette_darkens_the_corners() {
+        let c = Canvas::new(9, 9, Colour::white()).post_process(&[PostOp::Vignette(0.5)]);
+
+        assert_eq!(c.read_pix(4, 4), Colour::white());
+        assert!(c.read_pix(0, 0).r < c.read_pix(0, 4).r);
+        assert!(c.read_pix(0, 0).r > 0.5);
+    }
+
+    #[test]
+    fn bloom_spreads_only_bright_light() {
+        let mut c = Canvas::new(7, 7, Colour::grey(0.5));
+        c.write_pix(3, 3, Colour::grey(10.0));
+        c.write_alpha(0, 0, 0.5);
+        let out = c.post_process(&[PostOp::Bloom { threshold: 1.0, radius: 1, strength: 1.0 }]);
+
+        assert!(out.read_pix(2, 3).r > 0.5);
+        assert!(out.read_pix(2, 2).r > 0.5);
+        assert_eq!(out.read_pix(3, 5), Colour::grey(0.5));
+        assert_eq!(out.read_alpha(0, 0), 0.5);
+    }
+}
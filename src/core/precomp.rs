@@ -1,33 +1,49 @@
+use crate::Float;
+use crate::core::Colour;
 use crate::primitives::Object;
 use nalgebra::Vector4;
+use std::borrow::Cow;
 
 #[derive(Debug)]
-pub struct PreCompData {
-    pub t: f64,
-    pub object: Object,
-    pub pos: Vector4<f64>,
-    pub over_pos: Vector4<f64>,
-    pub under_pos: Vector4<f64>,
-    pub eye_vec: Vector4<f64>,
+pub struct PreCompData<'a> {
+    pub t: Float,
+    /// Object that was hit, borrowed unless it's moving, in which case it's a
+    /// copy frozen at the time of the ray.
+    pub object: Cow<'a, Object>,
+    pub pos: Vector4<Float>,
+    pub over_pos: Vector4<Float>,
+    pub under_pos: Vector4<Float>,
+    pub eye_vec: Vector4<Float>,
     pub n1: f32,
     pub n2: f32,
-    pub normal_vec: Vector4<f64>,
-    pub reflect_vec: Vector4<f64>,
-    pub inside: bool
+    pub normal_vec: Vector4<Float>,
+    pub reflect_vec: Vector4<Float>,
+    pub inside: bool,
+    pub time: Float,
+    /// Width of the incoming ray's cone where it hit, across the ray.
+    pub footprint: Float,
+    /// How fast the incoming ray's cone was spreading, passed on to the rays
+    /// traced from the hit.
+    pub spread: Float,
+    /// Where on the surface it was hit, and any shape-specific data, as the
+    /// shape recorded them in the Intersection.
+    pub uv: Option<(Float, Float)>,
+    pub data: usize
 }
 
-impl PreCompData {
+impl<'a> PreCompData<'a> {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
-        t: f64,
-        object: Object,
-        pos: Vector4<f64>,
-        over_pos: Vector4<f64>,
-        under_pos: Vector4<f64>,
-        eye_vec: Vector4<f64>,
+        t: Float,
+        object: Cow<'a, Object>,
+        pos: Vector4<Float>,
+        over_pos: Vector4<Float>,
+        under_pos: Vector4<Float>,
+        eye_vec: Vector4<Float>,
         n1: f32,
         n2: f32,
-        normal_vec: Vector4<f64>,
-        reflect_vec: Vector4<f64>,
+        normal_vec: Vector4<Float>,
+        reflect_vec: Vector4<Float>,
         inside: bool
     ) -> Self {
         Self {
@@ -41,16 +57,55 @@ impl PreCompData {
             n2,
             normal_vec,
             reflect_vec,
-            inside
+            inside,
+            time: 0.0,
+            footprint: 0.0,
+            spread: 0.0,
+            uv: None,
+            data: 0
         }
     }
 
+    /// Carries the time of the incoming ray over to secondary rays.
+    pub fn with_time(mut self, time: Float) -> Self {
+        self.time = time;
+
+        self
+    }
+
+    /// Carries the cone of the incoming ray over to secondary rays, with its
+    /// width where it hit.
+    pub fn with_cone(mut self, footprint: Float, spread: Float) -> Self {
+        self.footprint = footprint;
+        self.spread = spread;
+
+        self
+    }
+
+    /// Carries over what the shape recorded about the hit.
+    pub fn with_hit_data(mut self, uv: Option<(Float, Float)>, data: usize) -> Self {
+        self.uv = uv;
+        self.data = data;
+
+        self
+    }
+
+    /// Colour of the object's pattern at the hit, filtered over the ray's
+    /// footprint on the surface. The footprint is stretched out on surfaces
+    /// seen at an angle, so it's taken as a circle of the same area.
+    pub fn pattern_colour(&self) -> Colour {
+        let cos = self.normal_vec.dot(&self.eye_vec).abs().max(0.01);
+        let width = self.footprint / cos.sqrt();
+
+        self.object.material.pattern.filtered_at_object(&self.object, self.over_pos, self.normal_vec, width)
+    }
+
     /// Schlick approximation of the Fresnel effect.
-    pub fn schlick(&self) -> f64 {
+    pub fn schlick(&self) -> Float {
         let mut cos = self.normal_vec.dot(&self.eye_vec);
 
         if self.n1 > self.n2 {
-            let n_ratio = (self.n1 / self.n2) as f64;
+            let n_ratio = (self.n1 / self.n2) as Float;
             let sin2_t = n_ratio.powi(2) * (1.0 - cos.powi(2));
             if sin2_t > 1.0 {
                 return 1.0;
@@ -59,13 +114,28 @@ impl PreCompData {
             cos = (1.0 - sin2_t).sqrt();
         }
 
-        let r0 = ((self.n1 - self.n2) / (self.n1 + self.n2)).powi(2) as f64;
+        let r0 = ((self.n1 - self.n2) / (self.n1 + self.n2)).powi(2) as Float;
         r0 + (1.0 - r0) * (1.0 - cos).powi(5)
     }
+
+    /// Direction of the refracted ray, from Snell's law. None under total
+    /// internal reflection.
+    pub fn refract_vec(&self) -> Option<Vector4<Float>> {
+        let n_ratio = (self.n1 / self.n2) as Float;
+        let cos_i = self.normal_vec.dot(&self.eye_vec);
+        let sin2_t = n_ratio.powi(2) * (1.0 - cos_i.powi(2));
+        if sin2_t > 1.0 {
+            return None;
+        }
+        let cos_t = (1.0 - sin2_t).sqrt();
+
+        Some(self.normal_vec * (n_ratio * cos_i - cos_t) - self.eye_vec * n_ratio)
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::Float;
     use crate::core::{point, vector, Intersection, Intersections, Ray};
     use crate::primitives::Object;
     use assert_approx_eq::assert_approx_eq;
@@ -73,11 +143,11 @@ mod tests {
     #[test]
     fn schlick_approximation_under_total_internal_reflection() {
         let object = Object::glass_orb();
-        let irr_no = 2.0f64.sqrt() / 2.0;
+        let irr_no = Float::sqrt(2.0) / 2.0;
         let ray = Ray::new(point(0.0, 0.0, irr_no), vector(0.0, 1.0, 0.0));
         let xs = Intersections::new(vec![
-            Intersection::new(-irr_no, object),
-            Intersection::new(irr_no, object)
+            Intersection::new(-irr_no, &object),
+            Intersection::new(irr_no, &object)
         ]);
         let comps = xs.prepare_computations(1, &ray);
 
@@ -89,8 +159,8 @@ mod tests {
         let object = Object::glass_orb();
         let ray = Ray::new(point(0.0, 0.0, 0.0), vector(0.0, 1.0, 0.0));
         let xs = Intersections::new(vec![
-            Intersection::new(-1.0, object),
-            Intersection::new(1.0, object)
+            Intersection::new(-1.0, &object),
+            Intersection::new(1.0, &object)
         ]);
         let comps = xs.prepare_computations(1, &ray);
 
@@ -102,7 +172,7 @@ mod tests {
         let object = Object::glass_orb();
         let ray = Ray::new(point(0.0, 0.99, -2.0), vector(0.0, 0.0, 1.0));
         let xs = Intersections::new(vec![
-            Intersection::new(1.8589, object)
+            Intersection::new(1.8589, &object)
         ]);
         let comps = xs.prepare_computations(0, &ray);
 
@@ -0,0 +1,157 @@
+use std::cell::Cell;
+use std::time::{Duration, Instant};
+
+thread_local! {
+    // Rays traced on this thread since the count was last taken.
+    static RAYS: Cell<u64> = const { Cell::new(0) };
+}
+
+/// Snapshot of how far along a render is. Passed to progress callbacks.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Progress {
+    pub pixels_done: usize,
+    pub total: usize,
+    pub elapsed: Duration,
+    /// Rays of every kind traced so far: camera, shadow, reflected, refracted
+    /// and bounced.
+    pub rays: u64
+}
+
+impl Progress {
+    pub fn new(pixels_done: usize, total: usize, elapsed: Duration) -> Self {
+        Progress { pixels_done, total, elapsed, rays: 0 }
+    }
+
+    pub fn with_rays(mut self, rays: u64) -> Self {
+        self.rays = rays;
+
+        self
+    }
+
+    /// Rays traced per second of the render so far.
+    pub fn rays_per_second(&self) -> f64 {
+        let secs = self.elapsed.as_secs_f64();
+        if secs > 0.0 {
+            self.rays as f64 / secs
+        } else {
+            0.0
+        }
+    }
+
+    /// Fraction of the render completed, between 0.0 and 1.0.
+    pub fn fraction(&self) -> f64 {
+        if self.total == 0 {
+            1.0
+        } else {
+            self.pixels_done as f64 / self.total as f64
+        }
+    }
+
+    /// Estimated time remaining, extrapolated from the time taken so far.
+    /// Returns None until at least one pixel has been completed.
+    pub fn eta(&self) -> Option<Duration> {
+        if self.pixels_done == 0 {
+            None
+        } else {
+            let remaining = self.total.saturating_sub(self.pixels_done) as f64;
+            let per_pixel = self.elapsed.as_secs_f64() / self.pixels_done as f64;
+            Some(Duration::from_secs_f64(remaining * per_pixel))
+        }
+    }
+
+    /// Is the render finished?
+    pub fn is_complete(&self) -> bool {
+        self.pixels_done >= self.total
+    }
+}
+
+// Keeps track of completed pixels, rays traced and the time since the render
+// started.
+pub(crate) struct ProgressTracker {
+    start: Option<Instant>,
+    done: usize,
+    total: usize,
+    rays: u64
+}
+
+impl ProgressTracker {
+    pub(crate) fn new(total: usize) -> Self {
+        ProgressTracker { start: now(), done: 0, total, rays: 0 }
+    }
+
+    pub(crate) fn advance(&mut self, pixels: usize, rays: u64) -> Progress {
+        self.done += pixels;
+        self.rays += rays;
+        let elapsed = self.start.map_or(Duration::ZERO, |start| start.elapsed());
+        Progress::new(self.done, self.total, elapsed).with_rays(self.rays)
+    }
+}
+
+// Browsers have no clock that Instant can read, and Instant::now() panics
+// there, so elapsed times stay at zero.
+#[cfg(not(target_arch = "wasm32"))]
+fn now() -> Option<Instant> {
+    Some(Instant::now())
+}
+#[cfg(target_arch = "wasm32")]
+fn now() -> Option<Instant> {
+    None
+}
+
+// Counts rays traced on this thread. Kept per thread so render workers don't
+// fight over a shared counter for every ray.
+pub(crate) fn count_rays(n: u64) {
+    RAYS.with(|rays| rays.set(rays.get() + n));
+}
+
+// Rays traced on this thread since the last call.
+pub(crate) fn take_rays() -> u64 {
+    RAYS.with(|rays| rays.replace(0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fraction_of_render_completed() {
+        let p = Progress::new(25, 100, Duration::from_secs(1));
+
+        assert_eq!(p.fraction(), 0.25);
+        assert!(!p.is_complete());
+    }
+
+    #[test]
+    fn eta_is_extrapolated_from_elapsed_time() {
+        let p = Progress::new(25, 100, Duration::from_secs(1));
+
+        assert_eq!(p.eta(), Some(Duration::from_secs(3)));
+        assert_eq!(Progress::new(120, 100, Duration::from_secs(1)).eta(), Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn no_eta_before_first_pixel() {
+        let p = Progress::new(0, 100, Duration::from_secs(1));
+
+        assert_eq!(p.eta(), None);
+    }
+
+    #[test]
+    fn rays_per_second_over_elapsed_time() {
+        let p = Progress::new(25, 100, Duration::from_secs(2)).with_rays(1000);
+
+        assert_eq!(p.rays_per_second(), 500.0);
+        assert_eq!(Progress::new(0, 100, Duration::ZERO).rays_per_second(), 0.0);
+    }
+
+    #[test]
+    fn tracker_accumulates_pixels() {
+        let mut tracker = ProgressTracker::new(10);
+        tracker.advance(4, 100);
+        let p = tracker.advance(6, 50);
+
+        assert_eq!(p.pixels_done, 10);
+        assert_eq!(p.rays, 150);
+        assert!(p.is_complete());
+    }
+}
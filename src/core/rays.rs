@@ -1,25 +1,116 @@
+use crate::{Float, FeorayError};
 use crate::core::Tuple;
 use nalgebra::{Matrix4, Vector4};
 
 #[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
 pub struct Ray {
-    pub origin: Vector4<f64>,
-    pub direction: Vector4<f64>
+    pub origin: Vector4<Float>,
+    pub direction: Vector4<Float>,
+    pub time: Float,
+    /// Furthest along the ray that hits count. Anything beyond is ignored.
+    pub t_max: Float,
+    pub kind: RayKind,
+    /// Width of the cone of space the ray stands for, at its origin. Camera
+    /// rays start as a point, and rays from a hit start as wide as the ray
+    /// that made them was there.
+    pub width: Float,
+    /// How much wider the cone gets per unit travelled, e.g. the size of a
+    /// pixel for camera rays. Patterns are filtered over the cone's width
+    /// where it hits. Zero for rays that don't need it.
+    pub spread: Float
+}
+
+/// What a ray is being traced for. Objects can be hidden from some kinds of
+/// ray, and renders can be broken down by kind when debugging.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Default)]
+pub enum RayKind {
+    #[default]
+    Camera,
+    Shadow,
+    Reflection,
+    Refraction,
+    /// Diffuse bounce in the path tracer.
+    Diffuse
+}
+
+impl RayKind {
+    pub const ALL: [RayKind; 5] = [RayKind::Camera, RayKind::Shadow, RayKind::Reflection, RayKind::Refraction, RayKind::Diffuse];
+
+    /// Name used in scene files.
+    pub fn name(&self) -> &'static str {
+        match self {
+            RayKind::Camera => "camera",
+            RayKind::Shadow => "shadow",
+            RayKind::Reflection => "reflection",
+            RayKind::Refraction => "refraction",
+            RayKind::Diffuse => "diffuse"
+        }
+    }
+
+    /// Bit for this kind in a set of kinds, such as Object::hidden_from.
+    pub fn bit(&self) -> u8 {
+        1 << *self as u8
+    }
 }
 
 impl Ray {
-    pub fn new(origin: Vector4<f64>, direction: Vector4<f64>) -> Self {
-        if !origin.is_point() { panic!("origin should be a point"); }
-        if !direction.is_vector() { panic!("direction should be a vector"); }
-        Ray { origin, direction }
+    pub fn new(origin: Vector4<Float>, direction: Vector4<Float>) -> Self {
+        Ray::try_new(origin, direction).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Same as new(), but returns an error rather than panicking if the origin
+    /// isn't a point or the direction isn't a vector.
+    pub fn try_new(origin: Vector4<Float>, direction: Vector4<Float>) -> Result<Self, FeorayError> {
+        if !origin.is_point() {
+            return Err(FeorayError::InvalidRay("origin should be a point"));
+        }
+        if !direction.is_vector() {
+            return Err(FeorayError::InvalidRay("direction should be a vector"));
+        }
+
+        Ok(Ray { origin, direction, time: 0.0, t_max: Float::INFINITY, kind: RayKind::Camera, width: 0.0, spread: 0.0 })
+    }
+
+    /// Sets the moment in time the ray was cast, used to sample moving objects.
+    pub fn with_time(mut self, time: Float) -> Self {
+        self.time = time;
+
+        self
+    }
+
+    /// Ignores hits further than t_max along the ray.
+    pub fn with_t_max(mut self, t_max: Float) -> Self {
+        self.t_max = t_max;
+
+        self
+    }
+
+    pub fn with_kind(mut self, kind: RayKind) -> Self {
+        self.kind = kind;
+
+        self
+    }
+
+    /// Gives the ray a cone of the given width at its origin, spreading out
+    /// by spread per unit travelled.
+    pub fn with_cone(mut self, width: Float, spread: Float) -> Self {
+        self.width = width;
+        self.spread = spread;
+
+        self
     }
 
-    pub fn position(&self, t: f64) -> Vector4<f64> {
+    /// Width of the ray's cone at t.
+    pub fn width_at(&self, t: Float) -> Float {
+        self.width + self.spread * t * self.direction.magnitude()
+    }
+
+    pub fn position(&self, t: Float) -> Vector4<Float> {
         self.origin + self.direction * t
     }
 
-    pub fn transform(&self, m: Matrix4<f64>) -> Ray {
-        Ray::new(m.clone() * self.origin, m * self.direction)
+    pub fn transform(&self, m: Matrix4<Float>) -> Ray {
+        Ray { origin: m * self.origin, direction: m * self.direction, ..*self }
     }
 }
 
@@ -27,6 +118,7 @@ impl Ray {
 mod tests {
     use super::*;
     use crate::core::{point, vector, Transform};
+    use crate::EPSILON;
 
     #[test]
     fn create_and_query_ray() {
@@ -67,4 +159,52 @@ mod tests {
         assert_eq!(r2.origin, point(2.0, 6.0, 12.0));
         assert_eq!(r2.direction, vector(0.0, 3.0, 0.0));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn rays_start_at_time_zero() {
+        let r = Ray::new(point(1.0, 2.0, 3.0), vector(0.0, 1.0, 0.0));
+
+        assert_eq!(r.time, 0.0);
+    }
+
+    #[test]
+    fn transforming_a_ray_keeps_its_time() {
+        let r = Ray::new(point(1.0, 2.0, 3.0), vector(0.0, 1.0, 0.0))
+            .with_time(0.25);
+        let r2 = r.transform(Matrix4::translate(3.0, 4.0, 5.0));
+
+        assert_eq!(r2.time, 0.25);
+    }
+
+    #[test]
+    fn transformed_rays_keep_their_range_and_kind() {
+        let r = Ray::new(point(1.0, 2.0, 3.0), vector(0.0, 1.0, 0.0))
+            .with_t_max(4.0)
+            .with_kind(RayKind::Shadow);
+        let r2 = r.transform(Matrix4::uscale(2.0));
+
+        assert_eq!(Ray::new(point(0.0, 0.0, 0.0), vector(0.0, 0.0, 1.0)).t_max, Float::INFINITY);
+        assert_eq!(r2.t_max, 4.0);
+        assert_eq!(r2.kind, RayKind::Shadow);
+    }
+
+    #[test]
+    fn rays_need_a_point_and_a_vector() {
+        let p = point(1.0, 2.0, 3.0);
+        let v = vector(0.0, 1.0, 0.0);
+
+        assert!(Ray::try_new(p, v).is_ok());
+        assert!(matches!(Ray::try_new(v, v), Err(FeorayError::InvalidRay(m)) if m.contains("origin")));
+        assert!(matches!(Ray::try_new(p, p), Err(FeorayError::InvalidRay(m)) if m.contains("direction")));
+    }
+
+    #[test]
+    fn ray_cones_widen_with_distance() {
+        let r = Ray::new(point(0.0, 0.0, 0.0), vector(0.0, 0.0, 2.0)).with_cone(0.5, 0.1);
+        let r2 = r.transform(Matrix4::translate(1.0, 0.0, 0.0));
+
+        assert_eq!(Ray::new(point(0.0, 0.0, 0.0), vector(0.0, 0.0, 1.0)).width_at(10.0), 0.0);
+        assert!((r.width_at(5.0) - 1.5).abs() < EPSILON);
+        assert_eq!((r2.width, r2.spread), (0.5, 0.1));
+    }
+}
@@ -0,0 +1,176 @@
+use std::f32::consts::PI;
+use crate::core::{Canvas, Colour};
+
+/// How pixels are worked out when a canvas is resized.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ResizeFilter {
+    /// Takes the nearest pixel. Blocky, but keeps hard edges for pixel peeping.
+    Nearest,
+    /// Blends the nearest pixels linearly (a tent filter when shrinking).
+    #[default]
+    Bilinear,
+    /// Windowed sinc over three pixels either side. Sharpest, at the cost of
+    /// slight ringing around hard edges.
+    Lanczos
+}
+
+impl ResizeFilter {
+    // Pixels either side of the centre the filter reaches, at full size.
+    fn radius(&self) -> f32 {
+        match self {
+            ResizeFilter::Nearest => 0.5,
+            ResizeFilter::Bilinear => 1.0,
+            ResizeFilter::Lanczos => 3.0
+        }
+    }
+
+    fn weight(&self, x: f32) -> f32 {
+        let x = x.abs();
+        match self {
+            ResizeFilter::Nearest => if x < 0.5 { 1.0 } else { 0.0 },
+            ResizeFilter::Bilinear => (1.0 - x).max(0.0),
+            ResizeFilter::Lanczos => {
+                if x < 1e-6 {
+                    1.0
+                } else if x < 3.0 {
+                    let px = PI * x;
+                    3.0 * px.sin() * (px / 3.0).sin() / (px * px)
+                } else {
+                    0.0
+                }
+            }
+        }
+    }
+}
+
+impl Canvas {
+    /// Copy of the canvas scaled to the given size, for thumbnails or blown up
+    /// previews. When shrinking, each new pixel averages all the pixels it
+    /// covers, so fine detail doesn't alias. Alpha is resized along with the
+    /// colours.
+    /// 
+    /// # Example
+    /// 
+    /// ```ignore
+    /// let thumbnail = canvas.resize(canvas.width / 4, canvas.height / 4, ResizeFilter::Lanczos);
+    /// ```
+    pub fn resize(&self, width: usize, height: usize, filter: ResizeFilter) -> Canvas {
+        let mut out = Canvas::new(width, height, Colour::black()).with_encoding(self.encoding);
+        if self.width == 0 || self.height == 0 || width == 0 || height == 0 {
+            return out;
+        }
+        let columns = taps(self.width, width, filter);
+        let rows = taps(self.height, height, filter);
+        // Rows first, then columns.
+        let resample = |get: &dyn Fn(usize, usize) -> Colour| {
+            let mut across = vec![Colour::black(); width * self.height];
+            for y in 0..self.height {
+                for (x, taps) in columns.iter().enumerate() {
+                    across[y * width + x] = taps.iter().fold(Colour::black(), |c, (i, w)| c + get(*i, y) * *w);
+                }
+            }
+            let mut pixels = vec![Colour::black(); width * height];
+            for (y, taps) in rows.iter().enumerate() {
+                for x in 0..width {
+                    pixels[y * width + x] = taps.iter().fold(Colour::black(), |c, (i, w)| c + across[i * width + x] * *w);
+                }
+            }
+            pixels
+        };
+        out.pixels = resample(&|x, y| self.read_pix(x, y));
+        if self.has_alpha() {
+            let alpha = resample(&|x, y| Colour::grey(self.read_alpha(x, y)));
+            out.alpha = Some(alpha.iter().map(|a| a.r.clamp(0.0, 1.0)).collect());
+        }
+
+        out
+    }
+}
+
+// Source pixels, with their normalised weights, making up each pixel along a
+// line resized from one length to another.
+fn taps(from: usize, to: usize, filter: ResizeFilter) -> Vec<Vec<(usize, f32)>> {
+    let scale = from as f32 / to as f32;
+    // Shrinking spreads the filter over every source pixel a new one covers.
+    let spread = scale.max(1.0);
+    let reach = filter.radius() * spread;
+    (0..to)
+        .map(|i| {
+            let centre = (i as f32 + 0.5) * scale;
+            let first = (centre - reach).floor().max(0.0) as usize;
+            let last = ((centre + reach).ceil() as usize).min(from);
+            let mut taps: Vec<(usize, f32)> = (first..last)
+                .map(|j| (j, filter.weight((j as f32 + 0.5 - centre) / spread)))
+                .filter(|(_, w)| *w != 0.0)
+                .collect();
+            if taps.is_empty() {
+                taps.push(((centre as usize).min(from - 1), 1.0));
+            }
+            let total: f32 = taps.iter().map(|(_, w)| w).sum();
+            taps.iter_mut().for_each(|(_, w)| *w /= total);
+            taps
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    // Black and white columns, one pixel wide.
+    fn stripes(width: usize, height: usize) -> Canvas {
+        let mut c = Canvas::new(width, height, Colour::black());
+        for y in 0..height {
+            for x in (0..width).step_by(2) {
+                c.write_pix(x, y, Colour::white());
+            }
+        }
+        c
+    }
+
+    #[test]
+    fn nearest_repeats_pixels_when_enlarging() {
+        let c = stripes(2, 1).resize(4, 2, ResizeFilter::Nearest);
+
+        assert_eq!((c.width, c.height), (4, 2));
+        assert_eq!(c.read_pix(1, 1), Colour::white());
+        assert_eq!(c.read_pix(2, 0), Colour::black());
+    }
+
+    #[test]
+    fn shrinking_averages_fine_detail() {
+        for filter in [ResizeFilter::Bilinear, ResizeFilter::Lanczos] {
+            let c = stripes(16, 16).resize(4, 4, filter);
+
+            assert_approx_eq!(c.read_pix(1, 1).r, 0.5, 0.05);
+        }
+    }
+
+    #[test]
+    fn flat_colours_and_alpha_survive_resizing() {
+        let mut c = Canvas::new(5, 3, Colour::new(0.2, 0.4, 0.6));
+        for y in 0..3 {
+            for x in 0..5 {
+                c.write_alpha(x, y, 0.5);
+            }
+        }
+        for filter in [ResizeFilter::Nearest, ResizeFilter::Bilinear, ResizeFilter::Lanczos] {
+            let out = c.resize(13, 2, filter);
+
+            assert_eq!(out.read_pix(7, 1).to_5dp(), Colour::new(0.2, 0.4, 0.6));
+            assert_approx_eq!(out.read_alpha(12, 0), 0.5);
+        }
+    }
+
+    #[test]
+    fn bilinear_blends_between_pixels() {
+        let mut c = Canvas::new(2, 1, Colour::black());
+        c.write_pix(1, 0, Colour::white());
+        let out = c.resize(4, 1, ResizeFilter::Bilinear);
+
+        assert_eq!(out.read_pix(0, 0), Colour::black());
+        assert_eq!(out.read_pix(1, 0), Colour::grey(0.25));
+        assert_eq!(out.read_pix(2, 0), Colour::grey(0.75));
+    }
+}
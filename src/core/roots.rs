@@ -0,0 +1,257 @@
+use crate::Float;
+use crate::consts::PI;
+
+// Coefficients below this are taken as zero while solving cubics and quartics,
+// before the roots are polished against the original polynomial.
+const ZERO: Float = 1.0e-9;
+
+/// Real roots of a polynomial, smallest first, as found by cubic() and
+/// quartic(). Kept inline, like the stops of a ColourRamp, so solving doesn't
+/// allocate in the middle of intersecting a shape.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Roots {
+    values: [Float; 4],
+    len: usize
+}
+
+impl Roots {
+    fn new(roots: &[Float]) -> Self {
+        let mut values = [0.0; 4];
+        values[..roots.len()].copy_from_slice(roots);
+        values[..roots.len()].sort_by(|a, b| a.total_cmp(b));
+
+        Roots { values, len: roots.len() }
+    }
+
+    pub fn as_slice(&self) -> &[Float] {
+        &self.values[..self.len]
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+/// Roots of a t² + b t + c, smallest first, or None if there are none. A
+/// single root, as for a ray just touching a sphere, comes back twice.
+///
+/// The textbook formula subtracts two nearly equal numbers for one of the
+/// roots when b is large next to a and c, as it is for rays that graze a
+/// shape or start far from it, and loses most of its precision. This works
+/// the other root out from the first instead, as their product is c / a.
+pub fn quadratic(a: Float, b: Float, c: Float) -> Option<(Float, Float)> {
+    if a == 0.0 {
+        return (b != 0.0).then(|| (-c / b, -c / b));
+    }
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+    let q = -0.5 * (b + discriminant.sqrt().copysign(b));
+    if q == 0.0 {
+        // Only when b and c are both zero.
+        return Some((0.0, 0.0));
+    }
+    let (t0, t1) = (q / a, c / q);
+
+    Some((t0.min(t1), t0.max(t1)))
+}
+
+/// Real roots of a t³ + b t² + c t + d. Repeated roots come back once.
+pub fn cubic(a: Float, b: Float, c: Float, d: Float) -> Roots {
+    if a == 0.0 {
+        return match quadratic(b, c, d) {
+            Some((t0, t1)) if t0 == t1 => Roots::new(&[t0]),
+            Some((t0, t1)) => Roots::new(&[t0, t1]),
+            None => Roots::new(&[])
+        };
+    }
+    let roots = depressed_cubic(b / a, c / a, d / a);
+
+    polish(roots, &[a, b, c, d])
+}
+
+/// Real roots of a t⁴ + b t³ + c t² + d t + e, e.g. for a ray against a torus.
+/// Repeated roots come back once.
+pub fn quartic(a: Float, b: Float, c: Float, d: Float, e: Float) -> Roots {
+    if a == 0.0 {
+        return cubic(b, c, d, e);
+    }
+    // Ferrari's method, after Schwarze's in Graphics Gems. Substituting
+    // t = y - b / 4a leaves y⁴ + p y² + q y + r.
+    let (b, c, d, e) = (b / a, c / a, d / a, e / a);
+    let shift = b / 4.0;
+    let b2 = b * b;
+    let p = -3.0 / 8.0 * b2 + c;
+    let q = b2 * b / 8.0 - b * c / 2.0 + d;
+    let r = -3.0 / 256.0 * b2 * b2 + b2 * c / 16.0 - b * d / 4.0 + e;
+    let mut ys = [0.0; 4];
+    let mut n = 0;
+    if r.abs() < ZERO {
+        // y (y³ + p y + q) = 0
+        ys[0] = 0.0;
+        n = 1;
+        for &y in cubic(1.0, 0.0, p, q).as_slice() {
+            ys[n] = y;
+            n += 1;
+        }
+    } else {
+        // Any real root of the resolvent cubic splits it into two quadratics.
+        let z = cubic(1.0, -p / 2.0, -r, r * p / 2.0 - q * q / 8.0).as_slice()[0];
+        let u = z * z - r;
+        let v = 2.0 * z - p;
+        let u = if u.abs() < ZERO { 0.0 } else if u > 0.0 { u.sqrt() } else { return Roots::new(&[]) };
+        let v = if v.abs() < ZERO { 0.0 } else if v > 0.0 { v.sqrt() } else { return Roots::new(&[]) };
+        let v = if q < 0.0 { -v } else { v };
+        for (b, c) in [(v, z - u), (-v, z + u)] {
+            if let Some((y0, y1)) = quadratic(1.0, b, c) {
+                ys[n] = y0;
+                ys[n + 1] = y1;
+                n += 2;
+            }
+        }
+    }
+    for y in &mut ys[..n] {
+        *y -= shift;
+    }
+
+    polish(dedup(&mut ys[..n]), &[1.0, b, c, d, e])
+}
+
+// Roots of t³ + a t² + b t + c, by Cardano's formula or, with three real
+// roots, the trigonometric method.
+fn depressed_cubic(a: Float, b: Float, c: Float) -> Roots {
+    // Substituting t = y - a / 3 leaves y³ + 3p y + 2q.
+    let shift = a / 3.0;
+    let p = (b - a * a / 3.0) / 3.0;
+    let q = (2.0 / 27.0 * a * a * a - a * b / 3.0 + c) / 2.0;
+    let discriminant = q * q + p * p * p;
+    let (mut ys, n) = if discriminant.abs() < ZERO {
+        if q.abs() < ZERO {
+            ([0.0; 3], 1)
+        } else {
+            let u = (-q).cbrt();
+            ([2.0 * u, -u, 0.0], 2)
+        }
+    } else if discriminant < 0.0 {
+        let phi = (-q / (-p * p * p).sqrt()).clamp(-1.0, 1.0).acos() / 3.0;
+        let t = 2.0 * (-p).sqrt();
+        ([t * phi.cos(), -t * (phi + PI / 3.0).cos(), -t * (phi - PI / 3.0).cos()], 3)
+    } else {
+        let s = discriminant.sqrt();
+        ([(s - q).cbrt() - (s + q).cbrt(), 0.0, 0.0], 1)
+    };
+    for y in &mut ys[..n] {
+        *y -= shift;
+    }
+
+    dedup(&mut ys[..n])
+}
+
+// Sorted roots with any that come out the same dropped.
+fn dedup(roots: &mut [Float]) -> Roots {
+    let mut roots = Roots::new(roots);
+    let mut n = 0;
+    for i in 0..roots.len {
+        if n == 0 || roots.values[i] != roots.values[n - 1] {
+            roots.values[n] = roots.values[i];
+            n += 1;
+        }
+    }
+    roots.len = n;
+
+    roots
+}
+
+// Refines each root with a couple of Newton steps on the polynomial with these
+// coefficients, highest power first, to win back precision lost solving it.
+fn polish(roots: Roots, coefficients: &[Float]) -> Roots {
+    let mut values = roots.values;
+    for t in &mut values[..roots.len] {
+        for _ in 0..2 {
+            let (mut f, mut df) = (0.0, 0.0);
+            for &k in coefficients {
+                df = df * *t + f;
+                f = f * *t + k;
+            }
+            if df != 0.0 {
+                let step = f / df;
+                if step.is_finite() {
+                    *t -= step;
+                }
+            }
+        }
+    }
+
+    dedup(&mut values[..roots.len])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(roots: Roots, expected: &[Float]) {
+        assert_eq!(roots.len(), expected.len(), "{:?}", roots);
+        for (t, e) in roots.as_slice().iter().zip(expected) {
+            assert!((t - e).abs() < 1e-6, "{:?} != {:?}", roots, expected);
+        }
+    }
+
+    #[test]
+    fn quadratic_roots_smallest_first() {
+        assert_eq!(quadratic(1.0, -10.0, 24.0), Some((4.0, 6.0)));
+        assert_eq!(quadratic(-1.0, 10.0, -24.0), Some((4.0, 6.0)));
+        assert_eq!(quadratic(1.0, 0.0, -4.0), Some((-2.0, 2.0)));
+        assert_eq!(quadratic(1.0, -2.0, 1.0), Some((1.0, 1.0)));
+        assert_eq!(quadratic(1.0, 0.0, 0.0), Some((0.0, 0.0)));
+        assert_eq!(quadratic(1.0, 0.0, 1.0), None);
+        assert_eq!(quadratic(0.0, 2.0, -4.0), Some((2.0, 2.0)));
+        assert_eq!(quadratic(0.0, 0.0, 1.0), None);
+    }
+
+    #[test]
+    #[cfg(not(feature = "f32"))]
+    fn quadratics_keep_precision_for_small_roots() {
+        // Roots 1e-8 and 1e8. The textbook formula gets the small one badly
+        // wrong, if it doesn't round it to zero altogether.
+        let (t0, t1) = quadratic(1.0, -1.0e8 - 1.0e-8, 1.0).unwrap();
+
+        assert!((t0 - 1.0e-8).abs() < 1.0e-16);
+        assert!((t1 - 1.0e8).abs() < 1.0e-6);
+    }
+
+    #[test]
+    fn cubic_roots() {
+        // (t - 1)(t - 2)(t - 3)
+        close(cubic(1.0, -6.0, 11.0, -6.0), &[1.0, 2.0, 3.0]);
+        // (t + 2)(t² + 1)
+        close(cubic(2.0, 4.0, 2.0, 4.0), &[-2.0]);
+        // (t - 1)²(t + 2)
+        close(cubic(1.0, 0.0, -3.0, 2.0), &[-2.0, 1.0]);
+        close(cubic(1.0, 0.0, 0.0, 0.0), &[0.0]);
+        close(cubic(0.0, 1.0, -3.0, 2.0), &[1.0, 2.0]);
+    }
+
+    #[test]
+    fn quartic_roots() {
+        // (t - 1)(t - 2)(t - 3)(t - 4)
+        close(quartic(1.0, -10.0, 35.0, -50.0, 24.0), &[1.0, 2.0, 3.0, 4.0]);
+        // (t² - 4)(t² + 1)
+        close(quartic(3.0, 0.0, -9.0, 0.0, -12.0), &[-2.0, 2.0]);
+        // t (t - 1)(t + 1)(t - 5)
+        close(quartic(1.0, -5.0, -1.0, 5.0, 0.0), &[-1.0, 0.0, 1.0, 5.0]);
+        close(quartic(1.0, 0.0, 2.0, 0.0, 1.0), &[]);
+        close(quartic(0.0, 1.0, -6.0, 11.0, -6.0), &[1.0, 2.0, 3.0]);
+    }
+
+    #[test]
+    fn quartics_for_a_ray_through_a_torus() {
+        // A ray along x through the middle of a torus with radii 2 and 0.5
+        // in the xz plane: (t² + 3.75)² - 16 t² = 0.
+        close(quartic(1.0, 0.0, 7.5 - 16.0, 0.0, 3.75 * 3.75), &[-2.5, -1.5, 1.5, 2.5]);
+    }
+}
@@ -0,0 +1,235 @@
+use crate::Float;
+
+// Tiny, fast and reproducible. Cryptographic quality is not needed for
+// scattering rays, and it saves pulling in the rand crate.
+/// SplitMix64 pseudo-random number generator.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rng {
+    state: u64
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Rng { state: seed }
+    }
+
+    /// Derives an independent generator for a stream (e.g. a pixel index), so
+    /// results do not depend on the order pixels are rendered in.
+    pub fn for_stream(seed: u64, stream: u64) -> Self {
+        let mut rng = Rng::new(seed ^ stream.wrapping_mul(0xD1B5_4A32_D192_ED03));
+        rng.next_u64();
+        rng
+    }
+
+    /// Folds a 64 bit seed down to the 32 bits the noise functions take, for
+    /// mixing a render seed into a pattern's or medium's own. Seed 0 folds to
+    /// 0, which mixes in nothing.
+    pub fn fold_seed(seed: u64) -> u32 {
+        (seed ^ (seed >> 32)) as u32
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform random number in [0.0, 1.0).
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Uniform random Float in [0.0, 1.0). The same as next_f64(), unless
+    /// built with the `f32` feature, where it uses only as many bits as fit
+    /// so it can never round up to 1.0.
+    pub fn next_float(&mut self) -> Float {
+        let bits = Float::MANTISSA_DIGITS;
+        (self.next_u64() >> (64 - bits)) as Float / (1u64 << bits) as Float
+    }
+}
+
+/// Strategy used to distribute samples over the unit square.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SamplerKind {
+    /// Regular grid through the centre of each cell. Deterministic, but prone to aliasing.
+    Grid,
+    /// Uniform random. Converges slowly.
+    Random,
+    /// One random sample within each cell of a grid (jittered).
+    Stratified,
+    /// Halton sequence in bases 2 and 3 with a random per-stream shift.
+    Halton
+}
+
+/// Generates sample positions for pixel jitter, lens sampling, soft shadows etc.
+/// The same seed and stream always produce the same samples.
+/// 
+/// # Example
+/// 
+/// ```ignore
+/// let sampler = Sampler::new(SamplerKind::Stratified).with_seed(42);
+/// let offsets = sampler.samples_2d(16, pixel_index);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sampler {
+    pub kind: SamplerKind,
+    pub seed: u64
+}
+
+impl Sampler {
+    pub fn new(kind: SamplerKind) -> Self {
+        Sampler { kind, seed: 0 }
+    }
+
+    /// Assigns the seed.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+
+        self
+    }
+
+    /// Number of samples actually produced when n are requested. Grid based
+    /// samplers round down to a square number.
+    pub fn sample_count(&self, n: usize) -> usize {
+        match self.kind {
+            SamplerKind::Grid | SamplerKind::Stratified => side(n).pow(2),
+            _ => n.max(1)
+        }
+    }
+
+    /// Generates sample positions in the unit square, each coordinate in [0.0, 1.0).
+    pub fn samples_2d(&self, n: usize, stream: u64) -> Vec<(Float, Float)> {
+        let mut rng = Rng::for_stream(self.seed, stream);
+        match self.kind {
+            SamplerKind::Grid => {
+                let k = side(n);
+                grid(k, |_| (0.5, 0.5))
+            },
+            SamplerKind::Stratified => {
+                let k = side(n);
+                grid(k, |_| (rng.next_float(), rng.next_float()))
+            },
+            SamplerKind::Random => (0..n.max(1))
+                .map(|_| (rng.next_float(), rng.next_float()))
+                .collect(),
+            SamplerKind::Halton => {
+                let (sx, sy) = (rng.next_float(), rng.next_float());
+                (1..=n.max(1))
+                    .map(|i| ((radical_inverse(i, 2) + sx) % 1.0, (radical_inverse(i, 3) + sy) % 1.0))
+                    .collect()
+            }
+        }
+    }
+}
+
+impl Default for Sampler {
+    fn default() -> Self {
+        Sampler::new(SamplerKind::Grid)
+    }
+}
+
+// Number of cells along each side of a grid holding at most n samples.
+fn side(n: usize) -> usize {
+    ((n.max(1) as Float).sqrt().floor() as usize).max(1)
+}
+
+// Lays out k x k samples, with the offset of each sample within its cell.
+fn grid<F>(k: usize, mut offset: F) -> Vec<(Float, Float)>
+where
+    F: FnMut(usize) -> (Float, Float)
+{
+    let mut samples = Vec::with_capacity(k * k);
+    for j in 0..k {
+        for i in 0..k {
+            let (ox, oy) = offset(j * k + i);
+            samples.push(((i as Float + ox) / k as Float, (j as Float + oy) / k as Float));
+        }
+    }
+
+    samples
+}
+
+/// Van der Corput radical inverse of i in the given base.
+pub fn radical_inverse(mut i: usize, base: usize) -> Float {
+    let inv_base = 1.0 / base as Float;
+    let mut factor = inv_base;
+    let mut result = 0.0;
+    while i > 0 {
+        result += (i % base) as Float * factor;
+        i /= base;
+        factor *= inv_base;
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rng_is_reproducible() {
+        let mut a = Rng::new(7);
+        let mut b = Rng::new(7);
+
+        assert_eq!(a.next_u64(), b.next_u64());
+        assert_eq!(a.next_f64(), b.next_f64());
+    }
+
+    #[test]
+    fn rng_produces_unit_interval() {
+        let mut rng = Rng::new(1);
+        for _ in 0..1000 {
+            let x = rng.next_f64();
+            assert!((0.0..1.0).contains(&x));
+        }
+    }
+
+    #[test]
+    fn grid_sampler_matches_pixel_centres() {
+        let s = Sampler::new(SamplerKind::Grid);
+
+        assert_eq!(s.samples_2d(1, 0), vec![(0.5, 0.5)]);
+        assert_eq!(s.samples_2d(4, 0), vec![(0.25, 0.25), (0.75, 0.25), (0.25, 0.75), (0.75, 0.75)]);
+    }
+
+    #[test]
+    fn stratified_samples_stay_in_their_cells() {
+        let s = Sampler::new(SamplerKind::Stratified).with_seed(3);
+        let samples = s.samples_2d(9, 12);
+
+        assert_eq!(samples.len(), 9);
+        for (i, (x, y)) in samples.iter().enumerate() {
+            assert_eq!((x * 3.0).floor() as usize, i % 3);
+            assert_eq!((y * 3.0).floor() as usize, i / 3);
+        }
+    }
+
+    #[test]
+    fn same_seed_and_stream_give_same_samples() {
+        let s = Sampler::new(SamplerKind::Random).with_seed(99);
+
+        assert_eq!(s.samples_2d(8, 5), s.samples_2d(8, 5));
+        assert_ne!(s.samples_2d(8, 5), s.samples_2d(8, 6));
+        assert_ne!(s.samples_2d(8, 5), s.with_seed(100).samples_2d(8, 5));
+    }
+
+    #[test]
+    fn radical_inverse_in_base_2() {
+        assert_eq!(radical_inverse(1, 2), 0.5);
+        assert_eq!(radical_inverse(2, 2), 0.25);
+        assert_eq!(radical_inverse(3, 2), 0.75);
+    }
+
+    #[test]
+    fn halton_sampler_gives_requested_count() {
+        let s = Sampler::new(SamplerKind::Halton);
+        let samples = s.samples_2d(5, 0);
+
+        assert_eq!(samples.len(), 5);
+        assert_eq!(s.sample_count(5), 5);
+        assert!(samples.iter().all(|(x, y)| (0.0..1.0).contains(x) && (0.0..1.0).contains(y)));
+    }
+}
@@ -0,0 +1,395 @@
+use crate::Float;
+use crate::core::{Colour, Encoding, Integrator, RayDepth, Rng, Sampler, World};
+use std::thread;
+
+/// Rendering policy, kept separate from the camera's geometry so that presets
+/// like "quick preview" and "final" can be swapped without touching the scene.
+/// 
+/// # Example
+/// 
+/// ```ignore
+/// let settings = RenderSettings::default()
+///     .with_samples(16)
+///     .with_max_depth(8)
+///     .with_threads(0);
+/// let canvas = cam.render_with(world, &settings);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RenderSettings {
+    /// Camera rays per pixel. Grid based samplers round down to a square number.
+    pub samples: usize,
+    /// How camera rays are distributed within each pixel.
+    pub sampler: Sampler,
+    /// Maximum number of reflection/refraction bounces, or of bounces of any
+    /// kind for the path tracer.
+    pub max_depth: u8,
+    /// Limits on reflection, refraction and diffuse bounces on their own, within
+    /// max_depth. None leaves only max_depth to limit them.
+    pub reflection_depth: Option<u8>,
+    pub refraction_depth: Option<u8>,
+    pub diffuse_depth: Option<u8>,
+    /// Bounces after which rays are cut short at random (Russian roulette),
+    /// dimmer rays more often than bright ones. None traces every ray to its
+    /// depth limit.
+    pub roulette: Option<u8>,
+    /// Whitted ray tracing, or path tracing for global illumination.
+    pub integrator: Integrator,
+    /// Brightest any one indirect or specular sample may be. Clamping removes
+    /// fireflies, single blown out pixels from rare bright paths, at the cost
+    /// of slightly darkening highlights. None leaves samples unclamped.
+    pub max_radiance: Option<f32>,
+    /// Whether objects cast shadows. Turning them off speeds up previews.
+    pub shadows: bool,
+    /// Whether stripes, checkers and the like are averaged over the area each
+    /// camera ray covers, so they fade to grey in the distance instead of
+    /// breaking up into moiré.
+    pub pattern_filtering: bool,
+    /// Replaces the world's epsilon for this render. See World::epsilon.
+    pub epsilon: Option<Float>,
+    /// Replaces the world's slope bias for this render. See World::slope_bias.
+    pub slope_bias: Option<Float>,
+    /// Number of worker threads. 0 uses all available cores.
+    pub threads: usize,
+    /// Encoding given to the rendered canvas, used when it's saved. Colours
+    /// on the canvas, and in AOVs, alpha and denoising, stay linear.
+    pub encoding: Encoding,
+    /// Colour of camera rays that miss everything, in place of the world's own
+    /// background (e.g. a flat matte for compositing). None uses the world's.
+    pub background: Option<Colour>,
+    /// Whether camera rays that miss everything leave transparent pixels, for
+    /// laying the render over other images. Edge pixels are as opaque as the
+    /// share of their samples that hit something.
+    pub alpha: bool,
+    /// Size of the square buckets the image is split into. None renders by scanline.
+    pub tile_size: Option<usize>,
+    /// Seed for every stochastic feature. The same scene, settings and seed always
+    /// produce an identical image.
+    pub seed: u64
+}
+
+impl RenderSettings {
+    /// Default settings, but with the recursion limit taken from the world.
+    pub fn for_world(world: &World) -> Self {
+        Self::default().with_max_depth(world.rcrs_lim)
+    }
+
+    /// Sets the number of camera rays per pixel.
+    pub fn with_samples(mut self, samples: usize) -> Self {
+        self.samples = samples.max(1);
+
+        self
+    }
+
+    /// Sets the sampler used to jitter camera rays. The sampler's own seed is
+    /// mixed with the render seed, so changing either changes the samples.
+    pub fn with_sampler(mut self, sampler: Sampler) -> Self {
+        self.sampler = sampler;
+
+        self
+    }
+
+    /// Sets the maximum recursion depth for secondary rays.
+    pub fn with_max_depth(mut self, max_depth: u8) -> Self {
+        self.max_depth = max_depth;
+
+        self
+    }
+
+    /// Limits mirror reflections, within max_depth.
+    pub fn with_reflection_depth(mut self, depth: u8) -> Self {
+        self.reflection_depth = Some(depth);
+
+        self
+    }
+
+    /// Limits refractions, within max_depth. Stacks of glass need plenty.
+    pub fn with_refraction_depth(mut self, depth: u8) -> Self {
+        self.refraction_depth = Some(depth);
+
+        self
+    }
+
+    /// Limits diffuse bounces in the path tracer, within max_depth.
+    pub fn with_diffuse_depth(mut self, depth: u8) -> Self {
+        self.diffuse_depth = Some(depth);
+
+        self
+    }
+
+    /// Cuts rays short at random once they've taken this many bounces. The
+    /// image is noisier but, on average, the same, and deep bounces that add
+    /// little cost much less.
+    pub fn with_roulette(mut self, after: u8) -> Self {
+        self.roulette = Some(after);
+
+        self
+    }
+
+    /// Bounces camera rays may take, of each kind.
+    pub fn depth(&self) -> RayDepth {
+        let mut depth = RayDepth::new(self.max_depth)
+            .with_reflection(self.reflection_depth.unwrap_or(self.max_depth))
+            .with_refraction(self.refraction_depth.unwrap_or(self.max_depth))
+            .with_diffuse(self.diffuse_depth.unwrap_or(self.max_depth));
+        if let Some(after) = self.roulette {
+            depth = depth.with_roulette(after);
+        }
+
+        depth
+    }
+
+    /// Selects how the colour along each camera ray is worked out.
+    pub fn with_integrator(mut self, integrator: Integrator) -> Self {
+        self.integrator = integrator;
+
+        self
+    }
+
+    /// Clamps each indirect or specular sample so no channel is brighter than
+    /// max_radiance. For the path tracer only light that has bounced at least
+    /// once is clamped; for the Whitted tracer it's each camera sample.
+    pub fn with_max_radiance(mut self, max_radiance: f32) -> Self {
+        self.max_radiance = Some(max_radiance.max(0.0));
+
+        self
+    }
+
+    /// Clamps a sample to max_radiance, if set.
+    pub fn clamp(&self, colour: Colour) -> Colour {
+        match self.max_radiance {
+            Some(max) => colour.clamp_to(max),
+            None => colour
+        }
+    }
+
+    /// Enables or disables shadows for every object in the scene.
+    pub fn with_shadows(mut self, shadows: bool) -> Self {
+        self.shadows = shadows;
+
+        self
+    }
+
+    /// Enables or disables pattern filtering.
+    pub fn with_pattern_filtering(mut self, pattern_filtering: bool) -> Self {
+        self.pattern_filtering = pattern_filtering;
+
+        self
+    }
+
+    /// Renders with this epsilon in place of the world's own, to try out a
+    /// fix for shadow acne or light leaks without editing the scene.
+    pub fn with_epsilon(mut self, epsilon: Float) -> Self {
+        self.epsilon = Some(epsilon);
+
+        self
+    }
+
+    /// Renders with this slope bias in place of the world's own.
+    pub fn with_slope_bias(mut self, slope_bias: Float) -> Self {
+        self.slope_bias = Some(slope_bias);
+
+        self
+    }
+
+    /// Cut down version of these settings for fast iteration while composing a
+    /// scene: one sample per pixel and a single bounce. Shadows and
+    /// reflections can be turned off entirely with with_shadows(false) and
+    /// with_max_depth(0).
+    pub fn preview(self) -> Self {
+        let max_depth = self.max_depth.min(1);
+        self.with_samples(1).with_max_depth(max_depth)
+    }
+
+    /// Sets the number of worker threads. 0 uses all available cores.
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = threads;
+
+        self
+    }
+
+    /// Sets how the rendered canvas is encoded when saved, the same as
+    /// Canvas::with_encoding() on the result. Use one or the other.
+    pub fn with_encoding(mut self, encoding: Encoding) -> Self {
+        self.encoding = encoding;
+
+        self
+    }
+
+    /// Saves the render with a plain gamma curve. Shorthand for
+    /// with_encoding(Encoding::Gamma(gamma)).
+    pub fn with_gamma(self, gamma: f32) -> Self {
+        self.with_encoding(Encoding::Gamma(gamma))
+    }
+
+    /// Overrides the world's background for camera rays.
+    pub fn with_background(mut self, background: Colour) -> Self {
+        self.background = Some(background);
+
+        self
+    }
+
+    /// Renders with an alpha channel, leaving pixels where nothing was hit
+    /// transparent instead of showing the background.
+    pub fn with_alpha(mut self, alpha: bool) -> Self {
+        self.alpha = alpha;
+
+        self
+    }
+
+    /// Renders in square tiles of the given size instead of scanlines.
+    pub fn with_tile_size(mut self, tile_size: usize) -> Self {
+        self.tile_size = Some(tile_size.max(1));
+
+        self
+    }
+
+    /// Sets the seed used for all random sampling.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+
+        self
+    }
+
+    /// Sampler seeded with its own seed and the render seed.
+    pub fn seeded_sampler(&self) -> Sampler {
+        self.sampler.with_seed(self.sampler.seed ^ self.seed)
+    }
+
+    /// Random number generator for a single stream (e.g. a pixel), derived from
+    /// the render seed. Any stochastic feature should draw from one of these.
+    pub fn rng(&self, stream: u64) -> Rng {
+        Rng::for_stream(self.seed, stream)
+    }
+
+    /// Actual number of threads to spawn. Always one on wasm32, which can't
+    /// spawn threads.
+    pub fn thread_count(&self) -> usize {
+        if cfg!(target_arch = "wasm32") {
+            1
+        } else if self.threads == 0 {
+            thread::available_parallelism().map_or(1, |n| n.get())
+        } else {
+            self.threads
+        }
+    }
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        RenderSettings {
+            samples: 1,
+            sampler: Sampler::default(),
+            max_depth: 5,
+            reflection_depth: None,
+            refraction_depth: None,
+            diffuse_depth: None,
+            roulette: None,
+            integrator: Integrator::default(),
+            max_radiance: None,
+            shadows: true,
+            pattern_filtering: true,
+            epsilon: None,
+            slope_bias: None,
+            threads: 1,
+            encoding: Encoding::Linear,
+            background: None,
+            alpha: false,
+            tile_size: None,
+            seed: 0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_render_settings() {
+        let s = RenderSettings::default();
+
+        assert_eq!(s.samples, 1);
+        assert_eq!(s.max_depth, 5);
+        assert_eq!(s.integrator, Integrator::Whitted);
+        assert_eq!(s.max_radiance, None);
+        assert!(s.shadows);
+        assert!(s.pattern_filtering);
+        assert_eq!(s.threads, 1);
+        assert_eq!(s.encoding, Encoding::Linear);
+        assert_eq!(s.with_gamma(2.2).encoding, Encoding::Gamma(2.2));
+        assert_eq!(s.background, None);
+        assert_eq!(s.tile_size, None);
+    }
+
+    #[test]
+    fn depth_limits_for_each_kind_of_bounce() {
+        let s = RenderSettings::default()
+            .with_max_depth(8)
+            .with_reflection_depth(2)
+            .with_roulette(4);
+        let depth = s.depth();
+
+        assert_eq!(depth, RayDepth::new(8).with_reflection(2).with_roulette(4));
+        assert_eq!(RenderSettings::default().depth(), RayDepth::new(5));
+    }
+
+    #[test]
+    fn samples_are_clamped_to_the_max_radiance() {
+        let bright = Colour::new(3.0, 1.5, 0.0);
+
+        assert_eq!(RenderSettings::default().clamp(bright), bright);
+        assert_eq!(RenderSettings::default().with_max_radiance(1.5).clamp(bright), Colour::new(1.5, 0.75, 0.0));
+    }
+
+    #[test]
+    fn settings_for_world_use_its_recursion_limit() {
+        let w = World::default().with_recursions(2);
+
+        assert_eq!(RenderSettings::for_world(&w).max_depth, 2);
+    }
+
+    #[test]
+    fn grid_samples_are_rounded_down_to_a_square() {
+        let s = RenderSettings::default().with_samples(8);
+
+        assert_eq!(s.sampler.sample_count(s.samples), 4);
+    }
+
+    #[test]
+    fn zero_threads_uses_available_cores() {
+        assert!(RenderSettings::default().with_threads(0).thread_count() >= 1);
+        assert_eq!(RenderSettings::default().with_threads(3).thread_count(), 3);
+    }
+
+    #[test]
+    fn seed_is_mixed_with_the_sampler_seed() {
+        let s = RenderSettings::default()
+            .with_sampler(Sampler::default().with_seed(1))
+            .with_seed(5);
+
+        assert_eq!(s.seeded_sampler().seed, 4);
+        assert_eq!(RenderSettings::default().with_seed(5).seeded_sampler().seed, 5);
+        assert_eq!(s.with_sampler(Sampler::default().with_seed(3)).seeded_sampler().seed, 6);
+    }
+
+    #[test]
+    fn rng_streams_follow_the_seed() {
+        let a = RenderSettings::default().with_seed(5);
+        let b = RenderSettings::default().with_seed(6);
+
+        assert_eq!(a.rng(3).next_u64(), a.rng(3).next_u64());
+        assert_ne!(a.rng(3).next_u64(), b.rng(3).next_u64());
+    }
+
+    #[test]
+    fn preview_settings_are_cheap() {
+        let s = RenderSettings::default()
+            .with_samples(16)
+            .with_seed(3)
+            .preview();
+
+        assert_eq!(s.samples, 1);
+        assert_eq!(s.max_depth, 1);
+        assert_eq!(s.seed, 3);
+    }
+}
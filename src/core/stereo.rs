@@ -0,0 +1,142 @@
+use crate::Float;
+use crate::core::{Camera, Canvas, Colour, RenderSettings, Transform, World};
+use nalgebra::Matrix4;
+
+/// A pair of cameras side by side, for stereo images and VR headsets.
+///
+/// The eyes look the same way as the rig's camera, the interocular distance
+/// apart, and their frames are shifted inwards so that things at the
+/// convergence distance line up in both images. Anything nearer seems to
+/// stand out of the screen, and anything further to sit behind it. Keeping
+/// the eyes parallel and shifting the frames, rather than turning the eyes
+/// in, gives no vertical parallax at the edges of the frame.
+///
+/// # Example
+///
+/// ```ignore
+/// let rig = StereoRig::new(cam, 0.065).with_convergence(3.0);
+/// rig.render_side_by_side(world, &settings).export("stereo.png")?;
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct StereoRig {
+    /// The camera between the eyes. Its transform, lens and settings are
+    /// shared by both.
+    pub camera: Camera,
+    /// Distance between the eyes, in scene units.
+    pub interocular: Float,
+    /// Distance in front of the camera that appears at the depth of the
+    /// screen. Infinite leaves the eyes looking straight ahead, so everything
+    /// seems to be in front of the screen.
+    pub convergence: Float
+}
+
+impl StereoRig {
+    /// Eyes the given distance apart either side of the camera, converging
+    /// at infinity.
+    pub fn new(camera: Camera, interocular: Float) -> Self {
+        StereoRig { camera, interocular, convergence: Float::INFINITY }
+    }
+
+    pub fn with_convergence(mut self, distance: Float) -> Self {
+        self.convergence = distance;
+
+        self
+    }
+
+    /// The left and right eyes' cameras. An animated camera is frozen where
+    /// it is at time 0.0, so render each frame from a rig around
+    /// camera.at_time().
+    pub fn eyes(&self) -> (Camera, Camera) {
+        let camera = if self.camera.is_animated() { self.camera.at_time(0.0) } else { self.camera };
+
+        (self.eye(camera, 1.0), self.eye(camera, -1.0))
+    }
+
+    // The eye on the given side, 1.0 for the left, which is along the camera's
+    // +x, and -1.0 for the right.
+    fn eye(&self, camera: Camera, side: Float) -> Camera {
+        let offset = side * self.interocular / 2.0;
+        let mut eye = camera;
+        eye.with_transform(Matrix4::translate(-offset, 0.0, 0.0) * camera.transform);
+        // A point straight ahead of the camera at the convergence distance is
+        // offset / convergence across the film from the eye's centre.
+        let shift = if self.convergence.is_finite() { offset / (2.0 * self.convergence * camera.half_width) } else { 0.0 };
+
+        eye.with_shift(camera.shift.0 + shift, camera.shift.1)
+    }
+
+    /// Renders both eyes, left then right.
+    pub fn render_with(&self, world: World, settings: &RenderSettings) -> (Canvas, Canvas) {
+        let (left, right) = self.eyes();
+
+        (left.render_with(world.clone(), settings), right.render_with(world, settings))
+    }
+
+    /// Renders both eyes onto one canvas twice as wide, left eye on the left,
+    /// as most headsets and 3D TVs expect.
+    pub fn render_side_by_side(&self, world: World, settings: &RenderSettings) -> Canvas {
+        let (left, right) = self.render_with(world, settings);
+        let (width, height) = (left.width, left.height);
+        let mut canvas = Canvas::new(width * 2, height, Colour::black()).with_encoding(left.encoding);
+        for (eye, x0) in [(&left, 0), (&right, width)] {
+            for y in 0..height {
+                for x in 0..width {
+                    canvas.write_pix(x0 + x, y, eye.read_pix(x, y));
+                    if eye.has_alpha() {
+                        canvas.write_alpha(x0 + x, y, eye.read_alpha(x, y));
+                    }
+                }
+            }
+        }
+
+        canvas
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{point, vector, Tuple};
+    use crate::consts::PI;
+
+    fn camera() -> Camera {
+        Camera::new(21, 11, PI / 2.0)
+            .with_transform(Matrix4::view_transform(point(0.0, 0.0, -5.0), point(0.0, 0.0, 0.0), vector(0.0, 1.0, 0.0)))
+    }
+
+    #[test]
+    fn eyes_sit_either_side_of_the_camera() {
+        let (left, right) = StereoRig::new(camera(), 0.5).eyes();
+
+        // The camera looks along +z, so its left is towards -x.
+        assert_eq!(left.ray_for_pixel(10, 5).origin.to_5dp(), point(-0.25, 0.0, -5.0));
+        assert_eq!(right.ray_for_pixel(10, 5).origin.to_5dp(), point(0.25, 0.0, -5.0));
+        assert_eq!(left.ray_for_pixel(10, 5).direction.to_5dp(), vector(0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn eyes_converge_at_the_convergence_distance() {
+        let (left, right) = StereoRig::new(camera(), 0.5).with_convergence(5.0).eyes();
+        let centre = |eye: &Camera| {
+            let ray = eye.ray_for_subpixel(10, 5, 0.5, 0.5);
+            let t = 5.0 / ray.direction.z;
+            ray.origin + ray.direction * t
+        };
+
+        assert_eq!(centre(&left).to_5dp(), point(0.0, 0.0, 0.0));
+        assert_eq!(centre(&right).to_5dp(), point(0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn side_by_side_renders_put_the_left_eye_on_the_left() {
+        let rig = StereoRig::new(camera(), 0.5).with_convergence(5.0);
+        let settings = RenderSettings::default();
+        let (left, right) = rig.render_with(World::default_world(), &settings);
+        let both = rig.render_side_by_side(World::default_world(), &settings);
+
+        assert_eq!((both.width, both.height), (42, 11));
+        assert_eq!(both.read_pix(10, 5), left.read_pix(10, 5));
+        assert_eq!(both.read_pix(31, 5), right.read_pix(10, 5));
+        assert_ne!(left, right);
+    }
+}
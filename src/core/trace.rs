@@ -0,0 +1,240 @@
+use crate::Float;
+use crate::core::world::roulette;
+use crate::core::{Bounce, Camera, Colour, Intersection, Intersections, PreCompData, Ray, RayDepth, RayKind, World};
+use std::fmt;
+
+/// One ray of a debug trace: where it went, everything it hit, and the rays
+/// traced on from the nearest hit. Made by World::debug_trace() and
+/// Camera::debug_pixel(), for working out why a pixel comes out wrong.
+#[derive(Debug)]
+pub struct TraceNode<'a> {
+    pub ray: Ray,
+    /// Bounces the ray had left.
+    pub depth: RayDepth,
+    /// Every intersection along the ray, nearest first.
+    pub intersections: Vec<Intersection<'a>>,
+    pub hit: Option<TraceHit<'a>>,
+    /// Colour seen along the ray, exactly as colour_at() works it out.
+    pub colour: Colour
+}
+
+/// What happened at the nearest hit of a traced ray.
+#[derive(Debug)]
+pub struct TraceHit<'a> {
+    /// Point, normal, n1 and n2 and the rest, as used for shading.
+    pub comps: PreCompData<'a>,
+    /// Shadow test for each light that was shaded.
+    pub shadows: Vec<ShadowTest<'a>>,
+    /// Fresnel reflectance, for surfaces that both reflect and refract.
+    pub reflectance: Option<Float>,
+    pub reflection: Option<Box<TraceNode<'a>>>,
+    pub refraction: Option<Box<TraceNode<'a>>>,
+    /// Whether the refracted ray was lost to total internal reflection.
+    pub total_internal_reflection: bool
+}
+
+/// Whether a light was blocked from a hit.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ShadowTest<'a> {
+    /// Index of the light in the world.
+    pub light: usize,
+    /// The light's name, if it has one.
+    pub name: Option<&'a str>,
+    /// Weight the light was shaded with. 1.0 unless lights are sampled.
+    pub weight: f32,
+    pub shadowed: bool
+}
+
+impl World {
+    /// Traces a ray the way colour_at() does, but keeps every step: each
+    /// intersection, n1 and n2, shadow tests, and the reflected and refracted
+    /// rays, as a tree. Slow, so only meant for looking into single rays.
+    pub fn debug_trace(&self, ray: &Ray, remaining: impl Into<RayDepth>) -> TraceNode<'_> {
+        let depth = remaining.into();
+        let xs = self.intersect(ray);
+        let hit = xs.hit_index().map(|i| self.debug_hit(self.prepare_computations(&xs, i, ray), depth));
+
+        TraceNode {
+            ray: *ray,
+            depth,
+            intersections: xs.intrsc,
+            hit,
+            colour: self.colour_at(ray, depth)
+        }
+    }
+
+    // Follows shade_hit() through a hit, tracing the same secondary rays.
+    fn debug_hit<'a>(&'a self, comps: PreCompData<'a>, depth: RayDepth) -> TraceHit<'a> {
+        let mut xs = Intersections::default();
+        let lights = self.lights_to_shade(&comps);
+        let shadows = lights.iter()
+            .zip(self.shadows_into(&lights, &comps, &mut xs))
+            .map(|(&(light, weight), shadowed)| ShadowTest { light, name: self.lights[light].name.as_deref(), weight, shadowed })
+            .collect();
+        let material = comps.object.material;
+        let reflection = match depth.bounce(Bounce::Reflection) {
+            Some(next) if material.reflectivity != 0.0 && roulette(&next, material.reflectivity, &comps, 1).is_some() => {
+                let ray = Ray::new(comps.over_pos, comps.reflect_vec)
+                    .with_time(comps.time)
+                    .with_kind(RayKind::Reflection);
+                Some(Box::new(self.debug_trace(&ray, next)))
+            },
+            _ => None
+        };
+        let refract_vec = comps.refract_vec();
+        let refraction = match (depth.bounce(Bounce::Refraction), refract_vec) {
+            (Some(next), Some(direction))
+                if material.transparency != 0.0 && roulette(&next, material.transparency, &comps, 2).is_some() =>
+            {
+                let ray = Ray::new(comps.under_pos, direction)
+                    .with_time(comps.time)
+                    .with_kind(RayKind::Refraction);
+                Some(Box::new(self.debug_trace(&ray, next)))
+            },
+            _ => None
+        };
+        let reflectance = if material.reflectivity > 0.0 && material.transparency > 0.0 {
+            Some(comps.schlick())
+        } else {
+            None
+        };
+
+        TraceHit {
+            shadows,
+            reflectance,
+            reflection,
+            refraction,
+            total_internal_reflection: material.transparency != 0.0 && refract_vec.is_none(),
+            comps
+        }
+    }
+}
+
+impl Camera {
+    /// Debug trace of the ray through the centre of a pixel, with the world's
+    /// recursion limit.
+    pub fn debug_pixel<'a>(&self, world: &'a World, x: usize, y: usize) -> TraceNode<'a> {
+        world.debug_trace(&self.ray_for_pixel(x, y), world.rcrs_lim)
+    }
+}
+
+// Prints the tree one ray per line, with each ray's hit and branches indented
+// beneath it.
+impl fmt::Display for TraceNode<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write(f, 0, "ray")
+    }
+}
+
+impl TraceNode<'_> {
+    fn write(&self, f: &mut fmt::Formatter<'_>, indent: usize, label: &str) -> fmt::Result {
+        let pad = "  ".repeat(indent);
+        let (o, d, c) = (self.ray.origin, self.ray.direction, self.colour);
+        writeln!(
+            f,
+            "{}{} from ({:.3}, {:.3}, {:.3}) towards ({:.3}, {:.3}, {:.3}), {} left: {} hits, colour ({:.3}, {:.3}, {:.3})",
+            pad, label, o.x, o.y, o.z, d.x, d.y, d.z, self.depth.total, self.intersections.len(), c.r, c.g, c.b
+        )?;
+        let hit = match &self.hit {
+            Some(hit) => hit,
+            None => return writeln!(f, "{}  missed", pad)
+        };
+        let (comps, p) = (&hit.comps, hit.comps.pos);
+        writeln!(
+            f,
+            "{}  hit {}{} at t {:.5}, ({:.3}, {:.3}, {:.3}), n1 {} n2 {}{}",
+            pad, comps.object.shape.name(), quoted(comps.object.name.as_deref()), comps.t, p.x, p.y, p.z, comps.n1, comps.n2,
+            if comps.inside { ", inside" } else { "" }
+        )?;
+        for shadow in &hit.shadows {
+            let state = if shadow.shadowed { "shadowed" } else { "lit" };
+            writeln!(f, "{}  light {}{}: {} (weight {})", pad, shadow.light, quoted(shadow.name), state, shadow.weight)?;
+        }
+        if let Some(reflectance) = hit.reflectance {
+            writeln!(f, "{}  reflectance {:.5}", pad, reflectance)?;
+        }
+        if hit.total_internal_reflection {
+            writeln!(f, "{}  total internal reflection", pad)?;
+        }
+        if let Some(node) = &hit.reflection {
+            node.write(f, indent + 1, "reflection")?;
+        }
+        if let Some(node) = &hit.refraction {
+            node.write(f, indent + 1, "refraction")?;
+        }
+
+        Ok(())
+    }
+}
+
+// Name to print after whatever it labels, if there is one.
+fn quoted(name: Option<&str>) -> String {
+    name.map_or_else(String::new, |name| format!(" \"{}\"", name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{point, vector, Transform};
+    use crate::materials::Material;
+    use crate::primitives::Object;
+    use nalgebra::Matrix4;
+
+    #[test]
+    fn trace_matches_the_colour_seen() {
+        let w = World::default_world();
+        let r = Ray::new(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+        let trace = w.debug_trace(&r, 5);
+        let hit = trace.hit.as_ref().unwrap();
+
+        assert_eq!(trace.colour, w.colour_at(&r, 5));
+        assert_eq!(trace.intersections.len(), 4);
+        assert_eq!(hit.comps.t, 4.0);
+        assert_eq!(hit.shadows, vec![ShadowTest { light: 0, name: None, weight: 1.0, shadowed: false }]);
+        assert!(hit.reflection.is_none() && hit.refraction.is_none());
+    }
+
+    #[test]
+    fn glass_branches_into_reflection_and_refraction() {
+        let glass = Object::glass_orb()
+            .with_material(Material::null().with_transparency(1.0).with_reflectivity(0.5).with_ior(1.5));
+        let w = World::default()
+            .with_light(crate::lights::PointLight::new(Colour::white(), point(-10.0, 10.0, -10.0)))
+            .with_object(glass)
+            .with_object(Object::new_plane().with_transform(Matrix4::translate(0.0, -1.0, 0.0)));
+        let r = Ray::new(point(0.0, 0.2, -5.0), vector(0.0, 0.0, 1.0));
+        let trace = w.debug_trace(&r, 3);
+        let hit = trace.hit.as_ref().unwrap();
+        let refraction = hit.refraction.as_ref().unwrap();
+
+        assert!(hit.reflectance.is_some());
+        assert!(hit.reflection.is_some());
+        assert_eq!(refraction.depth.total, 2);
+        assert!(refraction.hit.as_ref().unwrap().comps.inside);
+        assert!(trace.to_string().contains("refraction from"));
+    }
+
+    #[test]
+    fn pixels_are_traced_through_their_centre() {
+        let cam = Camera::new(11, 11, crate::consts::PI / 2.0)
+            .with_transform(Matrix4::view_transform(point(0.0, 0.0, -5.0), point(0.0, 0.0, 0.0), vector(0.0, 1.0, 0.0)));
+        let w = World::default_world();
+        let trace = cam.debug_pixel(&w, 5, 5);
+
+        assert_eq!(trace.colour, cam.render(w.clone()).read_pix(5, 5));
+        assert!(trace.to_string().starts_with("ray from (0.000, 0.000, -5.000)"));
+    }
+
+    #[test]
+    fn traces_show_the_names_of_objects_and_lights() {
+        let w = World::default()
+            .with_light(crate::lights::PointLight::new(Colour::white(), point(-10.0, 10.0, -10.0)).with_name("key"))
+            .with_object(Object::new_sphere().with_name("ball"));
+        let trace = w.debug_trace(&Ray::new(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0)), 1);
+        let text = trace.to_string();
+
+        assert_eq!(trace.hit.as_ref().unwrap().shadows[0].name, Some("key"));
+        assert!(text.contains("hit Sphere \"ball\" at t"));
+        assert!(text.contains("light 0 \"key\": lit"));
+    }
+}
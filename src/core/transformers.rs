@@ -1,6 +1,8 @@
 // More than meets the eye
+use crate::Float;
 use crate::core::Tuple;
-use nalgebra::{Matrix4, Vector3, Vector4};
+use nalgebra::{Matrix3, Matrix4, Unit, UnitQuaternion, Vector3, Vector4};
+use std::ops::SubAssign;
 
 // Previous iterations of transformers.rs (i.e. pre-nalgebra refactoring) can be
 // found in the archive folder. This version mostly adapts native nalgebra functionality
@@ -9,44 +11,45 @@ use nalgebra::{Matrix4, Vector3, Vector4};
 // Original, direct application of transforms. Now deprecated for complex transforms.
 // Do not use from v0.0.10 onwrds, except for single transforms or view_transform.
 pub trait Transform {
-    fn translate(x: f64, y: f64, z: f64) -> Matrix4<f64>;
-    fn nuscale(x: f64, y: f64, z: f64) -> Matrix4<f64>;
-    fn uscale(s: f64) -> Matrix4<f64>;
-    fn rot_x(rad: f64) -> Matrix4<f64>;
-    fn rot_y(rad: f64) -> Matrix4<f64>;
-    fn rot_z(rad: f64) -> Matrix4<f64>;
-    fn shear(xy: f64, xz: f64, yx: f64, yz: f64, zx: f64, zy: f64) -> Matrix4<f64>;
-    fn view_transform(from: Vector4<f64>, to: Vector4<f64>, up: Vector4<f64>) -> Matrix4<f64>;
+    fn translate(x: Float, y: Float, z: Float) -> Matrix4<Float>;
+    fn nuscale(x: Float, y: Float, z: Float) -> Matrix4<Float>;
+    fn uscale(s: Float) -> Matrix4<Float>;
+    fn rot_x(rad: Float) -> Matrix4<Float>;
+    fn rot_y(rad: Float) -> Matrix4<Float>;
+    fn rot_z(rad: Float) -> Matrix4<Float>;
+    fn shear(xy: Float, xz: Float, yx: Float, yz: Float, zx: Float, zy: Float) -> Matrix4<Float>;
+    fn view_transform(from: Vector4<Float>, to: Vector4<Float>, up: Vector4<Float>) -> Matrix4<Float>;
+    fn look_at(from: Vector4<Float>, to: Vector4<Float>, up: Vector4<Float>) -> Matrix4<Float>;
 }
 
 // Original, direct application of transforms. Now deprecated for complex transforms.
 // Do not use from v0.0.10 onwrds, except for single transforms or view_transform.
-impl Transform for Matrix4<f64> {
-    fn translate(x: f64, y: f64, z: f64) -> Matrix4<f64> {
+impl Transform for Matrix4<Float> {
+    fn translate(x: Float, y: Float, z: Float) -> Matrix4<Float> {
         Matrix4::new_translation(&Vector3::new(x, y, z))
     }
 
-    fn nuscale(x: f64, y: f64, z: f64) -> Matrix4<f64> {
+    fn nuscale(x: Float, y: Float, z: Float) -> Matrix4<Float> {
         Matrix4::new_nonuniform_scaling(&Vector3::new(x, y, z))
     }
 
-    fn uscale(s: f64) -> Matrix4<f64> {
+    fn uscale(s: Float) -> Matrix4<Float> {
         Matrix4::new_scaling(s)
     }
 
-    fn rot_x(rad: f64) -> Matrix4<f64> {
+    fn rot_x(rad: Float) -> Matrix4<Float> {
         Matrix4::new_rotation(Vector3::new(rad, 0.0, 0.0))
     }
 
-    fn rot_y(rad: f64) -> Matrix4<f64> {
+    fn rot_y(rad: Float) -> Matrix4<Float> {
         Matrix4::new_rotation(Vector3::new(0.0, rad, 0.0))
     }
 
-    fn rot_z(rad: f64) -> Matrix4<f64> {
+    fn rot_z(rad: Float) -> Matrix4<Float> {
         Matrix4::new_rotation(Vector3::new(0.0, 0.0, rad))
     }
 
-    fn shear(xy: f64, xz: f64, yx: f64, yz: f64, zx: f64, zy: f64) -> Matrix4<f64> {
+    fn shear(xy: Float, xz: Float, yx: Float, yz: Float, zx: Float, zy: Float) -> Matrix4<Float> {
         let mut shm = Matrix4::identity();
         shm.m12 = xy;
         shm.m13 = xz;
@@ -58,7 +61,7 @@ impl Transform for Matrix4<f64> {
     }
 
     /// Transform for the camera.
-    fn view_transform(from: Vector4<f64>, to: Vector4<f64>, up: Vector4<f64>) -> Matrix4<f64> {
+    fn view_transform(from: Vector4<Float>, to: Vector4<Float>, up: Vector4<Float>) -> Matrix4<Float> {
         let forward = (to - from).normalize();
         let left = forward.xprod(&up.normalize());
         let true_up = left.xprod(&forward);
@@ -71,11 +74,27 @@ impl Transform for Matrix4<f64> {
         
         orientation * Matrix4::translate(-from.x, -from.y, -from.z)
     }
+
+    /// Places an object at from, turned so that its -z axis points at to, the
+    /// same way the camera faces. This is view_transform() in reverse, except
+    /// that the axes are kept square so the object isn't skewed.
+    fn look_at(from: Vector4<Float>, to: Vector4<Float>, up: Vector4<Float>) -> Matrix4<Float> {
+        let forward = (to - from).normalize();
+        let left = forward.xprod(&up.normalize()).normalize();
+        let true_up = left.xprod(&forward);
+
+        Matrix4::new(
+            left.x, true_up.x, -forward.x, from.x,
+            left.y, true_up.y, -forward.y, from.y,
+            left.z, true_up.z, -forward.z, from.z,
+            0.0, 0.0, 0.0, 1.0
+        )
+    }
 }
 
 // Defacto standard for chaining transforms.
 pub struct TransformBuilder {
-    pub transforms: Vec<Matrix4<f64>>
+    pub transforms: Vec<Matrix4<Float>>
 }
 
 // Defacto standard for chaining transforms.
@@ -98,56 +117,56 @@ impl TransformBuilder {
     }
 
     /// Translation transformation.
-    pub fn translate(mut self, x: f64, y: f64, z: f64) -> TransformBuilder {
+    pub fn translate(mut self, x: Float, y: Float, z: Float) -> TransformBuilder {
         let translation = Matrix4::new_translation(&Vector3::new(x, y, z));
         self.transforms.push(translation);
         self
     }
 
     /// Non-uniform scale transformation.
-    pub fn nuscale(mut self, x: f64, y: f64, z: f64) -> TransformBuilder {
+    pub fn nuscale(mut self, x: Float, y: Float, z: Float) -> TransformBuilder {
         let scaling = Matrix4::new_nonuniform_scaling(&Vector3::new(x, y, z));
         self.transforms.push(scaling);
         self
     }
 
     /// Uniform scale transformation.
-    pub fn uscale(mut self, s: f64) -> TransformBuilder {
+    pub fn uscale(mut self, s: Float) -> TransformBuilder {
         let scaling = Matrix4::new_scaling(s);
         self.transforms.push(scaling);
         self
     }
 
     /// 3-axis rotation transformation.
-    pub fn rot(mut self, rx: f64, ry: f64, rz: f64) -> TransformBuilder {
+    pub fn rot(mut self, rx: Float, ry: Float, rz: Float) -> TransformBuilder {
         let rotation = Matrix4::new_rotation(Vector3::new(rx, ry, rz));
         self.transforms.push(rotation);
         self
     }
 
     /// Rotation transformation around the x-axis.
-    pub fn rot_x(mut self, rad: f64) -> TransformBuilder {
+    pub fn rot_x(mut self, rad: Float) -> TransformBuilder {
         let rotation = Matrix4::new_rotation(Vector3::new(rad, 0.0, 0.0));
         self.transforms.push(rotation);
         self
     }
 
     /// Rotation transformation around the y-axis.
-    pub fn rot_y(mut self, rad: f64) -> TransformBuilder {
+    pub fn rot_y(mut self, rad: Float) -> TransformBuilder {
         let rotation = Matrix4::new_rotation(Vector3::new(0.0, rad, 0.0));
         self.transforms.push(rotation);
         self
     }
 
     /// Rotation transformation around the z-axis.
-    pub fn rot_z(mut self, rad: f64) -> TransformBuilder {
+    pub fn rot_z(mut self, rad: Float) -> TransformBuilder {
         let rotation = Matrix4::new_rotation(Vector3::new(0.0, 0.0, rad));
         self.transforms.push(rotation);
         self
     }
 
     /// Shear, aka keystone, transformation.
-    pub fn shear(mut self, xy: f64, xz: f64, yx: f64, yz: f64, zx: f64, zy: f64) -> TransformBuilder {
+    pub fn shear(mut self, xy: Float, xz: Float, yx: Float, yz: Float, zx: Float, zy: Float) -> TransformBuilder {
         let mut shm = Matrix4::identity();
         shm.m12 = xy;
         shm.m13 = xz;
@@ -159,8 +178,63 @@ impl TransformBuilder {
         self
     }
 
+    /// Mirrors across the yz plane, negating x.
+    pub fn reflect_x(self) -> TransformBuilder {
+        self.nuscale(-1.0, 1.0, 1.0)
+    }
+
+    /// Mirrors across the xz plane, negating y.
+    pub fn reflect_y(self) -> TransformBuilder {
+        self.nuscale(1.0, -1.0, 1.0)
+    }
+
+    /// Mirrors across the xy plane, negating z.
+    pub fn reflect_z(self) -> TransformBuilder {
+        self.nuscale(1.0, 1.0, -1.0)
+    }
+
+    /// Mirrors across an arbitrary plane, given by its normal and its distance
+    /// from the origin along that normal.
+    pub fn reflect_across_plane(mut self, normal: Vector4<Float>, offset: Float) -> TransformBuilder {
+        let n = normal.xyz().normalize();
+        let mut reflection = Matrix4::identity();
+        reflection.fixed_view_mut::<3, 3>(0, 0).sub_assign(n * n.transpose() * 2.0);
+        reflection.fixed_view_mut::<3, 1>(0, 3).copy_from(&(n * 2.0 * offset));
+        self.transforms.push(reflection);
+        self
+    }
+
+    /// Moves the object to from and turns its -z axis towards to. See
+    /// Transform::look_at().
+    pub fn look_at(mut self, from: Vector4<Float>, to: Vector4<Float>, up: Vector4<Float>) -> TransformBuilder {
+        self.transforms.push(Matrix4::look_at(from, to, up));
+        self
+    }
+
+    /// Rotation around an arbitrary axis passing through a pivot point, e.g. to
+    /// spin an object that has already been moved around its own centre.
+    pub fn rot_about(self, pivot: Vector4<Float>, axis: Vector4<Float>, rad: Float) -> TransformBuilder {
+        let rotation = Matrix4::from_axis_angle(&Unit::new_normalize(axis.xyz()), rad);
+        self.about(pivot, rotation)
+    }
+
+    /// Non-uniform scale transformation centred on a pivot point rather than
+    /// the origin.
+    pub fn scale_about(self, pivot: Vector4<Float>, x: Float, y: Float, z: Float) -> TransformBuilder {
+        let scaling = Matrix4::new_nonuniform_scaling(&Vector3::new(x, y, z));
+        self.about(pivot, scaling)
+    }
+
+    // Moves the pivot to the origin, applies the transform, and moves it back.
+    fn about(mut self, pivot: Vector4<Float>, transform: Matrix4<Float>) -> TransformBuilder {
+        self.transforms.push(Matrix4::new_translation(&-pivot.xyz()));
+        self.transforms.push(transform);
+        self.transforms.push(Matrix4::new_translation(&pivot.xyz()));
+        self
+    }
+
     /// Transform builder. This should always end the chain.
-    pub fn build(self) -> Matrix4<f64> {
+    pub fn build(self) -> Matrix4<Float> {
         let mut result = Matrix4::identity();
         for transform in self.transforms.into_iter().rev() {
             result *= transform;
@@ -169,11 +243,82 @@ impl TransformBuilder {
     }
 }
 
+impl Default for TransformBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Lets a builder be passed straight to with_transform() without build().
+impl From<TransformBuilder> for Matrix4<Float> {
+    fn from(builder: TransformBuilder) -> Self {
+        builder.build()
+    }
+}
+
+/// Transform split into translation, rotation and scale, so that two transforms
+/// can be blended without shearing the object in between. Any shear in the
+/// original transform is lost.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Decomposed {
+    pub translation: Vector3<Float>,
+    pub rotation: UnitQuaternion<Float>,
+    pub scale: Vector3<Float>
+}
+
+impl Decomposed {
+    /// Rebuilds the transform, scaling first, then rotating, then translating.
+    pub fn recompose(&self) -> Matrix4<Float> {
+        Matrix4::new_translation(&self.translation)
+            * self.rotation.to_homogeneous()
+            * Matrix4::new_nonuniform_scaling(&self.scale)
+    }
+
+    /// Blends towards another decomposed transform. Translation and scale are
+    /// interpolated linearly and rotation is slerped, taking the shortest way round.
+    pub fn lerp(&self, other: &Decomposed, t: Float) -> Decomposed {
+        Decomposed {
+            translation: self.translation.lerp(&other.translation, t),
+            rotation: self.rotation.slerp(&other.rotation, t),
+            scale: self.scale.lerp(&other.scale, t)
+        }
+    }
+}
+
+/// Splits an affine transform into translation, rotation and scale. A mirrored
+/// transform is given a negative x scale.
+pub fn decompose(m: &Matrix4<Float>) -> Decomposed {
+    let translation = Vector3::new(m.m14, m.m24, m.m34);
+    let mut linear: Matrix3<Float> = m.fixed_view::<3, 3>(0, 0).into_owned();
+    let mut scale = Vector3::new(
+        linear.column(0).norm(),
+        linear.column(1).norm(),
+        linear.column(2).norm()
+    );
+    if linear.determinant() < 0.0 {
+        scale.x = -scale.x;
+    }
+    for (i, s) in scale.iter().enumerate() {
+        if s.abs() > Float::EPSILON {
+            linear.column_mut(i).unscale_mut(*s);
+        }
+    }
+    let rotation = UnitQuaternion::from_matrix(&linear);
+
+    Decomposed { translation, rotation, scale }
+}
+
+/// Transform part way between a and b, where t runs from 0.0 to 1.0. Unlike
+/// blending the matrices, rotations keep their shape.
+pub fn interpolate(a: &Matrix4<Float>, b: &Matrix4<Float>, t: Float) -> Matrix4<Float> {
+    decompose(a).lerp(&decompose(b), t).recompose()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::core::{point, vector, Test};
-    use std::f64::consts::PI;
+    use crate::consts::PI;
     use assert_approx_eq::assert_approx_eq;
 
     #[test]
@@ -248,8 +393,8 @@ mod tests {
         let fqp = fq * p;
 
         assert_approx_eq!(hqp.x, 0.0);
-        assert_approx_eq!(hqp.y, 2.0_f64.sqrt()/2.0);
-        assert_approx_eq!(hqp.z, 2.0_f64.sqrt()/2.0);
+        assert_approx_eq!(hqp.y, Float::sqrt(2.0)/2.0);
+        assert_approx_eq!(hqp.z, Float::sqrt(2.0)/2.0);
         assert_approx_eq!(fqp.x, 0.0);
         assert_approx_eq!(fqp.y, 0.0);
         assert_approx_eq!(fqp.z, 1.0);
@@ -262,8 +407,8 @@ mod tests {
         let hqpi = hq.try_inverse().unwrap() * p;
 
         assert_approx_eq!(hqpi.x, 0.0);
-        assert_approx_eq!(hqpi.y, 2.0_f64.sqrt()/2.0);
-        assert_approx_eq!(hqpi.z, -2.0_f64.sqrt()/2.0);
+        assert_approx_eq!(hqpi.y, Float::sqrt(2.0)/2.0);
+        assert_approx_eq!(hqpi.z, -Float::sqrt(2.0)/2.0);
     }
 
     #[test]
@@ -274,9 +419,9 @@ mod tests {
         let hqp = hq * p;
         let fqp = fq * p;
 
-        assert_approx_eq!(hqp.x, 2.0_f64.sqrt()/2.0);
+        assert_approx_eq!(hqp.x, Float::sqrt(2.0)/2.0);
         assert_approx_eq!(hqp.y, 0.0);
-        assert_approx_eq!(hqp.z, 2.0_f64.sqrt()/2.0);
+        assert_approx_eq!(hqp.z, Float::sqrt(2.0)/2.0);
         assert_approx_eq!(fqp.x, 1.0);
         assert_approx_eq!(fqp.y, 0.0);
         assert_approx_eq!(fqp.z, 0.0);
@@ -290,8 +435,8 @@ mod tests {
         let hqp = hq * p;
         let fqp = fq * p;
 
-        assert_approx_eq!(hqp.x, -2.0_f64.sqrt()/2.0);
-        assert_approx_eq!(hqp.y, 2.0_f64.sqrt()/2.0);
+        assert_approx_eq!(hqp.x, -Float::sqrt(2.0)/2.0);
+        assert_approx_eq!(hqp.y, Float::sqrt(2.0)/2.0);
         assert_approx_eq!(hqp.z, 0.0);
         assert_approx_eq!(fqp.x, -1.0);
         assert_approx_eq!(fqp.y, 0.0);
@@ -451,4 +596,138 @@ mod tests {
         assert_approx_eq!(r.y, 0.0);
         assert_approx_eq!(r.z, 7.0);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn decomposing_and_recomposing_a_transform() {
+        let m = TransformBuilder::new()
+            .nuscale(2.0, 3.0, 4.0)
+            .rot(0.3, -1.2, 0.5)
+            .translate(1.0, -2.0, 3.0)
+            .build();
+        let d = decompose(&m);
+
+        assert_eq!(d.translation, Vector3::new(1.0, -2.0, 3.0));
+        assert_approx_eq!(d.scale.x, 2.0);
+        assert_approx_eq!(d.scale.y, 3.0);
+        assert_approx_eq!(d.scale.z, 4.0);
+        assert_eq!(d.recompose().to_5dp(), m.to_5dp());
+    }
+
+    #[test]
+    fn decomposing_a_mirrored_transform() {
+        let m = Matrix4::nuscale(-1.0, 1.0, 1.0);
+        let d = decompose(&m);
+
+        assert_eq!(d.scale, Vector3::new(-1.0, 1.0, 1.0));
+        assert_eq!(d.recompose().to_5dp(), m);
+    }
+
+    #[test]
+    fn interpolating_rotations_keeps_their_shape() {
+        let a = Matrix4::identity();
+        let b = Matrix4::rot_y(PI / 2.0);
+        let halfway = interpolate(&a, &b, 0.5);
+
+        assert_eq!(halfway.to_5dp(), Matrix4::rot_y(PI / 4.0).to_5dp());
+        assert_approx_eq!(halfway.determinant(), 1.0);
+    }
+
+    #[test]
+    fn interpolating_translation_and_scale() {
+        let a = Matrix4::translate(0.0, 0.0, 0.0);
+        let b = Matrix4::translate(4.0, 2.0, 0.0) * Matrix4::uscale(3.0);
+
+        assert_eq!(interpolate(&a, &b, 0.5).to_5dp(), (Matrix4::translate(2.0, 1.0, 0.0) * Matrix4::uscale(2.0)).to_5dp());
+        assert_eq!(interpolate(&a, &b, 0.0), a);
+    }
+
+    #[test]
+    fn rotating_about_a_pivot() {
+        let pivot = point(2.0, 0.0, 0.0);
+        let t = TransformBuilder::new()
+            .rot_about(pivot, vector(0.0, 0.0, 1.0), PI / 2.0)
+            .build();
+
+        assert!((t * pivot).approx_eq(&pivot));
+        assert!((t * point(3.0, 0.0, 0.0)).approx_eq(&point(2.0, 1.0, 0.0)));
+    }
+
+    #[test]
+    fn rotating_about_an_arbitrary_axis() {
+        let t = TransformBuilder::new()
+            .rot_about(point(0.0, 0.0, 0.0), vector(0.0, 2.0, 0.0), PI / 3.0)
+            .build();
+
+        assert_eq!(t.to_5dp(), Matrix4::rot_y(PI / 3.0).to_5dp());
+    }
+
+    #[test]
+    fn scaling_about_a_pivot() {
+        let pivot = point(1.0, 1.0, 1.0);
+        let t = TransformBuilder::new()
+            .translate(0.0, 1.0, 0.0)
+            .scale_about(point(1.0, 2.0, 1.0), 2.0, 3.0, 1.0)
+            .build();
+
+        assert_eq!(t * pivot, point(1.0, 2.0, 1.0));
+        assert_eq!(t * point(2.0, 2.0, 1.0), point(3.0, 5.0, 1.0));
+    }
+
+    #[test]
+    fn reflecting_across_the_axis_planes() {
+        let p = point(1.0, 2.0, 3.0);
+        let x = TransformBuilder::new().reflect_x().build();
+        let y = TransformBuilder::new().reflect_y().build();
+        let z = TransformBuilder::new().reflect_z().build();
+
+        assert_eq!(x * p, point(-1.0, 2.0, 3.0));
+        assert_eq!(y * p, point(1.0, -2.0, 3.0));
+        assert_eq!(z * p, point(1.0, 2.0, -3.0));
+    }
+
+    #[test]
+    fn reflecting_across_an_arbitrary_plane() {
+        let t = TransformBuilder::new()
+            .reflect_across_plane(vector(0.0, 2.0, 0.0), 1.0)
+            .build();
+        let d = TransformBuilder::new()
+            .reflect_across_plane(vector(1.0, 1.0, 0.0), 0.0)
+            .build();
+
+        assert_eq!(t * point(3.0, 0.0, 4.0), point(3.0, 2.0, 4.0));
+        assert_eq!(t * vector(0.0, 1.0, 0.0), vector(0.0, -1.0, 0.0));
+        assert_eq!((d * point(1.0, 0.0, 0.0)).to_5dp(), point(0.0, -1.0, 0.0));
+    }
+
+    #[test]
+    fn look_at_is_the_inverse_of_the_view_transform() {
+        let from = point(1.0, 3.0, 2.0);
+        let to = point(4.0, 3.0, 8.0);
+        let up = vector(0.0, 1.0, 0.0);
+        let t = Matrix4::look_at(from, to, up);
+
+        assert_eq!((t * Matrix4::view_transform(from, to, up)).to_5dp(), Matrix4::identity());
+        assert_eq!(t * point(0.0, 0.0, 0.0), from);
+    }
+
+    #[test]
+    fn look_at_points_the_negative_z_axis_at_the_target() {
+        let t = TransformBuilder::new()
+            .uscale(2.0)
+            .look_at(point(0.0, 0.0, 0.0), point(5.0, 0.0, 0.0), vector(0.0, 1.0, 0.0))
+            .build();
+
+        assert_eq!((t * vector(0.0, 0.0, -1.0)).to_5dp(), vector(2.0, 0.0, 0.0));
+        assert_eq!((t * vector(0.0, 1.0, 0.0)).to_5dp(), vector(0.0, 2.0, 0.0));
+    }
+
+    #[test]
+    fn builder_converts_into_a_matrix() {
+        let m: Matrix4<Float> = TransformBuilder::new()
+            .rot_x(PI / 2.0)
+            .translate(1.0, 0.0, 0.0)
+            .into();
+
+        assert_eq!(m, Matrix4::translate(1.0, 0.0, 0.0) * Matrix4::rot_x(PI / 2.0));
+    }
+}
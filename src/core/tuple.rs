@@ -1,3 +1,5 @@
+use crate::{Float, EPSILON};
+use crate::consts::PI;
 use nalgebra::Vector4;
 
 // Previous iteration of tuple.rs (i.e. pre-nalgebra refactoring) can be
@@ -8,14 +10,15 @@ use nalgebra::Vector4;
 pub trait Tuple {
     fn is_point(&self) -> bool;
     fn is_vector(&self) -> bool;
-    fn reflect(&self, n: Vector4<f64>) -> Vector4<f64>;
-    fn to_5dp(&self) -> Vector4<f64>;
-    fn to_point(&self) -> Vector4<f64>;
-    fn to_vector(&self) -> Vector4<f64>;
-    fn xprod(&self, rhs: &Vector4<f64>) -> Vector4<f64>;
+    fn reflect(&self, n: Vector4<Float>) -> Vector4<Float>;
+    fn to_5dp(&self) -> Vector4<Float>;
+    fn approx_eq(&self, other: &Vector4<Float>) -> bool;
+    fn to_point(&self) -> Vector4<Float>;
+    fn to_vector(&self) -> Vector4<Float>;
+    fn xprod(&self, rhs: &Vector4<Float>) -> Vector4<Float>;
 }
 
-impl Tuple for Vector4<f64> {
+impl Tuple for Vector4<Float> {
     /// Tests if a Tuple is a point
     fn is_point(&self) -> bool {
         self.w == 1.0
@@ -26,7 +29,7 @@ impl Tuple for Vector4<f64> {
         self.w == 0.0
     }
 
-    fn reflect(&self, n: Vector4<f64>) -> Vector4<f64> {
+    fn reflect(&self, n: Vector4<Float>) -> Vector4<Float> {
         self - n * 2.0 * self.dot(&n)
     }
 
@@ -39,6 +42,12 @@ impl Tuple for Vector4<f64> {
         res
     }
 
+    /// Tests if every component is within EPSILON of another Tuple's, which
+    /// is as close as Float keeps them. Only useful for tests.
+    fn approx_eq(&self, other: &Vector4<Float>) -> bool {
+        (self - other).amax() < EPSILON
+    }
+
     /// Converts or corrects a Tuple struct to a point type
     fn to_point(&self) -> Self {
         Vector4::new(self.x, self.y, self.z, 1.0)
@@ -62,7 +71,7 @@ impl Tuple for Vector4<f64> {
     /// assert_eq!(v1.xprod(v2), vector(-1.0, 2.0, -1.0));
     /// assert_eq!(v2.xprod(v1), vector(1.0, -2.0, 1.0));
     /// ```
-    fn xprod(&self, rhs: &Vector4<f64>) -> Vector4<f64> {
+    fn xprod(&self, rhs: &Vector4<Float>) -> Vector4<Float> {
         let x = (self.y * rhs.z) - (self.z * rhs.y);
         let y = (self.z * rhs.x) - (self.x * rhs.z);
         let z = (self.x * rhs.y) - (self.y * rhs.x);
@@ -71,24 +80,68 @@ impl Tuple for Vector4<f64> {
     }
 }
 
-pub fn point(x: f64, y: f64, z: f64) -> Vector4<f64> {
+pub fn point(x: Float, y: Float, z: Float) -> Vector4<Float> {
     Vector4::new(x, y, z, 1.0)
 }
 
-pub fn vector(x: f64, y: f64, z: f64) -> Vector4<f64> {
+pub fn vector(x: Float, y: Float, z: Float) -> Vector4<Float> {
     Vector4::new(x, y, z, 0.0)
 }
 
+/// Orthonormal basis about a normal, for working with directions relative to
+/// a surface, e.g. sampling bounces about +z and turning them into world
+/// space. The tangent, bitangent and normal make a right-handed set.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Frame {
+    pub tangent: Vector4<Float>,
+    pub bitangent: Vector4<Float>,
+    pub normal: Vector4<Float>
+}
+
+impl Frame {
+    /// Basis about a unit normal, with the tangent picked to be well away
+    /// from it.
+    pub fn new(normal: Vector4<Float>) -> Self {
+        let helper = if normal.x.abs() > 0.9 { vector(0.0, 1.0, 0.0) } else { vector(1.0, 0.0, 0.0) };
+        let tangent = helper.xprod(&normal).normalize();
+        let bitangent = normal.xprod(&tangent);
+
+        Frame { tangent, bitangent, normal }
+    }
+
+    /// A world space vector in the frame's terms, with the normal along +z.
+    pub fn to_local(&self, v: Vector4<Float>) -> Vector4<Float> {
+        vector(v.dot(&self.tangent), v.dot(&self.bitangent), v.dot(&self.normal))
+    }
+
+    /// A vector in the frame's terms back in world space.
+    pub fn to_world(&self, v: Vector4<Float>) -> Vector4<Float> {
+        self.tangent * v.x + self.bitangent * v.y + self.normal * v.z
+    }
+}
+
+/// Unit direction about +z, more likely towards it than away, as a diffuse
+/// surface scatters light, from two uniform random numbers between 0 and 1.
+/// The chance of each direction is its cosine over PI. Use Frame::to_world()
+/// to turn it about a normal.
+pub fn cosine_direction(u1: Float, u2: Float) -> Vector4<Float> {
+    let r = u1.sqrt();
+    let phi = 2.0 * PI * u2;
+
+    vector(r * phi.cos(), r * phi.sin(), (1.0 - u1).sqrt())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use assert_approx_eq::assert_approx_eq;
 
     #[test]
     fn tuple_is_point() {
         let tup = Vector4::new(4.3, -4.2, 3.1, 1.0);
 
         assert_eq!((tup.x, tup.y, tup.z, tup.w), (4.3, -4.2, 3.1, 1.0));
-        assert_eq!(tup.is_point(), true);
+        assert!(tup.is_point());
     }
 
     #[test]
@@ -96,7 +149,7 @@ mod tests {
         let tup = Vector4::new(4.3, -4.2, 3.1, 0.0);
 
         assert_eq!((tup.x, tup.y, tup.z, tup.w), (4.3, -4.2, 3.1, 0.0));
-        assert_eq!(tup.is_vector(), true);
+        assert!(tup.is_vector());
     }
 
     #[test]
@@ -197,14 +250,14 @@ mod tests {
     fn magnitude_of_vector4() {
         let v = vector(1.0, 2.0, 3.0);
 
-        assert_eq!(v.magnitude(), 14.0_f64.sqrt());
+        assert_eq!(v.magnitude(), Float::sqrt(14.0));
     }
 
     #[test]
     fn magnitude_of_vector5() {
         let v = vector(-1.0, -2.0, -3.0);
 
-        assert_eq!(v.magnitude(), 14.0_f64.sqrt());
+        assert_eq!(v.magnitude(), Float::sqrt(14.0));
     }
 
     #[test]
@@ -217,7 +270,7 @@ mod tests {
     #[test]
     fn normalise_vector2() {
         let v = vector(1.0, 2.0, 3.0);
-        let div = 14.0_f64.sqrt();
+        let div = Float::sqrt(14.0);
 
         assert_eq!(v.normalize(), vector(1.0/div, 2.0/div, 3.0/div));
     }
@@ -226,7 +279,7 @@ mod tests {
     fn magnitude_of_normalised_vector() {
         let v = vector(1.0, 2.0, 3.0);
 
-        assert_eq!(v.normalize().magnitude(), 1.0);
+        assert_approx_eq!(v.normalize().magnitude(), 1.0, EPSILON);
     }
 
     #[test]
@@ -257,9 +310,44 @@ mod tests {
     #[test]
     fn reflecting_vector_off_slanted_surface() {
         let v = vector(0.0, -1.0, 0.0);
-        let irr_no = 2.0f64.sqrt() / 2.0;
+        let irr_no = Float::sqrt(2.0) / 2.0;
         let n = vector(irr_no, irr_no, 0.0);
 
         assert_eq!(v.reflect(n).to_5dp(), vector(1.0, 0.0, 0.0));
     }
+
+    #[test]
+    fn frames_are_orthonormal_about_the_normal() {
+        for normal in [vector(0.0, 0.0, 1.0), vector(1.0, 0.0, 0.0), vector(1.0, -2.0, 3.0).normalize()] {
+            let f = Frame::new(normal);
+
+            assert_eq!(f.normal, normal);
+            assert!(f.tangent.dot(&normal).abs() < EPSILON);
+            assert!(f.bitangent.dot(&normal).abs() < EPSILON);
+            assert!(f.tangent.dot(&f.bitangent).abs() < EPSILON);
+            assert!((f.bitangent.magnitude() - 1.0).abs() < EPSILON);
+            assert_eq!(f.tangent.xprod(&f.bitangent).to_5dp(), normal.to_5dp());
+        }
+    }
+
+    #[test]
+    fn vectors_move_in_and_out_of_a_frame() {
+        let f = Frame::new(vector(0.0, 1.0, 0.0));
+        let v = vector(0.3, -0.4, 1.2);
+
+        assert_eq!(f.to_local(f.normal).to_5dp(), vector(0.0, 0.0, 1.0));
+        assert_eq!(f.to_world(vector(0.0, 0.0, 2.0)).to_5dp(), vector(0.0, 2.0, 0.0));
+        assert_eq!(f.to_world(f.to_local(v)).to_5dp(), v);
+    }
+
+    #[test]
+    fn cosine_directions_are_unit_and_face_up() {
+        assert_eq!(cosine_direction(0.0, 0.3), vector(0.0, 0.0, 1.0));
+        for i in 0..10 {
+            let d = cosine_direction(i as Float / 10.0, (i * 7 % 10) as Float / 10.0);
+
+            assert!(d.z >= 0.0);
+            assert!((d.magnitude() - 1.0).abs() < EPSILON);
+        }
+    }
 }
\ No newline at end of file
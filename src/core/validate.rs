@@ -0,0 +1,222 @@
+use crate::Float;
+use crate::core::{Camera, World};
+use crate::core::matrix::inverse;
+use crate::primitives::{Object, Primitive};
+use std::fmt;
+
+// Diffuse plus reflectivity above this gives out noticeably more light than
+// reaches the surface.
+const ENERGY_LIMIT: f32 = 1.5;
+
+/// How bad a problem found by World::validate() is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Severity {
+    /// Renders, but probably not as intended.
+    Warning,
+    /// Can't be rendered properly, or will panic.
+    Error
+}
+
+/// What's wrong with a scene.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Issue {
+    /// Nothing lights the scene, so only ambient light is seen.
+    NoLights,
+    /// A light with no colour, which adds nothing but time.
+    BlackLight,
+    /// Rays leave surfaces from no offset at all, or from the wrong side.
+    BadEpsilon(Float),
+    /// A slope bias that pulls rays back into the surface they left.
+    BadSlopeBias(Float),
+    /// An object's transform can't be inverted, so it can't be hit.
+    SingularTransform,
+    /// The transform of an object's pattern can't be inverted.
+    SingularPatternTransform,
+    /// A transparent material with a refractive index of 1.0, which looks
+    /// like nothing is there at all.
+    NoRefraction,
+    /// A material giving out more light than reaches it, with its diffuse
+    /// and reflectivity added up.
+    TooBright(f32),
+    /// The camera starts inside a solid object, so sees its inside.
+    CameraInside,
+    /// A medium filling a plane, which has no inside to hold it, so it spreads
+    /// out endlessly on one side.
+    UnboundedVolume
+}
+
+/// One problem found in a scene, with the object or light it's about.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub issue: Issue,
+    /// Index of the object in the world, when it's about one.
+    pub object: Option<usize>,
+    /// Index of the light in the world, when it's about one.
+    pub light: Option<usize>,
+    /// Name of the object or light, if it has one.
+    pub name: Option<String>
+}
+
+impl Diagnostic {
+    fn world(severity: Severity, issue: Issue) -> Self {
+        Diagnostic { severity, issue, object: None, light: None, name: None }
+    }
+
+    fn object(severity: Severity, issue: Issue, index: usize, object: &Object) -> Self {
+        Diagnostic { object: Some(index), name: object.name.clone(), ..Diagnostic::world(severity, issue) }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error"
+        };
+        write!(f, "{}: ", severity)?;
+        let subject = match (self.object, self.light) {
+            (Some(i), _) => Some(format!("object {}", i)),
+            (None, Some(i)) => Some(format!("light {}", i)),
+            (None, None) => None
+        };
+        if let Some(subject) = subject {
+            match &self.name {
+                Some(name) => write!(f, "{} \"{}\": ", subject, name)?,
+                None => write!(f, "{}: ", subject)?
+            }
+        }
+        match &self.issue {
+            Issue::NoLights => write!(f, "there are no lights"),
+            Issue::BlackLight => write!(f, "light has no colour"),
+            Issue::BadEpsilon(e) => write!(f, "epsilon of {} must be more than 0", e),
+            Issue::BadSlopeBias(b) => write!(f, "slope bias of {} can't be negative", b),
+            Issue::SingularTransform => write!(f, "transform can't be inverted"),
+            Issue::SingularPatternTransform => write!(f, "pattern transform can't be inverted"),
+            Issue::NoRefraction => write!(f, "transparent with a refractive index of 1.0, so it bends no light"),
+            Issue::TooBright(total) => write!(f, "diffuse and reflectivity add up to {}, so it gives out more light than it gets", total),
+            Issue::CameraInside => write!(f, "camera is inside it"),
+            Issue::UnboundedVolume => write!(f, "medium fills a plane, which has no inside")
+        }
+    }
+}
+
+impl World {
+    /// Looks for problems that would spoil a render, before spending time on
+    /// it. Errors come first, then warnings, each in the order of the objects
+    /// and lights they're about.
+    pub fn validate(&self) -> Vec<Diagnostic> {
+        let mut found = vec![];
+        if self.lights.is_empty() {
+            found.push(Diagnostic::world(Severity::Warning, Issue::NoLights));
+        }
+        if self.epsilon.is_nan() || self.epsilon <= 0.0 {
+            found.push(Diagnostic::world(Severity::Error, Issue::BadEpsilon(self.epsilon)));
+        }
+        if self.slope_bias.is_nan() || self.slope_bias < 0.0 {
+            found.push(Diagnostic::world(Severity::Error, Issue::BadSlopeBias(self.slope_bias)));
+        }
+        for (i, light) in self.lights.iter().enumerate() {
+            if light.colour.r <= 0.0 && light.colour.g <= 0.0 && light.colour.b <= 0.0 {
+                found.push(Diagnostic {
+                    light: Some(i),
+                    name: light.name.clone(),
+                    ..Diagnostic::world(Severity::Warning, Issue::BlackLight)
+                });
+            }
+        }
+        for (i, object) in self.objects.iter().enumerate() {
+            let m = &object.material;
+            if inverse(&object.transform).is_none() {
+                found.push(Diagnostic::object(Severity::Error, Issue::SingularTransform, i, object));
+            }
+            if inverse(&m.pattern.transform).is_none() {
+                found.push(Diagnostic::object(Severity::Error, Issue::SingularPatternTransform, i, object));
+            }
+            if object.is_volume() {
+                if matches!(object.shape, Primitive::Plane()) {
+                    found.push(Diagnostic::object(Severity::Error, Issue::UnboundedVolume, i, object));
+                }
+                continue;
+            }
+            if m.transparency > 0.0 && m.ior == 1.0 {
+                found.push(Diagnostic::object(Severity::Warning, Issue::NoRefraction, i, object));
+            }
+            if m.diffuse + m.reflectivity > ENERGY_LIMIT {
+                found.push(Diagnostic::object(Severity::Warning, Issue::TooBright(m.diffuse + m.reflectivity), i, object));
+            }
+        }
+        found.sort_by_key(|d| std::cmp::Reverse(d.severity));
+
+        found
+    }
+
+    /// Same as validate(), also checking the scene as seen from the camera.
+    pub fn validate_for(&self, camera: &Camera) -> Vec<Diagnostic> {
+        let mut found = self.validate();
+        let eye = camera.ray_for_pixel(camera.hsize / 2, camera.vsize / 2).origin;
+        for (i, object) in self.objects.iter().enumerate() {
+            let solid = matches!(object.shape, Primitive::Sphere(s) if s.is_whole()) && !object.is_volume();
+            if solid && object.world_to_object(eye).xyz().magnitude() < 1.0 {
+                found.push(Diagnostic::object(Severity::Warning, Issue::CameraInside, i, object));
+            }
+        }
+        found.sort_by_key(|d| std::cmp::Reverse(d.severity));
+
+        found
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{point, vector, Colour, Transform};
+    use crate::lights::PointLight;
+    use crate::materials::{Material, Medium};
+    use nalgebra::Matrix4;
+
+    #[test]
+    fn the_default_world_is_fine() {
+        assert_eq!(World::default_world().validate(), vec![]);
+    }
+
+    #[test]
+    fn problems_are_reported_errors_first() {
+        let mut w = World::default()
+            .with_light(PointLight::new(Colour::black(), point(0.0, 10.0, 0.0)).with_name("dud"))
+            .with_object(Object::new_sphere().with_material(Material::null().with_transparency(1.0)).with_name("ghost"))
+            .with_object(Object::new_plane().with_material(Material::default().with_diffuse(0.9).with_reflectivity(0.9)));
+        w.objects[0].transform = Matrix4::nuscale(1.0, 0.0, 1.0);
+        let found = w.validate();
+        let issues: Vec<&Issue> = found.iter().map(|d| &d.issue).collect();
+
+        assert_eq!(issues, vec![&Issue::SingularTransform, &Issue::BlackLight, &Issue::NoRefraction, &Issue::TooBright(1.8)]);
+        assert_eq!(found[0].object, Some(0));
+        assert_eq!(found[1].light, Some(0));
+        assert_eq!(found[0].to_string(), "error: object 0 \"ghost\": transform can't be inverted");
+        assert_eq!(World::default().validate()[0].to_string(), "warning: there are no lights");
+    }
+
+    #[test]
+    fn cameras_inside_objects_are_found() {
+        let w = World::default_world();
+        let inside = Camera::new(10, 10, 1.0)
+            .with_transform(Matrix4::view_transform(point(0.0, 0.0, 0.5), point(0.0, 0.0, 1.0), vector(0.0, 1.0, 0.0)));
+        let outside = Camera::new(10, 10, 1.0)
+            .with_transform(Matrix4::view_transform(point(0.0, 0.0, -5.0), point(0.0, 0.0, 0.0), vector(0.0, 1.0, 0.0)));
+        let found = w.validate_for(&inside);
+
+        assert_eq!(found.iter().map(|d| d.object).collect::<Vec<_>>(), vec![Some(0)]);
+        assert_eq!(found[0].issue, Issue::CameraInside);
+        assert!(w.validate_for(&outside).is_empty());
+    }
+
+    #[test]
+    fn volumes_filling_planes_are_errors() {
+        let w = World::default_world().with_object(Object::new_plane().with_medium(Medium::new(0.5)));
+        let found = w.validate();
+
+        assert_eq!(found[0].issue, Issue::UnboundedVolume);
+        assert_eq!(found[0].severity, Severity::Error);
+    }
+}
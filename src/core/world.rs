@@ -1,30 +1,98 @@
-use crate::core::{point, Colour, Intersections, PreCompData, Ray, Transform};
-use crate::materials::Material;
+use crate::{Float, EPSILON};
+use crate::core::depth::rng_at;
+use crate::core::progress::count_rays;
+use crate::core::{point, Background, Bounce, Colour, Intersections, Keyframes, PhotonMap, PreCompData, Ray, RayDepth, RayKind, RayPacket, Rng, Transform, LANES};
+use crate::materials::{Material, MaterialId};
 use crate::primitives::Object;
 use crate::lights::PointLight;
+use crate::scene::loader::load_world;
+use crate::scene::{world_to_yaml, SceneError};
 use nalgebra::{Matrix4, Vector4};
-
-#[derive(Debug, PartialEq)]
+use std::fs;
+use std::borrow::Cow;
+use std::path::Path;
+use std::sync::Arc;
+
+// Furthest a ray is marched through any one volume. A medium that barely dims
+// light reaches further than any scene, and one filling a shape with no far
+// side, like a plane, never ends at all.
+const MAX_VOLUME_DEPTH: Float = 1.0e4;
+
+/// Handle to an object in a world, returned by World::add(). Unlike an index
+/// into World::objects, it keeps pointing at the same object as others are
+/// added and removed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ObjectId(u64);
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct World {
     pub objects: Vec<Object>,
     pub lights: Vec<PointLight>,
-    pub rcrs_lim: u8
+    pub rcrs_lim: u8,
+    pub background: Background,
+    /// Colour of the world's fill light, added to every surface on top of the
+    /// material's own ambient term.
+    pub ambient: Colour,
+    /// Brightness of the fill light. 0.0 turns it off.
+    pub ambient_strength: f32,
+    /// Light focused onto surfaces by glass and mirrors. See with_caustics().
+    pub caustics: Option<Arc<PhotonMap>>,
+    /// Number of lights shaded at each hit, picked at random in proportion to
+    /// how much each is likely to contribute. None shades every light.
+    pub light_samples: Option<usize>,
+    /// How far off a surface rays leaving it start, so they don't hit the
+    /// surface they left. Scaled up with distance from the origin, so far off
+    /// parts of large scenes don't get shadow acne, but tiny scenes may need
+    /// a smaller value to stop light leaking through thin objects.
+    pub epsilon: Float,
+    /// Extra offset for rays leaving surfaces seen at a grazing angle, per unit
+    /// of the tangent of the angle. Stops acne on large floors, whose far off
+    /// parts are only ever seen side on. 0.0 offsets every hit by epsilon.
+    pub slope_bias: Float,
+    // Handles of the first handles.len() objects. Objects put straight into
+    // objects, rather than through add(), take the next handles in order.
+    handles: Vec<ObjectId>,
+    next_handle: u64,
+    // Shared materials, with their names, in the order they were added.
+    palette: Vec<(Option<String>, Material)>
 }
 
 impl World {
     /// NWO - New World Object.
     pub fn new(objects: Vec<Object>, lights: Vec<PointLight>, rcrs_lim: u8) -> Self {
-        World { objects, lights, rcrs_lim }
+        World { objects, lights, rcrs_lim, ..Default::default() }
     }
 
-    /// Calculates the colour of a pixel.
-    pub fn colour_at(&self, ray: &Ray, remaining: u8) -> Colour {
-        let xs = self.intersect(ray);
-        if xs.hit_index().is_some() {
-            self.shade_hit(&xs.prepare_computations(xs.hit_index().unwrap(), ray), remaining)
-        } else {
-            Colour::black()
-        }
+    /// Calculates the colour of a pixel. Remaining is the number of bounces
+    /// left, either as a RayDepth or as a plain total.
+    pub fn colour_at(&self, ray: &Ray, remaining: impl Into<RayDepth>) -> Colour {
+        self.colour_at_with_background(ray, remaining, None)
+    }
+
+    /// Same as colour_at(), but a ray that misses everything sees the given
+    /// colour, when there is one, instead of the world's background.
+    pub fn colour_at_with_background(&self, ray: &Ray, remaining: impl Into<RayDepth>, background: Option<Colour>) -> Colour {
+        self.colour_at_into(ray, remaining, background, &mut Intersections::default())
+    }
+
+    /// Same as colour_at_with_background(), but finds intersections in the
+    /// given buffer, which is reused for every ray traced from here on rather
+    /// than allocating a new list each time. Keep one buffer per thread.
+    pub fn colour_at_into<'a>(&'a self, ray: &Ray, remaining: impl Into<RayDepth>, background: Option<Colour>, xs: &mut Intersections<'a>) -> Colour {
+        self.intersect_into(ray, xs);
+        self.colour_of_hits(ray, remaining, background, xs)
+    }
+
+    // Colour seen along a ray whose intersections are already in xs. xs is then
+    // reused for the rays traced from the hit.
+    pub(crate) fn colour_of_hits<'a>(&'a self, ray: &Ray, remaining: impl Into<RayDepth>, background: Option<Colour>, xs: &mut Intersections<'a>) -> Colour {
+        let hit = xs.hit_index().map(|i| (self.prepare_computations(xs, i, ray), xs[i].t));
+        let (colour, distance) = match hit {
+            Some((comps, t)) => (self.shade_hit_into(&comps, remaining, xs), t),
+            None => (background.unwrap_or_else(|| self.background.colour_at(&ray.direction)), Float::INFINITY)
+        };
+
+        self.through_volumes(ray, colour, distance)
     }
 
     /// Not the same as default(). This is only for testing.
@@ -45,49 +113,97 @@ impl World {
     }
 
     /// Intersections of rays and world objects rather than individual objects.
-    pub fn intersect(&self, ray: &Ray) -> Intersections {
-        let mut intersections = vec![];
-        for o in self.objects.iter() {
-            intersections.extend(o.intersect(ray).intrsc);
-        }
+    /// Volumes have no surface to hit, so are left out, as are objects hidden
+    /// from this kind of ray and hits beyond the ray's t_max.
+    pub fn intersect(&self, ray: &Ray) -> Intersections<'_> {
+        let mut xs = Intersections::default();
+        self.intersect_into(ray, &mut xs);
 
-        intersections.sort_by(|a, b| a.t.partial_cmp(&b.t).unwrap());
+        xs
+    }
 
-        Intersections { intrsc: intersections }
+    /// Same as intersect(), but replaces the contents of a reusable buffer.
+    pub fn intersect_into<'a>(&'a self, ray: &Ray, xs: &mut Intersections<'a>) {
+        count_rays(1);
+        xs.clear();
+        for o in self.objects.iter().filter(|o| !o.is_volume() && o.is_visible_to(ray.kind)) {
+            xs.add_hits(o, ray);
+        }
     }
 
     /// Determines if the point is occulted. Must be calculated for each light source.
-    pub fn is_shadowed(&self, light_pos: Vector4<f64>, point: Vector4<f64>) -> bool {
+    pub fn is_shadowed(&self, light_pos: Vector4<Float>, point: Vector4<Float>) -> bool {
+        self.is_shadowed_at(light_pos, point, 0.0)
+    }
+
+    /// Same as is_shadowed(), but with moving objects sampled at the given time.
+    pub fn is_shadowed_at(&self, light_pos: Vector4<Float>, point: Vector4<Float>, time: Float) -> bool {
+        self.is_shadowed_into(light_pos, point, time, &mut Intersections::default())
+    }
+
+    /// Same as is_shadowed_at(), using a reusable buffer for the intersections.
+    pub fn is_shadowed_into<'a>(&'a self, light_pos: Vector4<Float>, point: Vector4<Float>, time: Float, xs: &mut Intersections<'a>) -> bool {
         let v = light_pos - point;
         let distance = v.magnitude();
         let direction = v.normalize();
-        let ray = Ray::new(point, direction);
-        let intersections = self.intersect(&ray);
-        let h = intersections.hit();
-        if h != None && h.unwrap().t < distance && h.unwrap().object.umbra {
-            true
-        } else {
-            false
-        }
+        let ray = Ray::new(point, direction)
+            .with_time(time)
+            .with_t_max(distance)
+            .with_kind(RayKind::Shadow);
+
+        self.intersect_any_into(&ray, distance, xs)
+    }
+
+    /// Whether the ray hits any object it can see closer than max_distance.
+    /// Stops at the first one found, rather than finding and sorting every hit
+    /// like intersect(), as any hit at all is enough to block a shadow ray.
+    pub fn intersect_any(&self, ray: &Ray, max_distance: Float) -> bool {
+        self.intersect_any_into(ray, max_distance, &mut Intersections::default())
+    }
+
+    /// Same as intersect_any(), using a reusable buffer for each object's hits.
+    pub fn intersect_any_into<'a>(&'a self, ray: &Ray, max_distance: Float, xs: &mut Intersections<'a>) -> bool {
+        count_rays(1);
+        let max_distance = max_distance.min(ray.t_max);
+        self.objects.iter()
+            .filter(|o| !o.is_volume() && o.is_visible_to(ray.kind))
+            .any(|o| {
+                xs.clear();
+                xs.add_hits(o, ray);
+                xs.hits().any(|i| i.t < max_distance)
+            })
     }
 
     /// Calculates colour of hit. Support multiple lights right out of the box!
-    pub fn shade_hit(&self, comps: &PreCompData, remaining: u8) -> Colour {
-        let mut surface = Colour::black();
-        let mut reflected = Colour::black();
-        let mut refracted = Colour::black();
-        for i in 0..self.lights.len() {
-            surface += comps.object.material.lighting(
-                comps.object,
-                self.lights[i],
+    pub fn shade_hit(&self, comps: &PreCompData, remaining: impl Into<RayDepth>) -> Colour {
+        self.shade_hit_into(comps, remaining, &mut Intersections::default())
+    }
+
+    /// Same as shade_hit(), using a reusable buffer for the shadow, reflection
+    /// and refraction rays.
+    pub fn shade_hit_into<'a>(&'a self, comps: &PreCompData, remaining: impl Into<RayDepth>, xs: &mut Intersections<'a>) -> Colour {
+        let remaining = remaining.into();
+        let mut surface = self.ambient_at(comps) + self.caustic_at(comps);
+        let lights = self.lights_to_shade(comps);
+        let shadowed = self.shadows_into(&lights, comps, xs);
+        let colour = comps.pattern_colour();
+        for ((i, weight), shadowed) in lights.into_iter().zip(shadowed) {
+            let light = &self.lights[i];
+            let dimmed = PointLight::new(light.colour_at(comps.over_pos) * self.volume_transmittance(comps.over_pos, light.position, comps.time), light.position);
+            surface += comps.object.material.lighting_with(
+                colour,
+                &dimmed,
                 comps.over_pos,
                 comps.eye_vec,
                 comps.normal_vec,
-                self.is_shadowed(self.lights[i].position, comps.over_pos)
-            );
-            reflected += self.reflected_colour(comps, remaining);
-            refracted += self.refracted_colour(comps, remaining);
+                shadowed
+            ) * weight;
         }
+        // Reflections and refractions are weighted by the number of lights, as
+        // they always have been, but only traced once.
+        let lights = self.lights.len() as f32;
+        let reflected = self.reflected_colour_into(comps, remaining, xs) * lights;
+        let refracted = self.refracted_colour_into(comps, remaining, xs) * lights;
 
         if comps.object.material.reflectivity > 0.0 && comps.object.material.transparency > 0.0 {
             let reflectance = comps.schlick();
@@ -98,36 +214,240 @@ impl World {
         }
     }
 
+    // Whether each of the lights is shadowed at a hit, with the shadow rays
+    // traced in packets.
+    pub(crate) fn shadows_into<'a>(&'a self, lights: &[(usize, f32)], comps: &PreCompData, xs: &mut Intersections<'a>) -> Vec<bool> {
+        let mut shadowed = Vec::with_capacity(lights.len());
+        for chunk in lights.chunks(LANES) {
+            let rays: Vec<Ray> = chunk.iter()
+                .map(|(i, _)| {
+                    let v = self.lights[*i].position - comps.over_pos;
+                    Ray::new(comps.over_pos, v.normalize())
+                        .with_time(comps.time)
+                        .with_t_max(v.magnitude())
+                        .with_kind(RayKind::Shadow)
+                })
+                .collect();
+            let blocked = self.intersect_any_packet(&RayPacket::new(&rays), xs);
+            shadowed.extend_from_slice(&blocked[..chunk.len()]);
+        }
+
+        shadowed
+    }
+
+    /// How likely each light is to light up a hit, relative to the others: its
+    /// brightness, more so if it faces the surface. Used to pick which lights
+    /// are worth shading when there are too many to shade them all.
+    pub fn light_weights(&self, comps: &PreCompData) -> Vec<Float> {
+        self.lights.iter()
+            .map(|light| {
+                let facing = (light.position - comps.over_pos).normalize().dot(&comps.normal_vec);
+                let brightness = (light.colour.r + light.colour.g + light.colour.b) as Float;
+                // Lights behind the surface still add their ambient share.
+                brightness.max(0.0) * (facing.max(0.0) + 0.1)
+            })
+            .collect()
+    }
+
+    // Indices of the lights to shade at a hit, and the weight to give each.
+    // Sampled lights are weighted by one over their chance of being picked, so
+    // on average the result is the same as shading every light. The picks are
+    // seeded by the hit itself, so renders stay reproducible.
+    pub(crate) fn lights_to_shade(&self, comps: &PreCompData) -> Vec<(usize, f32)> {
+        let every_light = || (0..self.lights.len()).map(|i| (i, 1.0)).collect();
+        let samples = match self.light_samples {
+            Some(samples) if samples < self.lights.len() => samples,
+            _ => return every_light()
+        };
+        let weights = self.light_weights(comps);
+        let total: Float = weights.iter().sum();
+        if total <= 0.0 {
+            return every_light();
+        }
+        let mut rng = rng_at(comps.over_pos, comps.time, 0);
+
+        (0..samples)
+            .map(|_| {
+                let i = pick_light(&weights, total, rng.next_float());
+                (i, (total / (weights[i] * samples as Float)) as f32)
+            })
+            .collect()
+    }
+
+    /// Fill light from the world's ambient term at a hit. Like the material's
+    /// ambient, it ignores shadows.
+    pub fn ambient_at(&self, comps: &PreCompData) -> Colour {
+        if self.ambient_strength == 0.0 {
+            return Colour::black();
+        }
+        comps.pattern_colour() * self.ambient * self.ambient_strength
+    }
+
+    /// Light arriving along a ray from something at the given distance, after
+    /// passing through any volumes on the way: dimmed by each volume, plus the
+    /// light the volumes scatter towards the eye.
+    pub fn through_volumes(&self, ray: &Ray, colour: Colour, distance: Float) -> Colour {
+        let (scattered, transmittance) = self.volume_light(ray, distance);
+
+        colour * transmittance + scattered
+    }
+
+    /// Light the volumes along a ray scatter towards the eye before the given
+    /// distance, and the fraction of light from beyond them that gets through.
+    pub fn volume_light(&self, ray: &Ray, distance: Float) -> (Colour, Float) {
+        let mut spans = self.volume_spans(ray, distance);
+        spans.sort_by(|a, b| a.0.total_cmp(&b.0));
+        let mut transmittance = 1.0;
+        let mut scattered = Colour::black();
+        for (t0, t1, volume) in spans {
+            let (light, span_transmittance) = self.in_scattering(ray, t0, t1, &volume);
+            scattered += light * transmittance;
+            transmittance *= span_transmittance;
+        }
+
+        (scattered, transmittance)
+    }
+
+    /// Fraction of light from a light source that reaches a point through the
+    /// volumes in between. 1.0 in a world without volumes.
+    pub fn volume_transmittance(&self, point: Vector4<Float>, light_pos: Vector4<Float>, time: Float) -> Float {
+        let v = light_pos - point;
+        let ray = Ray::new(point, v.normalize()).with_time(time);
+        self.volume_spans(&ray, v.magnitude())
+            .iter()
+            .map(|(t0, t1, volume)| span_transmittance(&ray, *t0, *t1, volume))
+            .product()
+    }
+
+    // Stretches of the ray inside each volume, between 0 and max_distance, with
+    // the volume placed where it is at the time of the ray.
+    fn volume_spans(&self, ray: &Ray, max_distance: Float) -> Vec<(Float, Float, Cow<'_, Object>)> {
+        let mut spans = vec![];
+        // A medium that neither absorbs nor scatters has no effect on light.
+        let volumes = self.objects.iter().filter(|o| o.medium.map_or(false, |m| m.extinction() > 0.0));
+        for object in volumes {
+            let object = if object.is_animated() { Cow::Owned(object.at_time(ray.time)) } else { Cow::Borrowed(object) };
+            let reach = object.medium.unwrap().reach().min(MAX_VOLUME_DEPTH);
+            // A degenerate transform can give NaN crossings, which mark nothing.
+            let mut ts: Vec<Float> = object.intersect(ray).iter().map(|x| x.t).filter(|t| !t.is_nan()).collect();
+            ts.sort_by(|a, b| a.total_cmp(b));
+            // Each crossing of the boundary either enters or leaves the volume.
+            let mut entered = None;
+            let mut clip = |t0: Float, t1: Float| {
+                let (t0, t1) = (t0.max(0.0), t1.min(max_distance));
+                if t1 > t0 {
+                    spans.push((t0, t1.min(t0 + reach), object.clone()));
+                }
+            };
+            for t in ts {
+                if object.normal_at(ray.position(t)).dot(&ray.direction) < 0.0 {
+                    entered = Some(t);
+                } else {
+                    clip(entered.take().unwrap_or(Float::NEG_INFINITY), t);
+                }
+            }
+            if let Some(t0) = entered {
+                clip(t0, Float::INFINITY);
+            }
+        }
+
+        spans
+    }
+
+    // Light scattered towards the eye along part of a ray through a volume, and
+    // the fraction of light that makes it through. The lights are sampled at
+    // even steps along the ray, with the density looked up at each step.
+    fn in_scattering(&self, ray: &Ray, t0: Float, t1: Float, volume: &Object) -> (Colour, Float) {
+        let medium = volume.medium.unwrap();
+        let field = medium.field();
+        let step = (t1 - t0) / medium.steps as Float;
+        let mut colour = Colour::black();
+        let mut transmittance = 1.0;
+        for i in 0..medium.steps {
+            let point = ray.position(t0 + (i as Float + 0.5) * step);
+            let density = field.density_at(volume.world_to_object(point));
+            let loss = (-density * (medium.absorption + medium.scattering) * step).exp();
+            if density > 0.0 {
+                let mut light = Colour::black();
+                for l in &self.lights {
+                    if !self.is_shadowed_at(l.position, point, ray.time) {
+                        light += l.colour_at(point) * self.volume_transmittance(point, l.position, ray.time);
+                    }
+                }
+                colour += light * (transmittance * loss.sqrt() * density * medium.scattering * step);
+            }
+            transmittance *= loss;
+        }
+
+        (colour * medium.colour, transmittance)
+    }
+
+    /// Light focused onto a hit by glass and mirrors, from the photon map.
+    /// Black if caustics are off.
+    pub fn caustic_at(&self, comps: &PreCompData) -> Colour {
+        let material = comps.object.material;
+        match &self.caustics {
+            Some(map) if material.diffuse > 0.0 => {
+                comps.pattern_colour() * map.irradiance_at(comps.pos, comps.normal_vec) * material.diffuse
+            },
+            _ => Colour::black()
+        }
+    }
+
     /// Calculates colour of reflected light ray.
-    pub fn reflected_colour(&self, comps: &PreCompData, remaining: u8) -> Colour {
-        if remaining <= 0 || comps.object.material.reflectivity == 0.0 {
-            Colour::black()
-        } else {
-            let ray = Ray::new(comps.over_pos, comps.reflect_vec);
-            self.colour_at(&ray, remaining - 1) * comps.object.material.reflectivity
+    pub fn reflected_colour(&self, comps: &PreCompData, remaining: impl Into<RayDepth>) -> Colour {
+        self.reflected_colour_into(comps, remaining, &mut Intersections::default())
+    }
+
+    fn reflected_colour_into<'a>(&'a self, comps: &PreCompData, remaining: impl Into<RayDepth>, xs: &mut Intersections<'a>) -> Colour {
+        let reflectivity = comps.object.material.reflectivity;
+        match remaining.into().bounce(Bounce::Reflection) {
+            Some(next) if reflectivity != 0.0 => match roulette(&next, reflectivity, comps, 1) {
+                Some(survival) => {
+                    let ray = Ray::new(comps.over_pos, comps.reflect_vec)
+                        .with_time(comps.time)
+                        .with_kind(RayKind::Reflection)
+                        .with_cone(comps.footprint, comps.spread);
+                    self.colour_at_into(&ray, next, None, xs) * (reflectivity / survival)
+                },
+                None => Colour::black()
+            },
+            _ => Colour::black()
         }
     }
 
     /// Calculates colour of refracted light ray.
-    pub fn refracted_colour(&self, comps: &PreCompData, remaining: u8) -> Colour {
-        if remaining <= 0 || comps.object.material.transparency == 0.0 {
-            Colour::black()
-        } else {
-            let n_ratio = (comps.n1 / comps.n2) as f64;
-            let cos_i = comps.normal_vec.dot(&comps.eye_vec);
-            let sin2_t = n_ratio.powi(2) * (1.0 - cos_i.powi(2));
-            if sin2_t > 1.0 {
-                Colour::black()
-            } else {
-                let cos_t = (1.0 - sin2_t).sqrt();
-                let direction = comps.normal_vec * (n_ratio * cos_i - cos_t) - comps.eye_vec * n_ratio;
-                let refracted_ray = Ray::new(comps.under_pos, direction);
-                
-                self.colour_at(&refracted_ray, remaining - 1) * comps.object.material.transparency
-            }
+    pub fn refracted_colour(&self, comps: &PreCompData, remaining: impl Into<RayDepth>) -> Colour {
+        self.refracted_colour_into(comps, remaining, &mut Intersections::default())
+    }
+
+    fn refracted_colour_into<'a>(&'a self, comps: &PreCompData, remaining: impl Into<RayDepth>, xs: &mut Intersections<'a>) -> Colour {
+        let transparency = comps.object.material.transparency;
+        let next = match remaining.into().bounce(Bounce::Refraction) {
+            Some(next) if transparency != 0.0 => next,
+            _ => return Colour::black()
+        };
+        match (comps.refract_vec(), roulette(&next, transparency, comps, 2)) {
+            (Some(direction), Some(survival)) => {
+                let refracted_ray = Ray::new(comps.under_pos, direction)
+                    .with_time(comps.time)
+                    .with_kind(RayKind::Refraction)
+                    .with_cone(comps.footprint, comps.spread);
+
+                self.colour_at_into(&refracted_ray, next, None, xs) * (transparency / survival)
+            },
+            _ => Colour::black()
         }
     }
 
+    /// ID of an object from the world, as used in object ID passes and masks:
+    /// its own ID if it has one, otherwise its position in the world. Objects
+    /// are found by address, not value, so identical copies keep apart. None
+    /// for objects that aren't in the world, copies of them included.
+    pub fn object_id(&self, object: &Object) -> Option<u32> {
+        object.id.or_else(|| self.objects.iter().position(|o| std::ptr::eq(o, object)).map(|i| i as u32))
+    }
+
     /// Applies a light to the world.
     pub fn with_light(mut self, light: PointLight) -> Self {
         self.lights.push(light);
@@ -137,17 +457,348 @@ impl World {
 
     /// Adds an object to the world.
     pub fn with_object(mut self, object: Object) -> Self {
-        self.objects.push(object);
+        self.add(object);
+
+        self
+    }
+
+    /// Adds every light given, in order.
+    pub fn with_lights<I: IntoIterator<Item = PointLight>>(mut self, lights: I) -> Self {
+        self.lights.extend(lights);
+
+        self
+    }
+
+    /// Adds every object given, in order, so generated scenes don't need a
+    /// fold over with_object().
+    pub fn with_objects<I: IntoIterator<Item = Object>>(mut self, objects: I) -> Self {
+        for object in objects {
+            self.add(object);
+        }
 
         self
     }
 
+    /// Merges another world's objects, lights and shared materials into this
+    /// one. Objects keep sharing the materials they shared before. Everything
+    /// else, like the background and recursion limit, stays as this world's,
+    /// and any caustics aren't traced again.
+    pub fn extend_from(&mut self, other: World) {
+        let offset = self.palette.len();
+        self.palette.extend(other.palette);
+        self.lights.extend(other.lights);
+        for mut object in other.objects {
+            object.material_id = object.material_id.map(|id| MaterialId(id.0 + offset));
+            self.add(object);
+        }
+    }
+
+    /// Adds an object to the world, returning a handle to find it by later.
+    pub fn add(&mut self, object: Object) -> ObjectId {
+        self.objects.push(object);
+        self.sync_handles();
+
+        self.handles[self.handles.len() - 1]
+    }
+
+    /// Handle of the object at an index in objects.
+    pub fn object_handle(&self, index: usize) -> Option<ObjectId> {
+        match (self.handles.get(index), index < self.objects.len()) {
+            (_, false) => None,
+            (Some(handle), true) => Some(*handle),
+            (None, true) => Some(ObjectId(self.next_handle + (index - self.handles.len()) as u64))
+        }
+    }
+
+    pub fn get(&self, id: ObjectId) -> Option<&Object> {
+        self.index_of(id).map(|i| &self.objects[i])
+    }
+
+    /// Object to change in place. Change its transform and material with its
+    /// builders, which keep its cached transforms up to date.
+    pub fn get_mut(&mut self, id: ObjectId) -> Option<&mut Object> {
+        self.index_of(id).map(|i| &mut self.objects[i])
+    }
+
+    /// Takes an object out of the world. Objects after it move down in
+    /// objects, but their handles stay the same. Remove objects this way
+    /// rather than from objects directly, which mixes up the handles.
+    pub fn remove(&mut self, id: ObjectId) -> Option<Object> {
+        let i = self.index_of(id)?;
+        self.sync_handles();
+        self.handles.remove(i);
+
+        Some(self.objects.remove(i))
+    }
+
+    /// Handle of the first object with the given name.
+    pub fn find(&self, name: &str) -> Option<ObjectId> {
+        self.objects.iter()
+            .position(|o| o.name.as_deref() == Some(name))
+            .and_then(|i| self.object_handle(i))
+    }
+
+    /// Index of the first light with the given name.
+    pub fn find_light(&self, name: &str) -> Option<usize> {
+        self.lights.iter().position(|l| l.name.as_deref() == Some(name))
+    }
+
+    /// Adds a material to the palette, to be shared by any number of objects
+    /// with use_material() and changed for all of them with set_material().
+    pub fn add_material(&mut self, material: Material) -> MaterialId {
+        self.palette.push((None, material));
+
+        MaterialId(self.palette.len() - 1)
+    }
+
+    /// Same as add_material(), with a name to find it by with find_material().
+    pub fn add_named_material(&mut self, name: &str, material: Material) -> MaterialId {
+        self.palette.push((Some(name.to_string()), material));
+
+        MaterialId(self.palette.len() - 1)
+    }
+
+    pub fn material(&self, id: MaterialId) -> Option<&Material> {
+        self.palette.get(id.0).map(|(_, m)| m)
+    }
+
+    /// First material in the palette with the given name.
+    pub fn find_material(&self, name: &str) -> Option<MaterialId> {
+        self.palette.iter().position(|(n, _)| n.as_deref() == Some(name)).map(MaterialId)
+    }
+
+    /// Every material in the palette, with its name if it has one.
+    pub fn palette(&self) -> impl Iterator<Item = (MaterialId, Option<&str>, &Material)> {
+        self.palette.iter().enumerate().map(|(i, (name, m))| (MaterialId(i), name.as_deref(), m))
+    }
+
+    /// Gives an object a material from the palette, which it keeps in step
+    /// with until it's given a material of its own. False if either the
+    /// object or the material isn't in the world.
+    pub fn use_material(&mut self, object: ObjectId, material: MaterialId) -> bool {
+        let (i, m) = match (self.index_of(object), self.material(material)) {
+            (Some(i), Some(m)) => (i, *m),
+            _ => return false
+        };
+        self.objects[i].with_material(m);
+        self.objects[i].material_id = Some(material);
+
+        true
+    }
+
+    /// Changes a material in the palette, and every object using it. False if
+    /// the material isn't in the world.
+    pub fn set_material(&mut self, id: MaterialId, material: Material) -> bool {
+        match self.palette.get_mut(id.0) {
+            Some(entry) => entry.1 = material,
+            None => return false
+        }
+        for object in self.objects.iter_mut().filter(|o| o.material_id == Some(id)) {
+            object.with_material(material);
+            object.material_id = Some(id);
+        }
+
+        true
+    }
+
+    /// Mixes a render seed into the noise behind pattern jitter and volume
+    /// density, for objects and the palette alike, so that with the sampler
+    /// it fixes every random choice in a render. Seed 0 leaves them as they
+    /// are. Called by the camera with RenderSettings::seed.
+    pub fn reseed(&mut self, seed: u64) {
+        if seed == 0 {
+            return;
+        }
+        let materials = self.objects.iter_mut().map(|o| &mut o.material).chain(self.palette.iter_mut().map(|(_, m)| m));
+        for material in materials {
+            material.pattern.reseed(seed);
+        }
+        for noise in self.objects.iter_mut().filter_map(|o| o.medium.as_mut()?.noise.as_mut()) {
+            noise.seed ^= Rng::fold_seed(seed);
+        }
+    }
+
+    /// Places a copy of another world's objects and lights in this one, moved
+    /// as a group by transform, so an asset like a lamp can be built once and
+    /// put in many scenes, or many times in one. Shared materials come along
+    /// too, as for extend_from(). Returns the handles of the new objects.
+    ///
+    /// Panics if the transform can't be inverted.
+    pub fn include(&mut self, prefab: &World, transform: impl Into<Matrix4<Float>>) -> Vec<ObjectId> {
+        let transform = transform.into();
+        let mut placed = prefab.clone();
+        for light in &mut placed.lights {
+            light.position = transform * light.position;
+        }
+        for object in &mut placed.objects {
+            object.with_transform(transform * object.transform);
+            let mut keyframes = Keyframes::new();
+            for (time, m) in object.keyframes.keys() {
+                keyframes.insert(*time, transform * m);
+            }
+            object.keyframes = keyframes;
+        }
+        let first = self.objects.len();
+        self.extend_from(placed);
+
+        (first..self.objects.len()).filter_map(|i| self.object_handle(i)).collect()
+    }
+
+    fn index_of(&self, id: ObjectId) -> Option<usize> {
+        match self.handles.iter().position(|h| *h == id) {
+            Some(i) => Some(i),
+            None if id.0 >= self.next_handle => Some(self.handles.len() + (id.0 - self.next_handle) as usize),
+            None => None
+        }.filter(|i| *i < self.objects.len())
+    }
+
+    // Gives every object a handle of its own.
+    fn sync_handles(&mut self) {
+        self.handles.truncate(self.objects.len());
+        while self.handles.len() < self.objects.len() {
+            self.handles.push(ObjectId(self.next_handle));
+            self.next_handle += 1;
+        }
+    }
+
     /// Adjust recursion limit from default (5).
     pub fn with_recursions(mut self, rcrs_lim: u8) -> Self {
         self.rcrs_lim = rcrs_lim;
 
         self
     }
+
+    /// Sets what rays see when they miss everything. Defaults to black.
+    pub fn with_background(mut self, background: Background) -> Self {
+        self.background = background;
+
+        self
+    }
+
+    /// Sets a global fill light, so the overall ambient level of a scene can be
+    /// adjusted in one place rather than on every material.
+    pub fn with_ambient(mut self, colour: Colour, strength: f32) -> Self {
+        self.ambient = colour;
+        self.ambient_strength = strength;
+
+        self
+    }
+
+    /// Traces caustics, so glass and mirrors focus light onto the surfaces
+    /// around them. Photons are fired from every light at each reflective or
+    /// transparent sphere, and gathered within radius of each shaded point:
+    /// more photons allow a smaller radius and sharper caustics. Call this once
+    /// the world is complete, as the photons are traced straight away.
+    pub fn with_caustics(mut self, photons_per_light: usize, radius: Float) -> Self {
+        self.caustics = Some(Arc::new(PhotonMap::build(&self, photons_per_light, radius)));
+
+        self
+    }
+
+    /// Shades only this many lights at each hit, picked at random with the
+    /// brightest and best placed lights favoured, rather than every light. The
+    /// result is noisier but has the same average, so scenes with dozens of
+    /// lights stay quick to render once a few samples per pixel are taken.
+    pub fn with_light_samples(mut self, samples: usize) -> Self {
+        self.light_samples = Some(samples.max(1));
+
+        self
+    }
+
+    /// Sets how far off a surface rays leaving it start. Defaults to EPSILON.
+    pub fn with_epsilon(mut self, epsilon: Float) -> Self {
+        self.epsilon = epsilon;
+
+        self
+    }
+
+    /// Sets the extra offset for rays leaving surfaces seen side on.
+    pub fn with_slope_bias(mut self, slope_bias: Float) -> Self {
+        self.slope_bias = slope_bias;
+
+        self
+    }
+
+    /// Same as Intersections::prepare_computations(), but with rays leaving the
+    /// hit offset by the world's epsilon and slope bias.
+    pub fn prepare_computations<'a>(&self, xs: &Intersections<'a>, index: usize, ray: &Ray) -> PreCompData<'a> {
+        xs.prepare_computations_biased(index, ray, self.epsilon, self.slope_bias)
+    }
+
+    /// Saves the world to a scene file, so a generated scene can be rendered
+    /// again later with different settings.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), SceneError> {
+        fs::write(path, world_to_yaml(self)?.to_string())?;
+
+        Ok(())
+    }
+
+    /// Loads a world saved with save(). Any camera in the file is ignored; use
+    /// Scene::load() for that.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<World, SceneError> {
+        load_world(path.as_ref())
+    }
+
+    /// Snapshot of the world at a moment in time, with every animated object
+    /// frozen where it is at that time.
+    pub fn at_time(&self, time: Float) -> Self {
+        World {
+            objects: self.objects.iter().map(|o| o.at_time(time)).collect(),
+            lights: self.lights.clone(),
+            rcrs_lim: self.rcrs_lim,
+            background: self.background.clone(),
+            ambient: self.ambient,
+            ambient_strength: self.ambient_strength,
+            caustics: self.caustics.clone(),
+            light_samples: self.light_samples,
+            epsilon: self.epsilon,
+            slope_bias: self.slope_bias,
+            handles: self.handles.clone(),
+            next_handle: self.next_handle,
+            palette: self.palette.clone()
+        }
+    }
+}
+
+// Russian roulette for a secondary ray carrying the given weight. The chance it
+// survived, to scale it up by, or None if it was cut short.
+pub(crate) fn roulette(next: &RayDepth, weight: f32, comps: &PreCompData, seed: u64) -> Option<f32> {
+    let survival = next.survival(weight);
+    if survival < 1.0 && rng_at(comps.over_pos, comps.time, seed).next_float() >= survival as Float {
+        return None;
+    }
+
+    Some(survival)
+}
+
+/// Index of the light picked by a uniform random number u in [0.0, 1.0), with
+/// each light's chance of being picked in proportion to its weight.
+pub(crate) fn pick_light(weights: &[Float], total: Float, u: Float) -> usize {
+    let mut target = u * total;
+    for (i, weight) in weights.iter().enumerate() {
+        if target < *weight {
+            return i;
+        }
+        target -= weight;
+    }
+
+    // Rounding can leave u * total just past the end.
+    weights.iter().rposition(|w| *w > 0.0).unwrap_or(0)
+}
+
+// Fraction of light that makes it along part of a ray through a volume.
+fn span_transmittance(ray: &Ray, t0: Float, t1: Float, volume: &Object) -> Float {
+    let medium = volume.medium.unwrap();
+    if medium.is_uniform() {
+        return medium.transmittance(t1 - t0);
+    }
+    let field = medium.field();
+    let step = (t1 - t0) / medium.steps as Float;
+    let depth: Float = (0..medium.steps)
+        .map(|i| field.density_at(volume.world_to_object(ray.position(t0 + (i as Float + 0.5) * step))))
+        .sum();
+
+    (-depth * (medium.absorption + medium.scattering) * step).exp()
 }
 
 impl Default for World {
@@ -155,7 +806,17 @@ impl Default for World {
         World {
             objects: vec![],
             lights: vec![],
-            rcrs_lim: 5
+            rcrs_lim: 5,
+            background: Background::default(),
+            ambient: Colour::white(),
+            ambient_strength: 0.0,
+            caustics: None,
+            light_samples: None,
+            epsilon: EPSILON,
+            slope_bias: 0.0,
+            handles: vec![],
+            next_handle: 0,
+            palette: vec![]
         }
     }
 }
@@ -163,8 +824,11 @@ impl Default for World {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::core::{vector, Intersection};
-    use crate::materials::Pattern;
+    use crate::to_f32;
+    use crate::core::{vector, Intersection, TransformBuilder};
+    use crate::core::RayKind;
+    use crate::materials::{DensityNoise, Jitter, Medium, Pattern};
+    use assert_approx_eq::assert_approx_eq;
 
     #[test]
     fn creating_a_world() {
@@ -174,6 +838,173 @@ mod tests {
         assert_eq!(w.lights.len(), 0);
     }
 
+    #[test]
+    fn saving_and_loading_a_world() {
+        let floor = Object::new_plane()
+            .with_material(Material::default()
+                .with_reflectivity(0.3)
+                .with_pattern(Pattern::new_checkers(Colour::white(), Colour::new(0.1, 0.2, 0.3))
+                    .with_transform(Matrix4::uscale(0.25))))
+            .cast_no_shadow()
+            .with_name("floor");
+        let ball = Object::glass_orb()
+            .with_transform(TransformBuilder::new().rot_y(0.3).translate(0.5, 1.0, -0.25))
+            .with_motion(Matrix4::translate(1.0, 1.0, 0.0))
+            .use_manifold();
+        let gradient = Object::new_sphere()
+            .with_material(Material::default()
+                .with_pattern(Pattern::new_gradient(Colour::red(), Colour::blue()).with_jitter(Jitter::new(7, 0.5))))
+            .hide_from(RayKind::Camera)
+            .hide_from(RayKind::Diffuse);
+        let w = World::default_world()
+            .with_object(floor)
+            .with_object(ball)
+            .with_object(gradient)
+            .with_recursions(3)
+            .with_background(Background::gradient(Colour::white(), Colour::new(0.2, 0.4, 0.9)))
+            .with_ambient(Colour::new(1.0, 0.9, 0.8), 0.15)
+            .with_object(Object::new_sphere().with_medium(Medium::new(0.3).with_absorption(0.2).with_steps(8)))
+            .with_object(Object::new_sphere().with_medium(Medium::new(1.0)
+                .with_noise(DensityNoise::new(4).with_frequency(2.5).with_octaves(3).with_threshold(0.25))))
+            .with_light(PointLight::new(Colour::new(0.5, 0.5, 0.6), point(5.0, 2.0, 5.0)).with_name("rim"))
+            .with_light_samples(2)
+            .with_epsilon(2.0e-5)
+            .with_caustics(200, 0.3);
+        let path = std::env::temp_dir().join("feoray_saved_world.yaml");
+        w.save(&path).unwrap();
+        let loaded = World::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded, w);
+    }
+
+    #[test]
+    fn shared_materials_are_changed_in_one_place() {
+        let mut w = World::default();
+        let tile = w.add_named_material("tile", Material::default().with_colour(Colour::white()));
+        let tiles: Vec<ObjectId> = (0..3)
+            .map(|i| w.add(Object::new_plane().with_transform(Matrix4::translate(i as Float, 0.0, 0.0))))
+            .collect();
+        let odd = w.add(Object::new_sphere());
+        for t in &tiles {
+            assert!(w.use_material(*t, tile));
+        }
+        w.get_mut(tiles[2]).unwrap().with_material(Material::default());
+        let red = w.material(tile).unwrap().with_colour(Colour::red());
+
+        assert!(w.set_material(tile, red));
+        assert_eq!(w.find_material("tile"), Some(tile));
+        assert_eq!(w.palette().count(), 1);
+        assert_eq!(w.get(tiles[0]).unwrap().material, red);
+        assert_eq!(w.get(tiles[1]).unwrap().material_id, Some(tile));
+        // Objects given a material of their own no longer follow the palette.
+        assert_eq!(w.get(tiles[2]).unwrap().material, Material::default());
+        assert_eq!(w.get(odd).unwrap().material, Material::default());
+        assert!(!w.use_material(odd, MaterialId(5)));
+    }
+
+    #[test]
+    fn objects_and_lights_can_be_added_in_bulk() {
+        let row = (0..4).map(|i| Object::new_sphere().with_transform(Matrix4::translate(i as Float, 0.0, 0.0)));
+        let lights = vec![
+            PointLight::new(Colour::white(), point(0.0, 10.0, 0.0)),
+            PointLight::new(Colour::red(), point(0.0, 10.0, 5.0))
+        ];
+        let w = World::default().with_objects(row).with_lights(lights.clone());
+
+        assert_eq!(w.objects.len(), 4);
+        assert_eq!(w.lights, lights);
+        assert_eq!(w.object_handle(3).and_then(|id| w.get(id)), Some(&w.objects[3]));
+    }
+
+    #[test]
+    fn merged_worlds_keep_their_shared_materials() {
+        let mut w = World::default_world();
+        w.add_material(Material::default());
+        let mut other = World::default();
+        let glass = other.add_named_material("glass", Material::null().with_transparency(1.0));
+        let orb = other.add(Object::glass_orb());
+        other.use_material(orb, glass);
+        other = other.with_light(PointLight::new(Colour::white(), point(5.0, 5.0, 5.0)));
+        w.extend_from(other);
+        let glass = w.find_material("glass").unwrap();
+
+        assert_eq!(w.objects.len(), 3);
+        assert_eq!(w.lights.len(), 2);
+        assert_eq!(w.objects[2].material_id, Some(glass));
+        assert!(w.set_material(glass, Material::default().with_transparency(0.5)));
+        assert_eq!(w.objects[2].material.transparency, 0.5);
+    }
+
+    #[test]
+    fn prefabs_are_placed_as_a_group() {
+        let lamp = World::default()
+            .with_light(PointLight::new(Colour::white(), point(0.0, 2.0, 0.0)))
+            .with_object(Object::new_sphere().with_transform(Matrix4::translate(0.0, 1.0, 0.0)).with_name("shade"))
+            .with_object(Object::new_sphere().with_motion(Matrix4::translate(0.0, 1.0, 0.0)));
+        let mut w = World::default();
+        let first = w.include(&lamp, Matrix4::translate(5.0, 0.0, 0.0));
+        let second = w.include(&lamp, Matrix4::translate(-5.0, 0.0, 0.0));
+        let shade = w.get(second[0]).unwrap();
+
+        assert_eq!(w.objects.len(), 4);
+        assert_eq!(w.lights[1].position, point(-5.0, 2.0, 0.0));
+        assert_eq!(shade.transform * point(0.0, 0.0, 0.0), point(-5.0, 1.0, 0.0));
+        assert_eq!(shade.name.as_deref(), Some("shade"));
+        assert_eq!(w.get(first[1]).unwrap().transform_at(1.0) * point(0.0, 0.0, 0.0), point(5.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn handles_keep_pointing_at_their_objects() {
+        let mut w = World::default_world();
+        let first = w.object_handle(0).unwrap();
+        let ball = w.add(Object::new_sphere().with_transform(Matrix4::translate(0.0, 3.0, 0.0)).with_name("ball"));
+        let floor = w.add(Object::new_plane().with_name("floor"));
+
+        assert_eq!(w.find("floor"), Some(floor));
+        assert_eq!(w.find("wall"), None);
+        w.lights[0].name = Some(String::from("key"));
+        assert_eq!(w.find_light("key"), Some(0));
+        assert_eq!(w.find_light("fill"), None);
+        assert_eq!(w.remove(first), Some(World::default_world().objects[0].clone()));
+        assert_eq!(w.get(first), None);
+        assert_eq!(w.get(ball).unwrap().name.as_deref(), Some("ball"));
+        assert_eq!(w.object_handle(2), Some(floor));
+        w.get_mut(floor).unwrap().with_transform(Matrix4::translate(0.0, -1.0, 0.0));
+        assert_eq!(w.objects[2].transform, Matrix4::translate(0.0, -1.0, 0.0));
+        assert_eq!(w.object_handle(3), None);
+    }
+
+    #[test]
+    fn objects_put_straight_into_the_world_have_handles() {
+        let mut w = World::new(vec![Object::new_sphere(), Object::new_plane()], vec![], 5);
+        let plane = w.object_handle(1).unwrap();
+        w.objects.push(Object::new_sphere().with_name("late"));
+        let late = w.find("late").unwrap();
+        let added = w.add(Object::new_sphere());
+
+        assert_eq!(w.get(plane), Some(&Object::new_plane()));
+        assert_eq!(w.object_handle(2), Some(late));
+        assert!(w.remove(plane).is_some());
+        assert_eq!(w.get(late).unwrap().name.as_deref(), Some("late"));
+        assert_eq!(w.get(added), Some(&Object::new_sphere()));
+    }
+
+    #[test]
+    fn world_at_a_time_freezes_animated_objects() {
+        let ball = Object::new_sphere()
+            .with_transform_at(0.0, Matrix4::identity())
+            .with_transform_at(2.0, Matrix4::translate(0.0, 2.0, 0.0));
+        let w = World::default()
+            .with_object(ball)
+            .with_object(Object::new_plane())
+            .at_time(1.0);
+
+        assert!(w.objects.iter().all(|o| !o.is_animated()));
+        assert_eq!(w.objects[0].transform, Matrix4::translate(0.0, 1.0, 0.0));
+        assert_eq!(w.objects[1].transform, Matrix4::identity());
+    }
+
     #[test]
     fn the_default_world() {
         let w = World::default_world();
@@ -204,17 +1035,49 @@ mod tests {
         assert_eq!(xs[3].t, 6.0);
     }
 
+    #[test]
+    fn hits_from_many_objects_are_merged_in_order() {
+        let mut w = World::default();
+        for i in [3.0, -1.0, 7.0, 0.5, 5.0, 2.0] {
+            w = w.with_object(Object::new_sphere().with_transform(Matrix4::translate(0.0, 0.0, i)));
+        }
+        let r = Ray::new(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+        let ts: Vec<Float> = w.intersect(&r).iter().map(|i| i.t).collect();
+        let mut sorted = ts.clone();
+        sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+        assert_eq!(ts.len(), 12);
+        assert_eq!(ts, sorted);
+        assert_eq!(w.intersect(&r.with_t_max(5.0)).len(), 3);
+    }
+
+    #[test]
+    fn intersection_buffers_are_reused_between_rays() {
+        let w = World::default_world();
+        let hit = Ray::new(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+        let miss = Ray::new(point(0.0, 5.0, -5.0), vector(0.0, 0.0, 1.0));
+        let mut xs = Intersections::default();
+        w.intersect_into(&hit, &mut xs);
+        let capacity = xs.intrsc.capacity();
+
+        assert_eq!(xs.len(), 4);
+        w.intersect_into(&miss, &mut xs);
+        assert!(xs.is_empty());
+        assert_eq!(xs.intrsc.capacity(), capacity);
+        assert_eq!(w.colour_at_into(&hit, 1, None, &mut xs), w.colour_at(&hit, 1));
+    }
+
     #[test]
     fn shading_intersection() {
         let w = World::default_world();
         let r = Ray::new(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
-        let s = w.objects[0];
-        let int = Intersection::new(4.0, s);
+        let s = w.objects[0].clone();
+        let int = Intersection::new(4.0, &s);
         let ints = Intersections::new(vec![int]);
         let comps = ints.prepare_computations(0, &r);
         let clr = w.shade_hit(&comps, 1);
 
-        assert_eq!(clr.to_5dp(), Colour::new(0.38066, 0.47583, 0.2855));
+        assert!(clr.approx_eq(&Colour::new(0.38066, 0.47583, 0.2855)));
     }
 
     #[test]
@@ -222,13 +1085,13 @@ mod tests {
         let mut w = World::default_world();
         w.lights[0] = PointLight::new(Colour::new(1.0, 1.0, 1.0), point(0.0, 0.25, 0.0));
         let r = Ray::new(point(0.0, 0.0, 0.0), vector(0.0, 0.0, 1.0));
-        let s = w.objects[1];
-        let int = Intersection::new(0.5, s);
+        let s = w.objects[1].clone();
+        let int = Intersection::new(0.5, &s);
         let ints = Intersections::new(vec![int]);
         let comps = ints.prepare_computations(0, &r);
         let clr = w.shade_hit(&comps, 1);
 
-        assert_eq!(clr.to_5dp(), Colour::new(0.90498, 0.90498, 0.90498));
+        assert!(clr.approx_eq(&Colour::new(0.90498, 0.90498, 0.90498)));
     }
 
     #[test]
@@ -240,26 +1103,279 @@ mod tests {
         assert_eq!(clr, Colour::black());
     }
 
+    #[test]
+    fn missed_rays_see_the_background() {
+        let w = World::default_world()
+            .with_background(Background::gradient(Colour::white(), Colour::blue()));
+        let up = Ray::new(point(0.0, 0.0, -5.0), vector(0.0, 1.0, 0.0));
+        let level = Ray::new(point(0.0, 0.0, -5.0), vector(1.0, 0.0, 0.0));
+
+        assert_eq!(w.colour_at(&up, 1), Colour::blue());
+        assert_eq!(w.colour_at(&level, 1), Colour::white());
+    }
+
+    #[test]
+    fn world_ambient_supplements_material_ambient() {
+        let m = Material::default().with_colour(Colour::new(0.5, 1.0, 1.0));
+        let ball = Object::new_sphere().with_material(m);
+        let w = World::default()
+            .with_object(ball.clone())
+            .with_ambient(Colour::new(1.0, 0.0, 1.0), 0.5);
+        let r = Ray::new(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+        let xs = w.intersect(&r);
+        let comps = xs.prepare_computations(0, &r);
+
+        assert_eq!(w.ambient_at(&comps), Colour::new(0.25, 0.0, 0.5));
+        assert_eq!(w.colour_at(&r, 1), Colour::new(0.25, 0.0, 0.5));
+        assert_eq!(World::default().with_object(ball).colour_at(&r, 1), Colour::black());
+    }
+
+    #[test]
+    fn volumes_are_not_surfaces() {
+        let fog = Object::new_sphere().with_medium(Medium::new(1.0));
+        let w = World::default().with_object(fog);
+        let r = Ray::new(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+
+        assert!(w.intersect(&r).is_empty());
+    }
+
+    #[test]
+    fn volumes_absorb_light_passing_through() {
+        let smoke = Object::new_sphere()
+            .with_medium(Medium::new(0.5).with_absorption(1.0).with_scattering(0.0));
+        let w = World::default()
+            .with_object(smoke)
+            .with_background(Background::Solid(Colour::white()));
+        let through = Ray::new(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+        let inside = Ray::new(point(0.0, 0.0, 0.0), vector(0.0, 0.0, 1.0));
+        let past = Ray::new(point(0.0, 2.0, -5.0), vector(0.0, 0.0, 1.0));
+        let expected = (-0.5f32).exp();
+
+        assert_approx_eq!(w.colour_at(&through, 1).r, expected * expected, 1e-5);
+        assert_approx_eq!(w.colour_at(&inside, 1).r, expected, 1e-5);
+        assert_eq!(w.colour_at(&past, 1), Colour::white());
+    }
+
+    #[test]
+    fn lit_volumes_scatter_light_towards_the_eye() {
+        let fog = Object::new_sphere()
+            .with_medium(Medium::new(0.5).with_colour(Colour::red()));
+        let w = World::default()
+            .with_light(PointLight::new(Colour::white(), point(0.0, 10.0, 0.0)))
+            .with_object(fog);
+        let r = Ray::new(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+        let c = w.colour_at(&r, 1);
+
+        assert!(c.r > 0.1 && c.r < 1.0);
+        assert_eq!((c.g, c.b), (0.0, 0.0));
+    }
+
+    #[test]
+    fn light_through_a_volume_is_dimmed() {
+        let fog = Object::new_sphere()
+            .with_medium(Medium::new(1.0).with_absorption(1.0).with_scattering(0.0));
+        let w = World::default().with_object(fog);
+
+        assert_approx_eq!(w.volume_transmittance(point(0.0, -5.0, 0.0), point(0.0, 5.0, 0.0), 0.0), Float::exp(-2.0));
+        assert_eq!(w.volume_transmittance(point(3.0, -5.0, 0.0), point(3.0, 5.0, 0.0), 0.0), 1.0);
+    }
+
+    #[test]
+    fn noisy_volumes_are_patchy() {
+        let cloud = Object::new_sphere()
+            .with_transform(Matrix4::uscale(3.0))
+            .with_medium(Medium::new(0.5).with_absorption(1.0).with_noise(DensityNoise::new(1).with_frequency(3.0)));
+        let w = World::default()
+            .with_object(cloud)
+            .with_background(Background::Solid(Colour::white()));
+        let seen: Vec<f32> = (0..8)
+            .map(|i| w.colour_at(&Ray::new(point(i as Float * 0.2, 0.0, -5.0), vector(0.0, 0.0, 1.0)), 1).r)
+            .collect();
+        let uniform = to_f32(Medium::new(0.5).with_absorption(1.0).transmittance(6.0));
+        let (lightest, darkest) = (seen.iter().cloned().fold(0.0, f32::max), seen.iter().cloned().fold(1.0, f32::min));
+
+        assert!(seen.iter().all(|c| *c > uniform && *c < 1.0));
+        assert!(lightest > darkest * 1.1);
+    }
+
+    #[test]
+    fn objects_cast_shadows_into_volumes() {
+        let fog = Object::new_sphere()
+            .with_medium(Medium::new(0.5));
+        let blocker = Object::new_plane()
+            .with_transform(Matrix4::translate(0.0, 2.0, 0.0));
+        let light = PointLight::new(Colour::white(), point(0.0, 10.0, 0.0));
+        let lit = World::default().with_light(light).with_object(fog);
+        let shaded = lit.clone().with_object(blocker);
+        let r = Ray::new(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+
+        assert!(lit.colour_at(&r, 1).r > 0.0);
+        assert_eq!(shaded.colour_at(&r, 1), Colour::black());
+    }
+
+    #[test]
+    fn glass_brightens_the_floor_beneath_it() {
+        let floor = Object::new_plane()
+            .with_material(Material::default().with_specular(0.0));
+        let orb = Object::glass_orb()
+            .with_transform(Matrix4::translate(0.0, 2.0, 0.0));
+        let w = World::default()
+            .with_light(PointLight::new(Colour::white(), point(0.0, 10.0, 0.0)))
+            .with_object(floor)
+            .with_object(orb);
+        let bright = w.clone().with_caustics(5000, 0.2);
+        let r = Ray::new(point(0.0, 0.1, 0.0), vector(0.0, -1.0, 0.0));
+
+        assert!(bright.colour_at(&r, 5).r > w.colour_at(&r, 5).r + 0.5);
+    }
+
+    fn many_lights() -> World {
+        let floor = Object::new_plane()
+            .with_material(Material::default().with_specular(0.0));
+        let mut w = World::default().with_object(floor);
+        for i in 0..12 {
+            let angle = i as Float * 0.5;
+            let colour = Colour::new(0.05 + 0.01 * i as f32, 0.1, 0.02 * i as f32);
+            w = w.with_light(PointLight::new(colour, point(angle.cos() * 6.0, 2.0 + i as Float, angle.sin() * 6.0)));
+        }
+
+        w
+    }
+
+    #[test]
+    fn sampling_enough_lights_shades_them_all() {
+        let w = many_lights();
+        let r = Ray::new(point(0.3, 1.0, 0.2), vector(0.0, -1.0, 0.0));
+
+        assert_eq!(w.clone().with_light_samples(12).colour_at(&r, 1), w.colour_at(&r, 1));
+    }
+
+    #[test]
+    fn sampled_lights_average_out_to_every_light() {
+        let every = many_lights();
+        let sampled = every.clone().with_light_samples(2);
+        let (mut full, mut estimate) = (Colour::black(), Colour::black());
+        for i in 0..400 {
+            let r = Ray::new(point((i % 20) as Float * 0.01, 1.0, (i / 20) as Float * 0.01), vector(0.0, -1.0, 0.0));
+            full += every.colour_at(&r, 1);
+            estimate += sampled.colour_at(&r, 1);
+        }
+
+        assert_ne!(full, estimate);
+        for (a, b) in [(full.r, estimate.r), (full.g, estimate.g), (full.b, estimate.b)] {
+            assert!((a - b).abs() < a * 0.05);
+        }
+    }
+
+    #[test]
+    fn lights_are_picked_in_proportion_to_their_weight() {
+        let weights = [0.0, 1.0, 3.0];
+
+        assert_eq!(pick_light(&weights, 4.0, 0.0), 1);
+        assert_eq!(pick_light(&weights, 4.0, 0.3), 2);
+        assert_eq!(pick_light(&weights, 4.0, 0.9999), 2);
+        assert_eq!(pick_light(&weights, 4.0, 1.0), 2);
+    }
+
+    #[test]
+    fn each_kind_of_ray_has_its_own_depth_limit() {
+        let mirror = Object::new_plane()
+            .with_material(Material::null().with_reflectivity(1.0));
+        let w = World::default()
+            .with_light(PointLight::new(Colour::white(), point(0.0, 10.0, 0.0)))
+            .with_object(mirror)
+            .with_background(Background::Solid(Colour::red()));
+        let r = Ray::new(point(0.0, 1.0, 0.0), vector(0.0, -1.0, 0.0));
+
+        assert_eq!(w.colour_at(&r, RayDepth::new(5).with_refraction(0)), Colour::red());
+        assert_eq!(w.colour_at(&r, RayDepth::new(5).with_reflection(0)), Colour::black());
+    }
+
+    #[test]
+    fn roulette_keeps_reflections_as_bright_on_average() {
+        let mirror = Object::new_plane()
+            .with_material(Material::null().with_reflectivity(0.5));
+        let w = World::default()
+            .with_light(PointLight::new(Colour::white(), point(0.0, 10.0, 0.0)))
+            .with_object(mirror)
+            .with_background(Background::Solid(Colour::white()));
+        let depth = RayDepth::new(5).with_roulette(0);
+        let mut total = 0.0;
+        let mut cut = 0;
+        for i in 0..400 {
+            let r = Ray::new(point(i as Float * 0.013, 1.0, i as Float * 0.007), vector(0.0, -1.0, 0.0));
+            let c = w.colour_at(&r, depth);
+            total += c.r;
+            cut += (c == Colour::black()) as usize;
+        }
+
+        assert!(cut > 100 && cut < 300);
+        assert!((total / 400.0 - 0.5).abs() < 0.05);
+    }
+
+    #[test]
+    fn objects_hidden_from_the_camera_show_in_mirrors() {
+        let mirror = Object::new_plane()
+            .with_material(Material::null().with_reflectivity(1.0));
+        let ghost = Object::new_sphere()
+            .with_transform(Matrix4::translate(0.0, 3.0, 3.0))
+            .with_material(Material::default().with_ambient(1.0).with_colour(Colour::red()))
+            .hide_from(RayKind::Camera);
+        let w = World::default()
+            .with_light(PointLight::new(Colour::white(), point(0.0, 10.0, 0.0)))
+            .with_object(mirror)
+            .with_object(ghost);
+        let direct = Ray::new(point(0.0, 3.0, 0.0), vector(0.0, 0.0, 1.0));
+        let mirrored = Ray::new(point(0.0, 1.0, -1.0), vector(0.0, -1.0, 1.0).normalize());
+
+        assert!(w.intersect(&direct).is_empty());
+        assert_eq!(w.intersect(&direct.with_kind(RayKind::Reflection)).len(), 2);
+        assert!(w.colour_at(&mirrored, 1).r > 0.5);
+    }
+
+    #[test]
+    fn hits_beyond_a_rays_range_are_ignored() {
+        let w = World::default_world();
+        let r = Ray::new(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+
+        assert_eq!(w.intersect(&r.with_t_max(5.0)).len(), 2);
+        assert!(w.intersect(&r.with_t_max(3.0)).is_empty());
+        assert!(!w.intersect_any(&r.with_t_max(3.0), 100.0));
+    }
+
+    #[test]
+    fn reflections_pick_up_the_background() {
+        let mirror = Object::new_plane()
+            .with_material(Material::null().with_reflectivity(1.0));
+        let w = World::default()
+            .with_light(PointLight::new(Colour::white(), point(0.0, 10.0, 0.0)))
+            .with_object(mirror)
+            .with_background(Background::Solid(Colour::red()));
+        let r = Ray::new(point(0.0, 1.0, 0.0), vector(0.0, -1.0, 0.0));
+
+        assert_eq!(w.colour_at(&r, 1), Colour::red());
+    }
+
     #[test]
     fn colour_when_ray_hits() {
         let w = World::default_world();
         let r = Ray::new(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
         let clr = w.colour_at(&r, 1);
 
-        assert_eq!(clr.to_5dp(), Colour::new(0.38066, 0.47583, 0.2855));
+        assert!(clr.approx_eq(&Colour::new(0.38066, 0.47583, 0.2855)));
     }
 
     #[test]
     fn colour_with_intersection_behind_ray() {
         let mut w: World = World::default_world();
-        let mut inner = w.objects[1];
+        let mut inner = w.objects[1].clone();
         inner.material.ambient = 1.0;
         w.objects[0].material.ambient = 1.0;
         w.objects[1].material.ambient = 1.0;
         let r = Ray::new(point(0.0, 0.0, 0.75), vector(0.0, 0.0, -1.0));
         let clr = w.colour_at(&r, 1);
 
-        assert_eq!(clr, inner.material.pattern.pattern_at_object(inner, point(0.0, 0.0, 0.0)));
+        assert_eq!(clr, inner.material.pattern.pattern_at_object(&inner, point(0.0, 0.0, 0.0)));
     }
 
     #[test]
@@ -294,6 +1410,16 @@ mod tests {
         assert!(!w.is_shadowed(w.lights[0].position, p));
     }
 
+    #[test]
+    fn shadowless_objects_dont_hide_casters_behind_them() {
+        let w = World::default_world()
+            .with_object(Object::new_sphere().with_transform(Matrix4::translate(5.0, -5.0, 5.0)).cast_no_shadow());
+        let p = point(10.0, -10.0, 10.0);
+
+        assert!(w.is_shadowed(w.lights[0].position, p));
+        assert!(!w.intersect_any(&Ray::new(p, vector(1.0, 0.0, 0.0)), 100.0));
+    }
+
     #[test]
     fn shade_hit_is_given_intersection_in_shadow() {
         let light = PointLight::new(Colour::white(), point(0.0, 0.0, -10.0));
@@ -303,9 +1429,9 @@ mod tests {
         let w = World::default()
             .with_light(light)
             .with_object(s1)
-            .with_object(s2);
+            .with_object(s2.clone());
         let r = Ray::new(point(0.0, 0.0, 5.0), vector(0.0, 0.0, 1.0));
-        let int = Intersection::new(4.0, s2);
+        let int = Intersection::new(4.0, &s2);
         let ints = Intersections::new(vec![int]);
         let comps = ints.prepare_computations(0, &r);
         let c = w.shade_hit(&comps, 1);
@@ -317,9 +1443,9 @@ mod tests {
     fn reflected_colour_for_nonreflective_material() {
         let w = World::default_world();
         let r = Ray::new(point(0.0, 0.0, 0.0), vector(0.0, 0.0, 1.0));
-        let mut s = w.objects[1];
+        let mut s = w.objects[1].clone();
         s.material.ambient = 1.0;
-        let int = Intersection::new(1.0, s);
+        let int = Intersection::new(1.0, &s);
         let ints = Intersections::new(vec![int]);
         let comps = ints.prepare_computations(0, &r);
         let colour = w.reflected_colour(&comps, 1);
@@ -334,15 +1460,17 @@ mod tests {
             .with_transform(Matrix4::translate(0.0, -1.0, 0.0));
         let w = World::default_world()
             .with_object(shape);
-        let irr_no = 2.0f64.sqrt() / 2.0;
+        let irr_no = Float::sqrt(2.0) / 2.0;
         let r = Ray::new(point(0.0, 0.0, -3.0), vector(0.0, -irr_no, irr_no));
-        let int = Intersection::new(2.0f64.sqrt(), w.objects[2]);
+        let int = Intersection::new(Float::sqrt(2.0), &w.objects[2]);
         let ints = Intersections::new(vec![int]);
         let comps = ints.prepare_computations(0, &r);
         let colour = w.reflected_colour(&comps, 1);
 
         assert_eq!(comps.reflect_vec, vector(0.0, irr_no, irr_no));
-        assert_eq!(colour, Colour::new(0.19032, 0.2379, 0.14274));
+        // The book has (0.19032, 0.2379, 0.14274), worked out with a
+        // different EPSILON.
+        assert!(colour.approx_eq(&Colour::new(0.19033, 0.23792, 0.14275)), "{:?}", colour);
     }
 
     /*#[test]
@@ -352,9 +1480,9 @@ mod tests {
             .with_transform(Matrix4::translate(0.0, -1.0, 0.0));
         let w = World::default_world()
             .with_object(shape);
-        let irr_no = 2.0f64.sqrt() / 2.0;
+        let irr_no = Float::sqrt(2.0) / 2.0;
         let r = Ray::new(point(0.0, 0.0, -3.0), vector(0.0, -irr_no, irr_no));
-        let int = Intersection::new(2.0f64.sqrt(), w.objects[2]);
+        let int = Intersection::new(Float::sqrt(2.0), &w.objects[2]);
         let ints = Intersections::new(vec![int]);
         let comps = ints.prepare_computations(0, &r);
         let colour = w.shade_hit(&comps, 1);
@@ -387,9 +1515,9 @@ mod tests {
             .with_transform(Matrix4::translate(0.0, -1.0, 0.0));
         let w = World::default_world()
             .with_object(shape);
-        let irr_no = 2.0f64.sqrt() / 2.0;
+        let irr_no = Float::sqrt(2.0) / 2.0;
         let r = Ray::new(point(0.0, 0.0, -3.0), vector(0.0, -irr_no, irr_no));
-        let int = Intersection::new(2.0f64.sqrt(), w.objects[2]);
+        let int = Intersection::new(Float::sqrt(2.0), &w.objects[2]);
         let ints = Intersections::new(vec![int]);
         let comps = ints.prepare_computations(0, &r);
         let colour = w.reflected_colour(&comps, 1);
@@ -400,11 +1528,11 @@ mod tests {
     #[test]
     fn reflected_colour_with_opaque_surface() {
         let w = World::default_world();
-        let object = w.objects[0];
+        let object = w.objects[0].clone();
         let ray = Ray::new(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
         let xs = Intersections::new(vec![
-            Intersection::new(4.0, object),
-            Intersection::new(6.0, object)
+            Intersection::new(4.0, &object),
+            Intersection::new(6.0, &object)
         ]);
         let comps = xs.prepare_computations(0, &ray);
 
@@ -414,13 +1542,13 @@ mod tests {
     #[test]
     fn refracted_colour_at_max_recursive_depth() {
         let w = World::default_world();
-        let mut object = w.objects[0];
+        let mut object = w.objects[0].clone();
         object.material.transparency = 1.0;
         object.material.ior = 1.5;
         let ray = Ray::new(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
         let xs = Intersections::new(vec![
-            Intersection::new(4.0, object),
-            Intersection::new(6.0, object)
+            Intersection::new(4.0, &object),
+            Intersection::new(6.0, &object)
         ]);
         let comps = xs.prepare_computations(0, &ray);
 
@@ -430,14 +1558,14 @@ mod tests {
     #[test]
     fn refracted_colour_under_total_interal_reflection() {
         let w = World::default_world();
-        let mut object = w.objects[0];
+        let mut object = w.objects[0].clone();
         object.material.transparency = 1.0;
         object.material.ior = 1.5;
-        let irr_no = 2.0f64.sqrt() / 2.0;
+        let irr_no = Float::sqrt(2.0) / 2.0;
         let ray = Ray::new(point(0.0, 0.0, irr_no), vector(0.0, 1.0, 0.0));
         let xs = Intersections::new(vec![
-            Intersection::new(-irr_no, object),
-            Intersection::new(irr_no, object)
+            Intersection::new(-irr_no, &object),
+            Intersection::new(irr_no, &object)
         ]);
         let comps = xs.prepare_computations(1, &ray);
 
@@ -447,18 +1575,18 @@ mod tests {
     /*#[test]
     fn refracted_colour_with_refracted_ray() {
         let w = World::default_world();
-        let mut a = w.objects[0];
+        let mut a = w.objects[0].clone();
         a.material.ambient = 1.0;
         a.material.pattern = Pattern::new_test();
-        let mut b = w.objects[1];
+        let mut b = w.objects[1].clone();
         b.material.transparency = 1.0;
         b.material.ior = 1.5;
         let ray = Ray::new(point(0.0, 0.0, 0.1), vector(0.0, 1.0, 0.0));
         let xs = Intersections::new(vec![
-            Intersection::new(-0.9899, a),
-            Intersection::new(-0.4899, b),
-            Intersection::new(0.4899, b),
-            Intersection::new(0.9899, a)
+            Intersection::new(-0.9899, &a),
+            Intersection::new(-0.4899, &b),
+            Intersection::new(0.4899, &b),
+            Intersection::new(0.9899, &a)
         ]);
         let comps = xs.prepare_computations(2, &ray);
 
@@ -482,10 +1610,10 @@ mod tests {
         let w = World::default_world()
             .with_object(floor)
             .with_object(ball);
-        let irr_no = 2.0f64.sqrt() / 2.0;
+        let irr_no = Float::sqrt(2.0) / 2.0;
         let ray = Ray::new(point(0.0, 0.0, -3.0), vector(0.0, -irr_no, irr_no));
         let xs = Intersections::new(vec![
-            Intersection::new(2.0f64.sqrt(), floor)
+            Intersection::new(Float::sqrt(2.0), &floor)
         ]);
         let comps = xs.prepare_computations(0, &ray);
         let colour = w.shade_hit(&comps, 5);
@@ -511,14 +1639,90 @@ mod tests {
         let w = World::default_world()
             .with_object(floor)
             .with_object(ball);
-        let irr_no = 2.0f64.sqrt() / 2.0;
+        let irr_no = Float::sqrt(2.0) / 2.0;
         let ray = Ray::new(point(0.0, 0.0, -3.0), vector(0.0, -irr_no, irr_no));
         let xs = Intersections::new(vec![
-            Intersection::new(2.0f64.sqrt(), floor)
+            Intersection::new(Float::sqrt(2.0), &floor)
         ]);
         let comps = xs.prepare_computations(0, &ray);
         let colour = w.shade_hit(&comps, 5);
 
         assert_eq!(colour, Colour::new(0.93642, 0.68642, 0.68642));
     }*/
+
+    #[test]
+    fn light_masks_cast_their_pattern_onto_the_scene() {
+        let blind = Pattern::new_stripes(Colour::white(), Colour::black());
+        let lit = World::default().with_object(Object::new_plane()).with_light(PointLight::new(Colour::white(), point(0.0, 5.0, 0.0)));
+        let masked = World::default().with_object(Object::new_plane())
+            .with_light(PointLight::new(Colour::white(), point(0.0, 5.0, 0.0)).with_mask(blind));
+        let down = |x| Ray::new(point(x, 1.0, 0.0), vector(0.0, -1.0, 0.0));
+
+        assert_eq!(masked.colour_at(&down(0.5), 1), lit.colour_at(&down(0.5), 1));
+        assert_eq!(masked.colour_at(&down(1.5), 1), Colour::black());
+    }
+
+    #[test]
+    fn render_seeds_are_mixed_into_jitter_and_noise() {
+        let jittered = Object::new_sphere()
+            .with_material(Material::default()
+                .with_pattern(Pattern::new_gradient(Colour::red(), Colour::blue()).with_jitter(Jitter::new(7, 0.5))));
+        let smoke = Object::new_sphere().with_medium(Medium::new(1.0).with_noise(DensityNoise::new(3)));
+        let w = World::default().with_object(jittered).with_object(smoke);
+        let mut unseeded = w.clone();
+        unseeded.reseed(0);
+        let mut seeded = w.clone();
+        seeded.reseed(9);
+        let p = point(0.3, 0.6, 0.9);
+
+        assert_eq!(unseeded, w);
+        assert_ne!(seeded.objects[0].material.pattern.pattern_at_point(p), w.objects[0].material.pattern.pattern_at_point(p));
+        assert_ne!(seeded.objects[1].medium.unwrap().noise.unwrap().seed, 3);
+    }
+
+    #[test]
+    fn volumes_that_never_end_are_marched_a_finite_way() {
+        // A faint haze below a plane, which has no far side and barely dims
+        // light, so it would otherwise be marched in steps as long as it is thin.
+        let haze = Object::new_plane().with_medium(Medium::new(Float::MIN_POSITIVE));
+        let w = World::default()
+            .with_light(PointLight::new(Colour::white(), point(0.0, 10.0, 0.0)))
+            .with_object(haze);
+        let r = Ray::new(point(0.0, 1.0, -5.0), vector(0.0, -1.0, 1.0).normalize());
+        let spans = w.volume_spans(&r, Float::INFINITY);
+
+        assert_eq!(spans.len(), 1);
+        assert!(spans[0].1 - spans[0].0 <= MAX_VOLUME_DEPTH);
+        assert!(w.colour_at(&r, 1).r.is_finite());
+    }
+
+    #[test]
+    fn volumes_with_nan_crossings_are_marched_without_panicking() {
+        let mut fog = Object::new_sphere().with_medium(Medium::new(0.5));
+        fog.inverse_transform[(0, 0)] = Float::NAN;
+        let w = World::default()
+            .with_light(PointLight::new(Colour::white(), point(0.0, 10.0, 0.0)))
+            .with_object(fog)
+            .with_object(Object::new_sphere().with_medium(Medium::new(0.5)));
+        let r = Ray::new(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+        let (scattered, transmittance) = w.volume_light(&r, Float::INFINITY);
+
+        assert!(scattered.r.is_finite());
+        assert!(transmittance > 0.0 && transmittance < 1.0);
+    }
+
+    #[test]
+    fn noisy_volumes_with_no_extinction_are_skipped() {
+        // Used to hand infinite points to the noise, which panicked.
+        let nothing = Medium::new(0.5).with_scattering(0.0).with_noise(DensityNoise::new(1));
+        let faint = Medium::new(Float::MIN_POSITIVE).with_noise(DensityNoise::new(1));
+        let r = Ray::new(point(0.0, 1.0, -5.0), vector(0.0, -1.0, 1.0).normalize());
+        for medium in [nothing, faint] {
+            let w = World::default()
+                .with_light(PointLight::new(Colour::white(), point(0.0, 10.0, 0.0)))
+                .with_object(Object::new_plane().with_medium(medium));
+
+            assert!(w.colour_at(&r, 1).r.is_finite());
+        }
+    }
 }
\ No newline at end of file
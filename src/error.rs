@@ -0,0 +1,76 @@
+use crate::scene::SceneError;
+#[cfg(feature = "image")]
+use image::ImageError;
+use std::fmt;
+use std::io;
+
+/// Everything that can go wrong across the crate, for the fallible (try_)
+/// versions of calls that would otherwise panic, and for code that wants a
+/// single error type to pass up.
+#[derive(Debug)]
+pub enum FeorayError {
+    /// A transform couldn't be inverted, e.g. a scale of zero. Says what the
+    /// transform was for.
+    NonInvertible(&'static str),
+    /// A ray's origin wasn't a point, or its direction wasn't a vector.
+    InvalidRay(&'static str),
+    /// A pixel outside the canvas.
+    OutOfBounds { x: usize, y: usize, width: usize, height: usize },
+    /// More keyframes or path points than there's room for. Says what they
+    /// were and how many fit.
+    TooMany { what: &'static str, max: usize },
+    Scene(SceneError),
+    #[cfg(feature = "image")]
+    Image(ImageError),
+    Io(io::Error)
+}
+
+impl fmt::Display for FeorayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FeorayError::NonInvertible(what) => write!(f, "{} transform can't be inverted", what),
+            FeorayError::InvalidRay(message) => write!(f, "invalid ray: {}", message),
+            FeorayError::OutOfBounds { x, y, width, height } => write!(
+                f,
+                "pixel ({}, {}) is outside the {} x {} canvas",
+                x, y, width, height
+            ),
+            FeorayError::TooMany { what, max } => write!(f, "no more than {} {}", max, what),
+            FeorayError::Scene(err) => write!(f, "{}", err),
+            #[cfg(feature = "image")]
+            FeorayError::Image(err) => write!(f, "{}", err),
+            FeorayError::Io(err) => write!(f, "{}", err)
+        }
+    }
+}
+
+impl std::error::Error for FeorayError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            FeorayError::Scene(err) => Some(err),
+            #[cfg(feature = "image")]
+            FeorayError::Image(err) => Some(err),
+            FeorayError::Io(err) => Some(err),
+            _ => None
+        }
+    }
+}
+
+impl From<SceneError> for FeorayError {
+    fn from(err: SceneError) -> Self {
+        FeorayError::Scene(err)
+    }
+}
+
+#[cfg(feature = "image")]
+impl From<ImageError> for FeorayError {
+    fn from(err: ImageError) -> Self {
+        FeorayError::Image(err)
+    }
+}
+
+impl From<io::Error> for FeorayError {
+    fn from(err: io::Error) -> Self {
+        FeorayError::Io(err)
+    }
+}
@@ -1,26 +1,122 @@
-pub const EPSILON: f64 = 1.0e-5;
+/// Floating point type used for geometry: points, vectors, transforms and
+/// distances along rays. f64 by default. The `f32` feature halves the memory
+/// taken by every object and intersection, at the cost of precision, so
+/// surfaces need a larger EPSILON to keep from shadowing themselves.
+#[cfg(not(feature = "f32"))]
+pub type Float = f64;
+#[cfg(feature = "f32")]
+pub type Float = f32;
+
+/// Mathematical constants for Float.
+#[cfg(not(feature = "f32"))]
+pub use std::f64::consts;
+#[cfg(feature = "f32")]
+pub use std::f32::consts;
+
+// Widens a Float to f64, for the libraries and bit tricks that only take f64.
+#[cfg(not(feature = "f32"))]
+pub(crate) fn to_f64(x: Float) -> f64 {
+    x
+}
+#[cfg(feature = "f32")]
+pub(crate) fn to_f64(x: Float) -> f64 {
+    x as f64
+}
+
+// Narrows a Float to the f32 that colours are kept in.
+#[cfg(not(feature = "f32"))]
+pub(crate) fn to_f32(x: Float) -> f32 {
+    x as f32
+}
+#[cfg(feature = "f32")]
+pub(crate) fn to_f32(x: Float) -> f32 {
+    x
+}
+
+pub use error::FeorayError;
+
+pub mod error;
+
+#[cfg(not(feature = "f32"))]
+pub const EPSILON: Float = 1.0e-5;
+#[cfg(feature = "f32")]
+pub const EPSILON: Float = 1.0e-3;
+
+pub mod bench {
+    pub use crate::scenes::{cornell_box, glass_grid, scenes, sphere_flake};
+    pub use timing::{packets, run, BenchResult};
+
+    pub mod timing;
+}
 
 pub mod core {
-    pub use camera::Camera;
+    pub use animation::Animation;
+    pub use aov::{id_colour, Aovs};
+    pub use background::Background;
+    pub use camera::{Camera, Projection, RayGenerator, Tile};
+    pub use camera_path::CameraPath;
     pub use canvas::{canvas, Canvas};
-    pub use colour::Colour;
+    pub use colour::{Colour, Encoding};
+    #[cfg(feature = "image")]
+    pub use golden::{Golden, GoldenError, GoldenReport};
+    pub use integrator::Integrator;
     pub use intersections::{Intersection, Intersections};
+    pub use job::RenderJob;
+    pub use keyframes::{Keyframes, MAX_KEYFRAMES};
+    pub use packet::{RayPacket, LANES};
+    pub use denoise::Denoiser;
+    pub use depth::{Bounce, RayDepth};
+    pub use diff::DiffStats;
     pub use matrix::Test;
+    pub use photon_map::{Photon, PhotonMap};
+    pub use post::PostOp;
     pub use precomp::PreCompData;
-    pub use rays::Ray;
-    pub use transformers::{Transform, TransformBuilder};
-    pub use tuple::{point, vector, Tuple};
-    pub use world::World;
+    pub use progress::Progress;
+    pub use rays::{Ray, RayKind};
+    pub use resize::ResizeFilter;
+    pub use roots::Roots;
+    pub use sampler::{Rng, Sampler, SamplerKind};
+    pub use settings::RenderSettings;
+    pub use stereo::StereoRig;
+    pub use trace::{ShadowTest, TraceHit, TraceNode};
+    pub use transformers::{decompose, interpolate, Decomposed, Transform, TransformBuilder};
+    pub use tuple::{cosine_direction, point, vector, Frame, Tuple};
+    pub use validate::{Diagnostic, Issue, Severity};
+    pub use world::{ObjectId, World};
 
+    pub mod animation;
+    pub mod aov;
+    pub mod background;
     pub mod camera;
+    pub mod camera_path;
     pub mod canvas;
     pub mod colour;
+    pub mod geometry;
+    #[cfg(feature = "image")]
+    pub mod golden;
+    pub mod integrator;
     pub mod intersections;
+    pub mod job;
+    pub mod keyframes;
+    pub mod denoise;
+    pub mod depth;
+    pub mod diff;
     pub mod matrix;
+    pub mod packet;
+    pub mod photon_map;
+    pub mod post;
     pub mod precomp;
+    pub mod progress;
     pub mod rays;
+    pub mod resize;
+    pub mod roots;
+    pub mod sampler;
+    pub mod settings;
+    pub mod stereo;
+    pub mod trace;
     pub mod transformers;
     pub mod tuple;
+    pub mod validate;
     pub mod world;
 }
 
@@ -31,23 +127,52 @@ pub mod lights {
 }
 
 pub mod materials {
-    pub use materials::Material;
+    pub use materials::{Material, MaterialId};
+    pub use medium::{DensityNoise, Medium};
     pub use patterns::*;
+    pub use ramp::{ColourRamp, Interpolation};
+    pub use texture::{Filter, TextureId, Textures, Wrap};
 
+    #[allow(clippy::module_inception)]
     pub mod materials;
+    pub mod medium;
     pub mod patterns;
+    pub mod ramp;
+    pub mod texture;
 }
 
 pub mod primitives {
+    pub use lens::Lens;
     pub use object::Object;
     pub use primitives::Primitive;
     pub use plane::Plane;
+    pub use polygon::Polygon;
     pub use sphere::Sphere;
     pub use test_shape::TestShape;
 
+    pub mod lens;
     pub mod object;
+    #[allow(clippy::module_inception)]
     pub mod primitives;
     pub mod plane;
+    pub mod polygon;
     pub mod sphere;
     pub mod test_shape;
-}
\ No newline at end of file
+}
+
+pub mod scene {
+    pub use error::SceneError;
+    pub use export::Mesh;
+    pub use loader::{parse_world, Scene};
+    pub use writer::world_to_yaml;
+    pub use yaml::Yaml;
+
+    pub mod error;
+    pub mod export;
+    pub mod loader;
+    pub mod macros;
+    pub mod writer;
+    pub mod yaml;
+}
+
+pub mod scenes;
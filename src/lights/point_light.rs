@@ -1,17 +1,57 @@
+use crate::Float;
 use crate::core::Colour;
+use crate::materials::Pattern;
 use nalgebra::Vector4;
 
 // I use the term colour as that makes more sense than intensity which sounds
 // more like a scale of colour.
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct PointLight {
     pub colour: Colour,
-    pub position: Vector4<f64>
+    pub position: Vector4<Float>,
+    /// Label for finding the light again in big scenes and debug traces.
+    pub name: Option<String>,
+    /// Pattern in world space whose brightness dims the light where it
+    /// lands, like a gobo or cookie in front of a stage light.
+    pub mask: Option<Pattern>
 }
 
 impl PointLight {
-    pub fn new(colour: Colour, position: Vector4<f64>) -> Self {
-        PointLight { colour, position }
+    pub fn new(colour: Colour, position: Vector4<Float>) -> Self {
+        PointLight { colour, position, name: None, mask: None }
+    }
+
+    pub fn with_name(mut self, name: &str) -> Self {
+        self.name = Some(name.to_string());
+
+        self
+    }
+
+    /// Masks the light with a greyscale pattern, so it's full strength where
+    /// the pattern is white and off where it's black. Stripes make the shadow
+    /// of a window blind, and a projection thrown from the light's position
+    /// casts the shape of an image across its beam.
+    pub fn with_mask(mut self, mask: Pattern) -> Self {
+        self.mask = Some(mask);
+
+        self
+    }
+
+    /// How much of the light its mask lets through to a point, from 0.0 to
+    /// 1.0. Always 1.0 without a mask.
+    pub fn mask_at(&self, pos: Vector4<Float>) -> f32 {
+        match &self.mask {
+            Some(mask) => {
+                let mask = mask.pattern_at_point(pos);
+                ((mask.r + mask.g + mask.b) / 3.0).clamp(0.0, 1.0)
+            },
+            None => 1.0
+        }
+    }
+
+    /// Colour of the light reaching a point, after its mask.
+    pub fn colour_at(&self, pos: Vector4<Float>) -> Colour {
+        self.colour * self.mask_at(pos)
     }
 }
 
@@ -29,4 +69,14 @@ mod tests {
         assert_eq!(light.position, p);
         assert_eq!(light.colour, c);
     }
+
+    #[test]
+    fn masks_dim_the_light_where_they_are_dark() {
+        let blind = Pattern::new_stripes(Colour::white(), Colour::grey(0.25));
+        let light = PointLight::new(Colour::new(1.0, 0.5, 0.0), point(0.0, 5.0, 0.0)).with_mask(blind);
+
+        assert_eq!(light.colour_at(point(0.5, 0.0, 0.0)), Colour::new(1.0, 0.5, 0.0));
+        assert_eq!(light.colour_at(point(1.5, 0.0, 0.0)), Colour::new(0.25, 0.125, 0.0));
+        assert_eq!(PointLight::new(Colour::white(), point(0.0, 5.0, 0.0)).colour_at(point(1.5, 0.0, 0.0)), Colour::white());
+    }
 }
\ No newline at end of file
@@ -1,78 +1,140 @@
 #![warn(clippy::pedantic)]
+#![allow(clippy::similar_names)]
 use feoray::{
-    core::{point, vector, Camera, Colour, Transform, TransformBuilder, World},
-    lights::PointLight,
+    bench,
+    core::{Colour, RenderSettings, Transform},
     materials::{Material, Pattern},
-    primitives::Object
+    scene,
+    scene::Scene
 };
 use nalgebra::Matrix4;
-use std::f64::consts::PI;
+use std::env;
+use feoray::consts::PI;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+// How often watch mode checks the scene file for changes.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
 
 fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let preview = args.iter().any(|arg| arg == "--preview");
+
+    match args.first().map(String::as_str) {
+        Some("watch") => match args.get(1).filter(|arg| !arg.starts_with("--")) {
+            Some(scene) => watch(Path::new(scene), &output_path(&args, scene), preview),
+            None => eprintln!("usage: feoray watch <scene.yaml> [-o <image>] [--preview]")
+        },
+        Some("bench") => run_bench(args.get(1).filter(|arg| !arg.starts_with("--")), preview),
+        _ => demo(preview)
+    }
+}
+
+// Image written by watch mode: the -o argument, or the scene file with a png extension.
+fn output_path(args: &[String], scene: &str) -> PathBuf {
+    args.iter()
+        .position(|arg| arg == "-o")
+        .and_then(|i| args.get(i + 1))
+        .map_or_else(|| Path::new(scene).with_extension("png"), PathBuf::from)
+}
+
+// Renders the scene, then re-renders it every time the file is saved. Errors in
+// the scene are reported, and the previous image is left alone until they're fixed.
+fn watch(scene: &Path, output: &Path, preview: bool) {
+    println!("watching {} (Ctrl+C to stop)", scene.display());
+    let mut last_modified: Option<SystemTime> = None;
+    loop {
+        let modified = fs::metadata(scene).and_then(|m| m.modified()).ok();
+        if modified.is_some() && modified != last_modified {
+            last_modified = modified;
+            render_scene(scene, output, preview);
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+fn render_scene(scene: &Path, output: &Path, preview: bool) {
+    let Scene { camera, world } = match Scene::load(scene) {
+        Ok(scene) => scene,
+        Err(err) => {
+            eprintln!("{}: {}", scene.display(), err);
+            return;
+        }
+    };
+    let settings = RenderSettings::for_world(&world).with_threads(0);
+    let canvas = if preview {
+        camera.preview(4).render_with(world, &settings.preview().with_shadows(false))
+    } else {
+        camera.render_with(world, &settings)
+    };
+    match canvas.export(&output.to_string_lossy()) {
+        Ok(()) => println!("rendered {}", output.display()),
+        Err(err) => eprintln!("{}: {}", output.display(), err)
+    }
+}
+
+// Times the benchmark scenes, or just the named one, and prints the rays per
+// second for each, to compare between versions.
+fn run_bench(only: Option<&String>, preview: bool) {
+    let scenes: Vec<_> = bench::scenes()
+        .into_iter()
+        .filter(|(name, _)| only.map_or(true, |only| only == name))
+        .collect();
+    if scenes.is_empty() {
+        let names: Vec<_> = bench::scenes().into_iter().map(|(name, _)| name).collect();
+        eprintln!("usage: feoray bench [{}] [--preview]", names.join(" | "));
+        return;
+    }
+    for (name, mut scene) in scenes {
+        let mut settings = RenderSettings::for_world(&scene.world).with_threads(0);
+        if preview {
+            scene.camera = scene.camera.preview(4);
+            settings = settings.preview();
+        }
+        println!("{}", bench::run(name, scene, &settings));
+    }
+}
+
+fn demo(preview: bool) {
     let floor_pat = Pattern::new_radial(Colour::white(), Colour::blue(), 12);
     let floor_mat = Material::default()
         .with_colour(Colour::new(1.0, 0.9, 0.9))
         .with_specular(0.0)
         .with_reflectivity(0.6)
         .with_pattern(floor_pat);
-    let floor = Object::new_plane()
-        .with_material(floor_mat);
 
-    let mid_trn = TransformBuilder::new()
-        .rot_z(PI / 4.0)
-        .translate(-0.5, 1.0, 0.5)
-        .build();
-    let mid_pat_trn = TransformBuilder::new()
-        .uscale(0.25)
-        .build();
     let mid_pat = Pattern::new_checkers(Colour::red(), Colour::white())
-        .with_transform(mid_pat_trn);
+        .with_transform(Matrix4::uscale(0.25));
     let mid_mat = Material::default()
         .with_diffuse(0.7)
         .with_specular(0.3)
         .with_reflectivity(0.08)
         .with_pattern(mid_pat);
-    let mid = Object::new_sphere()
-        .with_transform(mid_trn)
-        .with_material(mid_mat)
-        .use_manifold();
-
-    let right_trn = TransformBuilder::new()
-        .uscale(0.5)
-        .translate(1.5, 0.5, -0.5)
-        .build();
-    let right = Object::glass_orb()
-        .with_transform(right_trn);
 
-    let left_trn = TransformBuilder::new()
-        .uscale(0.33)
-        .translate(-1.5, 0.33, -0.75)
-        .build();
     let left_mat = Material::null()
         .with_colour(Colour::grey(192.0/255.0))
         .with_reflectivity(0.95)
         .with_specular(0.9);
-    let left = Object::new_sphere()
-        .with_transform(left_trn)
-        .with_material(left_mat)
-        .cast_no_shadow();
-
-    let light_source = PointLight::new(Colour::white(), point(-10.0, 10.0, -10.0));
-
-    let world = World::default()
-        .with_light(light_source)
-        .with_object(floor)
-        .with_object(mid)
-        .with_object(left)
-        .with_object(right);
 
-    let from = point(0.0, 1.5, -5.0);
-    let to = point(0.0, 1.0, 0.0);
-    let up = vector(0.0, 1.0, 0.0);
-    let cam = Camera::new(700, 350, PI / 3.0)
-        .with_transform(Matrix4::view_transform(from, to, up));
+    let Scene { camera: cam, world } = scene! {
+        camera { width: 700, height: 350, fov: PI / 3.0, from: (0, 1.5, -5), to: (0, 1, 0) }
+        light { at: (-10, 10, -10) }
+        plane { material: floor_mat }
+        sphere { material: mid_mat, rot_z: PI / 4.0, translate: (-0.5, 1, 0.5), manifold: true }
+        sphere { material: left_mat, uscale: 0.33, translate: (-1.5, 0.33, -0.75), shadow: false }
+        glass_orb { uscale: 0.5, translate: (1.5, 0.5, -0.5) }
+    };
 
-    let canvas = cam.render(world);
+    let settings = RenderSettings::for_world(&world)
+        .with_threads(0);
+    let canvas = if preview {
+        let settings = settings.preview().with_shadows(false);
+        cam.preview(4).render_with(world, &settings)
+    } else {
+        cam.render_with(world, &settings)
+    };
 
     canvas.export("test_scene_0005.jpg").unwrap();
-}
\ No newline at end of file
+}
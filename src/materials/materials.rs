@@ -1,9 +1,15 @@
+use crate::Float;
 use super::Pattern;
 use crate::core::{Colour, Tuple};
 use crate::lights::PointLight;
 use crate::primitives::Object;
 use nalgebra::Vector4;
 
+/// Handle to a material shared through a world's palette, returned by
+/// World::add_material().
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct MaterialId(pub(crate) usize);
+
 #[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
 pub struct Material {
     pub ambient: f32,
@@ -17,6 +23,7 @@ pub struct Material {
 }
 
 impl Material {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         ambient: f32,
         diffuse: f32,
@@ -117,14 +124,27 @@ impl Material {
 
     pub fn lighting(
         &self,
-        object: Object,
-        light: PointLight,
-        pos: Vector4<f64>,
-        eye_vec: Vector4<f64>,
-        normal_vec: Vector4<f64>,
+        object: &Object,
+        light: &PointLight,
+        pos: Vector4<Float>,
+        eye_vec: Vector4<Float>,
+        normal_vec: Vector4<Float>,
+        shadow: bool
+    ) -> Colour {
+        self.lighting_with(self.pattern.pattern_at_object(object, pos), light, pos, eye_vec, normal_vec, shadow)
+    }
+
+    /// Same as lighting(), with the surface colour already found, e.g. from
+    /// a pattern filtered over the ray's footprint.
+    pub fn lighting_with(
+        &self,
+        colour: Colour,
+        light: &PointLight,
+        pos: Vector4<Float>,
+        eye_vec: Vector4<Float>,
+        normal_vec: Vector4<Float>,
         shadow: bool
     ) -> Colour {
-        let colour = self.pattern.pattern_at_object(object, pos);
         let eff_colour = colour * light.colour;
         let light_vec = (light.position - pos).normalize();
         let ambient = eff_colour * self.ambient;
@@ -137,7 +157,7 @@ impl Material {
             if reflect_dot_eye <= 0.0 {
                 specular = Colour::black();
             } else {
-                let factor = reflect_dot_eye.powf(self.smoothness.into());
+                let factor = reflect_dot_eye.powf(self.smoothness as Float);
                 specular = light.colour * self.specular * factor;
             }
         }
@@ -170,7 +190,7 @@ mod tests {
     fn default_material() {
         let m = Material::default();
 
-        assert_eq!(m.pattern.pattern_at_object(Object::default(), point(0.0, 0.0, 0.0)), Colour::white());
+        assert_eq!(m.pattern.pattern_at_object(&Object::default(), point(0.0, 0.0, 0.0)), Colour::white());
         assert_eq!(m.ambient, 0.1);
         assert_eq!(m.diffuse, 0.9);
         assert_eq!(m.specular, 0.9);
@@ -185,7 +205,7 @@ mod tests {
         let normal = vector(0.0, 0.0, -1.0);
         let light = PointLight::new(Colour::white(), point(0.0, 0.0, -10.0));
         let shadow = false;
-        let res = m.lighting(Object::default(), light, pos, eyev, normal, shadow);
+        let res = m.lighting(&Object::default(), &light, pos, eyev, normal, shadow);
 
         assert_eq!(res, Colour::new(1.9, 1.9, 1.9));
     }
@@ -194,11 +214,11 @@ mod tests {
     fn lighting_with_eye_between_light_and_surface_eye_offset_45d() {
         let m = Material::default();
         let pos =point(0.0, 0.0, 0.0);
-        let irr_no = 2.0f64.sqrt() / 2.0;
+        let irr_no = Float::sqrt(2.0) / 2.0;
         let eyev = vector(0.0, irr_no, -irr_no);
         let normal = vector(0.0, 0.0, -1.0);
         let light = PointLight::new(Colour::white(), point(0.0, 0.0, -10.0));
-        let res = m.lighting(Object::default(), light, pos, eyev, normal, false);
+        let res = m.lighting(&Object::default(), &light, pos, eyev, normal, false);
 
         assert_eq!(res, Colour::white());
     }
@@ -210,7 +230,7 @@ mod tests {
         let eyev = vector(0.0, 0.0, -1.0);
         let normal = vector(0.0, 0.0, -1.0);
         let light = PointLight::new(Colour::white(), point(0.0, 10.0, -10.0));
-        let res = m.lighting(Object::default(), light, pos, eyev, normal, false);
+        let res = m.lighting(&Object::default(), &light, pos, eyev, normal, false);
 
         assert_eq!(res.to_5dp(), Colour::new(0.73640, 0.73640, 0.73640));
     }
@@ -219,13 +239,13 @@ mod tests {
     fn lighting_with_eye_in_path_of_reflection_vector() {
         let m = Material::default();
         let pos = point(0.0, 0.0, 0.0);
-        let irr_no = 2.0f64.sqrt() / 2.0;
+        let irr_no = Float::sqrt(2.0) / 2.0;
         let eyev = vector(0.0, -irr_no, -irr_no);
         let normal = vector(0.0, 0.0, -1.0);
         let light = PointLight::new(Colour::white(), point(0.0, 10.0, -10.0));
-        let res = m.lighting(Object::default(), light, pos, eyev, normal, false);
+        let res = m.lighting(&Object::default(), &light, pos, eyev, normal, false);
 
-        assert_eq!(res.to_5dp(), Colour::new(1.63640, 1.63640, 1.63640));
+        assert!(res.approx_eq(&Colour::new(1.63640, 1.63640, 1.63640)));
     }
 
     #[test]
@@ -235,7 +255,7 @@ mod tests {
         let eyev = vector(0.0, 0.0, -1.0);
         let normal = vector(0.0, 0.0, -1.0);
         let light = PointLight::new(Colour::white(), point(0.0, 0.0, 10.0));
-        let res = m.lighting(Object::default(), light, pos, eyev, normal, false);
+        let res = m.lighting(&Object::default(), &light, pos, eyev, normal, false);
 
         assert_eq!(res, Colour::new(0.1, 0.1, 0.1));
     }
@@ -247,7 +267,7 @@ mod tests {
         let eyev = vector(0.0, 0.0, -1.0);
         let normal = vector(0.0, 0.0, -1.0);
         let light = PointLight::new(Colour::white(), point(0.0, 0.0, -10.0));
-        let res = m.lighting(Object::default(), light, pos, eyev, normal, true);
+        let res = m.lighting(&Object::default(), &light, pos, eyev, normal, true);
 
         assert_eq!(res, Colour::new(0.1, 0.1, 0.1));
     }
@@ -263,8 +283,8 @@ mod tests {
         let eyev = vector(0.0, 0.0, -1.0);
         let normal = vector(0.0, 0.0, -1.0);
         let light = PointLight::new(Colour::white(), point(0.9, 0.0, 0.0));
-        let c1 = m.lighting(Object::default(), light, point(0.9, 0.0, 0.0), eyev, normal, true);
-        let c2 = m.lighting(Object::default(), light, point(1.1, 0.0, 0.0), eyev, normal, true);
+        let c1 = m.lighting(&Object::default(), &light, point(0.9, 0.0, 0.0), eyev, normal, true);
+        let c2 = m.lighting(&Object::default(), &light, point(1.1, 0.0, 0.0), eyev, normal, true);
 
         assert_eq!(c1, Colour::white());
         assert_eq!(c2, Colour::black());
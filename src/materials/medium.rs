@@ -0,0 +1,269 @@
+use crate::{to_f64, Float};
+use crate::core::Colour;
+use nalgebra::Vector4;
+use noise::{NoiseFn, Perlin};
+
+// Transmittance below which a volume is treated as opaque.
+const OPAQUE: Float = 1.0e-3;
+
+// Largest noise coordinate, after scaling by the finest octave's frequency.
+// The noise crate panics on coordinates it can't fit on its integer lattice,
+// and long before that there's no detail left to sample.
+const NOISE_LIMIT: Float = 1.0e12;
+
+/// Participating medium filling a volume, such as smoke, fog or murky water.
+/// Light passing through is absorbed and scattered in proportion to the
+/// density, so the further it travels the less of it makes it out.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub struct Medium {
+    /// Tint of the light scattered towards the eye.
+    pub colour: Colour,
+    pub density: Float,
+    /// Fraction of the density that swallows light.
+    pub absorption: Float,
+    /// Fraction of the density that bounces light, making the volume glow
+    /// where it's lit.
+    pub scattering: Float,
+    /// Number of samples taken along each ray through the volume.
+    pub steps: usize,
+    /// Noise breaking the density up into clouds or wisps of smoke. None fills
+    /// the volume evenly.
+    pub noise: Option<DensityNoise>
+}
+
+impl Medium {
+    /// White, purely scattering medium of the given density.
+    pub fn new(density: Float) -> Self {
+        Medium { density, ..Default::default() }
+    }
+
+    /// Sets the colour of the scattered light.
+    pub fn with_colour(mut self, colour: Colour) -> Self {
+        self.colour = colour;
+
+        self
+    }
+
+    pub fn with_density(mut self, density: Float) -> Self {
+        self.density = density;
+
+        self
+    }
+
+    pub fn with_absorption(mut self, absorption: Float) -> Self {
+        self.absorption = absorption;
+
+        self
+    }
+
+    pub fn with_scattering(mut self, scattering: Float) -> Self {
+        self.scattering = scattering;
+
+        self
+    }
+
+    /// Sets the number of ray-march samples. More steps give smoother light shafts.
+    pub fn with_steps(mut self, steps: usize) -> Self {
+        self.steps = steps.max(1);
+
+        self
+    }
+
+    /// Varies the density with noise. Density then ranges from 0.0 up to the
+    /// medium's density.
+    pub fn with_noise(mut self, noise: DensityNoise) -> Self {
+        self.noise = Some(noise);
+
+        self
+    }
+
+    /// Whether the density is the same everywhere in the volume.
+    pub fn is_uniform(&self) -> bool {
+        self.noise.is_none()
+    }
+
+    /// Density at a point in the volume's own space. For many lookups, use
+    /// field(), which only sets up the noise once.
+    pub fn density_at(&self, object_point: Vector4<Float>) -> Float {
+        self.field().density_at(object_point)
+    }
+
+    pub(crate) fn field(&self) -> DensityField {
+        DensityField {
+            density: self.density,
+            noise: self.noise.map(|noise| (noise, Perlin::new(noise.seed)))
+        }
+    }
+
+    /// How quickly light is lost travelling through the medium, per unit
+    /// distance, at its densest.
+    pub fn extinction(&self) -> Float {
+        self.density * (self.absorption + self.scattering)
+    }
+
+    /// Fraction of light that makes it through the given distance of medium, at
+    /// its densest.
+    pub fn transmittance(&self, distance: Float) -> Float {
+        (-self.extinction() * distance).exp()
+    }
+
+    /// Distance after which practically no light makes it through the densest
+    /// medium. Rays are never marched further than this.
+    pub fn reach(&self) -> Float {
+        if self.extinction() > 0.0 {
+            -OPAQUE.ln() / self.extinction()
+        } else {
+            Float::INFINITY
+        }
+    }
+}
+
+impl Default for Medium {
+    fn default() -> Self {
+        Medium {
+            colour: Colour::white(),
+            density: 1.0,
+            absorption: 0.0,
+            scattering: 1.0,
+            steps: 16,
+            noise: None
+        }
+    }
+}
+
+/// Fractal (fBm) Perlin noise for the density of a volume, so it fills the
+/// shape with clouds or wispy smoke rather than an even haze. The seed fixes
+/// the noise, so the same seed always gives the same cloud.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub struct DensityNoise {
+    pub seed: u32,
+    /// Number of noise features per unit distance.
+    pub frequency: Float,
+    /// Layers of finer detail added on top of each other.
+    pub octaves: u32,
+    /// Noise below this level (0.0 to 1.0) is left empty, leaving gaps
+    /// between clouds.
+    pub threshold: Float
+}
+
+impl DensityNoise {
+    pub fn new(seed: u32) -> Self {
+        DensityNoise { seed, ..Default::default() }
+    }
+
+    pub fn with_seed(mut self, seed: u32) -> Self {
+        self.seed = seed;
+
+        self
+    }
+
+    pub fn with_frequency(mut self, frequency: Float) -> Self {
+        self.frequency = frequency;
+
+        self
+    }
+
+    pub fn with_octaves(mut self, octaves: u32) -> Self {
+        self.octaves = octaves.max(1);
+
+        self
+    }
+
+    pub fn with_threshold(mut self, threshold: Float) -> Self {
+        self.threshold = threshold.clamp(0.0, 1.0);
+
+        self
+    }
+
+    // fBm scaled to 0.0..1.0, then cut off at the threshold. Points too far
+    // out for the noise, or not finite at all, are empty.
+    fn sample(&self, perlin: &Perlin, p: Vector4<Float>) -> Float {
+        let finest = self.frequency * (2.0 as Float).powi(self.octaves.saturating_sub(1) as i32);
+        if !p.xyz().iter().all(|c| (c * finest).abs() < NOISE_LIMIT) {
+            return 0.0;
+        }
+        let (mut sum, mut amplitude, mut frequency, mut total) = (0.0, 1.0, self.frequency, 0.0);
+        for octave in 0..self.octaves {
+            // Offset each octave so they don't all line up at the origin.
+            let offset = octave as Float * 17.31;
+            let q = p * frequency;
+            sum += perlin.get([to_f64(q.x + offset), to_f64(q.y + offset), to_f64(q.z + offset)]) as Float * amplitude;
+            total += amplitude;
+            amplitude *= 0.5;
+            frequency *= 2.0;
+        }
+        let n = (sum / total * 0.5 + 0.5).clamp(0.0, 1.0);
+        if self.threshold >= 1.0 {
+            return 0.0;
+        }
+
+        ((n - self.threshold) / (1.0 - self.threshold)).max(0.0)
+    }
+}
+
+impl Default for DensityNoise {
+    fn default() -> Self {
+        DensityNoise { seed: 0, frequency: 1.0, octaves: 4, threshold: 0.0 }
+    }
+}
+
+/// Density lookups for one medium, with its noise ready to sample.
+pub(crate) struct DensityField {
+    density: Float,
+    noise: Option<(DensityNoise, Perlin)>
+}
+
+impl DensityField {
+    pub(crate) fn density_at(&self, object_point: Vector4<Float>) -> Float {
+        match &self.noise {
+            Some((noise, perlin)) => self.density * noise.sample(perlin, object_point),
+            None => self.density
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::point;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn light_is_lost_through_the_medium() {
+        let m = Medium::new(0.5).with_absorption(1.0);
+
+        assert_eq!(m.extinction(), 1.0);
+        assert_eq!(m.transmittance(0.0), 1.0);
+        assert_approx_eq!(m.transmittance(2.0), Float::exp(-2.0));
+    }
+
+    #[test]
+    fn noise_varies_the_density() {
+        let m = Medium::new(2.0).with_noise(DensityNoise::new(3).with_frequency(2.0));
+        let samples: Vec<Float> = (0..50)
+            .map(|i| m.density_at(point(i as Float * 0.37, 0.1, -0.2)))
+            .collect();
+
+        assert!(!m.is_uniform());
+        assert!(samples.iter().all(|d| (0.0..=2.0).contains(d)));
+        assert!(samples.iter().any(|d| (d - samples[0]).abs() > 0.1));
+        assert_eq!(m.density_at(point(0.5, 0.5, 0.5)), m.density_at(point(0.5, 0.5, 0.5)));
+        assert_eq!(m.density_at(point(Float::INFINITY, 0.0, 0.0)), 0.0);
+        assert_eq!(m.density_at(point(Float::NAN, 0.0, 0.0)), 0.0);
+        assert_eq!(m.density_at(point(Float::MAX, 0.0, 0.0)), 0.0);
+    }
+
+    #[test]
+    fn threshold_leaves_gaps_between_clouds() {
+        let m = Medium::new(1.0).with_noise(DensityNoise::new(3).with_threshold(0.5));
+
+        assert!((0..50).any(|i| m.density_at(point(i as Float * 0.37, 0.0, 0.0)) == 0.0));
+        assert_eq!(Medium::new(1.0).density_at(point(9.0, 0.0, 0.0)), 1.0);
+    }
+
+    #[test]
+    fn empty_media_reach_forever() {
+        assert_eq!(Medium::new(0.0).reach(), Float::INFINITY);
+        assert_approx_eq!(Medium::new(1.0).transmittance(Medium::new(1.0).reach()), OPAQUE);
+    }
+}
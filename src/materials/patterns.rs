@@ -1,17 +1,58 @@
-use crate::core::Colour;
+use crate::{to_f32, to_f64, Float, FeorayError, EPSILON};
+use crate::core::{Camera, Colour, Rng};
+use crate::core::matrix::inverse;
+use crate::materials::{ColourRamp, Filter, TextureId, Textures, Wrap};
 use crate::primitives::Object;
-use nalgebra::{Matrix4, Vector4};
+use crate::scene::Yaml;
+use crate::scene::writer::pattern_to_yaml;
+use nalgebra::{Matrix3, Matrix4, Vector3, Vector4};
 use noise::{NoiseFn, Perlin};
-use std::f64::consts::PI;
+use crate::consts::PI;
+use std::sync::RwLock;
+
+// Every pattern used inside another one so far, by handle. Patterns are Copy,
+// so they hold a handle into this rather than the patterns themselves, the
+// same as with textures.
+static CHILDREN: RwLock<Vec<Pattern>> = RwLock::new(Vec::new());
 
 #[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
 pub struct Pattern {
     pattern: Patterns,
-    pub transform: Matrix4<f64>,
-    pub inverse_transform: Matrix4<f64>
+    pub transform: Matrix4<Float>,
+    pub inverse_transform: Matrix4<Float>,
+    /// Times the pattern repeats across one unit of u and v, on objects using
+    /// their uv manifold.
+    pub tiling: (Float, Float),
+    /// Shift of the pattern in u and v, in tiles, on objects using their uv
+    /// manifold.
+    pub offset: (Float, Float)
 }
 
 impl Pattern {
+    /// Constructs a wall of bricks over the pattern's x and z, the same as
+    /// the uv manifold, with mortar between them. Bricks are 1.0 along x and
+    /// 0.5 along z, with 0.05 of mortar, and every other row is shifted along
+    /// by half a brick, until told otherwise with with_bricks().
+    pub fn new_bricks(brick: Colour, mortar: Colour) -> Self {
+        Pattern {
+            pattern: Patterns::Bricks(BrickPattern {
+                brick,
+                mortar,
+                size: (1.0, 0.5),
+                gap: 0.05,
+                row_offset: 0.5,
+                jitter: None
+            }),
+            ..Default::default()
+        }
+    }
+
+    /// Constructs square tiles with grout between them: bricks one unit
+    /// square, with the rows lined up.
+    pub fn new_tiles(tile: Colour, grout: Colour) -> Self {
+        Pattern::new_bricks(tile, grout).with_bricks((1.0, 1.0), 0.05, 0.0)
+    }
+
     /// Constructs a checker pattern
     pub fn new_checkers(a: Colour, b: Colour) -> Self {
         Pattern {
@@ -20,10 +61,11 @@ impl Pattern {
         }
     }
 
-    /// Constructs a gradient pattern
+    /// Constructs a gradient pattern, from a to b along each unit of x. Takes
+    /// more colours with with_ramp().
     pub fn new_gradient(a: Colour, b: Colour) -> Self {
         Pattern {
-            pattern: Patterns::Gradient(GradientPattern { a, b, jitter: None }),
+            pattern: Patterns::Gradient(GradientPattern { ramp: ColourRamp::new(a, b), jitter: None }),
             ..Default::default()
         }
     }
@@ -36,10 +78,19 @@ impl Pattern {
         }
     }
 
+    /// Constructs a gradient that runs out from the y axis, from a to b over
+    /// each unit of distance, like a smooth version of rings.
+    pub fn new_radial_gradient(a: Colour, b: Colour) -> Self {
+        Pattern {
+            pattern: Patterns::RadialGradient(RadialGradientPattern { ramp: ColourRamp::new(a, b) }),
+            ..Default::default()
+        }
+    }
+
     /// Constructs a ring pattern
     pub fn new_rings(a: Colour, b: Colour) -> Self {
         Pattern {
-            pattern: Patterns::Rings(RingPattern { a, b }),
+            pattern: Patterns::Rings(RingPattern { a, b, width: 1.0 }),
             ..Default::default()
         }
     }
@@ -55,7 +106,69 @@ impl Pattern {
     /// Constructs a stripe pattern
     pub fn new_stripes(a: Colour, b: Colour) -> Self {
         Pattern {
-            pattern: Patterns::Stripes(StripePattern { a, b }),
+            pattern: Patterns::Stripes(StripePattern { a, b, direction: Vector3::x() }),
+            ..Default::default()
+        }
+    }
+
+    /// Constructs a pattern from an image in the texture cache, laid over the
+    /// unit square of the pattern's x and z, the same as the uv manifold. It
+    /// repeats and is filtered bilinearly until told otherwise.
+    pub fn new_texture(texture: TextureId) -> Self {
+        Pattern {
+            pattern: Patterns::Texture(TexturePattern { texture, wrap: Wrap::Repeat, filter: Filter::Bilinear }),
+            ..Default::default()
+        }
+    }
+
+    /// Constructs a slide projector throwing an image from a texture onto
+    /// whatever is in front of it, for light through a window, fake caustics
+    /// or a logo on a wall. The projector sits at the pattern's origin facing
+    /// down -z, the same as a camera, with the field of view spanning the
+    /// longer side of the image. Place it with e.g.
+    /// `with_transform(Matrix4::look_at(from, to, up))`. Unlike other patterns
+    /// it's placed in world space, so one projector lines up across every
+    /// object it's on. Where the image doesn't reach, the pattern is the
+    /// outside colour.
+    pub fn new_projection(texture: TextureId, fov: Float, outside: Colour) -> Self {
+        let image = Textures::get(texture);
+        let aspect = image.width.max(1) as Float / image.height.max(1) as Float;
+        Pattern {
+            pattern: Patterns::Projection(ProjectionPattern { texture, fov, aspect, outside, filter: Filter::Bilinear }),
+            ..Default::default()
+        }
+    }
+
+    /// Constructs a projection of an image from where a camera is, through
+    /// its field of view, so e.g. its own render lands back on the objects
+    /// it's of. The image is stretched to the camera's frame.
+    pub fn new_projection_from(camera: &Camera, texture: TextureId, outside: Colour) -> Self {
+        let mut pattern = Pattern::new_projection(texture, camera.fov, outside);
+        if let Patterns::Projection(ref mut projection) = pattern.pattern {
+            projection.aspect = camera.hsize as Float / camera.vsize as Float;
+        }
+        pattern.transform = camera.inverse_transform;
+        pattern.inverse_transform = camera.transform;
+
+        pattern
+    }
+
+    /// Constructs a pattern that's the average of two others. Each keeps its
+    /// own transform, on top of the blend's.
+    pub fn new_blend(a: Pattern, b: Pattern) -> Self {
+        Pattern {
+            pattern: Patterns::Blend(BlendPattern { a: PatternId::of(a), b: PatternId::of(b) }),
+            ..Default::default()
+        }
+    }
+
+    /// Constructs a pattern that shows a where the mask is black and b where
+    /// it's white, mixing them in between. A black and white stripe mask
+    /// gives stripes of two patterns, a gradient fades from one to the other.
+    /// Each keeps its own transform, on top of this one's.
+    pub fn new_nested(mask: Pattern, a: Pattern, b: Pattern) -> Self {
+        Pattern {
+            pattern: Patterns::Nested(NestedPattern { mask: PatternId::of(mask), a: PatternId::of(a), b: PatternId::of(b) }),
             ..Default::default()
         }
     }
@@ -68,35 +181,385 @@ impl Pattern {
         }
     }
 
-    fn pattern_at(&self, point: Vector4<f64>) -> Colour {
+    fn pattern_at(&self, point: Vector4<Float>) -> Colour {
         match &self.pattern {
+            Patterns::Blend(pattern) => pattern.pattern_at(point),
+            Patterns::Bricks(pattern) => pattern.pattern_at(point),
             Patterns::Checkers(pattern) => pattern.pattern_at(point),
             Patterns::Gradient(pattern) => pattern.pattern_at(point),
+            Patterns::Nested(pattern) => pattern.pattern_at(point),
+            Patterns::Projection(pattern) => pattern.pattern_at(point),
             Patterns::Radial(pattern) => pattern.pattern_at(point),
+            Patterns::RadialGradient(pattern) => pattern.pattern_at(point),
             Patterns::Rings(pattern) => pattern.pattern_at(point),
             Patterns::Solid(pattern) => pattern.pattern_at(point),
             Patterns::Stripes(pattern) => pattern.pattern_at(point),
-            Patterns::Test(pattern) => pattern.pattern_at(point)
+            Patterns::Test(pattern) => pattern.pattern_at(point),
+            Patterns::Texture(pattern) => pattern.pattern_at(point)
         }
     }
 
-    pub fn pattern_at_object(&self, object: Object, pos: Vector4<f64>) -> Colour {
-        let object_point = object.inverse_transform * pos;
-        let mut point = self.inverse_transform * object_point;
+    // Colour of the pattern as a child of another, at a point in the parent's
+    // space.
+    fn child_at(&self, point: Vector4<Float>) -> Colour {
+        self.pattern_at(self.inverse_transform * point)
+    }
+
+    /// Colour of the pattern at a point in world space, for patterns that
+    /// aren't on an object, such as a light's mask.
+    pub fn pattern_at_point(&self, pos: Vector4<Float>) -> Colour {
+        self.child_at(pos)
+    }
+
+    pub fn pattern_at_object(&self, object: &Object, pos: Vector4<Float>) -> Colour {
+        self.pattern_at(self.local_point(object, pos))
+    }
+
+    /// Same as pattern_at_object(), but averaged over a disc of the given
+    /// width in world space, centred on pos and lying flat on the surface with
+    /// the given normal. Stripes, checkers, rings and radial patterns fade to
+    /// their average in the distance rather than breaking up into moiré, and
+    /// are still sharp where the disc fits in one band. Other patterns aren't
+    /// filtered.
+    pub fn filtered_at_object(&self, object: &Object, pos: Vector4<Float>, normal: Vector4<Float>, width: Float) -> Colour {
+        let point = self.local_point(object, pos);
+        if width <= 0.0 || !width.is_finite() {
+            return self.pattern_at(point);
+        }
+        let w = self.footprint(object, normal, width);
+        let (a, b, odd) = match &self.pattern {
+            Patterns::Checkers(p) => {
+                // Odd squares are where an odd number of the axes are odd.
+                let even = (0..3)
+                    .map(|i| 1.0 - 2.0 * odd_share(point[i], w[i]))
+                    .product::<Float>();
+                (p.a, p.b, (1.0 - even) / 2.0)
+            },
+            Patterns::Stripes(p) => (p.a, p.b, odd_share(point.xyz().dot(&p.direction), w.component_mul(&p.direction).norm())),
+            Patterns::Rings(p) => {
+                let r = (point.x.powi(2) + point.z.powi(2)).sqrt();
+                (p.a, p.b, odd_share(r / p.width, w.x.max(w.z) / p.width))
+            },
+            Patterns::Radial(p) => {
+                let sector_size = PI / (p.n as Float);
+                let r = (point.x.powi(2) + point.z.powi(2)).sqrt();
+                let sector = (point.z.atan2(point.x) + PI) / sector_size;
+                (p.a, p.b, odd_share(sector, w.x.max(w.z) / (r * sector_size)))
+            },
+            _ => return self.pattern_at(point)
+        };
+        let odd = to_f32(odd);
+
+        a * (1.0 - odd) + b * odd
+    }
+
+    // Where a point on an object is in the pattern's own space, or in its
+    // tiled uv space on objects using their manifold.
+    fn local_point(&self, object: &Object, pos: Vector4<Float>) -> Vector4<Float> {
+        if let Patterns::Projection(_) = self.pattern {
+            return self.inverse_transform * pos;
+        }
+        // Objects keep their own pattern's transform combined with theirs,
+        // for as long as neither has changed since.
+        let mut point = match object.pattern_transform_for(&self.inverse_transform) {
+            Some(pattern_transform) => pattern_transform * pos,
+            None => self.inverse_transform * object.world_to_object(pos)
+        };
 
         if object.uv_manifold {
             point = object.uv_at(point);
+            point.x = point.x * self.tiling.0 + self.offset.0;
+            point.z = point.z * self.tiling.1 + self.offset.1;
         }
 
-        self.pattern_at(point)
+        point
+    }
+
+    // How far a disc of the given width on the surface reaches along each of
+    // the pattern's axes. Nothing along the normal, so a floor's checkers stay
+    // sharp up close, and the full width across it. On objects using their
+    // manifold, u and v are x and z.
+    fn footprint(&self, object: &Object, normal: Vector4<Float>, width: Float) -> Vector3<Float> {
+        let m = (self.inverse_transform * object.inverse_transform).fixed_view::<3, 3>(0, 0).into_owned();
+        let mut width = width * m.determinant().abs().cbrt();
+        let n = if object.uv_manifold {
+            width *= (self.tiling.0 * self.tiling.1).abs().sqrt();
+            Vector3::y()
+        } else {
+            let inverse = m.try_inverse().unwrap_or_else(Matrix3::identity);
+            (inverse.transpose() * normal.xyz()).try_normalize(0.0).unwrap_or_else(Vector3::zeros)
+        };
+
+        n.map(|n| width * (1.0 - n * n).max(0.0).sqrt())
     }
 
-    pub fn with_transform(&mut self, transform: Matrix4<f64>) -> Self {
+    /// Transforms the pattern relative to the object it's on. Panics if the
+    /// transform can't be inverted. See try_with_transform().
+    pub fn with_transform(&mut self, transform: impl Into<Matrix4<Float>>) -> Self {
+        self.try_with_transform(transform).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Same as with_transform(), but returns an error, leaving the pattern as
+    /// it was, if the transform can't be inverted.
+    pub fn try_with_transform(&mut self, transform: impl Into<Matrix4<Float>>) -> Result<Self, FeorayError> {
+        let transform = transform.into();
+        self.inverse_transform = inverse(&transform).ok_or(FeorayError::NonInvertible("pattern"))?;
         self.transform = transform;
-        self.inverse_transform = transform.try_inverse().unwrap();
+
+        Ok(*self)
+    }
+
+    /// Describes the pattern as it's written in a scene file, less its transform.
+    pub(crate) fn to_yaml(self) -> Yaml {
+        let colour = |c: &Colour| Yaml::Array(vec![c.r.into(), c.g.into(), c.b.into()]);
+        let pair = |kind: &str, a: &Colour, b: &Colour| vec![
+            (String::from("type"), kind.into()),
+            (String::from("colors"), Yaml::Array(vec![colour(a), colour(b)]))
+        ];
+        let ramp = |kind: &str, ramp: &ColourRamp| match ramp.pair() {
+            Some((a, b)) => pair(kind, &a, &b),
+            None => {
+                let stops = ramp.stops().iter().map(|(p, c)| Yaml::Array(vec![(*p).into(), colour(c)])).collect();
+                vec![
+                    (String::from("type"), kind.into()),
+                    (String::from("ramp"), Yaml::Hash(vec![
+                        (String::from("interpolation"), ramp.interpolation.name().into()),
+                        (String::from("stops"), Yaml::Array(stops))
+                    ]))
+                ]
+            }
+        };
+        let children = |ids: &[PatternId]| Yaml::Array(ids.iter().map(|id| pattern_to_yaml(&id.get())).collect());
+        let mut entries = match &self.pattern {
+            Patterns::Blend(p) => vec![
+                (String::from("type"), "blend".into()),
+                (String::from("patterns"), children(&[p.a, p.b]))
+            ],
+            Patterns::Bricks(p) => {
+                let mut entries = pair("bricks", &p.brick, &p.mortar);
+                entries.push((String::from("size"), Yaml::Array(vec![p.size.0.into(), p.size.1.into()])));
+                entries.push((String::from("mortar"), p.gap.into()));
+                entries.push((String::from("row-offset"), p.row_offset.into()));
+                if let Some(jitter) = p.jitter {
+                    let jitter = Yaml::Array(vec![(jitter.seed as Float).into(), jitter.amp.into()]);
+                    entries.push((String::from("jitter"), jitter));
+                }
+                entries
+            },
+            Patterns::Checkers(p) => pair("checkers", &p.a, &p.b),
+            Patterns::Gradient(p) => {
+                let mut entries = ramp("gradient", &p.ramp);
+                if let Some(jitter) = p.jitter {
+                    let jitter = Yaml::Array(vec![(jitter.seed as Float).into(), jitter.amp.into()]);
+                    entries.push((String::from("jitter"), jitter));
+                }
+                entries
+            },
+            Patterns::Nested(p) => vec![
+                (String::from("type"), "nested".into()),
+                (String::from("mask"), pattern_to_yaml(&p.mask.get())),
+                (String::from("patterns"), children(&[p.a, p.b]))
+            ],
+            Patterns::Projection(p) => {
+                let mut entries = vec![(String::from("type"), "projection".into())];
+                if let Some(path) = Textures::path(p.texture) {
+                    entries.push((String::from("file"), path.to_string_lossy().as_ref().into()));
+                }
+                entries.push((String::from("field-of-view"), p.fov.into()));
+                let image = Textures::get(p.texture);
+                if p.aspect != image.width.max(1) as Float / image.height.max(1) as Float {
+                    entries.push((String::from("aspect"), p.aspect.into()));
+                }
+                entries.push((String::from("outside"), colour(&p.outside)));
+                entries.push((String::from("filter"), p.filter.name().into()));
+                entries
+            },
+            Patterns::Radial(p) => {
+                let mut entries = pair("radial", &p.a, &p.b);
+                entries.push((String::from("count"), (p.n as Float).into()));
+                entries
+            },
+            Patterns::RadialGradient(p) => ramp("radial-gradient", &p.ramp),
+            Patterns::Rings(p) => {
+                let mut entries = pair("rings", &p.a, &p.b);
+                if p.width != 1.0 {
+                    entries.push((String::from("width"), p.width.into()));
+                }
+                entries
+            },
+            Patterns::Solid(p) => vec![
+                (String::from("type"), "solid".into()),
+                (String::from("colors"), Yaml::Array(vec![colour(&p.colour)]))
+            ],
+            Patterns::Stripes(p) => {
+                let mut entries = pair("stripes", &p.a, &p.b);
+                if p.direction != Vector3::x() {
+                    let d = p.direction;
+                    entries.push((String::from("direction"), Yaml::Array(vec![d.x.into(), d.y.into(), d.z.into()])));
+                }
+                entries
+            },
+            Patterns::Test(_) => vec![(String::from("type"), "test".into())],
+            Patterns::Texture(p) => {
+                let mut entries = vec![(String::from("type"), "texture".into())];
+                if let Some(path) = Textures::path(p.texture) {
+                    entries.push((String::from("file"), path.to_string_lossy().as_ref().into()));
+                }
+                entries.push((String::from("wrap"), p.wrap.name().into()));
+                entries.push((String::from("filter"), p.filter.name().into()));
+                entries
+            }
+        };
+
+        let uv = |(u, v): (Float, Float)| Yaml::Array(vec![u.into(), v.into()]);
+        if self.tiling != (1.0, 1.0) {
+            entries.push((String::from("tiling"), uv(self.tiling)));
+        }
+        if self.offset != (0.0, 0.0) {
+            entries.push((String::from("offset"), uv(self.offset)));
+        }
+
+        Yaml::Hash(entries)
+    }
+
+    /// The colour of a solid pattern, or None for any other pattern.
+    pub fn solid_colour(&self) -> Option<Colour> {
+        match self.pattern {
+            Patterns::Solid(p) => Some(p.colour),
+            _ => None
+        }
+    }
+
+    /// Applies noise jitter. The jitter seed fixes the noise, so the same seed
+    /// always gives the same result. Gradients are darkened by noise, and each
+    /// brick is made lighter or darker by up to the jitter's amplitude, so a
+    /// wall isn't all one flat colour. Other patterns are unchanged.
+    pub fn with_jitter(&mut self, jitter: Jitter) -> Self {
+        match self.pattern {
+            Patterns::Gradient(ref mut pattern) => pattern.jitter = Some(jitter),
+            Patterns::Bricks(ref mut pattern) => pattern.jitter = Some(jitter),
+            _ => ()
+        }
+
+        *self
+    }
+
+    /// Gives a gradient or radial gradient pattern the colours of a ramp, for
+    /// more than two colours or a different blend between them. Other
+    /// patterns are unchanged.
+    pub fn with_ramp(&mut self, ramp: ColourRamp) -> Self {
+        match self.pattern {
+            Patterns::Gradient(ref mut pattern) => pattern.ramp = ramp,
+            Patterns::RadialGradient(ref mut pattern) => pattern.ramp = ramp,
+            _ => ()
+        }
 
         *self
     }
+
+    /// Lays out a brick pattern: the size of each brick along x and z, the
+    /// width of the mortar between them, and how far along each row is
+    /// shifted from the one before, as a share of a brick. Other patterns are
+    /// unchanged.
+    pub fn with_bricks(&mut self, size: (Float, Float), mortar: Float, row_offset: Float) -> Self {
+        if let Patterns::Bricks(ref mut pattern) = self.pattern {
+            pattern.size = size;
+            pattern.gap = mortar;
+            pattern.row_offset = row_offset;
+        }
+
+        *self
+    }
+
+    /// Turns a stripe pattern so the colours change along the given direction
+    /// rather than along x, e.g. (0, 1, 0) for horizontal bands on a wall, or
+    /// (1, 0, 1) for diagonal ones. Stripes stay one unit wide. Other patterns,
+    /// and a zero direction, are left unchanged.
+    pub fn with_stripe_direction(&mut self, x: Float, y: Float, z: Float) -> Self {
+        let direction = Vector3::new(x, y, z);
+        if let (Patterns::Stripes(ref mut pattern), true) = (&mut self.pattern, direction.norm() > 0.0) {
+            // Leaves directions that are unit length already alone, so they
+            // come back from a scene file exactly as they were saved.
+            pattern.direction = if (direction.norm() - 1.0).abs() < EPSILON { direction } else { direction.normalize() };
+        }
+
+        *self
+    }
+
+    /// Sets the width over height of a projection pattern's frame, stretching
+    /// the image to fit. Other patterns are unchanged.
+    pub fn with_projection_aspect(&mut self, aspect: Float) -> Self {
+        if let Patterns::Projection(ref mut pattern) = self.pattern {
+            pattern.aspect = aspect;
+        }
+
+        *self
+    }
+
+    /// Sets how wide each ring of a ring pattern is. Other patterns are
+    /// unchanged.
+    pub fn with_ring_width(&mut self, width: Float) -> Self {
+        if let Patterns::Rings(ref mut pattern) = self.pattern {
+            pattern.width = width;
+        }
+
+        *self
+    }
+
+    /// Repeats the pattern the given number of times across one unit of u
+    /// and v, so e.g. a checkerboard floor can be sized in texture space.
+    /// Only affects objects using their uv manifold.
+    pub fn with_tiling(&mut self, u: Float, v: Float) -> Self {
+        self.tiling = (u, v);
+
+        *self
+    }
+
+    /// Shifts the pattern along u and v by the given number of tiles, to line
+    /// it up with the edges of a floor or a wall. Only affects objects using
+    /// their uv manifold.
+    pub fn with_offset(&mut self, u: Float, v: Float) -> Self {
+        self.offset = (u, v);
+
+        *self
+    }
+
+    /// Sets how a texture pattern repeats outside the unit square. Other
+    /// patterns are unchanged.
+    pub fn with_wrap(&mut self, wrap: Wrap) -> Self {
+        if let Patterns::Texture(ref mut pattern) = self.pattern {
+            pattern.wrap = wrap;
+        }
+
+        *self
+    }
+
+    /// Sets how a texture or projection pattern is read between pixels.
+    /// Other patterns are unchanged.
+    pub fn with_filter(&mut self, filter: Filter) -> Self {
+        match self.pattern {
+            Patterns::Texture(ref mut pattern) => pattern.filter = filter,
+            Patterns::Projection(ref mut pattern) => pattern.filter = filter,
+            _ => ()
+        }
+
+        *self
+    }
+
+    /// Mixes a render seed into the jitter's own, so that changing either
+    /// changes the noise. Seed 0 leaves the pattern as it is.
+    pub fn reseed(&mut self, seed: u64) {
+        let reseed = |jitter: &mut Option<Jitter>| {
+            if let Some(jitter) = jitter {
+                jitter.seed ^= Rng::fold_seed(seed);
+            }
+        };
+        match self.pattern {
+            Patterns::Gradient(ref mut pattern) => reseed(&mut pattern.jitter),
+            Patterns::Bricks(ref mut pattern) => reseed(&mut pattern.jitter),
+            _ => ()
+        }
+    }
 }
 
 impl Default for Pattern {
@@ -104,20 +567,117 @@ impl Default for Pattern {
         Pattern {
             pattern: Patterns::Solid(SolidPattern { colour: Colour::white() }),
             transform: Matrix4::identity(),
-            inverse_transform: Matrix4::identity()
+            inverse_transform: Matrix4::identity(),
+            tiling: (1.0, 1.0),
+            offset: (0.0, 0.0)
         }
     }
 }
 
+// Share of the span of the given width centred on x that lands on odd bands,
+// where bands are one unit wide and even ones start at 0. Exactly 0.0 or 1.0
+// when the span is all in one band.
+fn odd_share(x: Float, width: Float) -> Float {
+    if !width.is_finite() {
+        return 0.5;
+    }
+    let (lo, hi) = (x - width / 2.0, x + width / 2.0);
+    if lo.floor() == hi.floor() {
+        return lo.floor().rem_euclid(2.0);
+    }
+    // Length of odd bands from 0 up to x.
+    let odd_up_to = |x: Float| {
+        let pairs = (x / 2.0).floor();
+        pairs + (x - 2.0 * pairs - 1.0).max(0.0)
+    };
+
+    (odd_up_to(hi) - odd_up_to(lo)) / width
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
 enum Patterns {
+    Blend(BlendPattern),
+    Bricks(BrickPattern),
     Checkers(CheckerPattern),
     Gradient(GradientPattern),
+    Nested(NestedPattern),
+    Projection(ProjectionPattern),
     Radial(RadialPattern),
+    RadialGradient(RadialGradientPattern),
     Rings(RingPattern),
     Solid(SolidPattern),
     Stripes(StripePattern),
-    Test(TestPattern)
+    Test(TestPattern),
+    Texture(TexturePattern)
+}
+
+// Handle to a pattern used inside another one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd)]
+struct PatternId(usize);
+
+impl PatternId {
+    // Handle of the pattern, storing it if it isn't already. Loading the same
+    // scene again reuses what it stored the first time.
+    fn of(pattern: Pattern) -> Self {
+        let mut children = CHILDREN.write().unwrap_or_else(|err| err.into_inner());
+        if let Some(i) = children.iter().position(|p| *p == pattern) {
+            return PatternId(i);
+        }
+        children.push(pattern);
+
+        PatternId(children.len() - 1)
+    }
+
+    // A copy of the pattern, so the cache isn't locked while it's used, as it
+    // may have children of its own.
+    fn get(self) -> Pattern {
+        CHILDREN.read().unwrap_or_else(|err| err.into_inner())[self.0]
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub struct BlendPattern {
+    a: PatternId,
+    b: PatternId
+}
+
+impl BlendPattern {
+    fn pattern_at(&self, point: Vector4<Float>) -> Colour {
+        (self.a.get().child_at(point) + self.b.get().child_at(point)) / 2.0
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub struct BrickPattern {
+    brick: Colour,
+    mortar: Colour,
+    size: (Float, Float),
+    gap: Float,
+    row_offset: Float,
+    jitter: Option<Jitter>
+}
+
+impl BrickPattern {
+    fn pattern_at(&self, point: Vector4<Float>) -> Colour {
+        let (width, height) = self.size;
+        let row = (point.z / height).floor();
+        let x = point.x - row * self.row_offset * width;
+        let col = (x / width).floor();
+        // Mortar is half on each side of the joints.
+        let (u, v) = (x - col * width, point.z - row * height);
+        let half = self.gap / 2.0;
+        if u < half || u >= width - half || v < half || v >= height - half {
+            return self.mortar;
+        }
+        match self.jitter {
+            Some(jitter) => {
+                let brick = (col as i64 as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15) ^ row as i64 as u64;
+                let shade = Rng::for_stream(jitter.seed as u64, brick).next_float() * 2.0 - 1.0;
+                self.brick * (1.0 + shade * jitter.amp).max(0.0)
+            },
+            None => self.brick
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
@@ -127,7 +687,7 @@ pub struct CheckerPattern {
 }
 
 impl CheckerPattern {
-    fn pattern_at(&self, point: Vector4<f64>) -> Colour {
+    fn pattern_at(&self, point: Vector4<Float>) -> Colour {
         if (point.x.floor() + point.y.floor() + point.z.floor()) % 2.0 == 0.0 {
             self.a
         } else {
@@ -138,24 +698,77 @@ impl CheckerPattern {
 
 #[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
 pub struct GradientPattern {
-    a: Colour,
-    b: Colour,
+    ramp: ColourRamp,
     jitter: Option<Jitter>
 }
 
 impl GradientPattern {
-    fn pattern_at(&self, point: Vector4<f64>) -> Colour {
-        let gradient = self.a + (self.b - self.a) * (point.x - point.x.floor());
+    fn pattern_at(&self, point: Vector4<Float>) -> Colour {
+        let gradient = self.ramp.at(point.x - point.x.floor());
         let mut noise_colour = Colour::white();
-        if self.jitter.is_some() {
-            let perlin = Perlin::new(self.jitter.unwrap().seed);
-            let jitter = perlin.get([point.x, point.y, point.z]).abs() as f32;
-            noise_colour = Colour::new(jitter, jitter, jitter) * self.jitter.unwrap().amp as f32;
+        if let Some(jitter) = self.jitter {
+            let perlin = Perlin::new(jitter.seed);
+            let noise = perlin.get([to_f64(point.x), to_f64(point.y), to_f64(point.z)]).abs() as f32;
+            noise_colour = Colour::new(noise, noise, noise) * to_f32(jitter.amp);
         }
         gradient * noise_colour
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub struct NestedPattern {
+    mask: PatternId,
+    a: PatternId,
+    b: PatternId
+}
+
+impl NestedPattern {
+    fn pattern_at(&self, point: Vector4<Float>) -> Colour {
+        let mask = self.mask.get().child_at(point);
+        let mix = (mask.r + mask.g + mask.b) / 3.0;
+        // Only the side that shows is worked out, on the mask's hard edges.
+        if mix <= 0.0 {
+            self.a.get().child_at(point)
+        } else if mix >= 1.0 {
+            self.b.get().child_at(point)
+        } else {
+            self.a.get().child_at(point) * (1.0 - mix) + self.b.get().child_at(point) * mix
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub struct ProjectionPattern {
+    texture: TextureId,
+    fov: Float,
+    aspect: Float,
+    outside: Colour,
+    filter: Filter
+}
+
+impl ProjectionPattern {
+    fn pattern_at(&self, point: Vector4<Float>) -> Colour {
+        // Nothing is thrown backwards.
+        if point.z >= 0.0 {
+            return self.outside;
+        }
+        let half_view = (self.fov / 2.0).tan();
+        let (half_width, half_height) = if self.aspect >= 1.0 {
+            (half_view, half_view / self.aspect)
+        } else {
+            (half_view * self.aspect, half_view)
+        };
+        // The image's left edge is along +x, as it is for the camera.
+        let u = (1.0 - point.x / (-point.z * half_width)) / 2.0;
+        let v = (1.0 + point.y / (-point.z * half_height)) / 2.0;
+        if !(0.0..=1.0).contains(&u) || !(0.0..=1.0).contains(&v) {
+            return self.outside;
+        }
+
+        Textures::sample(self.texture, u, v, Wrap::Clamp, self.filter)
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
 pub struct RadialPattern {
     a: Colour,
@@ -164,9 +777,9 @@ pub struct RadialPattern {
 }
 
 impl RadialPattern {
-    fn pattern_at(&self, point: Vector4<f64>) -> Colour {
+    fn pattern_at(&self, point: Vector4<Float>) -> Colour {
         let angle = point.z.atan2(point.x);
-        let sector_size = PI / (self.n as f64);
+        let sector_size = PI / (self.n as Float);
         let sector_number = ((angle + PI)/sector_size).floor() as usize;
         if sector_number % 2 == 0 {
             self.a
@@ -176,15 +789,28 @@ impl RadialPattern {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub struct RadialGradientPattern {
+    ramp: ColourRamp
+}
+
+impl RadialGradientPattern {
+    fn pattern_at(&self, point: Vector4<Float>) -> Colour {
+        let r = (point.x.powi(2) + point.z.powi(2)).sqrt();
+        self.ramp.at(r - r.floor())
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
 pub struct RingPattern {
     a: Colour,
-    b: Colour
+    b: Colour,
+    width: Float
 }
 
 impl RingPattern {
-    fn pattern_at(&self, point: Vector4<f64>) -> Colour {
-        if (point.x.powi(2) + point.z.powi(2)).sqrt().floor() % 2.0 == 0.0 {
+    fn pattern_at(&self, point: Vector4<Float>) -> Colour {
+        if ((point.x.powi(2) + point.z.powi(2)).sqrt() / self.width).floor() % 2.0 == 0.0 {
             self.a
         } else {
             self.b
@@ -198,7 +824,7 @@ pub struct SolidPattern {
 }
 
 impl SolidPattern {
-    fn pattern_at(&self, _point: Vector4<f64>) -> Colour {
+    fn pattern_at(&self, _point: Vector4<Float>) -> Colour {
         self.colour
     }
 }
@@ -206,12 +832,13 @@ impl SolidPattern {
 #[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
 pub struct StripePattern {
     a: Colour,
-    b: Colour
+    b: Colour,
+    direction: Vector3<Float>
 }
 
 impl StripePattern {
-    fn pattern_at(&self, point: Vector4<f64>) -> Colour {
-        if point.x.floor() % 2.0 == 0.0 {
+    fn pattern_at(&self, point: Vector4<Float>) -> Colour {
+        if point.xyz().dot(&self.direction).floor().rem_euclid(2.0) == 0.0 {
             self.a
         } else {
             self.b
@@ -223,46 +850,40 @@ impl StripePattern {
 pub struct TestPattern {}
 
 impl TestPattern {
-    fn pattern_at(&self, point: Vector4<f64>) -> Colour {
-        Colour::new(point.x as f32, point.y as f32, point.z as f32)
+    fn pattern_at(&self, point: Vector4<Float>) -> Colour {
+        Colour::new(to_f32(point.x), to_f32(point.y), to_f32(point.z))
     }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
-pub struct Jitter {
-    seed: u32,
-    amp: f64
+pub struct TexturePattern {
+    texture: TextureId,
+    wrap: Wrap,
+    filter: Filter
 }
 
-impl Jitter {
-    pub fn new(seed: u32, amp: f64) -> Self {
-        Self { seed, amp }
+impl TexturePattern {
+    fn pattern_at(&self, point: Vector4<Float>) -> Colour {
+        Textures::sample(self.texture, point.x, point.z, self.wrap, self.filter)
     }
 }
 
-// Can't piggyback off Pattern. Must include it as a Pattern, which gets very messy.
-// It's the same issue as nested patterns.
-/*pub struct BlendedPattern {
-    a: Pattern,
-    b: Pattern
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub struct Jitter {
+    seed: u32,
+    amp: Float
 }
 
-impl BlendedPattern {
-    /// Blend two patterns together. You probably don't want to do more than that.
-    pub fn new(a: Pattern, b: Pattern) -> Self {
-        BlendedPattern { a, b }
-    }
-
-    fn pattern_at(&self, point: Vector4<f64>) -> Colour {
-        let colour_a = self.a.pattern_at(point);
-        let colour_b = self.b.pattern_at(point);
-        (colour_a + colour_b) / 2.0
+impl Jitter {
+    pub fn new(seed: u32, amp: Float) -> Self {
+        Self { seed, amp }
     }
-}*/
+}
 
 #[cfg(test)]
 mod tests {
-    use crate::core::{point, Transform};
+    use crate::core::{point, vector, Canvas, Transform};
+    use crate::materials::Material;
 
     use super::*;
 
@@ -270,7 +891,8 @@ mod tests {
     fn creating_stripe_pattern() {
         let pattern = StripePattern {
             a: Colour::white(),
-            b: Colour::black()
+            b: Colour::black(),
+            direction: Vector3::x()
         };
 
         assert_eq!(pattern.a, Colour::white());
@@ -281,7 +903,8 @@ mod tests {
     fn stripe_pattern_is_constant_in_y() {
         let pattern = StripePattern {
             a: Colour::white(),
-            b: Colour::black()
+            b: Colour::black(),
+            direction: Vector3::x()
         };
 
         assert_eq!(pattern.pattern_at(point(0.0, 0.0, 0.0)), Colour::white());
@@ -293,7 +916,8 @@ mod tests {
     fn stripe_pattern_is_constant_in_z() {
         let pattern = StripePattern {
             a: Colour::white(),
-            b: Colour::black()
+            b: Colour::black(),
+            direction: Vector3::x()
         };
 
         assert_eq!(pattern.pattern_at(point(0.0, 0.0, 0.0)), Colour::white());
@@ -305,7 +929,8 @@ mod tests {
     fn stripe_pattern_alternates_in_x() {
         let pattern = StripePattern {
             a: Colour::white(),
-            b: Colour::black()
+            b: Colour::black(),
+            direction: Vector3::x()
         };
 
         assert_eq!(pattern.pattern_at(point(0.0, 0.0, 0.0)), Colour::white());
@@ -322,7 +947,7 @@ mod tests {
             .with_transform(Matrix4::uscale(2.0));
         let pattern = Pattern::new_stripes(Colour::white(), Colour::black());
 
-        assert_eq!(pattern.pattern_at_object(object, point(1.5, 0.0, 0.0)), Colour::white());
+        assert_eq!(pattern.pattern_at_object(&object, point(1.5, 0.0, 0.0)), Colour::white());
     }
 
     #[test]
@@ -331,7 +956,7 @@ mod tests {
         let pattern = Pattern::new_stripes(Colour::white(), Colour::black())
             .with_transform(Matrix4::uscale(2.0));
 
-        assert_eq!(pattern.pattern_at_object(object, point(1.5, 0.0, 0.0)), Colour::white())
+        assert_eq!(pattern.pattern_at_object(&object, point(1.5, 0.0, 0.0)), Colour::white())
     }
 
     #[test]
@@ -341,7 +966,7 @@ mod tests {
         let pattern = Pattern::new_stripes(Colour::white(), Colour::black())
             .with_transform(Matrix4::translate(0.5, 0.0, 0.0));
 
-        assert_eq!(pattern.pattern_at_object(object, point(2.5, 0.0, 0.0)), Colour::white());
+        assert_eq!(pattern.pattern_at_object(&object, point(2.5, 0.0, 0.0)), Colour::white());
     }
 
     #[test]
@@ -365,7 +990,7 @@ mod tests {
             .with_transform(Matrix4::uscale(2.0));
         let pattern = Pattern::new_test();
 
-        assert_eq!(pattern.pattern_at_object(object, point(2.0, 3.0, 4.0)), Colour::new(1.0, 1.5, 2.0));
+        assert_eq!(pattern.pattern_at_object(&object, point(2.0, 3.0, 4.0)), Colour::new(1.0, 1.5, 2.0));
     }
 
     #[test]
@@ -374,7 +999,7 @@ mod tests {
         let pattern = Pattern::new_test()
             .with_transform(Matrix4::uscale(2.0));
 
-        assert_eq!(pattern.pattern_at_object(object, point(2.0, 3.0, 4.0)), Colour::new(1.0, 1.5, 2.0));
+        assert_eq!(pattern.pattern_at_object(&object, point(2.0, 3.0, 4.0)), Colour::new(1.0, 1.5, 2.0));
     }
 
     #[test]
@@ -384,7 +1009,7 @@ mod tests {
         let pattern = Pattern::new_test()
             .with_transform(Matrix4::translate(0.5, 1.0, 1.5));
 
-        assert_eq!(pattern.pattern_at_object(object, point(2.5, 3.0, 3.5)), Colour::new(0.75, 0.5, 0.25));
+        assert_eq!(pattern.pattern_at_object(&object, point(2.5, 3.0, 3.5)), Colour::new(0.75, 0.5, 0.25));
     }
 
     #[test]
@@ -433,4 +1058,231 @@ mod tests {
         assert_eq!(pattern.pattern_at(point(0.0, 0.0, 0.99)), Colour::white());
         assert_eq!(pattern.pattern_at(point(0.0, 0.0, 1.01)), Colour::black());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn jitter_is_reproducible_for_a_seed() {
+        let a = Pattern::new_gradient(Colour::white(), Colour::black())
+            .with_jitter(Jitter::new(7, 1.0));
+        let b = Pattern::new_gradient(Colour::white(), Colour::black())
+            .with_jitter(Jitter::new(7, 1.0));
+        let p = point(0.3, 0.6, 0.9);
+
+        assert_eq!(a.pattern_at(p), b.pattern_at(p));
+        assert_ne!(a.pattern_at(p), Pattern::new_gradient(Colour::white(), Colour::black()).pattern_at(p));
+    }
+
+    #[test]
+    fn singular_pattern_transforms_are_refused() {
+        let mut pattern = Pattern::new_stripes(Colour::white(), Colour::black());
+
+        assert!(pattern.try_with_transform(Matrix4::nuscale(0.0, 1.0, 1.0)).is_err());
+        assert_eq!(pattern.inverse_transform, Matrix4::identity());
+    }
+
+    #[test]
+    fn textures_are_laid_over_x_and_z() {
+        let mut image = Canvas::new(2, 1, Colour::red());
+        image.write_pix(1, 0, Colour::blue());
+        let mut pattern = Pattern::new_texture(Textures::add(image)).with_filter(Filter::Nearest);
+
+        assert_eq!(pattern.pattern_at(point(0.25, 5.0, 0.5)), Colour::red());
+        assert_eq!(pattern.pattern_at(point(1.75, 0.0, 0.5)), Colour::blue());
+        pattern.with_wrap(Wrap::Clamp);
+        assert_eq!(pattern.pattern_at(point(-0.75, 0.0, 0.5)), Colour::red());
+        assert_eq!(pattern.pattern_at(point(2.25, 0.0, 0.5)), Colour::blue());
+        assert_eq!(Pattern::new_stripes(Colour::white(), Colour::black()).with_wrap(Wrap::Clamp),
+            Pattern::new_stripes(Colour::white(), Colour::black()));
+    }
+
+    #[test]
+    fn tiling_and_offset_work_in_uv_space() {
+        let floor = Object::new_plane()
+            .with_material(Material::default().with_pattern(Pattern::new_checkers(Colour::white(), Colour::black())))
+            .use_manifold();
+        let pattern = floor.material.pattern;
+        let tiled = Pattern::new_checkers(Colour::white(), Colour::black()).with_tiling(2.0, 2.0).with_offset(0.5, 0.0);
+
+        // Just under the surface is still the same square.
+        assert_eq!(pattern.pattern_at_object(&floor, point(0.5, -0.00001, 0.5)), Colour::white());
+        assert_eq!(tiled.pattern_at_object(&floor, point(0.1, 0.0, 0.1)), Colour::white());
+        assert_eq!(tiled.pattern_at_object(&floor, point(0.3, 0.0, 0.1)), Colour::black());
+        assert_eq!(tiled.pattern_at_object(&floor, point(0.3, 0.0, 0.6)), Colour::white());
+        // Off the manifold, they're ignored.
+        let mut solid = floor.clone();
+        solid.uv_manifold = false;
+        assert_eq!(tiled.pattern_at_object(&solid, point(0.3, 0.0, 0.1)), Colour::white());
+    }
+
+    #[test]
+    fn filtered_checkers_fade_to_grey_in_the_distance() {
+        let floor = Object::new_plane();
+        let pattern = Pattern::new_checkers(Colour::white(), Colour::black());
+        let up = vector(0.0, 1.0, 0.0);
+        let p = point(0.5, 0.0001, 0.5);
+
+        // Up close, it's as sharp as ever, even just above the surface.
+        assert_eq!(pattern.filtered_at_object(&floor, p, up, 0.0), Colour::white());
+        assert_eq!(pattern.filtered_at_object(&floor, p, up, 0.5), Colour::white());
+        assert_eq!(pattern.filtered_at_object(&floor, point(1.5, 0.0001, 0.5), up, 0.5), Colour::black());
+        // Far off, many squares fall under one pixel.
+        assert_eq!(pattern.filtered_at_object(&floor, p, up, 20.0), Colour::grey(0.5));
+    }
+
+    #[test]
+    fn filtered_stripes_blend_across_edges() {
+        let object = Object::default();
+        let pattern = Pattern::new_stripes(Colour::white(), Colour::black());
+        let facing = vector(0.0, 0.0, -1.0);
+
+        assert_eq!(pattern.filtered_at_object(&object, point(1.0, 0.0, 0.0), facing, 0.5), Colour::grey(0.5));
+        assert_eq!(pattern.filtered_at_object(&object, point(1.125, 0.0, 0.0), facing, 0.5), Colour::grey(0.25));
+        // Side on, the footprint never crosses an edge.
+        assert_eq!(pattern.filtered_at_object(&object, point(1.0, 0.0, 0.0), vector(1.0, 0.0, 0.0), 0.5),
+            pattern.pattern_at(point(1.0, 0.0, 0.0)));
+    }
+
+    #[test]
+    fn blended_patterns_average_their_children() {
+        let stripes = Pattern::new_stripes(Colour::white(), Colour::black());
+        let across = Pattern::new_stripes(Colour::white(), Colour::black())
+            .with_transform(Matrix4::rot_y(PI / 2.0));
+        let blend = Pattern::new_blend(stripes, across);
+
+        assert_eq!(blend.pattern_at(point(0.5, 0.0, -0.5)), Colour::white());
+        assert_eq!(blend.pattern_at(point(1.5, 0.0, -0.5)), Colour::grey(0.5));
+        assert_eq!(blend.pattern_at(point(1.5, 0.0, 0.5)), Colour::black());
+    }
+
+    #[test]
+    fn nested_patterns_follow_their_mask() {
+        let mask = Pattern::new_checkers(Colour::black(), Colour::white());
+        let nested = Pattern::new_nested(mask, Pattern::new_solid(Colour::red()), Pattern::new_solid(Colour::blue()));
+        let faded = Pattern::new_nested(Pattern::new_gradient(Colour::black(), Colour::white()),
+            Pattern::new_solid(Colour::red()), Pattern::new_solid(Colour::blue()));
+        let material = Material::default().with_pattern(nested);
+        let copy = material;
+
+        assert_eq!(nested.pattern_at(point(0.5, 0.0, 0.5)), Colour::red());
+        assert_eq!(nested.pattern_at(point(1.5, 0.0, 0.5)), Colour::blue());
+        assert_eq!(faded.pattern_at(point(0.25, 0.0, 0.0)), Colour::new(0.75, 0.0, 0.25));
+        assert_eq!(copy, material);
+        // Building the same pattern again reuses its children.
+        assert_eq!(Pattern::new_nested(mask, Pattern::new_solid(Colour::red()), Pattern::new_solid(Colour::blue())), nested);
+    }
+
+    #[test]
+    fn radial_gradients_fade_out_from_the_y_axis() {
+        let pattern = Pattern::new_radial_gradient(Colour::white(), Colour::black());
+
+        assert_eq!(pattern.pattern_at(point(0.0, 3.0, 0.0)), Colour::white());
+        assert_eq!(pattern.pattern_at(point(0.0, 0.0, 0.25)), Colour::grey(0.75));
+        assert_eq!(pattern.pattern_at(point(0.3, 0.0, 0.4)), Colour::grey(0.5));
+        assert_eq!(pattern.pattern_at(point(1.0, 0.0, 0.0)), Colour::white());
+    }
+
+    #[test]
+    fn rings_can_be_any_width() {
+        let pattern = Pattern::new_rings(Colour::white(), Colour::black()).with_ring_width(0.5);
+
+        assert_eq!(pattern.pattern_at(point(0.4, 0.0, 0.0)), Colour::white());
+        assert_eq!(pattern.pattern_at(point(0.6, 0.0, 0.0)), Colour::black());
+        assert_eq!(pattern.pattern_at(point(0.0, 0.0, 1.1)), Colour::white());
+        assert_eq!(Pattern::new_stripes(Colour::white(), Colour::black()).with_ring_width(0.5),
+            Pattern::new_stripes(Colour::white(), Colour::black()));
+    }
+
+    #[test]
+    fn bricks_are_laid_in_offset_rows() {
+        let pattern = Pattern::new_bricks(Colour::red(), Colour::white());
+
+        assert_eq!(pattern.pattern_at(point(0.5, 0.0, 0.25)), Colour::red());
+        assert_eq!(pattern.pattern_at(point(0.01, 0.0, 0.25)), Colour::white());
+        assert_eq!(pattern.pattern_at(point(0.5, 0.0, 0.49)), Colour::white());
+        // The next row's joint is half a brick along.
+        assert_eq!(pattern.pattern_at(point(0.01, 0.0, 0.75)), Colour::red());
+        assert_eq!(pattern.pattern_at(point(0.51, 0.0, 0.75)), Colour::white());
+        let tiles = Pattern::new_tiles(Colour::red(), Colour::white());
+        assert_eq!(tiles.pattern_at(point(0.5, 0.0, 0.5)), Colour::red());
+        assert_eq!(tiles.pattern_at(point(0.5, 0.0, 1.01)), Colour::white());
+    }
+
+    #[test]
+    fn brick_jitter_shades_each_brick_on_its_own() {
+        let pattern = Pattern::new_bricks(Colour::grey(0.5), Colour::white()).with_jitter(Jitter::new(3, 0.5));
+        let shade = |x, z| pattern.pattern_at(point(x, 0.0, z)).r;
+
+        assert_eq!(shade(0.2, 0.1), shade(0.8, 0.4));
+        assert_ne!(shade(0.5, 0.25), shade(1.5, 0.25));
+        assert!((1..20).all(|i| (0.25..=0.75).contains(&shade(i as Float + 0.5, 0.25))));
+        assert_eq!(pattern.pattern_at(point(0.01, 0.0, 0.25)), Colour::white());
+    }
+
+    #[test]
+    fn stripes_can_run_along_any_direction() {
+        let mut pattern = Pattern::new_stripes(Colour::white(), Colour::black());
+        let unchanged = pattern.with_stripe_direction(0.0, 0.0, 0.0);
+        let bands = pattern.with_stripe_direction(0.0, 1.0, 0.0);
+
+        assert_eq!(unchanged, Pattern::new_stripes(Colour::white(), Colour::black()));
+        assert_eq!(bands.pattern_at(point(5.0, 0.5, 0.0)), Colour::white());
+        assert_eq!(bands.pattern_at(point(0.0, 1.5, 0.0)), Colour::black());
+        let diagonal = Pattern::new_stripes(Colour::white(), Colour::black()).with_stripe_direction(1.0, 0.0, 1.0);
+        assert_eq!(diagonal.pattern_at(point(0.5, 0.0, 0.5)), Colour::white());
+        assert_eq!(diagonal.pattern_at(point(0.5, 0.0, 0.3)), Colour::white());
+        assert_eq!(diagonal.pattern_at(point(1.0, 0.0, 0.5)), Colour::black());
+        assert_eq!(diagonal.pattern_at(point(0.9, 0.0, -0.9)), Colour::white());
+    }
+
+    #[test]
+    fn gradients_can_follow_a_colour_ramp() {
+        let ramp = ColourRamp::new(Colour::black(), Colour::white()).with_stop(0.5, Colour::red());
+        let gradient = Pattern::new_gradient(Colour::white(), Colour::black()).with_ramp(ramp);
+        let radial = Pattern::new_radial_gradient(Colour::white(), Colour::black()).with_ramp(ramp);
+
+        assert_eq!(gradient.pattern_at(point(0.25, 0.0, 0.0)), Colour::new(0.5, 0.0, 0.0));
+        assert_eq!(gradient.pattern_at(point(1.5, 0.0, 0.0)), Colour::red());
+        assert_eq!(radial.pattern_at(point(0.0, 0.0, 0.75)), Colour::new(1.0, 0.5, 0.5));
+        assert_eq!(Pattern::new_stripes(Colour::white(), Colour::black()).with_ramp(ramp),
+                   Pattern::new_stripes(Colour::white(), Colour::black()));
+    }
+
+    #[test]
+    fn projections_land_where_the_camera_sees_each_pixel() {
+        let mut image = Canvas::new(4, 2, Colour::red());
+        image.write_pix(0, 0, Colour::blue());
+        image.write_pix(3, 1, Colour::green());
+        let camera = Camera::new(4, 2, PI / 2.0)
+            .with_transform(Matrix4::view_transform(point(0.0, 1.0, -5.0), point(0.0, 0.0, 0.0), vector(0.0, 1.0, 0.0)));
+        let pattern = Pattern::new_projection_from(&camera, Textures::add(image), Colour::white()).with_filter(Filter::Nearest);
+        // Projections ignore the object's own transform.
+        let object = Object::new_sphere().with_transform(Matrix4::translate(2.0, 0.0, 0.0));
+        let seen = |px, py| {
+            let ray = camera.ray_for_pixel(px, py);
+            pattern.pattern_at_object(&object, ray.origin + ray.direction * 3.0)
+        };
+
+        assert_eq!(seen(0, 0), Colour::blue());
+        assert_eq!(seen(3, 1), Colour::green());
+        assert_eq!(seen(2, 0), Colour::red());
+        assert_eq!(pattern.pattern_at_object(&object, point(0.0, 1.0, -6.0)), Colour::white());
+        assert_eq!(pattern.pattern_at_object(&object, point(0.0, 5.0, 0.0)), Colour::white());
+    }
+
+    #[test]
+    fn projectors_face_down_their_z_axis() {
+        let mut image = Canvas::new(2, 1, Colour::red());
+        image.write_pix(1, 0, Colour::blue());
+        let projector = Matrix4::look_at(point(0.0, 10.0, 0.0), point(0.0, 0.0, 0.0), vector(0.0, 0.0, 1.0));
+        let mut pattern = Pattern::new_projection(Textures::add(image), PI / 2.0, Colour::black())
+            .with_filter(Filter::Nearest)
+            .with_transform(projector);
+        let floor = Object::new_plane();
+
+        // Looking down with z up, the image's left is towards -x.
+        assert_eq!(pattern.pattern_at_object(&floor, point(-5.0, 0.0, 0.0)), Colour::red());
+        assert_eq!(pattern.pattern_at_object(&floor, point(5.0, 0.0, 0.0)), Colour::blue());
+        assert_eq!(pattern.pattern_at_object(&floor, point(5.0, 0.0, 7.0)), Colour::black());
+        assert_eq!(pattern.pattern_at_object(&floor, point(11.0, 0.0, 0.0)), Colour::black());
+        assert_eq!(pattern.with_projection_aspect(1.0).pattern_at_object(&floor, point(5.0, 0.0, 7.0)), Colour::blue());
+    }
+}
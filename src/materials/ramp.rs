@@ -0,0 +1,194 @@
+use crate::{to_f32, Float};
+use crate::core::Colour;
+
+/// Most stops a colour ramp can hold. Ramps are kept inline so patterns stay
+/// Copy.
+pub const MAX_STOPS: usize = 8;
+
+/// How colours are blended between the stops of a ramp.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd)]
+pub enum Interpolation {
+    /// Holds each stop's colour up to the next one, for hard bands.
+    Constant,
+    /// Fades evenly from one stop to the next.
+    Linear,
+    /// Fades with a smoothstep, easing in and out of each stop.
+    Smooth
+}
+
+impl Interpolation {
+    pub const ALL: [Interpolation; 3] = [Interpolation::Constant, Interpolation::Linear, Interpolation::Smooth];
+
+    /// Name used in scene files.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Interpolation::Constant => "constant",
+            Interpolation::Linear => "linear",
+            Interpolation::Smooth => "smooth"
+        }
+    }
+}
+
+/// Colours at positions from 0 to 1, for patterns that blend between more than
+/// two colours, such as gradients.
+///
+/// # Example
+///
+/// ```ignore
+/// let fire = ColourRamp::new(Colour::black(), Colour::white())
+///     .with_stop(0.4, Colour::red())
+///     .with_stop(0.7, Colour::new(1.0, 0.8, 0.0));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub struct ColourRamp {
+    stops: [(Float, Colour); MAX_STOPS],
+    len: usize,
+    pub interpolation: Interpolation
+}
+
+impl ColourRamp {
+    /// A linear ramp from a at 0 to b at 1.
+    pub fn new(a: Colour, b: Colour) -> Self {
+        ColourRamp::from_stops(&[(0.0, a), (1.0, b)])
+    }
+
+    /// A linear ramp through the given positions and colours, as if each was
+    /// added with with_stop(). With no stops at all the ramp is black.
+    pub fn from_stops(stops: &[(Float, Colour)]) -> Self {
+        // Unused stops are always the same, so equal ramps compare equal.
+        let empty = ColourRamp { stops: [(0.0, Colour::black()); MAX_STOPS], len: 0, interpolation: Interpolation::Linear };
+
+        stops.iter().fold(empty, |ramp, &(position, colour)| ramp.with_stop(position, colour))
+    }
+
+    /// Adds a stop, with its position clamped to between 0 and 1. A stop at
+    /// the same position as another goes after it, so the two make a hard
+    /// edge. Once the ramp holds MAX_STOPS, further stops are ignored.
+    pub fn with_stop(mut self, position: Float, colour: Colour) -> Self {
+        if self.len < MAX_STOPS {
+            let position = position.clamp(0.0, 1.0);
+            let i = self.stops().iter().position(|&(p, _)| p > position).unwrap_or(self.len);
+            self.stops.copy_within(i..self.len, i + 1);
+            self.stops[i] = (position, colour);
+            self.len += 1;
+        }
+
+        self
+    }
+
+    pub fn with_interpolation(mut self, interpolation: Interpolation) -> Self {
+        self.interpolation = interpolation;
+
+        self
+    }
+
+    /// The stops in order of position.
+    pub fn stops(&self) -> &[(Float, Colour)] {
+        &self.stops[..self.len]
+    }
+
+    /// Colour of the ramp at t. Before the first stop it's the first stop's
+    /// colour, and after the last it's the last's.
+    pub fn at(&self, t: Float) -> Colour {
+        let stops = self.stops();
+        // The first stop past t, so equal stops step straight to the later one.
+        let i = stops.iter().position(|&(p, _)| p > t).unwrap_or(stops.len());
+        if i == 0 {
+            return stops.first().map_or(Colour::black(), |s| s.1);
+        }
+        let (p0, a) = stops[i - 1];
+        let Some(&(p1, b)) = stops.get(i) else {
+            return a;
+        };
+        let f = (t - p0) / (p1 - p0);
+        let f = match self.interpolation {
+            Interpolation::Constant => return a,
+            Interpolation::Linear => f,
+            Interpolation::Smooth => f * f * (3.0 - 2.0 * f)
+        };
+
+        a + (b - a) * to_f32(f)
+    }
+
+    // The two colours of a plain linear ramp from 0 to 1, as made by new(), or
+    // None for any other ramp.
+    pub(crate) fn pair(&self) -> Option<(Colour, Colour)> {
+        match self.stops() {
+            [(p0, a), (p1, b)] if *p0 == 0.0 && *p1 == 1.0 && self.interpolation == Interpolation::Linear => Some((*a, *b)),
+            _ => None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ramp() -> ColourRamp {
+        ColourRamp::new(Colour::black(), Colour::white()).with_stop(0.5, Colour::red())
+    }
+
+    #[test]
+    fn stops_are_kept_in_order() {
+        let positions: Vec<Float> = ramp().with_stop(0.25, Colour::blue()).stops().iter().map(|s| s.0).collect();
+
+        assert_eq!(positions, vec![0.0, 0.25, 0.5, 1.0]);
+        assert_eq!(ramp().with_stop(2.0, Colour::blue()).stops()[3], (1.0, Colour::blue()));
+    }
+
+    #[test]
+    fn ramps_blend_between_neighbouring_stops() {
+        let ramp = ramp();
+
+        assert_eq!(ramp.at(0.0), Colour::black());
+        assert_eq!(ramp.at(0.25), Colour::new(0.5, 0.0, 0.0));
+        assert_eq!(ramp.at(0.5), Colour::red());
+        assert_eq!(ramp.at(0.75), Colour::new(1.0, 0.5, 0.5));
+        assert_eq!(ramp.at(-1.0), Colour::black());
+        assert_eq!(ramp.at(2.0), Colour::white());
+    }
+
+    #[test]
+    fn interpolation_changes_how_stops_are_blended() {
+        let constant = ramp().with_interpolation(Interpolation::Constant);
+        let smooth = ramp().with_interpolation(Interpolation::Smooth);
+
+        assert_eq!(constant.at(0.49), Colour::black());
+        assert_eq!(constant.at(0.5), Colour::red());
+        assert_eq!(constant.at(1.0), Colour::white());
+        assert_eq!(smooth.at(0.25), Colour::new(0.5, 0.0, 0.0));
+        assert_eq!(smooth.at(0.125), Colour::new(0.15625, 0.0, 0.0));
+    }
+
+    #[test]
+    fn stops_at_the_same_place_make_a_hard_edge() {
+        let ramp = ColourRamp::new(Colour::black(), Colour::white())
+            .with_stop(0.5, Colour::red())
+            .with_stop(0.5, Colour::blue());
+
+        assert_eq!(ramp.at(0.5), Colour::blue());
+        assert_eq!(ramp.at(0.25), Colour::new(0.5, 0.0, 0.0));
+    }
+
+    #[test]
+    fn full_ramps_ignore_further_stops() {
+        let mut ramp = ColourRamp::new(Colour::black(), Colour::white());
+        for i in 0..10 {
+            ramp = ramp.with_stop(0.1 * i as Float, Colour::red());
+        }
+
+        assert_eq!(ramp.stops().len(), MAX_STOPS);
+        assert!(ramp.pair().is_none());
+        assert_eq!(ColourRamp::new(Colour::black(), Colour::white()).pair(), Some((Colour::black(), Colour::white())));
+    }
+
+    #[test]
+    fn ramps_can_be_made_from_a_list_of_stops() {
+        let ramp = ColourRamp::from_stops(&[(0.8, Colour::white()), (0.5, Colour::red()), (0.0, Colour::black())]);
+
+        assert_eq!(ramp.stops(), &[(0.0, Colour::black()), (0.5, Colour::red()), (0.8, Colour::white())]);
+        assert_eq!(ramp, ColourRamp::from_stops(&[(0.0, Colour::black()), (0.8, Colour::white()), (0.5, Colour::red())]));
+        assert_eq!(ramp.at(0.9), Colour::white());
+        assert_eq!(ColourRamp::from_stops(&[]).at(0.5), Colour::black());
+    }
+}
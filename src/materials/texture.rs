@@ -0,0 +1,242 @@
+use crate::{to_f32, Float};
+use crate::core::{Canvas, Colour};
+#[cfg(feature = "image")]
+use image::ImageResult;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+// Every texture loaded or added so far, by handle. Patterns are Copy, so they
+// hold a handle into this rather than the image itself.
+static TEXTURES: RwLock<Vec<Texture>> = RwLock::new(Vec::new());
+
+struct Texture {
+    path: Option<PathBuf>,
+    canvas: Arc<Canvas>
+}
+
+/// Handle to an image in the texture cache, for texture patterns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct TextureId(usize);
+
+/// How a texture is repeated outside the unit square.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd)]
+pub enum Wrap {
+    /// Tiles the image over and over.
+    Repeat,
+    /// Stretches the edge pixels out forever.
+    Clamp,
+    /// Tiles the image, flipping every other copy so the edges meet up.
+    Mirror
+}
+
+/// How colours are read between the centres of a texture's pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd)]
+pub enum Filter {
+    /// The nearest pixel, for a blocky look when magnified.
+    Nearest,
+    /// Blends the four nearest pixels.
+    Bilinear
+}
+
+impl Wrap {
+    pub const ALL: [Wrap; 3] = [Wrap::Repeat, Wrap::Clamp, Wrap::Mirror];
+
+    /// Name used in scene files.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Wrap::Repeat => "repeat",
+            Wrap::Clamp => "clamp",
+            Wrap::Mirror => "mirror"
+        }
+    }
+}
+
+impl Filter {
+    pub const ALL: [Filter; 2] = [Filter::Nearest, Filter::Bilinear];
+
+    /// Name used in scene files.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Filter::Nearest => "nearest",
+            Filter::Bilinear => "bilinear"
+        }
+    }
+}
+
+/// The texture cache, shared by every world and pattern. Each image is kept
+/// once however many objects use it.
+pub struct Textures;
+
+impl Textures {
+    /// Adds an image already in memory, e.g. a render to use as a texture.
+    pub fn add(canvas: Canvas) -> TextureId {
+        let mut textures = TEXTURES.write().unwrap_or_else(|err| err.into_inner());
+        textures.push(Texture { path: None, canvas: Arc::new(canvas) });
+
+        TextureId(textures.len() - 1)
+    }
+
+    /// Loads an image file, as Canvas::from_image() does. Loading the same
+    /// file again gives back the handle it already has, without reading it.
+    #[cfg(feature = "image")]
+    pub fn load<P: AsRef<Path>>(path: P) -> ImageResult<TextureId> {
+        let path = path.as_ref();
+        let key = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        if let Some(id) = Self::find(&key) {
+            return Ok(id);
+        }
+        let canvas = Canvas::from_image(path)?;
+        let mut textures = TEXTURES.write().unwrap_or_else(|err| err.into_inner());
+        // Another thread may have loaded it in the meantime.
+        if let Some(i) = textures.iter().position(|t| t.path.as_deref() == Some(key.as_path())) {
+            return Ok(TextureId(i));
+        }
+        textures.push(Texture { path: Some(key), canvas: Arc::new(canvas) });
+
+        Ok(TextureId(textures.len() - 1))
+    }
+
+    /// Handle of a file that's been loaded already.
+    pub fn find<P: AsRef<Path>>(path: P) -> Option<TextureId> {
+        let path = path.as_ref();
+        let key = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        let textures = TEXTURES.read().unwrap_or_else(|err| err.into_inner());
+
+        textures.iter().position(|t| t.path.as_deref() == Some(key.as_path())).map(TextureId)
+    }
+
+    /// The image behind a handle.
+    pub fn get(id: TextureId) -> Arc<Canvas> {
+        let textures = TEXTURES.read().unwrap_or_else(|err| err.into_inner());
+
+        Arc::clone(&textures[id.0].canvas)
+    }
+
+    /// File the texture was loaded from, or None for one added from memory.
+    pub fn path(id: TextureId) -> Option<PathBuf> {
+        let textures = TEXTURES.read().unwrap_or_else(|err| err.into_inner());
+
+        textures[id.0].path.clone()
+    }
+
+    /// Number of textures in the cache.
+    pub fn len() -> usize {
+        TEXTURES.read().unwrap_or_else(|err| err.into_inner()).len()
+    }
+
+    /// Colour of a texture at (u, v), with (0, 0) at the bottom left of the
+    /// image and (1, 1) at the top right.
+    pub fn sample(id: TextureId, u: Float, v: Float, wrap: Wrap, filter: Filter) -> Colour {
+        let textures = TEXTURES.read().unwrap_or_else(|err| err.into_inner());
+
+        sample(&textures[id.0].canvas, u, v, wrap, filter)
+    }
+}
+
+// Colour of an image at (u, v), as for Textures::sample().
+pub(crate) fn sample(image: &Canvas, u: Float, v: Float, wrap: Wrap, filter: Filter) -> Colour {
+    if image.width == 0 || image.height == 0 {
+        return Colour::black();
+    }
+    let x = u * image.width as Float;
+    let y = (1.0 - v) * image.height as Float;
+    let texel = |x: Float, y: Float| {
+        image.read_pix(wrap_index(x, image.width, wrap), wrap_index(y, image.height, wrap))
+    };
+    match filter {
+        Filter::Nearest => texel(x, y),
+        Filter::Bilinear => {
+            // Pixel centres are half way across each pixel.
+            let (x, y) = (x - 0.5, y - 0.5);
+            let (fx, fy) = (to_f32(x - x.floor()), to_f32(y - y.floor()));
+            let (x, y) = (x.floor(), y.floor());
+            let top = texel(x, y) * (1.0 - fx) + texel(x + 1.0, y) * fx;
+            let bottom = texel(x, y + 1.0) * (1.0 - fx) + texel(x + 1.0, y + 1.0) * fx;
+            top * (1.0 - fy) + bottom * fy
+        }
+    }
+}
+
+// Pixel that a coordinate lands on along a side of the given size.
+fn wrap_index(coord: Float, size: usize, wrap: Wrap) -> usize {
+    let i = coord.floor() as i64;
+    let n = size as i64;
+    let i = match wrap {
+        Wrap::Repeat => i.rem_euclid(n),
+        Wrap::Clamp => i.clamp(0, n - 1),
+        Wrap::Mirror => {
+            let i = i.rem_euclid(2 * n);
+            if i < n { i } else { 2 * n - 1 - i }
+        }
+    };
+
+    i as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Red and green along the top, blue and white along the bottom.
+    fn quad() -> TextureId {
+        let mut c = Canvas::new(2, 2, Colour::white());
+        c.write_pix(0, 0, Colour::red());
+        c.write_pix(1, 0, Colour::green());
+        c.write_pix(0, 1, Colour::blue());
+        Textures::add(c)
+    }
+
+    #[test]
+    fn nearest_filtering_picks_one_pixel() {
+        let id = quad();
+        let at = |u, v| Textures::sample(id, u, v, Wrap::Repeat, Filter::Nearest);
+
+        assert_eq!(at(0.25, 0.75), Colour::red());
+        assert_eq!(at(0.75, 0.75), Colour::green());
+        assert_eq!(at(0.25, 0.25), Colour::blue());
+        assert_eq!(at(0.75, 0.25), Colour::white());
+    }
+
+    #[test]
+    fn wrap_modes_handle_coordinates_outside_the_image() {
+        let id = quad();
+        let at = |u, wrap| Textures::sample(id, u, 0.75, wrap, Filter::Nearest);
+
+        assert_eq!(at(1.25, Wrap::Repeat), Colour::red());
+        assert_eq!(at(1.25, Wrap::Clamp), Colour::green());
+        assert_eq!(at(-3.0, Wrap::Clamp), Colour::red());
+        assert_eq!(at(1.25, Wrap::Mirror), Colour::green());
+        assert_eq!(at(-0.25, Wrap::Mirror), Colour::red());
+        assert_eq!(at(-0.75, Wrap::Mirror), Colour::green());
+    }
+
+    #[test]
+    fn bilinear_filtering_blends_neighbouring_pixels() {
+        let id = quad();
+        let at = |u, v, wrap| Textures::sample(id, u, v, wrap, Filter::Bilinear);
+
+        assert_eq!(at(0.25, 0.75, Wrap::Clamp), Colour::red());
+        assert_eq!(at(0.5, 0.75, Wrap::Clamp), Colour::new(0.5, 0.5, 0.0));
+        assert_eq!(at(0.5, 0.5, Wrap::Clamp), Colour::new(0.5, 0.5, 0.5));
+        // At the edge, repeating blends in the other side of the image.
+        assert_eq!(at(0.0, 0.75, Wrap::Repeat), Colour::new(0.5, 0.5, 0.0));
+        assert_eq!(at(0.0, 0.75, Wrap::Clamp), Colour::red());
+    }
+
+    #[test]
+    #[cfg(feature = "image")]
+    fn files_are_only_loaded_once() {
+        let path = std::env::temp_dir().join("feoray-texture.png");
+        Canvas::new(3, 2, Colour::red()).export(&path.to_string_lossy()).unwrap();
+        let a = Textures::load(&path).unwrap();
+        let count = Textures::len();
+        let b = Textures::load(&path).unwrap();
+
+        assert_eq!(a, b);
+        assert_eq!(Textures::len(), count);
+        assert_eq!(Textures::find(&path), Some(a));
+        assert_eq!(Textures::get(a).width, 3);
+        assert!(Textures::path(a).is_some());
+        assert!(Textures::load(std::env::temp_dir().join("feoray-missing-texture.png")).is_err());
+    }
+}
@@ -0,0 +1,308 @@
+use crate::Float;
+use crate::core::{point, roots, vector, Intersection, Intersections, Ray};
+use crate::primitives::Object;
+use nalgebra::Vector4;
+
+// Which of a lens's surfaces a point is on.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Face {
+    Front,
+    Back,
+    Rim
+}
+
+/// A solid lens along the z axis, centred on the origin, with its front face
+/// towards -z and its back towards +z. Each face is part of a sphere, or flat,
+/// and a cylindrical rim joins them. Give it a glassy material to focus light
+/// with the refraction system.
+///
+/// Radii are positive for a face that bulges out of the lens (convex) and
+/// negative for one that dips into it (concave), with 0.0 or infinity for a
+/// flat face.
+///
+/// # Example
+///
+/// ```
+/// use feoray::materials::Material;
+/// use feoray::primitives::{Lens, Object};
+///
+/// // A thin magnifying glass.
+/// let glass = Material::default().with_transparency(1.0).with_ior(1.5);
+/// let lens = Object::new_lens(Lens::new(3.0, 3.0, 0.2)).with_material(glass);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub struct Lens {
+    /// Radius of curvature of the front face.
+    pub front: Float,
+    /// Radius of curvature of the back face.
+    pub back: Float,
+    /// Distance between the faces along the axis.
+    pub thickness: Float,
+    /// Radius of the rim. Curved faces can't be wider than their spheres, and
+    /// a convex lens ends where its faces meet, so the rim may be smaller.
+    pub radius: Float
+}
+
+impl Default for Lens {
+    fn default() -> Self {
+        Lens::new(2.0, 2.0, 0.5)
+    }
+}
+
+impl Lens {
+    /// A lens with the given face radii and thickness, and a rim of radius
+    /// 1.0, like the unit sphere.
+    pub fn new(front: Float, back: Float, thickness: Float) -> Self {
+        Lens { front, back, thickness, radius: 1.0 }
+    }
+
+    pub fn with_radius(mut self, radius: Float) -> Self {
+        self.radius = radius;
+
+        self
+    }
+
+    /// Radius of the rim as it's actually made, after fitting it to the faces.
+    pub fn rim(&self) -> Float {
+        let mut rim = self.radius;
+        for r in [self.front, self.back] {
+            if curved(r) {
+                rim = rim.min(r.abs());
+            }
+        }
+        if self.back_z(rim) >= self.front_z(rim) {
+            return rim;
+        }
+        // The faces meet inside the rim, so the lens ends where they cross.
+        let (z1, z2) = (self.front_centre(), self.back_centre());
+        let z = match (curved(self.front), curved(self.back)) {
+            (true, true) => (z1 + z2) / 2.0 + (self.front.powi(2) - self.back.powi(2)) / (2.0 * (z2 - z1)),
+            (true, false) => self.thickness / 2.0,
+            _ => -self.thickness / 2.0
+        };
+        let (centre, r) = if curved(self.front) { (z1, self.front) } else { (z2, self.back) };
+
+        (r * r - (z - centre).powi(2)).max(0.0).sqrt().min(rim)
+    }
+
+    /// Where the front face is along z at a distance r from the axis.
+    pub fn front_z(&self, r: Float) -> Float {
+        if curved(self.front) {
+            self.front_centre() - self.front.signum() * (self.front.powi(2) - r * r).max(0.0).sqrt()
+        } else {
+            -self.thickness / 2.0
+        }
+    }
+
+    /// Where the back face is along z at a distance r from the axis.
+    pub fn back_z(&self, r: Float) -> Float {
+        if curved(self.back) {
+            self.back_centre() + self.back.signum() * (self.back.powi(2) - r * r).max(0.0).sqrt()
+        } else {
+            self.thickness / 2.0
+        }
+    }
+
+    // Centres of the faces' spheres along z.
+    fn front_centre(&self) -> Float {
+        -self.thickness / 2.0 + self.front
+    }
+
+    fn back_centre(&self) -> Float {
+        self.thickness / 2.0 - self.back
+    }
+
+    pub fn intersect<'a>(ray: &Ray, object: &'a Object) -> Intersections<'a> {
+        object.intersect(ray)
+    }
+
+    /// Appends the intersections, nearest first, to a reusable buffer. The ray
+    /// has already been taken into the object's own space. The lens is where
+    /// the solids behind each face and inside the rim overlap, so a hit on
+    /// one surface only counts if it's inside the other two.
+    pub fn intersect_into<'a>(&self, local_ray: &Ray, object: &'a Object, out: &mut Vec<Intersection<'a>>) {
+        let rim = self.rim();
+        let (o, d) = (local_ray.origin, local_ray.direction);
+        // At most two hits with each face's sphere and two with the rim.
+        let mut hits = [(0.0, Face::Rim); 6];
+        let mut n = 0;
+        let mut add = |t: Float, face: Face| {
+            hits[n] = (t, face);
+            n += 1;
+        };
+        for (face, r, centre, flat) in [
+            (Face::Front, self.front, self.front_centre(), -self.thickness / 2.0),
+            (Face::Back, self.back, self.back_centre(), self.thickness / 2.0)
+        ] {
+            if curved(r) {
+                let oc = o - point(0.0, 0.0, centre);
+                if let Some((t0, t1)) = roots::quadratic(d.dot(&d), 2.0 * oc.dot(&d), oc.dot(&oc) - r * r) {
+                    add(t0, face);
+                    add(t1, face);
+                }
+            } else if d.z != 0.0 {
+                add((flat - o.z) / d.z, face);
+            }
+        }
+        let a = d.x * d.x + d.y * d.y;
+        if a != 0.0 {
+            if let Some((t0, t1)) = roots::quadratic(a, 2.0 * (o.x * d.x + o.y * d.y), o.x * o.x + o.y * o.y - rim * rim) {
+                add(t0, Face::Rim);
+                add(t1, Face::Rim);
+            }
+        }
+        let hits = &mut hits[..n];
+        hits.sort_by(|a, b| a.0.total_cmp(&b.0));
+        for &(t, face) in hits.iter() {
+            if self.bounds(local_ray.position(t), face, rim) {
+                out.push(Intersection::new(t, object));
+            }
+        }
+    }
+
+    // Whether a point on one of the surfaces is inside the others, and on the
+    // side of a concave face's sphere that's part of the lens.
+    fn bounds(&self, p: Vector4<Float>, on: Face, rim: Float) -> bool {
+        let inside = |face: Face| match face {
+            Face::Front => side(p, self.front, self.front_centre(), -1.0, -self.thickness / 2.0) <= 0.0,
+            Face::Back => side(p, self.back, self.back_centre(), 1.0, self.thickness / 2.0) <= 0.0,
+            Face::Rim => p.x * p.x + p.y * p.y <= rim * rim
+        };
+        let hemisphere = match on {
+            Face::Front => self.front >= 0.0 || !curved(self.front) || p.z >= self.front_centre(),
+            Face::Back => self.back >= 0.0 || !curved(self.back) || p.z <= self.back_centre(),
+            Face::Rim => true
+        };
+
+        hemisphere && [Face::Front, Face::Back, Face::Rim].into_iter().filter(|f| *f != on).all(inside)
+    }
+
+    /// Resolves the normal vector at a specified point on an object, facing
+    /// out of whichever surface the point is nearest.
+    pub fn normal_at(&self, world_point: Vector4<Float>, object: &Object) -> Vector4<Float> {
+        let p = object.world_to_object(world_point);
+        let rim = self.rim();
+        let r = (p.x * p.x + p.y * p.y).sqrt();
+        let off = |radius: Float, centre: Float, flat: Float| {
+            if curved(radius) { ((p - point(0.0, 0.0, centre)).xyz().magnitude() - radius.abs()).abs() } else { (p.z - flat).abs() }
+        };
+        let front = off(self.front, self.front_centre(), -self.thickness / 2.0);
+        let back = off(self.back, self.back_centre(), self.thickness / 2.0);
+        let normal = if (r - rim).abs() < front.min(back) {
+            vector(p.x, p.y, 0.0)
+        } else if front <= back {
+            self.front_normal(p)
+        } else {
+            self.back_normal(p)
+        };
+
+        object.normal_to_world(normal)
+    }
+
+    // Normals, not normalised, of the faces' surfaces through a point.
+    pub(crate) fn front_normal(&self, p: Vector4<Float>) -> Vector4<Float> {
+        face_normal(p, self.front, self.front_centre(), -1.0)
+    }
+
+    pub(crate) fn back_normal(&self, p: Vector4<Float>) -> Vector4<Float> {
+        face_normal(p, self.back, self.back_centre(), 1.0)
+    }
+
+    /// Converts points on the lens to planar coordinates looking along its
+    /// axis, with u along x and v along y in the x and z of the result.
+    pub fn uv_manifold(pos: Vector4<Float>) -> Vector4<Float> {
+        point(pos.x, 0.0, pos.y)
+    }
+}
+
+// Faces with a radius of 0.0 or infinity are flat.
+fn curved(radius: Float) -> bool {
+    radius != 0.0 && radius.is_finite()
+}
+
+// How far outside a face's solid a point is, negative inside. Facing is -1.0
+// for the front face, whose outside is towards -z, and 1.0 for the back.
+fn side(p: Vector4<Float>, radius: Float, centre: Float, facing: Float, flat: Float) -> Float {
+    if curved(radius) {
+        radius.signum() * ((p - point(0.0, 0.0, centre)).xyz().magnitude() - radius.abs())
+    } else {
+        facing * (p.z - flat)
+    }
+}
+
+fn face_normal(p: Vector4<Float>, radius: Float, centre: Float, facing: Float) -> Vector4<Float> {
+    if curved(radius) {
+        let n = (p - point(0.0, 0.0, centre)) * radius.signum();
+        vector(n.x, n.y, n.z)
+    } else {
+        vector(0.0, 0.0, facing)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::Tuple;
+    use crate::EPSILON;
+
+    fn biconvex() -> Object {
+        Object::new_lens(Lens::new(2.0, 2.0, 0.5))
+    }
+
+    #[test]
+    fn rays_along_the_axis_hit_both_faces() {
+        let lens = biconvex();
+        let xs = lens.intersect(&Ray::new(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0)));
+
+        assert_eq!(xs.len(), 2);
+        assert_eq!(xs[0].t, 4.75);
+        assert_eq!(xs[1].t, 5.25);
+    }
+
+    #[test]
+    fn convex_lenses_end_where_their_faces_meet() {
+        let lens = Lens::new(2.0, 2.0, 0.5);
+        let rim = lens.rim();
+
+        assert!(rim < 1.0);
+        assert!((lens.front_z(rim) - lens.back_z(rim)).abs() < EPSILON);
+        assert_eq!(Lens::new(2.0, 2.0, 0.5).with_radius(0.5).rim(), 0.5);
+        assert_eq!(Lens::new(-0.5, 0.0, 0.5).rim(), 0.5);
+        let object = biconvex();
+        let miss = object.intersect(&Ray::new(point(0.0, 0.99, -5.0), vector(0.0, 0.0, 1.0)));
+        assert_eq!(miss.len(), 0);
+    }
+
+    #[test]
+    fn concave_lenses_are_thin_in_the_middle_with_a_rim() {
+        let lens = Object::new_lens(Lens::new(-2.0, -2.0, 0.2));
+        let middle = lens.intersect(&Ray::new(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0)));
+        let edge = lens.intersect(&Ray::new(point(0.0, 0.9, -5.0), vector(0.0, 0.0, 1.0)));
+        let side = lens.intersect(&Ray::new(point(0.0, 5.0, 0.0), vector(0.0, -1.0, 0.0)));
+
+        assert_eq!(middle.len(), 2);
+        assert!((middle[0].t - 4.9).abs() < EPSILON && (middle[1].t - 5.1).abs() < EPSILON);
+        assert!(edge[1].t - edge[0].t > 0.2);
+        // Across the middle of the rim, in through one side and out the other.
+        assert_eq!(side.len(), 2);
+        assert_eq!((side[0].t, side[1].t), (4.0, 6.0));
+    }
+
+    #[test]
+    fn normals_face_out_of_each_surface() {
+        let convex = biconvex();
+        let concave = Object::new_lens(Lens::new(-2.0, 0.0, 0.2));
+
+        assert_eq!(convex.normal_at(point(0.0, 0.0, -0.25)).to_5dp(), vector(0.0, 0.0, -1.0));
+        assert_eq!(convex.normal_at(point(0.0, 0.0, 0.25)).to_5dp(), vector(0.0, 0.0, 1.0));
+        assert_eq!(concave.normal_at(point(0.0, 0.0, -0.1)).to_5dp(), vector(0.0, 0.0, -1.0));
+        assert_eq!(concave.normal_at(point(0.0, 0.0, 0.1)).to_5dp(), vector(0.0, 0.0, 1.0));
+        assert_eq!(concave.normal_at(point(0.0, 1.0, 0.0)).to_5dp(), vector(0.0, 1.0, 0.0));
+        let n = concave.normal_at(point(0.0, 0.6, concave_front(0.6)));
+        assert!(n.y < 0.0 && n.z < 0.0);
+    }
+
+    fn concave_front(r: Float) -> Float {
+        Lens::new(-2.0, 0.0, 0.2).front_z(r)
+    }
+}
@@ -1,17 +1,50 @@
-use crate::core::{Intersections, Ray};
-use crate::materials::Material;
-use crate::primitives::{Plane, Primitive, Sphere, TestShape};
+use crate::{Float, FeorayError};
+use crate::core::{Intersection, Intersections, Keyframes, Ray, RayKind, Transform};
+use crate::core::matrix::inverse;
+use crate::materials::{Material, MaterialId, Medium};
+use crate::primitives::{Lens, Plane, Polygon, Primitive, Sphere, TestShape};
 use nalgebra::{Matrix4, Vector4};
 
 
-#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+/// A shape with its material and place in the world. Objects aren't Copy, so
+/// shapes are free to own their data; builders hand back a clone, and hits
+/// borrow the object rather than copying it.
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
 pub struct Object {
     pub shape: Primitive,
     pub material: Material,
-    pub transform: Matrix4<f64>,
-    pub inverse_transform: Matrix4<f64>,
+    /// Material from the world's palette the object's material is kept in
+    /// step with. Set by World::use_material(), and cleared by
+    /// with_material(), which gives the object a material of its own.
+    pub material_id: Option<MaterialId>,
+    pub transform: Matrix4<Float>,
+    pub inverse_transform: Matrix4<Float>,
+    /// Transpose of the inverse transform, which takes normals back to world
+    /// space. Kept up to date by with_transform().
+    pub normal_transform: Matrix4<Float>,
+    /// The material's pattern inverse transform times the object's inverse
+    /// transform, taking world points straight into pattern space. Kept up to
+    /// date by with_transform() and with_material().
+    pub pattern_transform: Matrix4<Float>,
+    /// The pattern and object inverse transforms pattern_transform was made
+    /// from. Patterns only use it while both still match, as material and
+    /// inverse_transform can be written directly.
+    pub pattern_transform_of: (Matrix4<Float>, Matrix4<Float>),
     pub umbra: bool,
-    pub uv_manifold: bool
+    /// Kinds of ray the object can't be seen by, as RayKind bits. Shadows are
+    /// controlled by umbra instead.
+    pub hidden_from: u8,
+    pub uv_manifold: bool,
+    pub keyframes: Keyframes,
+    /// Medium filling the object, turning it into a volume. A volume's surface
+    /// isn't drawn; its shape only marks out where the medium is.
+    pub medium: Option<Medium>,
+    /// Stable ID used in object ID passes and masks. None falls back to the
+    /// object's position in the world, which changes as objects are added.
+    pub id: Option<u32>,
+    /// Name to find the object by with World::find(), also shown in debug
+    /// traces and AOV passes.
+    pub name: Option<String>
 }
 
 impl Object {
@@ -23,7 +56,19 @@ impl Object {
 
     /// Creates a new sphere at 0.0, 0.0, 0.0 with radius 1.0.
     pub fn new_sphere() -> Self {
-        let shape = Primitive::Sphere();
+        let shape = Primitive::Sphere(Sphere::new());
+        Object { shape, ..Default::default() }
+    }
+
+    /// Creates a new lens at 0.0, 0.0, 0.0, looking along z.
+    pub fn new_lens(lens: Lens) -> Self {
+        let shape = Primitive::Lens(lens);
+        Object { shape, ..Default::default() }
+    }
+
+    /// Creates a new polygon at 0.0, 0.0, 0.0, lying in the xz plane.
+    pub fn new_polygon(polygon: Polygon) -> Self {
+        let shape = Primitive::Polygon(polygon);
         Object { shape, ..Default::default() }
     }
 
@@ -33,59 +78,259 @@ impl Object {
         Object { shape, ..Default::default() }
     }
 
-    /// Calculates intersections between a ray and an object, if any.
-    pub fn intersect(&self, ray: &Ray) -> Intersections {
+    /// Calculates intersections between a ray and an object, if any. Moving
+    /// objects are first placed where they are at the time of the ray.
+    pub fn intersect(&self, ray: &Ray) -> Intersections<'_> {
+        let mut intrsc = vec![];
+        self.intersect_into(ray, &mut intrsc);
+
+        Intersections { intrsc }
+    }
+
+    /// Same as intersect(), but appends the hits, nearest first, to a buffer
+    /// that can be reused from ray to ray rather than allocating a new one.
+    pub fn intersect_into<'a>(&'a self, ray: &Ray, out: &mut Vec<Intersection<'a>>) {
+        let local_ray = if self.is_animated() {
+            ray.transform(self.at_time(ray.time).inverse_transform)
+        } else {
+            Ray { origin: self.world_to_object(ray.origin), direction: self.world_to_object(ray.direction), ..*ray }
+        };
         match self.shape {
-            Primitive::Plane() => Plane::intersect(ray, self),
-            Primitive::Sphere() => Sphere::intersect(ray, self),
-            Primitive::TestShape(mut t) => t.intersect(ray, self)
+            Primitive::Plane() => Plane::intersect_into(&local_ray, self, out),
+            Primitive::Sphere(s) => s.intersect_into(&local_ray, self, out),
+            Primitive::Lens(l) => l.intersect_into(&local_ray, self, out),
+            Primitive::Polygon(ref p) => p.intersect_into(&local_ray, self, out),
+            Primitive::TestShape(mut t) => t.intersect_into(&local_ray, self, out)
         }
     }
 
+    /// Whether the ray hits the object anywhere between its origin and
+    /// max_distance (or the ray's t_max, if nearer), without collecting the
+    /// hits in order.
+    pub fn intersect_any(&self, ray: &Ray, max_distance: Float) -> bool {
+        let max_distance = max_distance.min(ray.t_max);
+        self.intersect(ray).hits().any(|i| i.t < max_distance)
+    }
+
     /// Calculates the normal at a specified point on an object.
-    pub fn normal_at(&self, object_point: Vector4<f64>) -> Vector4<f64> {
+    pub fn normal_at(&self, object_point: Vector4<Float>) -> Vector4<Float> {
         match self.shape {
             Primitive::Plane() => Plane::normal_at(object_point, self),
-            Primitive::Sphere() => Sphere::normal_at(object_point, self),
+            Primitive::Sphere(_) => Sphere::normal_at(object_point, self),
+            Primitive::Lens(l) => l.normal_at(object_point, self),
+            Primitive::Polygon(_) => Polygon::normal_at(object_point, self),
             Primitive::TestShape(t) => t.normal_at(object_point, self)
         }
     }
 
+    /// Converts a point from world space to the object's own space. Every shape
+    /// and pattern lookup goes through here, so that once objects can be
+    /// nested in groups only this needs to walk up through the parents.
+    pub fn world_to_object(&self, world_point: Vector4<Float>) -> Vector4<Float> {
+        self.inverse_transform * world_point
+    }
+
+    /// Converts a normal from the object's own space back to world space,
+    /// normalised. The counterpart to world_to_object().
+    pub fn normal_to_world(&self, object_normal: Vector4<Float>) -> Vector4<Float> {
+        let mut world_normal = self.normal_transform * object_normal;
+        world_normal.w = 0.0;
+
+        world_normal.normalize()
+    }
+
     /// Applies a transform directly to an object. For single transforms, use the
     /// trait methods, but for complex transforms use the transform builder.
-    pub fn with_transform(&mut self, transform: Matrix4<f64>) -> Self {
+    ///
+    /// Panics if the transform can't be inverted. See try_with_transform().
+    pub fn with_transform(&mut self, transform: impl Into<Matrix4<Float>>) -> Self {
+        self.try_with_transform(transform).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Same as with_transform(), but returns an error, leaving the object as it
+    /// was, if the transform can't be inverted (e.g. a scale of zero).
+    pub fn try_with_transform(&mut self, transform: impl Into<Matrix4<Float>>) -> Result<Self, FeorayError> {
+        let transform = transform.into();
+        let inverse = inverse(&transform).ok_or(FeorayError::NonInvertible("object"))?;
         self.transform = transform;
-        self.inverse_transform = transform.try_inverse().unwrap();
+        self.inverse_transform = inverse;
+        self.normal_transform = self.inverse_transform.transpose();
+        self.refresh_pattern_transform();
+
+        Ok(self.clone())
+    }
+
+    /// Moves the object to from and turns it to face target, with its -z axis
+    /// pointing at the target. Applied on top of the current transform, so
+    /// scale the object first.
+    pub fn look_at(&mut self, from: Vector4<Float>, target: Vector4<Float>, up: Vector4<Float>) -> Self {
+        self.with_transform(Matrix4::look_at(from, target, up) * self.transform)
+    }
+
+    /// Makes the object move during the camera's exposure, from its current
+    /// transform at time 0.0 to end_transform at time 1.0.
+    pub fn with_motion(&mut self, end_transform: Matrix4<Float>) -> Self {
+        self.keyframes.insert(0.0, self.transform);
+        self.keyframes.insert(1.0, end_transform);
+
+        self.clone()
+    }
+
+    /// Keys the object's transform at a moment in time. Between keys the
+    /// transform is interpolated, and outside them it holds the nearest key.
+    pub fn with_transform_at(&mut self, time: Float, transform: Matrix4<Float>) -> Self {
+        self.keyframes.insert(time, transform);
+
+        self.clone()
+    }
+
+    /// Same as with_transform_at(), but returns an error rather than
+    /// panicking if the object already has MAX_KEYFRAMES keys.
+    pub fn try_with_transform_at(&mut self, time: Float, transform: Matrix4<Float>) -> Result<Self, FeorayError> {
+        self.keyframes.try_insert(time, transform)?;
+
+        Ok(self.clone())
+    }
+
+    /// Whether the object's transform changes over time.
+    pub fn is_animated(&self) -> bool {
+        !self.keyframes.is_empty()
+    }
+
+    /// Returns the object's transform at a moment in time. Static objects always
+    /// return their transform.
+    pub fn transform_at(&self, time: Float) -> Matrix4<Float> {
+        self.keyframes.sample(time).unwrap_or(self.transform)
+    }
 
-        *self
+    /// Static copy of the object as it is at a moment in time.
+    pub fn at_time(&self, time: Float) -> Self {
+        let mut object = self.clone();
+        object.keyframes = Keyframes::new();
+        object.with_transform(self.transform_at(time))
     }
 
     /// Applies a material to an object.
     pub fn with_material(&mut self, material: Material) -> Self {
         self.material = material;
+        self.material_id = None;
+        self.refresh_pattern_transform();
+
+        self.clone()
+    }
+
+    fn refresh_pattern_transform(&mut self) {
+        let pattern_inverse = self.material.pattern.inverse_transform;
+        self.pattern_transform = pattern_inverse * self.inverse_transform;
+        self.pattern_transform_of = (pattern_inverse, self.inverse_transform);
+    }
+
+    /// pattern_transform, if it was made from these inverse transforms of a
+    /// pattern and of the object as it is now.
+    pub(crate) fn pattern_transform_for(&self, pattern_inverse: &Matrix4<Float>) -> Option<Matrix4<Float>> {
+        let (pattern, object) = &self.pattern_transform_of;
+        (pattern == pattern_inverse && *object == self.inverse_transform).then_some(self.pattern_transform)
+    }
+
+    /// Gives the object a stable ID, so it can be picked out of renders with
+    /// Camera::render_mask().
+    pub fn with_id(&mut self, id: u32) -> Self {
+        self.id = Some(id);
+
+        self.clone()
+    }
 
-        *self
+    /// Names the object, so it can be found in the world with World::find().
+    pub fn with_name(&mut self, name: &str) -> Self {
+        self.name = Some(name.to_string());
+
+        self.clone()
     }
 
     /// Removes ability for the object to cast a shadow.
     pub fn cast_no_shadow(&mut self) -> Self {
         self.umbra = false;
 
-        *self
+        self.clone()
+    }
+
+    /// Hides the object from a kind of ray. Hidden from the camera, say, it
+    /// still shows up in reflections and casts shadows; hiding it from shadow
+    /// rays is the same as cast_no_shadow().
+    pub fn hide_from(&mut self, kind: RayKind) -> Self {
+        match kind {
+            RayKind::Shadow => self.umbra = false,
+            _ => self.hidden_from |= kind.bit()
+        }
+
+        self.clone()
+    }
+
+    /// Whether rays of the given kind can hit the object.
+    pub fn is_visible_to(&self, kind: RayKind) -> bool {
+        match kind {
+            RayKind::Shadow => self.umbra,
+            _ => self.hidden_from & kind.bit() == 0
+        }
+    }
+
+    /// Fills the object with a participating medium, such as smoke or fog. The
+    /// object's shape becomes the boundary of the volume.
+    pub fn with_medium(&mut self, medium: Medium) -> Self {
+        self.medium = Some(medium);
+
+        self.clone()
+    }
+
+    /// Whether the object is a volume rather than a surface.
+    pub fn is_volume(&self) -> bool {
+        self.medium.is_some()
+    }
+
+    /// Keeps only the part of a sphere between two latitudes, in radians from
+    /// -PI/2 at its bottom to PI/2 at its top. (0.0, PI/2) leaves a dome, and
+    /// (-PI/2, 0.0) a bowl. Other shapes are unchanged.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// // A cap a quarter of the sphere's height.
+    /// let cap = Object::new_sphere().with_latitude((0.5 as Float).asin(), PI / 2.0);
+    /// ```
+    pub fn with_latitude(&mut self, min: Float, max: Float) -> Self {
+        if let Primitive::Sphere(ref mut sphere) = self.shape {
+            sphere.latitude = (min, max);
+        }
+
+        self.clone()
+    }
+
+    /// Keeps only the part of a sphere between two longitudes, in radians
+    /// about its y axis from -PI to PI, with 0.0 along +x and PI/2 along +z.
+    /// A min above max wraps round through PI, so (PI/4, -PI/4) cuts a wedge
+    /// out around +x. Other shapes are unchanged.
+    pub fn with_longitude(&mut self, min: Float, max: Float) -> Self {
+        if let Primitive::Sphere(ref mut sphere) = self.shape {
+            sphere.longitude = (min, max);
+        }
+
+        self.clone()
     }
 
     /// Commands the renderer to use the object's manifold.
     pub fn use_manifold(&mut self) -> Self {
         self.uv_manifold = true;
 
-        *self
+        self.clone()
     }
 
     /// Selects the correct manifold for the object and returns UV coordinates.
-    pub fn uv_at(&self, object_point: Vector4<f64>) -> Vector4<f64> {
+    pub fn uv_at(&self, object_point: Vector4<Float>) -> Vector4<Float> {
         match self.shape {
             Primitive::Plane() => Plane::uv_manifold(object_point),
-            Primitive::Sphere() => Sphere::uv_manifold(object_point, self.material.pattern.inverse_transform),
+            Primitive::Sphere(_) => Sphere::uv_manifold(object_point, self.material.pattern.inverse_transform),
+            Primitive::Lens(_) => Lens::uv_manifold(object_point),
+            Primitive::Polygon(_) => Polygon::uv_manifold(object_point),
             Primitive::TestShape(t) => t.uv_manifold(object_point)
         }
     }
@@ -93,7 +338,7 @@ impl Object {
     // Object presets
     /// Glass orb with transparency 1.0, and ior 1.5.
     pub fn glass_orb() -> Self {
-        let shape = Primitive::Sphere();
+        let shape = Primitive::Sphere(Sphere::new());
         let material = Material::null()
             .with_transparency(1.0)
             .with_ior(1.5);
@@ -105,12 +350,21 @@ impl Object {
 impl Default for Object {
     fn default() -> Self {
         Object {
-            shape: Primitive::Sphere(),
+            shape: Primitive::Sphere(Sphere::new()),
             material: Material::default(),
+            material_id: None,
             transform: Matrix4::identity(),
             inverse_transform: Matrix4::identity(),
+            normal_transform: Matrix4::identity(),
+            pattern_transform: Matrix4::identity(),
+            pattern_transform_of: (Matrix4::identity(), Matrix4::identity()),
             umbra: true,
-            uv_manifold: false
+            hidden_from: 0,
+            uv_manifold: false,
+            keyframes: Keyframes::new(),
+            medium: None,
+            id: None,
+            name: None
         }
     }
 }
@@ -118,7 +372,8 @@ impl Default for Object {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::core::Transform;
+    use crate::core::{point, vector, Colour, TransformBuilder, Tuple};
+    use crate::materials::Pattern;
 
     #[test]
     fn a_spheres_default_transformation() {
@@ -150,4 +405,160 @@ mod tests {
 
         assert_eq!(s.transform, Matrix4::translate(2.0, 3.0, 4.0));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn assigning_a_transformation_from_a_builder() {
+        let s = Object::new_test_shape()
+            .with_transform(TransformBuilder::new().uscale(2.0).translate(2.0, 3.0, 4.0));
+
+        assert_eq!(s.transform, Matrix4::translate(2.0, 3.0, 4.0) * Matrix4::uscale(2.0));
+    }
+
+    #[test]
+    fn converting_a_point_from_world_to_object_space() {
+        let s = Object::new_sphere()
+            .with_transform(TransformBuilder::new().uscale(2.0).translate(5.0, 0.0, 0.0));
+
+        assert_eq!(s.world_to_object(point(7.0, 0.0, 0.0)), point(1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn converting_a_normal_from_object_to_world_space() {
+        let s = Object::new_sphere()
+            .with_transform(Matrix4::nuscale(1.0, 2.0, 3.0));
+        let irr_no = Float::sqrt(3.0) / 3.0;
+        let n = s.normal_to_world(vector(irr_no, irr_no, irr_no));
+
+        assert_eq!(n.to_5dp(), vector(0.85714, 0.42857, 0.28571));
+    }
+
+    #[test]
+    fn normal_transform_follows_the_transform() {
+        let s = Object::new_sphere()
+            .with_transform(Matrix4::rot_z(0.6) * Matrix4::nuscale(1.0, 0.5, 1.0));
+
+        assert_eq!(s.normal_transform, s.inverse_transform.transpose());
+        assert_eq!(Object::new_sphere().normal_transform, Matrix4::identity());
+    }
+
+    #[test]
+    fn pattern_transform_follows_the_material_and_transform() {
+        let pattern = Pattern::new_stripes(Colour::white(), Colour::black())
+            .with_transform(Matrix4::uscale(2.0));
+        let s = Object::new_sphere()
+            .with_material(Material::default().with_pattern(pattern))
+            .with_transform(Matrix4::translate(0.5, 0.0, 0.0));
+        let t = Object::new_sphere()
+            .with_transform(Matrix4::translate(0.5, 0.0, 0.0))
+            .with_material(Material::default().with_pattern(pattern));
+
+        assert_eq!(s.pattern_transform, pattern.inverse_transform * s.inverse_transform);
+        assert_eq!(t.pattern_transform, s.pattern_transform);
+    }
+
+    #[test]
+    fn patterns_written_straight_into_the_material_are_placed_right() {
+        let pattern = Pattern::new_stripes(Colour::white(), Colour::black())
+            .with_transform(Matrix4::translate(1.0, 0.0, 0.0));
+        let mut s = Object::new_sphere().with_transform(Matrix4::uscale(2.0));
+        let m = Material { pattern, ..Default::default() };
+        s.material = m;
+        let p = point(2.5, 0.0, 0.0);
+
+        // In pattern space, x = 2.5 / 2 - 1 = 0.25, a white stripe. Stale, the
+        // cached transform would only undo the scale and give 1.25, black.
+        assert_eq!(pattern.pattern_at_object(&s, p), Colour::white());
+        assert_eq!(s.material.pattern.pattern_at_object(&s, p), pattern.pattern_at_object(&s.clone().with_material(m), p));
+    }
+
+    #[test]
+    fn static_objects_ignore_time() {
+        let s = Object::new_sphere()
+            .with_transform(Matrix4::translate(1.0, 0.0, 0.0));
+
+        assert_eq!(s.transform_at(0.7), Matrix4::translate(1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn moving_object_is_interpolated_over_the_exposure() {
+        let s = Object::new_sphere()
+            .with_motion(Matrix4::translate(4.0, 0.0, 0.0));
+
+        assert_eq!(s.transform_at(0.0), Matrix4::identity());
+        assert_eq!(s.transform_at(0.5), Matrix4::translate(2.0, 0.0, 0.0));
+        assert_eq!(s.transform_at(1.0), Matrix4::translate(4.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn moving_object_is_intersected_at_the_time_of_the_ray() {
+        let s = Object::new_sphere()
+            .with_motion(Matrix4::translate(4.0, 0.0, 0.0));
+        let r = Ray::new(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+
+        assert_eq!(s.intersect(&r.with_time(0.0)).len(), 2);
+        assert_eq!(s.intersect(&r.with_time(1.0)).len(), 0);
+        assert_eq!(s.intersect(&r.with_time(1.0).transform(Matrix4::translate(4.0, 0.0, 0.0))).len(), 2);
+    }
+
+    #[test]
+    fn keyframed_object_is_interpolated_between_keys() {
+        let s = Object::new_sphere()
+            .with_transform_at(0.0, Matrix4::identity())
+            .with_transform_at(2.0, Matrix4::translate(0.0, 4.0, 0.0))
+            .with_transform_at(3.0, Matrix4::translate(0.0, 4.0, 2.0));
+
+        assert!(s.is_animated());
+        assert_eq!(s.transform_at(1.0), Matrix4::translate(0.0, 2.0, 0.0));
+        assert_eq!(s.transform_at(2.5), Matrix4::translate(0.0, 4.0, 1.0));
+        assert_eq!(s.transform_at(5.0), Matrix4::translate(0.0, 4.0, 2.0));
+    }
+
+    #[test]
+    fn object_at_a_time_is_static() {
+        let s = Object::new_sphere()
+            .with_motion(Matrix4::translate(4.0, 0.0, 0.0))
+            .at_time(0.25);
+
+        assert!(!s.is_animated());
+        assert_eq!(s.transform, Matrix4::translate(1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn object_can_be_turned_to_face_a_target() {
+        let s = Object::new_plane()
+            .with_transform(Matrix4::rot_x(-crate::consts::PI / 2.0))
+            .look_at(point(0.0, 2.0, 0.0), point(0.0, 2.0, 10.0), vector(0.0, 1.0, 0.0));
+
+        assert_eq!(s.transform * point(0.0, 0.0, 0.0), point(0.0, 2.0, 0.0));
+        assert_eq!(s.normal_at(point(0.0, 2.0, 0.0)).to_5dp(), vector(0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn any_hit_within_a_distance() {
+        let s = Object::new_sphere();
+        let r = Ray::new(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+
+        assert!(s.intersect_any(&r, 10.0));
+        assert!(!s.intersect_any(&r, 3.0));
+        assert!(!s.intersect_any(&Ray::new(point(0.0, 0.0, 5.0), vector(0.0, 0.0, 1.0)), 10.0));
+    }
+
+    #[test]
+    fn objects_can_be_hidden_from_kinds_of_ray() {
+        let s = Object::new_sphere().hide_from(RayKind::Camera).hide_from(RayKind::Shadow);
+
+        assert!(!s.is_visible_to(RayKind::Camera));
+        assert!(!s.is_visible_to(RayKind::Shadow));
+        assert!(!s.umbra);
+        assert!(s.is_visible_to(RayKind::Reflection));
+    }
+
+    #[test]
+    fn singular_transforms_are_refused() {
+        let mut s = Object::new_sphere().with_transform(Matrix4::translate(1.0, 0.0, 0.0));
+        let before = s.clone();
+
+        assert!(matches!(s.try_with_transform(Matrix4::uscale(0.0)), Err(FeorayError::NonInvertible(_))));
+        assert_eq!(s, before);
+    }
+}
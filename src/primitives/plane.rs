@@ -1,10 +1,10 @@
-use crate::EPSILON;
-use crate::core::{vector, Intersection, Intersections, Ray};
+use crate::{Float, EPSILON};
+use crate::core::{point, vector, Intersection, Intersections, Ray};
 use crate::primitives::Object;
 use nalgebra::Vector4;
 
 // Object is infinite in size, and has constant normal at all points.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Default)]
 pub struct Plane;
 
 impl Plane {
@@ -12,28 +12,38 @@ impl Plane {
         Plane {}
     }
 
-    pub fn intersect(ray: &Ray, object: &Object) -> Intersections {
-        if ray.direction.y.abs() < EPSILON {
-            Intersections::default()
-        } else {
-            let t = -ray.origin.y / ray.direction.y;
-            Intersections::new(vec![Intersection::new(t, object.clone())])
+    pub fn intersect<'a>(ray: &Ray, object: &'a Object) -> Intersections<'a> {
+        object.intersect(ray)
+    }
+
+    /// Appends the intersection, if any, to a reusable buffer. The ray has
+    /// already been taken into the object's own space. Rays are taken to be
+    /// parallel to the plane by the angle between them, so scaling the plane
+    /// up or down doesn't change which rays miss it.
+    pub fn intersect_into<'a>(local_ray: &Ray, object: &'a Object, out: &mut Vec<Intersection<'a>>) {
+        if local_ray.direction.y.abs() >= EPSILON * local_ray.direction.magnitude() {
+            let t = -local_ray.origin.y / local_ray.direction.y;
+            out.push(Intersection::new(t, object));
         }
     }
 
-    pub fn normal_at(_object_point: Vector4<f64>, _object: &Object) -> Vector4<f64> {
-        vector(0.0, 1.0, 0.0)
+    pub fn normal_at(_world_point: Vector4<Float>, object: &Object) -> Vector4<Float> {
+        object.normal_to_world(vector(0.0, 1.0, 0.0))
     }
 
-    pub fn uv_manifold(pos: Vector4<f64>) -> Vector4<f64> {
-        pos
+    /// UV coordinates of a point on the plane, with u along x and v along z
+    /// in the x and z of the result. Height is dropped, so patterns on the
+    /// plane don't flicker between the two sides of y = 0.
+    pub fn uv_manifold(pos: Vector4<Float>) -> Vector4<Float> {
+        point(pos.x, 0.0, pos.z)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::core::point;
+    use crate::core::{point, Transform};
+    use nalgebra::Matrix4;
 
     #[test]
     fn normal_of_plane_constant_everywhere() {
@@ -73,7 +83,7 @@ mod tests {
 
         assert_eq!(xs.intrsc.len(), 1);
         assert_eq!(xs[0].t, 1.0);
-        assert_eq!(xs[0].object, p);
+        assert_eq!(xs[0].object, &p);
     }
 
     #[test]
@@ -84,6 +94,19 @@ mod tests {
 
         assert_eq!(xs.intrsc.len(), 1);
         assert_eq!(xs[0].t, 1.0);
-        assert_eq!(xs[0].object, p);
+        assert_eq!(xs[0].object, &p);
+    }
+
+    #[test]
+    fn scaled_up_planes_still_catch_glancing_rays() {
+        let p = Object::new_plane().with_transform(Matrix4::uscale(1.0e6));
+        let r = Ray::new(point(0.0, 1.0, 0.0), vector(0.0, -0.01, 1.0));
+
+        assert_eq!(p.intersect(&r).intrsc.len(), 1);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn plane_uv_drops_the_height() {
+        assert_eq!(Plane::uv_manifold(point(1.5, -0.00001, -2.0)), point(1.5, 0.0, -2.0));
+    }
+}
@@ -0,0 +1,191 @@
+use crate::{Float, EPSILON};
+use crate::core::{point, vector, Intersection, Intersections, Ray};
+use crate::primitives::Object;
+use nalgebra::Vector4;
+
+/// A flat polygon lying in the xz plane of its object, like a plane cut to a
+/// shape, with its normal along +y. The corners are given in order round the
+/// edge, either way round, as (x, z) pairs, so a floor plan can be traced
+/// straight off a drawing. The outline can be concave but shouldn't cross
+/// itself.
+///
+/// The polygon is split into triangles once, by ear clipping, and rays are
+/// tested against those.
+///
+/// # Example
+///
+/// ```ignore
+/// // An L-shaped room.
+/// let floor = Object::new_polygon(Polygon::new(vec![
+///     (0.0, 0.0), (6.0, 0.0), (6.0, 3.0), (3.0, 3.0), (3.0, 5.0), (0.0, 5.0)
+/// ]));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, PartialOrd)]
+pub struct Polygon {
+    vertices: Vec<(Float, Float)>,
+    // Corners of each triangle, as indices into vertices, wound to face +y.
+    triangles: Vec<[usize; 3]>,
+    min: (Float, Float),
+    max: (Float, Float)
+}
+
+impl Polygon {
+    /// A polygon with these corners. Fewer than three leave nothing to hit.
+    pub fn new(vertices: Vec<(Float, Float)>) -> Self {
+        let triangles = triangulate(&vertices);
+        let mut min = (Float::INFINITY, Float::INFINITY);
+        let mut max = (Float::NEG_INFINITY, Float::NEG_INFINITY);
+        for &(x, z) in &vertices {
+            min = (min.0.min(x), min.1.min(z));
+            max = (max.0.max(x), max.1.max(z));
+        }
+
+        Polygon { vertices, triangles, min, max }
+    }
+
+    pub fn vertices(&self) -> &[(Float, Float)] {
+        &self.vertices
+    }
+
+    /// Corners of the triangles the polygon is made of, as indices into
+    /// vertices(), wound anticlockwise seen from above.
+    pub fn triangles(&self) -> &[[usize; 3]] {
+        &self.triangles
+    }
+
+    /// Area of the polygon, from its triangles.
+    pub fn area(&self) -> Float {
+        self.triangles.iter()
+            .map(|&[a, b, c]| cross(self.vertices[a], self.vertices[b], self.vertices[c]) / 2.0)
+            .sum()
+    }
+
+    /// Whether a point in the xz plane is on the polygon, edges included.
+    pub fn contains(&self, x: Float, z: Float) -> bool {
+        if x < self.min.0 || x > self.max.0 || z < self.min.1 || z > self.max.1 {
+            return false;
+        }
+        self.triangles.iter().any(|&[a, b, c]| inside((x, z), self.vertices[a], self.vertices[b], self.vertices[c]))
+    }
+
+    pub fn intersect<'a>(ray: &Ray, object: &'a Object) -> Intersections<'a> {
+        object.intersect(ray)
+    }
+
+    /// Appends the intersection, if any, to a reusable buffer. The ray has
+    /// already been taken into the object's own space. It meets the polygon's
+    /// plane as it meets a Plane, and counts if that's inside the outline.
+    pub fn intersect_into<'a>(&self, local_ray: &Ray, object: &'a Object, out: &mut Vec<Intersection<'a>>) {
+        if local_ray.direction.y.abs() < EPSILON * local_ray.direction.magnitude() {
+            return;
+        }
+        let t = -local_ray.origin.y / local_ray.direction.y;
+        let p = local_ray.origin + local_ray.direction * t;
+        if self.contains(p.x, p.z) {
+            out.push(Intersection::new(t, object));
+        }
+    }
+
+    pub fn normal_at(_world_point: Vector4<Float>, object: &Object) -> Vector4<Float> {
+        object.normal_to_world(vector(0.0, 1.0, 0.0))
+    }
+
+    /// UV coordinates of a point on the polygon, as for a Plane.
+    pub fn uv_manifold(pos: Vector4<Float>) -> Vector4<Float> {
+        point(pos.x, 0.0, pos.z)
+    }
+}
+
+// Twice the signed area of the triangle oab, positive when it winds
+// anticlockwise seen from above, so that its normal points up +y.
+fn cross(o: (Float, Float), a: (Float, Float), b: (Float, Float)) -> Float {
+    (a.1 - o.1) * (b.0 - o.0) - (a.0 - o.0) * (b.1 - o.1)
+}
+
+// Whether p is inside the anticlockwise triangle abc, or on its edges.
+fn inside(p: (Float, Float), a: (Float, Float), b: (Float, Float), c: (Float, Float)) -> bool {
+    cross(a, b, p) >= 0.0 && cross(b, c, p) >= 0.0 && cross(c, a, p) >= 0.0
+}
+
+// Splits the outline into triangles by clipping off ears, corners whose
+// triangle with their neighbours is inside the outline, one at a time.
+fn triangulate(vertices: &[(Float, Float)]) -> Vec<[usize; 3]> {
+    let n = vertices.len();
+    if n < 3 {
+        return vec![];
+    }
+    let mut remaining: Vec<usize> = (0..n).collect();
+    let twice_area: Float = (0..n).map(|i| cross((0.0, 0.0), vertices[i], vertices[(i + 1) % n])).sum();
+    if twice_area < 0.0 {
+        remaining.reverse();
+    }
+    let mut triangles = Vec::with_capacity(n - 2);
+    while remaining.len() > 3 {
+        let len = remaining.len();
+        let corners = |i: usize| (remaining[(i + len - 1) % len], remaining[i], remaining[(i + 1) % len]);
+        let is_ear = |i: usize| {
+            let (a, b, c) = corners(i);
+            let (pa, pb, pc) = (vertices[a], vertices[b], vertices[c]);
+            cross(pa, pb, pc) > 0.0 && remaining.iter()
+                .filter(|&&j| j != a && j != b && j != c && vertices[j] != pa && vertices[j] != pb && vertices[j] != pc)
+                .all(|&j| !inside(vertices[j], pa, pb, pc))
+        };
+        // An outline that crosses itself, or has corners in a straight line,
+        // may have no ears left, so clip a corner anyway rather than loop.
+        let i = (0..len).find(|&i| is_ear(i)).unwrap_or(0);
+        let (a, b, c) = corners(i);
+        triangles.push([a, b, c]);
+        remaining.remove(i);
+    }
+    triangles.push([remaining[0], remaining[1], remaining[2]]);
+
+    triangles
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::Transform;
+    use nalgebra::Matrix4;
+
+    fn l_shape() -> Polygon {
+        Polygon::new(vec![(0.0, 0.0), (6.0, 0.0), (6.0, 3.0), (3.0, 3.0), (3.0, 5.0), (0.0, 5.0)])
+    }
+
+    #[test]
+    fn concave_outlines_split_into_triangles() {
+        let room = l_shape();
+
+        assert_eq!(room.triangles().len(), 4);
+        assert_eq!(room.area(), 24.0);
+        assert!(room.contains(1.0, 4.0) && room.contains(5.0, 1.0) && room.contains(3.0, 3.0));
+        assert!(!room.contains(4.0, 4.0) && !room.contains(-1.0, 1.0));
+        // Wound the other way round, it's the same shape.
+        let mut reversed = room.vertices().to_vec();
+        reversed.reverse();
+        assert_eq!(Polygon::new(reversed).area(), 24.0);
+        assert!(Polygon::new(vec![(0.0, 0.0), (1.0, 0.0)]).triangles().is_empty());
+    }
+
+    #[test]
+    fn triangles_face_up() {
+        let room = l_shape();
+        for &[a, b, c] in room.triangles() {
+            assert!(cross(room.vertices()[a], room.vertices()[b], room.vertices()[c]) > 0.0);
+        }
+    }
+
+    #[test]
+    fn rays_hit_inside_the_outline() {
+        let floor = Object::new_polygon(l_shape()).with_transform(Matrix4::translate(0.0, -1.0, 0.0));
+        let hit = floor.intersect(&Ray::new(point(1.0, 1.0, 4.0), vector(0.0, -1.0, 0.0)));
+        let notch = floor.intersect(&Ray::new(point(4.0, 1.0, 4.0), vector(0.0, -1.0, 0.0)));
+        let parallel = floor.intersect(&Ray::new(point(1.0, -1.0, 4.0), vector(1.0, 0.0, 0.0)));
+
+        assert_eq!(hit.intrsc.len(), 1);
+        assert_eq!(hit[0].t, 2.0);
+        assert_eq!(notch.intrsc.len(), 0);
+        assert_eq!(parallel.intrsc.len(), 0);
+        assert_eq!(floor.normal_at(point(1.0, -1.0, 4.0)), vector(0.0, 1.0, 0.0));
+    }
+}
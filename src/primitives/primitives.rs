@@ -1,8 +1,22 @@
-use super::TestShape;
+use super::{Lens, Polygon, Sphere, TestShape};
 
-#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
 pub enum Primitive {
     Plane(),
-    Sphere(),
+    Sphere(Sphere),
+    Lens(Lens),
+    Polygon(Polygon),
     TestShape(TestShape)
-}
\ No newline at end of file
+}
+impl Primitive {
+    /// Name of the kind of shape, as shown in debug traces.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Primitive::Plane() => "Plane",
+            Primitive::Sphere(_) => "Sphere",
+            Primitive::Lens(_) => "Lens",
+            Primitive::Polygon(_) => "Polygon",
+            Primitive::TestShape(_) => "TestShape"
+        }
+    }
+}
@@ -1,62 +1,88 @@
-use crate::core::{point, Intersection, Intersections, Ray};
+use crate::Float;
+use crate::core::{point, roots, Intersection, Intersections, Ray};
 use crate::primitives::Object;
 use nalgebra::{Vector4, Matrix4};
-use std::f64::consts::PI;
+use crate::consts::PI;
+
+// Centre and radius are defined by the identity matrix, so all a sphere holds
+// is how much of its surface is kept.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub struct Sphere {
+    /// Lowest and highest latitudes kept, in radians from -PI/2 at the bottom
+    /// (-y) to PI/2 at the top.
+    pub latitude: (Float, Float),
+    /// Longitudes kept, in radians about the y axis from -PI to PI, with 0.0
+    /// along +x and PI/2 along +z. A range running from a higher longitude to
+    /// a lower one wraps round through PI.
+    pub longitude: (Float, Float)
+}
 
-// Original struct no longer needed as centre and radius is defined by the
-// identity matrix anyway.
-#[derive(Debug, Clone, Copy)]
-pub struct Sphere;
+impl Default for Sphere {
+    fn default() -> Self {
+        Sphere::new()
+    }
+}
 
 impl Sphere {
+    /// The whole sphere.
     pub fn new() -> Self {
-        Sphere {}
+        Sphere { latitude: (-PI / 2.0, PI / 2.0), longitude: (-PI, PI) }
     }
 
-    /// Calculates intersections between the object and a ray.
-    pub fn intersect(ray: &Ray, object: &Object) -> Intersections {
-        let local_ray = Ray {
-            origin: object.inverse_transform * ray.origin,
-            direction: object.inverse_transform * ray.direction
+    /// Whether all of the surface is kept, so the sphere is closed.
+    pub fn is_whole(&self) -> bool {
+        let (lon0, lon1) = self.longitude;
+        self.latitude.0 <= -PI / 2.0 && self.latitude.1 >= PI / 2.0 && lon0 <= lon1 && lon1 - lon0 >= 2.0 * PI
+    }
+
+    /// Whether a point on the sphere, in its own space, is on the part kept.
+    pub fn covers(&self, p: Vector4<Float>) -> bool {
+        let latitude = p.y.atan2((p.x * p.x + p.z * p.z).sqrt());
+        let longitude = p.z.atan2(p.x);
+        let (lon0, lon1) = self.longitude;
+        let in_longitude = if lon0 <= lon1 {
+            lon1 - lon0 >= 2.0 * PI || (lon0..=lon1).contains(&longitude)
+        } else {
+            longitude >= lon0 || longitude <= lon1
         };
+
+        (self.latitude.0..=self.latitude.1).contains(&latitude) && in_longitude
+    }
+
+    /// Calculates intersections between the object and a ray.
+    pub fn intersect<'a>(ray: &Ray, object: &'a Object) -> Intersections<'a> {
+        object.intersect(ray)
+    }
+
+    /// Appends the intersections, nearest first, to a reusable buffer. The ray
+    /// has already been taken into the object's own space.
+    /// Only hits on the part of the surface kept count, so a ray can pass in
+    /// through an open side and hit the inside.
+    pub fn intersect_into<'a>(&self, local_ray: &Ray, object: &'a Object, out: &mut Vec<Intersection<'a>>) {
         let rosc = local_ray.origin - point(0.0, 0.0, 0.0);
         let a = local_ray.direction.dot(&local_ray.direction);
         let b = 2.0 * rosc.dot(&local_ray.direction);
         let c = rosc.dot(&rosc) - 1.0;
-        let d = b * b - 4.0 * a * c;
-        if d < 0.0 {
-            Intersections::default()
-        } else {
-            let t1 = (-b - d.sqrt()) / (2.0 * a);
-            let t2 = (-b + d.sqrt()) / (2.0 * a);
-            let mut intrsc = vec![];
-            
-            intrsc.push(Intersection {
-                t: t1,
-                object: object.clone()
-            });
-            intrsc.push(Intersection {
-                t: t2,
-                object: object.clone()
-            });
-
-            Intersections::new(intrsc)
+        if let Some((t1, t2)) = roots::quadratic(a, b, c) {
+            let whole = self.is_whole();
+            for t in [t1, t2] {
+                if whole || self.covers(local_ray.position(t)) {
+                    out.push(Intersection::new(t, object));
+                }
+            }
         }
     }
 
     /// Resolves the normal vector at a specified point on an object.
-    pub fn normal_at(object_point: Vector4<f64>, object: &Object) -> Vector4<f64> {
-        let object_normal = (object.inverse_transform * object_point) - point(0.0, 0.0, 0.0);
-        let mut world_normal = object.inverse_transform.transpose() * object_normal;
-        world_normal.w = 0.0;
-        world_normal.normalize_mut();
-        Vector4::new(world_normal.x, world_normal.y, world_normal.z, 0.0)
+    pub fn normal_at(world_point: Vector4<Float>, object: &Object) -> Vector4<Float> {
+        let object_normal = object.world_to_object(world_point) - point(0.0, 0.0, 0.0);
+        object.normal_to_world(object_normal)
     }
 
     /// Converts spherical surface coordinates to planar coordinates
     /// Do not use rotation transform on the pattern when using this.
     /// Rotate the object instead.
-    pub fn uv_manifold(pos: Vector4<f64>, transform: Matrix4<f64>) -> Vector4<f64> {
+    pub fn uv_manifold(pos: Vector4<Float>, transform: Matrix4<Float>) -> Vector4<Float> {
         let phi = (pos.x.powi(2) + pos.z.powi(2)).sqrt().atan2(pos.y);
         let theta = pos.z.atan2(pos.x);
         let u = (theta / PI) * 2.0;
@@ -71,6 +97,7 @@ mod tests {
     use super::*;
     use crate::core::{vector, Transform, Tuple};
     use crate::materials::Material;
+    use crate::primitives::Primitive;
     use nalgebra::Matrix4;
 
     #[test]
@@ -84,6 +111,17 @@ mod tests {
         assert_eq!(xs[1].t, 6.0);
     }
 
+    #[test]
+    fn intersections_are_appended_to_a_buffer() {
+        let r = Ray::new(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+        let s = Object::new_sphere();
+        let mut out = vec![];
+        s.intersect_into(&r, &mut out);
+        s.intersect_into(&r, &mut out);
+
+        assert_eq!(out.iter().map(|i| i.t).collect::<Vec<Float>>(), vec![4.0, 6.0, 4.0, 6.0]);
+    }
+
     #[test]
     fn ray_intersects_sphere_at_tangent() {
         let r = Ray::new(point(0.0, 1.0, -5.0), vector(0.0, 0.0, 1.0));
@@ -184,22 +222,23 @@ mod tests {
     #[test]
     fn normal_on_sphere_at_nonaxial_point() {
         let s = Object::new_sphere();
-        let irr_no = 3.0f64.sqrt() / 3.0;
+        let irr_no = Float::sqrt(3.0) / 3.0;
         let n = s.normal_at(point(irr_no, irr_no, irr_no));
 
-        assert_eq!(n, vector(irr_no, irr_no, irr_no));
+        assert!(n.approx_eq(&vector(irr_no, irr_no, irr_no)));
     }
 
     #[test]
     fn normal_is_normalised_vector() {
         let s = Object::new_sphere();
-        let irr_no = 3.0f64.sqrt() / 3.0;
+        let irr_no = Float::sqrt(3.0) / 3.0;
         let n = s.normal_at(point(irr_no, irr_no, irr_no));
 
-        assert_eq!(n, n.normalize());
+        assert!(n.approx_eq(&n.normalize()));
     }
 
     #[test]
+    #[allow(clippy::approx_constant)]
     fn computing_normal_on_translated_sphere() {
         let mut s = Object::new_sphere();
         s.with_transform(Matrix4::translate(0.0, 1.0, 0.0));
@@ -215,7 +254,7 @@ mod tests {
             Matrix4::nuscale(1.0, 0.5, 1.0) *
             Matrix4::rot_z(PI/5.0)
         );
-        let irr_no = 2.0f64.sqrt() / 2.0;
+        let irr_no = Float::sqrt(2.0) / 2.0;
         let n = s.normal_at(point(0.0, irr_no, -irr_no));
 
         assert_eq!(n.to_5dp(), vector(0.0, 0.97014, -0.24254));
@@ -232,8 +271,7 @@ mod tests {
     #[test]
     fn sphere_may_be_assigned_material() {
         let mut s = Object::new_sphere();
-        let mut m = Material::default();
-        m.ambient = 1.0;
+        let m = Material::default().with_ambient(1.0);
         s.material = m;
 
         assert_eq!(s.material, m);
@@ -247,4 +285,34 @@ mod tests {
         assert_eq!(s.material.transparency, 1.0);
         assert_eq!(s.material.ior, 1.5);
     }
+
+    #[test]
+    fn domes_only_keep_hits_on_the_top_half() {
+        let dome = Object::new_sphere().with_latitude(0.0, PI / 2.0);
+        let down = dome.intersect(&Ray::new(point(0.0, 5.0, 0.0), vector(0.0, -1.0, 0.0)));
+        let across = dome.intersect(&Ray::new(point(0.0, -0.5, -5.0), vector(0.0, 0.0, 1.0)));
+        let through = dome.intersect(&Ray::new(point(0.5, 0.5, -5.0), vector(0.0, 0.0, 1.0)));
+
+        assert_eq!(down.len(), 1);
+        assert_eq!(down[0].t, 4.0);
+        assert_eq!(across.len(), 0);
+        assert_eq!(through.len(), 2);
+    }
+
+    #[test]
+    fn longitudes_can_wrap_round() {
+        let wedge = Object::new_sphere().with_longitude(PI / 4.0, -PI / 4.0);
+        let Primitive::Sphere(sphere) = wedge.shape else { unreachable!() };
+
+        assert!(!sphere.is_whole());
+        assert!(!sphere.covers(point(1.0, 0.0, 0.0)));
+        assert!(sphere.covers(point(-1.0, 0.0, 0.0)));
+        assert!(sphere.covers(point(0.0, 0.0, 1.0)));
+        // Looking along -x into the open wedge, the ray goes in and hits the far side.
+        let xs = wedge.intersect(&Ray::new(point(5.0, 0.0, 0.0), vector(-1.0, 0.0, 0.0)));
+        assert_eq!(xs.len(), 1);
+        assert_eq!(xs[0].t, 6.0);
+        assert!(Sphere::new().is_whole());
+        assert_eq!(Object::new_plane().with_latitude(0.0, 1.0).shape, Primitive::Plane());
+    }
 }
\ No newline at end of file
@@ -1,4 +1,5 @@
-use crate::core::{point, vector, Intersections, Ray};
+use crate::Float;
+use crate::core::{point, vector, Intersection, Intersections, Ray};
 use crate::primitives::Object;
 use nalgebra::Vector4;
 
@@ -12,23 +13,37 @@ impl TestShape {
         TestShape { saved_ray: Ray::new(point(0.0, 0.0, 0.0), vector(0.0, 0.0, 1.0)) }
     }
 
-    pub fn intersect(&mut self, ray: &Ray, object: &Object) -> Intersections {
-        self.saved_ray = Ray {
-            origin: object.inverse_transform * ray.origin,
-            direction: object.inverse_transform * ray.direction
+    pub fn intersect(&mut self, ray: &Ray, object: &Object) -> Intersections<'static> {
+        let local_ray = Ray {
+            origin: object.world_to_object(ray.origin),
+            direction: object.world_to_object(ray.direction),
+            ..*ray
         };
+        self.intersect_into(&local_ray, object, &mut vec![]);
         Intersections::new(vec![])
     }
 
-    pub fn normal_at(&self, object_point: Vector4<f64>, _object: &Object) -> Vector4<f64> {
+    /// Saves the ray, already in the object's own space, and never hits
+    /// anything.
+    pub fn intersect_into(&mut self, local_ray: &Ray, _object: &Object, _out: &mut Vec<Intersection>) {
+        self.saved_ray = *local_ray;
+    }
+
+    pub fn normal_at(&self, object_point: Vector4<Float>, _object: &Object) -> Vector4<Float> {
         point(object_point.x, object_point.y, object_point.z)
     }
 
-    pub fn uv_manifold(&self, pos: Vector4<f64>) -> Vector4<f64> {
+    pub fn uv_manifold(&self, pos: Vector4<Float>) -> Vector4<Float> {
         pos
     }
 }
 
+impl Default for TestShape {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -48,8 +63,7 @@ mod tests {
     #[test]
     fn sphere_may_be_assigned_material() {
         let mut s = Object::new_test_shape();
-        let mut m = Material::default();
-        m.ambient = 1.0;
+        let m = Material::default().with_ambient(1.0);
         s.material = m;
 
         assert_eq!(s.material, m);
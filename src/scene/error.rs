@@ -0,0 +1,44 @@
+use std::fmt;
+use std::io;
+
+/// Everything that can go wrong while reading a scene file.
+#[derive(Debug)]
+pub enum SceneError {
+    /// The file couldn't be read.
+    Io(io::Error),
+    /// The file isn't valid YAML, or uses YAML the parser doesn't support.
+    Syntax { line: usize, message: String },
+    /// The YAML is fine, but doesn't describe a valid scene.
+    Invalid(String)
+}
+
+impl SceneError {
+    pub(crate) fn syntax(line: usize, message: &str) -> Self {
+        SceneError::Syntax { line, message: message.to_string() }
+    }
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SceneError::Io(err) => write!(f, "unable to read scene: {}", err),
+            SceneError::Syntax { line, message } => write!(f, "line {}: {}", line, message),
+            SceneError::Invalid(message) => write!(f, "invalid scene: {}", message)
+        }
+    }
+}
+
+impl std::error::Error for SceneError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SceneError::Io(err) => Some(err),
+            _ => None
+        }
+    }
+}
+
+impl From<io::Error> for SceneError {
+    fn from(err: io::Error) -> Self {
+        SceneError::Io(err)
+    }
+}
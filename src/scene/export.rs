@@ -0,0 +1,350 @@
+use crate::{to_f32, Float};
+use crate::consts::PI;
+use crate::core::{point, vector, World};
+use crate::primitives::{Lens, Object, Primitive, Sphere};
+use crate::scene::SceneError;
+use nalgebra::Vector4;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+// Rings from pole to pole, and segments around, of a tessellated sphere.
+const SPHERE_RINGS: usize = 16;
+const SPHERE_SEGMENTS: usize = 32;
+// Planes go on forever, so are cut off this far from their origin.
+const PLANE_HALF_SIZE: Float = 50.0;
+
+// Point on a shape, with the shape's normal there.
+type Vertex = (Vector4<Float>, Vector4<Float>);
+
+/// Triangles standing in for an object's shape, in world space, for looking
+/// at a scene in other tools.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mesh {
+    pub name: Option<String>,
+    pub positions: Vec<[f32; 3]>,
+    pub normals: Vec<[f32; 3]>,
+    /// Indices into positions and normals, three corners per triangle.
+    pub indices: Vec<u32>
+}
+
+impl Mesh {
+    /// Tessellates an object where it sits at time 0.0. Spheres become a
+    /// globe of quads, lenses two discs and a band for the rim, polygons
+    /// their own triangles, and planes a large square. Test shapes have no
+    /// geometry, so give None.
+    pub fn from_object(object: &Object) -> Option<Mesh> {
+        let (local_points, indices) = match object.shape {
+            Primitive::Sphere(sphere) => globe(&sphere),
+            Primitive::Lens(lens) => lens_mesh(&lens),
+            Primitive::Polygon(ref polygon) => (
+                polygon.vertices().iter().map(|&(x, z)| (point(x, 0.0, z), vector(0.0, 1.0, 0.0))).collect(),
+                polygon.triangles().iter().flatten().map(|&i| i as u32).collect()
+            ),
+            Primitive::Plane() => {
+                let h = PLANE_HALF_SIZE;
+                (vec![(-h, -h), (h, -h), (h, h), (-h, h)].into_iter()
+                    .map(|(x, z)| (point(x, 0.0, z), vector(0.0, 1.0, 0.0)))
+                    .collect(), vec![0, 3, 2, 0, 2, 1])
+            },
+            Primitive::TestShape(_) => return None
+        };
+        let transform = object.transform_at(0.0);
+        let normal_transform = transform.try_inverse()?.transpose();
+        let to_array = |v: Vector4<Float>| [to_f32(v.x), to_f32(v.y), to_f32(v.z)];
+        let (positions, normals) = local_points.into_iter()
+            .map(|(p, n): Vertex| {
+                let mut n = normal_transform * n;
+                n.w = 0.0;
+                (to_array(transform * p), to_array(n.normalize()))
+            })
+            .unzip();
+
+        Some(Mesh { name: object.name.clone(), positions, normals, indices })
+    }
+}
+
+// Points and normals of a unit sphere, with the triangles joining them. Quads
+// whose middle is on a part of the sphere cut away are left out.
+fn globe(sphere: &Sphere) -> (Vec<Vertex>, Vec<u32>) {
+    let mut points = vec![];
+    for ring in 0..=SPHERE_RINGS {
+        let theta = PI * ring as Float / SPHERE_RINGS as Float;
+        for segment in 0..=SPHERE_SEGMENTS {
+            let phi = 2.0 * PI * segment as Float / SPHERE_SEGMENTS as Float;
+            let (x, y, z) = (theta.sin() * phi.cos(), theta.cos(), theta.sin() * phi.sin());
+            points.push((point(x, y, z), vector(x, y, z)));
+        }
+    }
+    let row = SPHERE_SEGMENTS as u32 + 1;
+    let mut indices = vec![];
+    for ring in 0..SPHERE_RINGS as u32 {
+        for segment in 0..SPHERE_SEGMENTS as u32 {
+            let (a, b) = (ring * row + segment, (ring + 1) * row + segment);
+            let middle = (points[a as usize].0 + points[b as usize + 1].0) / 2.0;
+            if sphere.covers(middle) {
+                indices.extend_from_slice(&[a, a + 1, b, a + 1, b + 1, b]);
+            }
+        }
+    }
+
+    (points, indices)
+}
+
+// Points and normals of a lens, in rings out from the middle of each face and
+// round the rim, with the triangles joining them.
+fn lens_mesh(lens: &Lens) -> (Vec<Vertex>, Vec<u32>) {
+    let rim = lens.rim();
+    let rings = SPHERE_RINGS / 2;
+    let row = SPHERE_SEGMENTS as u32 + 1;
+    let mut points = vec![];
+    let mut indices = vec![];
+    // Each band runs between two circles, given by a radius and height for
+    // each of its rows.
+    let mut band = |rows: &mut dyn Iterator<Item = (Float, Float, bool)>| {
+        let start = points.len() as u32;
+        let mut count = 0;
+        for (r, z, rim_normal) in rows {
+            for segment in 0..=SPHERE_SEGMENTS {
+                let phi = 2.0 * PI * segment as Float / SPHERE_SEGMENTS as Float;
+                let p = point(r * phi.cos(), r * phi.sin(), z);
+                let normal = if rim_normal {
+                    vector(phi.cos(), phi.sin(), 0.0)
+                } else if z < 0.0 {
+                    lens.front_normal(p)
+                } else {
+                    lens.back_normal(p)
+                };
+                points.push((p, normal));
+            }
+            count += 1;
+        }
+        for ring in 0..count - 1 {
+            for segment in 0..SPHERE_SEGMENTS as u32 {
+                let (a, b) = (start + ring * row + segment, start + (ring + 1) * row + segment);
+                indices.extend_from_slice(&[a, a + 1, b, a + 1, b + 1, b]);
+            }
+        }
+    };
+    let radius = |ring: usize| rim * ring as Float / rings as Float;
+    band(&mut (0..=rings).map(|i| (radius(i), lens.front_z(radius(i)), false)));
+    band(&mut (0..=rings).map(|i| (radius(i), lens.back_z(radius(i)), false)));
+    band(&mut [(rim, lens.front_z(rim), true), (rim, lens.back_z(rim), true)].into_iter());
+
+    (points, indices)
+}
+
+impl World {
+    /// Tessellated geometry of every object that has a shape.
+    pub fn meshes(&self) -> Vec<Mesh> {
+        self.objects.iter().filter_map(Mesh::from_object).collect()
+    }
+
+    /// Saves the world's geometry for viewing in a 3D tool like Blender, to
+    /// check everything is where it should be. The format comes from the
+    /// extension: Wavefront OBJ (.obj) or glTF (.gltf). Only shapes are
+    /// saved, not materials, lights or the camera.
+    pub fn export_geometry<P: AsRef<Path>>(&self, path: P) -> Result<(), SceneError> {
+        let path = path.as_ref();
+        let text = match path.extension().and_then(|e| e.to_str()).map(str::to_lowercase).as_deref() {
+            Some("obj") => self.to_obj(),
+            Some("gltf") => self.to_gltf(),
+            _ => return Err(SceneError::Invalid(format!("can't export geometry to '{}'; use .obj or .gltf", path.display())))
+        };
+        fs::write(path, text)?;
+
+        Ok(())
+    }
+
+    /// The world's geometry as a Wavefront OBJ file, one object per mesh.
+    pub fn to_obj(&self) -> String {
+        let mut out = String::from("# feoray\n");
+        let mut offset = 1;
+        for (i, mesh) in self.meshes().iter().enumerate() {
+            let _ = writeln!(out, "o {}", mesh.name.clone().unwrap_or_else(|| format!("object-{}", i)));
+            for [x, y, z] in &mesh.positions {
+                let _ = writeln!(out, "v {} {} {}", x, y, z);
+            }
+            for [x, y, z] in &mesh.normals {
+                let _ = writeln!(out, "vn {} {} {}", x, y, z);
+            }
+            for tri in mesh.indices.chunks(3) {
+                let [a, b, c] = [tri[0] + offset, tri[1] + offset, tri[2] + offset];
+                let _ = writeln!(out, "f {}//{} {}//{} {}//{}", a, a, b, b, c, c);
+            }
+            offset += mesh.positions.len() as u32;
+        }
+
+        out
+    }
+
+    /// The world's geometry as a glTF 2.0 file, with its buffer embedded, and
+    /// a node for each mesh.
+    pub fn to_gltf(&self) -> String {
+        let meshes = self.meshes();
+        let mut buffer: Vec<u8> = vec![];
+        let (mut views, mut accessors, mut gltf_meshes, mut nodes) = (vec![], vec![], vec![], vec![]);
+        for (i, mesh) in meshes.iter().enumerate() {
+            let base = accessors.len();
+            for (values, kind) in [(&mesh.positions, "POSITION"), (&mesh.normals, "NORMAL")] {
+                let start = buffer.len();
+                values.iter().flatten().for_each(|v| buffer.extend_from_slice(&v.to_le_bytes()));
+                views.push(format!("{{\"buffer\":0,\"byteOffset\":{},\"byteLength\":{},\"target\":34962}}", start, buffer.len() - start));
+                let bounds = if kind == "POSITION" {
+                    let (min, max) = bounds(values);
+                    format!(",\"min\":[{},{},{}],\"max\":[{},{},{}]", min[0], min[1], min[2], max[0], max[1], max[2])
+                } else {
+                    String::new()
+                };
+                accessors.push(format!(
+                    "{{\"bufferView\":{},\"componentType\":5126,\"count\":{},\"type\":\"VEC3\"{}}}",
+                    views.len() - 1, values.len(), bounds
+                ));
+            }
+            let start = buffer.len();
+            mesh.indices.iter().for_each(|v| buffer.extend_from_slice(&v.to_le_bytes()));
+            views.push(format!("{{\"buffer\":0,\"byteOffset\":{},\"byteLength\":{},\"target\":34963}}", start, buffer.len() - start));
+            accessors.push(format!(
+                "{{\"bufferView\":{},\"componentType\":5125,\"count\":{},\"type\":\"SCALAR\"}}",
+                views.len() - 1, mesh.indices.len()
+            ));
+            gltf_meshes.push(format!(
+                "{{\"primitives\":[{{\"attributes\":{{\"POSITION\":{},\"NORMAL\":{}}},\"indices\":{}}}]}}",
+                base, base + 1, base + 2
+            ));
+            let name = mesh.name.clone().unwrap_or_else(|| format!("object-{}", i));
+            nodes.push(format!("{{\"name\":\"{}\",\"mesh\":{}}}", escape(&name), i));
+        }
+        let scene_nodes: Vec<String> = (0..nodes.len()).map(|i| i.to_string()).collect();
+
+        format!(
+            "{{\"asset\":{{\"version\":\"2.0\",\"generator\":\"feoray\"}},\"scene\":0,\"scenes\":[{{\"nodes\":[{}]}}],\
+             \"nodes\":[{}],\"meshes\":[{}],\"accessors\":[{}],\"bufferViews\":[{}],\
+             \"buffers\":[{{\"byteLength\":{},\"uri\":\"data:application/octet-stream;base64,{}\"}}]}}",
+            scene_nodes.join(","), nodes.join(","), gltf_meshes.join(","), accessors.join(","), views.join(","),
+            buffer.len(), base64(&buffer)
+        )
+    }
+}
+
+fn bounds(values: &[[f32; 3]]) -> ([f32; 3], [f32; 3]) {
+    values.iter().fold(([f32::INFINITY; 3], [f32::NEG_INFINITY; 3]), |(min, max), v| {
+        ([0, 1, 2].map(|c| min[c].min(v[c])), [0, 1, 2].map(|c| max[c].max(v[c])))
+    })
+}
+
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const DIGITS: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity((bytes.len() + 2) / 3 * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk.iter().enumerate().fold(0u32, |n, (i, b)| n | (*b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(DIGITS[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::Transform;
+    use crate::primitives::Polygon;
+    use nalgebra::Matrix4;
+
+    #[test]
+    fn spheres_are_tessellated_where_they_sit() {
+        let ball = Object::new_sphere().with_transform(Matrix4::translate(0.0, 2.0, 0.0) * Matrix4::uscale(0.5));
+        let mesh = Mesh::from_object(&ball).unwrap();
+
+        assert_eq!(mesh.positions.len(), (SPHERE_RINGS + 1) * (SPHERE_SEGMENTS + 1));
+        assert_eq!(mesh.indices.len(), SPHERE_RINGS * SPHERE_SEGMENTS * 6);
+        for (p, n) in mesh.positions.iter().zip(&mesh.normals) {
+            let offset = [p[0], p[1] - 2.0, p[2]];
+            assert!((offset.iter().map(|c| c * c).sum::<f32>().sqrt() - 0.5).abs() < 1e-5);
+            assert!(offset.iter().zip(n).all(|(o, n)| (o * 2.0 - n).abs() < 1e-4));
+        }
+        assert!(Mesh::from_object(&Object::new_test_shape()).is_none());
+        let dome = Mesh::from_object(&Object::new_sphere().with_latitude(0.0, PI / 2.0)).unwrap();
+        assert_eq!(dome.indices.len(), SPHERE_RINGS * SPHERE_SEGMENTS * 3);
+    }
+
+    #[test]
+    fn worlds_export_to_obj() {
+        let mut w = World::default_world().with_object(Object::new_plane());
+        w.objects[0].with_name("outer");
+        let obj = w.to_obj();
+        let sphere = (SPHERE_RINGS + 1) * (SPHERE_SEGMENTS + 1);
+
+        assert!(obj.contains("o outer\n") && obj.contains("o object-1\n"));
+        assert_eq!(obj.lines().filter(|l| l.starts_with("v ")).count(), 2 * sphere + 4);
+        // Faces of the plane point at its own vertices, after both spheres'.
+        let last = sphere as u32 * 2 + 1;
+        assert!(obj.ends_with(&format!("f {}//{} {}//{} {}//{}\n", last, last, last + 2, last + 2, last + 1, last + 1)));
+    }
+
+    #[test]
+    fn worlds_export_to_gltf() {
+        let w = World::default().with_object(Object::new_plane().with_name("floor"));
+        let gltf = w.to_gltf();
+
+        assert!(gltf.starts_with("{\"asset\":{\"version\":\"2.0\""));
+        assert!(gltf.contains("\"nodes\":[{\"name\":\"floor\",\"mesh\":0}]"));
+        assert!(gltf.contains("\"min\":[-50,0,-50],\"max\":[50,0,50]"));
+        // Four positions and normals of three floats, and six indices.
+        assert!(gltf.contains("\"byteLength\":120,"));
+    }
+
+    #[test]
+    fn geometry_is_saved_in_the_format_of_the_extension() {
+        let dir = std::env::temp_dir();
+        let w = World::default_world();
+        w.export_geometry(dir.join("feoray_geometry.obj")).unwrap();
+        w.export_geometry(dir.join("feoray_geometry.gltf")).unwrap();
+
+        assert!(fs::read_to_string(dir.join("feoray_geometry.obj")).unwrap().starts_with("# feoray"));
+        assert!(fs::read_to_string(dir.join("feoray_geometry.gltf")).unwrap().starts_with("{\"asset\""));
+        assert!(w.export_geometry(dir.join("feoray_geometry.stl")).is_err());
+    }
+
+    #[test]
+    fn base64_pads_to_whole_groups() {
+        assert_eq!(base64(b"Man"), "TWFu");
+        assert_eq!(base64(b"Ma"), "TWE=");
+        assert_eq!(base64(b"M"), "TQ==");
+    }
+
+    #[test]
+    fn lenses_are_tessellated_as_two_faces_and_a_rim() {
+        let lens = Lens::new(2.0, 2.0, 0.5);
+        let mesh = Mesh::from_object(&Object::new_lens(lens)).unwrap();
+        let rings = SPHERE_RINGS / 2;
+
+        assert_eq!(mesh.positions.len(), (2 * (rings + 1) + 2) * (SPHERE_SEGMENTS + 1));
+        assert_eq!(mesh.indices.len(), (2 * rings + 1) * SPHERE_SEGMENTS * 6);
+        // The middle of the front face looks back along the axis.
+        assert_eq!(mesh.positions[0], [0.0, 0.0, to_f32(lens.front_z(0.0))]);
+        assert_eq!(mesh.normals[0], [0.0, 0.0, -1.0]);
+        let last = mesh.normals.len() - 1;
+        assert!((mesh.normals[last][0] - 1.0).abs() < 1e-5 && mesh.normals[last][2] == 0.0);
+    }
+
+    #[test]
+    fn polygons_keep_their_own_triangles() {
+        let square = Polygon::new(vec![(0.0, 0.0), (0.0, 2.0), (2.0, 2.0), (2.0, 0.0)]);
+        let mesh = Mesh::from_object(&Object::new_polygon(square).with_transform(Matrix4::translate(0.0, 1.0, 0.0))).unwrap();
+
+        assert_eq!(mesh.positions, vec![[0.0, 1.0, 0.0], [0.0, 1.0, 2.0], [2.0, 1.0, 2.0], [2.0, 1.0, 0.0]]);
+        assert_eq!(mesh.indices.len(), 6);
+        assert!(mesh.normals.iter().all(|n| *n == [0.0, 1.0, 0.0]));
+    }
+}
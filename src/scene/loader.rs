@@ -0,0 +1,1275 @@
+use crate::{to_f32, Float, FeorayError};
+use crate::core::{point, vector, Background, Camera, Colour, Diagnostic, RayKind, Transform, TransformBuilder, World, MAX_KEYFRAMES};
+use crate::core::matrix::inverse;
+use crate::lights::PointLight;
+use crate::materials::{ColourRamp, DensityNoise, Filter, Interpolation, Jitter, Material, Medium, Pattern, Wrap};
+use crate::materials::ramp::MAX_STOPS;
+#[cfg(feature = "image")]
+use crate::materials::{TextureId, Textures};
+use crate::primitives::{Lens, Object, Polygon, Primitive};
+use crate::scene::{SceneError, Yaml};
+use nalgebra::{Matrix4, Vector4};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+// How deep includes can be nested, which stops a file including itself.
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+/// A world and the camera to view it with, read from a scene file.
+///
+/// Scene files use the YAML format from the appendix of The Ray Tracer
+/// Challenge: a list of `add` items for the camera, lights and objects, and
+/// `define` items for named materials and transforms that can be reused, and
+/// extended with `extend`.
+///
+/// # Example
+///
+/// ```ignore
+/// - add: camera
+///   width: 100
+///   height: 50
+///   field-of-view: 1.047
+///   from: [ 0, 1.5, -5 ]
+///   to: [ 0, 1, 0 ]
+///   up: [ 0, 1, 0 ]
+///
+/// - add: light
+///   at: [ -10, 10, -10 ]
+///   intensity: [ 1, 1, 1 ]
+///
+/// - define: red
+///   value:
+///     color: [ 1, 0, 0 ]
+///     diffuse: 0.7
+///
+/// - add: sphere
+///   material: red
+///   transform:
+///     - [ scale, 0.5, 0.5, 0.5 ]
+///     - [ translate, 0, 1, 0 ]
+/// ```
+///
+/// # Camera
+///
+/// Besides its size, field of view and placement, a camera can take an
+/// exposure, either in stops or as a real camera's `{ f-number, shutter,
+/// iso }`, with the shutter in seconds. It can also take lens distortion as
+/// `[ k1, k2 ]`, vignetting, a lens shift as `[ x, y ]` shares of the frame,
+/// and a lens tilt as `[ tilt, swing ]` angles.
+///
+/// # Lights
+///
+/// Lights take at, intensity, an optional name, and a mask pattern in world
+/// space that dims the light where it's dark.
+///
+/// # Objects
+///
+/// Objects can be a sphere, a plane, a lens or a polygon. They all take a
+/// material, transform, shadow, hidden-from (a list of camera, reflection,
+/// refraction or diffuse rays), manifold, keyframes, an id for object ID
+/// passes and masks, and a name.
+///
+/// - Spheres take latitude and longitude ranges, as `[ min, max ]` in
+///   radians, to cut them down to a dome, bowl or wedge.
+/// - Lenses take front, back, thickness and an optional radius (see
+///   Lens::new()).
+/// - Polygons take vertices, a list of `[ x, z ]` corners in order.
+///
+/// A medium fills the shape with smoke or fog. It takes color, density,
+/// absorption, scattering, steps, and noise with a seed, frequency, octaves
+/// and threshold.
+///
+/// Transforms are applied in the order they are listed, and can be
+/// translate, scale, rotate-x/y/z, shear, or a whole matrix given row by
+/// row.
+///
+/// # Materials
+///
+/// Materials take color, ambient, diffuse, specular, shininess, reflective,
+/// transparency, refractive-index and pattern. Objects naming a defined
+/// material share it through the world's palette (see World::set_material()).
+///
+/// Colors are a list of red, green and blue, a hex string like "#ffaa00" or
+/// a CSS color name.
+///
+/// # Patterns
+///
+/// Patterns take a type (solid, stripes, checkers, gradient, rings, radial,
+/// radial-gradient, bricks, tiles or texture), their colors, and an optional
+/// transform. Patterns on objects using their manifold also take tiling and
+/// offset in u and v.
+///
+/// - Stripes take a direction to run across, x, y, z or a vector.
+/// - Rings take a width for each ring.
+/// - Gradients and radial gradients can take a ramp in place of their
+///   colors, with a list of stops, each a position from 0 to 1 and a color,
+///   and how to blend between them (constant, linear or smooth).
+/// - Bricks and tiles take their colour and the mortar's, a size along x
+///   and z, the mortar width and a row-offset, as a share of a brick, and a
+///   jitter to vary the bricks' shade.
+/// - Textures take an image file, found relative to the scene, and
+///   optionally how it wraps (repeat, clamp or mirror) and is filtered
+///   (nearest or bilinear).
+/// - A projection throws an image file onto the scene like a slide
+///   projector. It's placed in world space with from, to and an optional up,
+///   or a transform, and takes its field-of-view, an optional aspect to
+///   stretch the image to, the outside color where the image doesn't reach,
+///   and a filter.
+/// - A blend takes two patterns to average.
+/// - A nested pattern takes a mask pattern and two patterns to show where
+///   it's black and where it's white.
+///
+/// # World
+///
+/// An `add: world` item sets:
+///
+/// - the recursion-limit;
+/// - the background, a color or a `{ horizon, zenith }` sky gradient;
+/// - a global ambient color with its ambient-strength;
+/// - the number of light-samples shaded at each hit;
+/// - the epsilon rays leaving a surface are offset by, with a slope-bias
+///   added for surfaces seen side on;
+/// - caustics, as `{ photons, radius }`, which are traced once the rest of
+///   the scene is read.
+///
+/// An `add: include` item places the objects and lights of another scene
+/// file, found relative to this one, with an optional transform, so assets
+/// can be built once and used in many scenes.
+#[derive(Debug)]
+pub struct Scene {
+    pub camera: Camera,
+    pub world: World
+}
+
+impl Scene {
+    /// Reads and parses a scene file.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Scene, SceneError> {
+        let path = path.as_ref();
+        let src = fs::read_to_string(path)?;
+        let (camera, world) = read(&src, path.parent().unwrap_or(Path::new("")), 0)?;
+        let camera = camera.ok_or_else(|| invalid("scene has no camera"))?;
+
+        Ok(Scene { camera, world })
+    }
+
+    /// Parses a scene from a string.
+    pub fn parse(src: &str) -> Result<Scene, SceneError> {
+        let (camera, world) = read(src, Path::new(""), 0)?;
+        let camera = camera.ok_or_else(|| invalid("scene has no camera"))?;
+
+        Ok(Scene { camera, world })
+    }
+
+    /// Problems with the scene that would spoil a render of it. See
+    /// World::validate_for().
+    pub fn validate(&self) -> Vec<Diagnostic> {
+        self.world.validate_for(&self.camera)
+    }
+}
+
+/// Parses only the world from a scene, which doesn't need a camera. This is
+/// the counterpart to World::save().
+pub fn parse_world(src: &str) -> Result<World, SceneError> {
+    read(src, Path::new(""), 0).map(|(_, world)| world)
+}
+
+// Reads the world from a scene file, with includes found next to it.
+pub(crate) fn load_world(path: &Path) -> Result<World, SceneError> {
+    let src = fs::read_to_string(path)?;
+
+    read(&src, path.parent().unwrap_or(Path::new("")), 0).map(|(_, world)| world)
+}
+
+// Files included are found relative to dir, and depth is how many includes
+// deep this file is.
+fn read(src: &str, dir: &Path, depth: usize) -> Result<(Option<Camera>, World), SceneError> {
+    let doc = Yaml::parse(src)?;
+    let items = match doc {
+        Yaml::Null => &[][..],
+        ref doc => doc.as_array().ok_or_else(|| invalid("a scene must be a list of items"))?
+    };
+    let mut defines: HashMap<String, Yaml> = HashMap::new();
+    let mut camera = None;
+    let mut world = World::default();
+    let mut caustics = None;
+    for item in items {
+        if let Some(name) = item.get("define") {
+            let name = string(name, "define")?;
+            let value = define(item, &defines)?;
+            defines.insert(name.to_string(), value);
+            continue;
+        }
+        let kind = item.get("add")
+            .ok_or_else(|| invalid("every item needs an 'add' or a 'define'"))?;
+        match string(kind, "add")? {
+            "camera" => camera = Some(parse_camera(item)?),
+            "light" => world = world.with_light(parse_light(item, &defines, dir)?),
+            "world" => {
+                if let Some(limit) = item.get("recursion-limit") {
+                    world = world.with_recursions(number(limit, "recursion-limit")?.clamp(0.0, 255.0) as u8);
+                }
+                if let Some(background) = item.get("background") {
+                    world = world.with_background(parse_background(background)?);
+                }
+                if let Some(ambient) = item.get("ambient") {
+                    let strength = item.get("ambient-strength").map_or(Ok(1.0), |s| number(s, "ambient-strength"))?;
+                    world = world.with_ambient(as_colour(ambient, "ambient")?, to_f32(strength));
+                }
+                if let Some(samples) = item.get("light-samples") {
+                    world = world.with_light_samples(number(samples, "light-samples")?.max(1.0) as usize);
+                }
+                if let Some(epsilon) = item.get("epsilon") {
+                    let epsilon = number(epsilon, "epsilon")?;
+                    if epsilon <= 0.0 {
+                        return Err(invalid("'epsilon' must be more than 0"));
+                    }
+                    world = world.with_epsilon(epsilon);
+                }
+                if let Some(bias) = item.get("slope-bias") {
+                    let bias = number(bias, "slope-bias")?;
+                    if bias < 0.0 {
+                        return Err(invalid("'slope-bias' can't be negative"));
+                    }
+                    world = world.with_slope_bias(bias);
+                }
+                if let Some(settings) = item.get("caustics") {
+                    caustics = Some(parse_caustics(settings)?);
+                }
+            },
+            "include" => {
+                if depth >= MAX_INCLUDE_DEPTH {
+                    return Err(invalid(&format!("includes are nested more than {} deep", MAX_INCLUDE_DEPTH)));
+                }
+                let file = dir.join(string(required(item, "file")?, "file")?);
+                let transform = match item.get("transform") {
+                    Some(value) => parse_transform(value, &defines)?,
+                    None => Matrix4::identity()
+                };
+                if inverse(&transform).is_none() {
+                    return Err(singular(FeorayError::NonInvertible("include")));
+                }
+                let src = fs::read_to_string(&file)
+                    .map_err(|err| invalid(&format!("unable to include '{}': {}", file.display(), err)))?;
+                let (_, prefab) = read(&src, file.parent().unwrap_or(dir), depth + 1)?;
+                world.include(&prefab, transform);
+            },
+            "sphere" => add_object(&mut world, item, Object::new_sphere(), &defines, dir)?,
+            "plane" => add_object(&mut world, item, Object::new_plane(), &defines, dir)?,
+            "lens" => add_object(&mut world, item, Object::new_lens(parse_lens(item)?), &defines, dir)?,
+            "polygon" => add_object(&mut world, item, Object::new_polygon(parse_polygon(item)?), &defines, dir)?,
+            other => return Err(invalid(&format!("unsupported item '{}'", other)))
+        }
+    }
+    if let Some((photons, radius)) = caustics {
+        world = world.with_caustics(photons, radius);
+    }
+
+    Ok((camera, world))
+}
+
+fn invalid(message: &str) -> SceneError {
+    SceneError::Invalid(message.to_string())
+}
+
+// A transform that can't be inverted, such as a scale of zero.
+fn singular(err: FeorayError) -> SceneError {
+    invalid(&err.to_string())
+}
+
+// A define's value, merged over the value it extends.
+fn define(item: &Yaml, defines: &HashMap<String, Yaml>) -> Result<Yaml, SceneError> {
+    let value = item.get("value").ok_or_else(|| invalid("a define needs a 'value'"))?;
+    let base = match item.get("extend") {
+        Some(name) => lookup(string(name, "extend")?, defines)?,
+        None => return Ok(value.clone())
+    };
+    match (base.as_hash(), value.as_hash()) {
+        (Some(base), Some(value)) => {
+            let mut merged = base.to_vec();
+            for (key, v) in value {
+                match merged.iter_mut().find(|(k, _)| k == key) {
+                    Some(entry) => entry.1 = v.clone(),
+                    None => merged.push((key.clone(), v.clone()))
+                }
+            }
+            Ok(Yaml::Hash(merged))
+        },
+        _ => Err(invalid("only mappings can be extended"))
+    }
+}
+
+fn lookup<'a>(name: &str, defines: &'a HashMap<String, Yaml>) -> Result<&'a Yaml, SceneError> {
+    defines.get(name).ok_or_else(|| invalid(&format!("'{}' has not been defined", name)))
+}
+
+fn string<'a>(value: &'a Yaml, key: &str) -> Result<&'a str, SceneError> {
+    value.as_str().ok_or_else(|| invalid(&format!("'{}' must be a name", key)))
+}
+
+fn number(value: &Yaml, key: &str) -> Result<Float, SceneError> {
+    value.as_f64().map(|n| n as Float).ok_or_else(|| invalid(&format!("'{}' must be a number", key)))
+}
+
+fn numbers<const N: usize>(value: &Yaml, key: &str) -> Result<[Float; N], SceneError> {
+    let err = || invalid(&format!("'{}' must be a list of {} numbers", key, N));
+    let items = value.as_array().filter(|items| items.len() == N).ok_or_else(err)?;
+    let mut result = [0.0; N];
+    for (n, item) in result.iter_mut().zip(items) {
+        *n = item.as_f64().ok_or_else(err)? as Float;
+    }
+
+    Ok(result)
+}
+
+fn required<'a>(item: &'a Yaml, key: &str) -> Result<&'a Yaml, SceneError> {
+    item.get(key).ok_or_else(|| invalid(&format!("missing '{}'", key)))
+}
+
+fn as_point(value: &Yaml, key: &str) -> Result<Vector4<Float>, SceneError> {
+    let [x, y, z] = numbers(value, key)?;
+    Ok(point(x, y, z))
+}
+
+fn as_colour(value: &Yaml, key: &str) -> Result<Colour, SceneError> {
+    if let Yaml::String(hex) = value {
+        return Colour::from_hex(hex)
+            .or_else(|| Colour::named(hex))
+            .ok_or_else(|| invalid(&format!("'{}' must be a color name or a hex color like \"#ffaa00\"", key)));
+    }
+    let [r, g, b] = numbers(value, key)?;
+    Ok(Colour::new(to_f32(r), to_f32(g), to_f32(b)))
+}
+
+fn parse_camera(item: &Yaml) -> Result<Camera, SceneError> {
+    let width = number(required(item, "width")?, "width")?;
+    let height = number(required(item, "height")?, "height")?;
+    let fov = number(required(item, "field-of-view")?, "field-of-view")?;
+    let from = as_point(required(item, "from")?, "from")?;
+    let to = as_point(required(item, "to")?, "to")?;
+    let [x, y, z] = numbers(required(item, "up")?, "up")?;
+    if width < 1.0 || height < 1.0 {
+        return Err(invalid("camera width and height must be at least 1"));
+    }
+    if from == to {
+        return Err(invalid("camera 'from' and 'to' must be different points"));
+    }
+
+    let mut camera = Camera::new(width as usize, height as usize, fov)
+        .try_with_transform(Matrix4::view_transform(from, to, vector(x, y, z)))
+        .map_err(singular)?;
+    match item.get("exposure") {
+        Some(exposure @ Yaml::Hash(_)) => {
+            let f_number = number(required(exposure, "f-number")?, "f-number")?;
+            let shutter = number(required(exposure, "shutter")?, "shutter")?;
+            let iso = exposure.get("iso").map_or(Ok(100.0), |iso| number(iso, "iso"))?;
+            if f_number <= 0.0 || shutter <= 0.0 || iso <= 0.0 {
+                return Err(invalid("'f-number', 'shutter' and 'iso' must be more than 0"));
+            }
+            camera.with_photographic_exposure(f_number, shutter, iso);
+        },
+        Some(stops) => { camera.with_exposure(number(stops, "exposure")?); },
+        None => ()
+    }
+    if let Some(distortion) = item.get("distortion") {
+        let [k1, k2] = numbers(distortion, "distortion")?;
+        camera.with_distortion(k1, k2);
+    }
+    if let Some(vignetting) = item.get("vignetting") {
+        camera.with_vignetting(number(vignetting, "vignetting")?);
+    }
+    if let Some(shift) = item.get("shift") {
+        let [x, y] = numbers(shift, "shift")?;
+        camera.with_shift(x, y);
+    }
+    if let Some(tilt) = item.get("tilt") {
+        let [tilt, swing] = numbers(tilt, "tilt")?;
+        camera.with_tilt(tilt, swing);
+    }
+
+    Ok(camera)
+}
+
+// Photons per light and gather radius.
+fn parse_caustics(value: &Yaml) -> Result<(usize, Float), SceneError> {
+    let photons = value.get("photons").map_or(Ok(10000.0), |p| number(p, "photons"))?;
+    let radius = value.get("radius").map_or(Ok(0.1), |r| number(r, "radius"))?;
+    if photons < 0.0 || radius <= 0.0 {
+        return Err(invalid("caustics need a positive number of photons and radius"));
+    }
+
+    Ok((photons as usize, radius))
+}
+
+// Either a flat colour, or a { horizon, zenith } sky gradient.
+fn parse_background(value: &Yaml) -> Result<Background, SceneError> {
+    if value.as_hash().is_some() {
+        let horizon = as_colour(required(value, "horizon")?, "horizon")?;
+        let zenith = as_colour(required(value, "zenith")?, "zenith")?;
+        Ok(Background::gradient(horizon, zenith))
+    } else {
+        Ok(Background::Solid(as_colour(value, "background")?))
+    }
+}
+
+fn parse_light(item: &Yaml, defines: &HashMap<String, Yaml>, dir: &Path) -> Result<PointLight, SceneError> {
+    let at = as_point(required(item, "at")?, "at")?;
+    let intensity = as_colour(required(item, "intensity")?, "intensity")?;
+    let mut light = PointLight::new(intensity, at);
+    if let Some(mask) = item.get("mask") {
+        light = light.with_mask(parse_pattern(mask, defines, dir)?);
+    }
+
+    match item.get("name") {
+        Some(name) => Ok(light.with_name(string(name, "name")?)),
+        None => Ok(light)
+    }
+}
+
+// Radii of curvature of the faces, thickness along the axis, and the radius of
+// the rim.
+fn parse_lens(item: &Yaml) -> Result<Lens, SceneError> {
+    let front = number(required(item, "front")?, "front")?;
+    let back = number(required(item, "back")?, "back")?;
+    let thickness = number(required(item, "thickness")?, "thickness")?;
+    let radius = item.get("radius").map_or(Ok(1.0), |r| number(r, "radius"))?;
+    if thickness <= 0.0 || radius <= 0.0 {
+        return Err(invalid("lenses need a positive thickness and radius"));
+    }
+
+    Ok(Lens::new(front, back, thickness).with_radius(radius))
+}
+
+// Corners of the outline in order, as [ x, z ] pairs.
+fn parse_polygon(item: &Yaml) -> Result<Polygon, SceneError> {
+    let vertices = required(item, "vertices")?.as_array().ok_or_else(|| invalid("'vertices' must be a list"))?;
+    if vertices.len() < 3 {
+        return Err(invalid("polygons need at least three vertices"));
+    }
+    let vertices = vertices.iter()
+        .map(|v| numbers(v, "vertices").map(|[x, z]| (x, z)))
+        .collect::<Result<_, _>>()?;
+
+    Ok(Polygon::new(vertices))
+}
+
+// Objects naming a defined material share it through the world's palette, so
+// it can be changed for all of them at once.
+fn add_object(world: &mut World, item: &Yaml, object: Object, defines: &HashMap<String, Yaml>, dir: &Path) -> Result<(), SceneError> {
+    let object = parse_object(item, object, defines, dir)?;
+    let material = object.material;
+    let id = world.add(object);
+    if let Some(Yaml::String(name)) = item.get("material") {
+        let shared = world.find_material(name).unwrap_or_else(|| world.add_named_material(name, material));
+        world.use_material(id, shared);
+    }
+
+    Ok(())
+}
+
+// Textures are found relative to dir, as includes are.
+fn parse_object(item: &Yaml, mut object: Object, defines: &HashMap<String, Yaml>, dir: &Path) -> Result<Object, SceneError> {
+    for (key, value) in item.as_hash().unwrap_or_default() {
+        match key.as_str() {
+            "add" => {},
+            "material" => {
+                let value = match value {
+                    Yaml::String(name) => lookup(name, defines)?,
+                    value => value
+                };
+                object.with_material(parse_material(value, defines, dir)?);
+            },
+            "transform" => {
+                object.try_with_transform(parse_transform(value, defines)?).map_err(singular)?;
+            },
+            "shadow" => {
+                if !value.as_bool().ok_or_else(|| invalid("'shadow' must be true or false"))? {
+                    object.cast_no_shadow();
+                }
+            },
+            "keyframes" => {
+                let keys = value.as_array().ok_or_else(|| invalid("'keyframes' must be a list"))?;
+                if keys.len() > MAX_KEYFRAMES {
+                    return Err(invalid(&format!("no more than {} keyframes per object", MAX_KEYFRAMES)));
+                }
+                for key in keys {
+                    let time = number(required(key, "time")?, "time")?;
+                    let transform = parse_transform(required(key, "transform")?, defines)?;
+                    if inverse(&transform).is_none() {
+                        return Err(singular(FeorayError::NonInvertible("keyframe")));
+                    }
+                    object.try_with_transform_at(time, transform).map_err(singular)?;
+                }
+            },
+            "hidden-from" => {
+                let kinds = value.as_array().ok_or_else(|| invalid("'hidden-from' must be a list"))?;
+                for kind in kinds {
+                    let name = string(kind, "hidden-from")?;
+                    let kind = RayKind::ALL.into_iter()
+                        .find(|k| k.name() == name)
+                        .ok_or_else(|| invalid(&format!("unknown ray kind '{}'", name)))?;
+                    object.hide_from(kind);
+                }
+            },
+            "id" => {
+                object.with_id(number(value, "id")? as u32);
+            },
+            "name" => {
+                object.with_name(string(value, "name")?);
+            },
+            "latitude" | "longitude" => {
+                if !matches!(object.shape, Primitive::Sphere(_)) {
+                    return Err(invalid(&format!("only spheres take '{}'", key)));
+                }
+                let [min, max] = numbers(value, key)?;
+                if key == "latitude" {
+                    object.with_latitude(min, max);
+                } else {
+                    object.with_longitude(min, max);
+                }
+            },
+            // Read already by parse_lens().
+            "front" | "back" | "thickness" | "radius" if matches!(object.shape, Primitive::Lens(_)) => {},
+            "vertices" if matches!(object.shape, Primitive::Polygon(_)) => {},
+            "manifold" => {
+                if value.as_bool().ok_or_else(|| invalid("'manifold' must be true or false"))? {
+                    object.use_manifold();
+                }
+            },
+            "medium" => {
+                if matches!(object.shape, Primitive::Plane()) {
+                    return Err(invalid("planes can't take a 'medium', as they have no inside to fill"));
+                }
+                let value = match value {
+                    Yaml::String(name) => lookup(name, defines)?,
+                    value => value
+                };
+                object.with_medium(parse_medium(value)?);
+            },
+            other => return Err(invalid(&format!("unknown object property '{}'", other)))
+        }
+    }
+
+    Ok(object)
+}
+
+fn parse_medium(value: &Yaml) -> Result<Medium, SceneError> {
+    let entries = value.as_hash().ok_or_else(|| invalid("a medium must be a mapping"))?;
+    let mut medium = Medium::default();
+    for (key, value) in entries {
+        medium = match key.as_str() {
+            "color" | "colour" => medium.with_colour(as_colour(value, key)?),
+            "density" => medium.with_density(number(value, key)?),
+            "absorption" => medium.with_absorption(number(value, key)?),
+            "scattering" => medium.with_scattering(number(value, key)?),
+            "steps" => medium.with_steps(number(value, key)? as usize),
+            "noise" => medium.with_noise(parse_noise(value)?),
+            other => return Err(invalid(&format!("unknown medium property '{}'", other)))
+        };
+    }
+
+    Ok(medium)
+}
+
+fn parse_noise(value: &Yaml) -> Result<DensityNoise, SceneError> {
+    let entries = value.as_hash().ok_or_else(|| invalid("noise must be a mapping"))?;
+    let mut noise = DensityNoise::default();
+    for (key, value) in entries {
+        noise = match key.as_str() {
+            "seed" => noise.with_seed(number(value, key)? as u32),
+            "frequency" => noise.with_frequency(number(value, key)?),
+            "octaves" => noise.with_octaves(number(value, key)? as u32),
+            "threshold" => noise.with_threshold(number(value, key)?),
+            other => return Err(invalid(&format!("unknown noise property '{}'", other)))
+        };
+    }
+
+    Ok(noise)
+}
+
+fn parse_material(value: &Yaml, defines: &HashMap<String, Yaml>, dir: &Path) -> Result<Material, SceneError> {
+    let entries = value.as_hash().ok_or_else(|| invalid("a material must be a mapping"))?;
+    let mut material = Material::default();
+    for (key, value) in entries {
+        material = match key.as_str() {
+            "color" | "colour" => material.with_colour(as_colour(value, key)?),
+            "ambient" => material.with_ambient(to_f32(number(value, key)?)),
+            "diffuse" => material.with_diffuse(to_f32(number(value, key)?)),
+            "specular" => material.with_specular(to_f32(number(value, key)?)),
+            "shininess" => material.with_smoothness(to_f32(number(value, key)?)),
+            "reflective" => material.with_reflectivity(to_f32(number(value, key)?)),
+            "transparency" => material.with_transparency(to_f32(number(value, key)?)),
+            "refractive-index" => material.with_ior(to_f32(number(value, key)?)),
+            "pattern" => material.with_pattern(parse_pattern(value, defines, dir)?),
+            other => return Err(invalid(&format!("unknown material property '{}'", other)))
+        };
+    }
+
+    Ok(material)
+}
+
+#[cfg_attr(not(feature = "image"), allow(clippy::only_used_in_recursion))]
+fn parse_pattern(value: &Yaml, defines: &HashMap<String, Yaml>, dir: &Path) -> Result<Pattern, SceneError> {
+    let kind = string(required(value, "type")?, "type")?;
+    let colours = |n: usize| -> Result<Vec<Colour>, SceneError> {
+        let err = || invalid(&format!("a {} pattern needs {} 'colors'", kind, n));
+        let colours = required(value, "colors")?.as_array().filter(|c| c.len() == n).ok_or_else(err)?;
+        colours.iter().map(|c| as_colour(c, "colors")).collect()
+    };
+    let children = || -> Result<[Pattern; 2], SceneError> {
+        let err = || invalid(&format!("a {} pattern needs 2 'patterns'", kind));
+        let children = required(value, "patterns")?.as_array().filter(|c| c.len() == 2).ok_or_else(err)?;
+        Ok([parse_pattern(&children[0], defines, dir)?, parse_pattern(&children[1], defines, dir)?])
+    };
+    // A ramp of colors, or the two 'colors' to fade between.
+    let ramp = || -> Result<ColourRamp, SceneError> {
+        let spec = match value.get("ramp") {
+            Some(spec) => spec,
+            None => { let c = colours(2)?; return Ok(ColourRamp::new(c[0], c[1])) }
+        };
+        let err = || invalid(&format!("a ramp needs 1 to {} 'stops', each a position and a color", MAX_STOPS));
+        let stops = required(spec, "stops")?.as_array().filter(|s| (1..=MAX_STOPS).contains(&s.len())).ok_or_else(err)?;
+        let stops = stops.iter().map(|stop| match stop.as_array() {
+            Some([position, colour]) => Ok((number(position, "stops")?, as_colour(colour, "stops")?)),
+            _ => Err(err())
+        }).collect::<Result<Vec<_>, _>>()?;
+        let mut ramp = ColourRamp::from_stops(&stops);
+        if let Some(interpolation) = spec.get("interpolation") {
+            let name = string(interpolation, "interpolation")?;
+            ramp.interpolation = Interpolation::ALL.into_iter()
+                .find(|i| i.name() == name)
+                .ok_or_else(|| invalid(&format!("unknown interpolation '{}'", name)))?;
+        }
+        Ok(ramp)
+    };
+    #[cfg(feature = "image")]
+    let texture = || -> Result<TextureId, SceneError> {
+        let file = dir.join(string(required(value, "file")?, "file")?);
+        Textures::load(&file).map_err(|err| invalid(&format!("unable to load texture '{}': {}", file.display(), err)))
+    };
+    let mut pattern = match kind {
+        "solid" => Pattern::new_solid(colours(1)?[0]),
+        "stripes" => {
+            let c = colours(2)?;
+            let mut pattern = Pattern::new_stripes(c[0], c[1]);
+            if let Some(direction) = value.get("direction") {
+                let [x, y, z] = match direction {
+                    Yaml::String(axis) => match axis.as_str() {
+                        "x" => [1.0, 0.0, 0.0],
+                        "y" => [0.0, 1.0, 0.0],
+                        "z" => [0.0, 0.0, 1.0],
+                        other => return Err(invalid(&format!("unknown stripe direction '{}'", other)))
+                    },
+                    _ => numbers(direction, "direction")?
+                };
+                if x == 0.0 && y == 0.0 && z == 0.0 {
+                    return Err(invalid("stripe 'direction' can't be zero"));
+                }
+                pattern.with_stripe_direction(x, y, z);
+            }
+            pattern
+        },
+        "checkers" => { let c = colours(2)?; Pattern::new_checkers(c[0], c[1]) },
+        "gradient" => Pattern::new_gradient(Colour::black(), Colour::white()).with_ramp(ramp()?),
+        "rings" => {
+            let c = colours(2)?;
+            let width = value.get("width").map_or(Ok(1.0), |w| number(w, "width"))?;
+            if width <= 0.0 {
+                return Err(invalid("'width' must be more than 0"));
+            }
+            Pattern::new_rings(c[0], c[1]).with_ring_width(width)
+        },
+        "radial-gradient" => Pattern::new_radial_gradient(Colour::black(), Colour::white()).with_ramp(ramp()?),
+        "radial" => {
+            let c = colours(2)?;
+            let n = value.get("count").map_or(Ok(8.0), |n| number(n, "count"))?;
+            Pattern::new_radial(c[0], c[1], n.max(1.0) as usize)
+        },
+        "bricks" | "tiles" => {
+            let c = colours(2)?;
+            let ((width, height), row_offset) = if kind == "bricks" { ((1.0, 0.5), 0.5) } else { ((1.0, 1.0), 0.0) };
+            let [width, height] = value.get("size").map_or(Ok([width, height]), |s| numbers(s, "size"))?;
+            if width <= 0.0 || height <= 0.0 {
+                return Err(invalid(&format!("{} must have a 'size' more than 0", kind)));
+            }
+            let mortar = value.get("mortar").map_or(Ok(0.05), |m| number(m, "mortar"))?;
+            let row_offset = value.get("row-offset").map_or(Ok(row_offset), |o| number(o, "row-offset"))?;
+            Pattern::new_bricks(c[0], c[1]).with_bricks((width, height), mortar, row_offset)
+        },
+        "blend" => { let [a, b] = children()?; Pattern::new_blend(a, b) },
+        "nested" => {
+            let mask = parse_pattern(required(value, "mask")?, defines, dir)?;
+            let [a, b] = children()?;
+            Pattern::new_nested(mask, a, b)
+        },
+        "test" => Pattern::new_test(),
+        #[cfg(feature = "image")]
+        "texture" => Pattern::new_texture(texture()?),
+        #[cfg(feature = "image")]
+        "projection" => {
+            let fov = number(required(value, "field-of-view")?, "field-of-view")?;
+            let outside = value.get("outside").map_or(Ok(Colour::black()), |c| as_colour(c, "outside"))?;
+            let mut pattern = Pattern::new_projection(texture()?, fov, outside);
+            if let Some(aspect) = value.get("aspect") {
+                let aspect = number(aspect, "aspect")?;
+                if aspect <= 0.0 {
+                    return Err(invalid("'aspect' must be more than 0"));
+                }
+                pattern.with_projection_aspect(aspect);
+            }
+            if let Some(from) = value.get("from") {
+                let from = as_point(from, "from")?;
+                let to = as_point(required(value, "to")?, "to")?;
+                let [x, y, z] = value.get("up").map_or(Ok([0.0, 1.0, 0.0]), |up| numbers(up, "up"))?;
+                if from == to {
+                    return Err(invalid("a projector can't point at where it is"));
+                }
+                pattern.try_with_transform(Matrix4::look_at(from, to, vector(x, y, z))).map_err(singular)?;
+            }
+            pattern
+        },
+        other => return Err(invalid(&format!("unsupported pattern '{}'", other)))
+    };
+    if let Some(tiling) = value.get("tiling") {
+        let [u, v] = numbers(tiling, "tiling")?;
+        pattern.with_tiling(u, v);
+    }
+    if let Some(offset) = value.get("offset") {
+        let [u, v] = numbers(offset, "offset")?;
+        pattern.with_offset(u, v);
+    }
+    if let Some(wrap) = value.get("wrap") {
+        let name = string(wrap, "wrap")?;
+        let wrap = Wrap::ALL.into_iter()
+            .find(|w| w.name() == name)
+            .ok_or_else(|| invalid(&format!("unknown wrap '{}'", name)))?;
+        pattern.with_wrap(wrap);
+    }
+    if let Some(filter) = value.get("filter") {
+        let name = string(filter, "filter")?;
+        let filter = Filter::ALL.into_iter()
+            .find(|f| f.name() == name)
+            .ok_or_else(|| invalid(&format!("unknown filter '{}'", name)))?;
+        pattern.with_filter(filter);
+    }
+    if let Some(jitter) = value.get("jitter") {
+        let [seed, amp] = numbers(jitter, "jitter")?;
+        pattern.with_jitter(Jitter::new(seed as u32, amp));
+    }
+    if let Some(transform) = value.get("transform") {
+        pattern.try_with_transform(parse_transform(transform, defines)?).map_err(singular)?;
+    }
+
+    Ok(pattern)
+}
+
+fn parse_transform(value: &Yaml, defines: &HashMap<String, Yaml>) -> Result<Matrix4<Float>, SceneError> {
+    let mut builder = TransformBuilder::new();
+    add_transforms(&mut builder, value, defines)?;
+
+    Ok(builder.build())
+}
+
+// Appends each step of a transform list, expanding any named transforms.
+fn add_transforms(builder: &mut TransformBuilder, value: &Yaml, defines: &HashMap<String, Yaml>) -> Result<(), SceneError> {
+    let steps = value.as_array().ok_or_else(|| invalid("a transform must be a list"))?;
+    for step in steps {
+        if let Yaml::String(name) = step {
+            add_transforms(builder, lookup(name, defines)?, defines)?;
+            continue;
+        }
+        let parts = step.as_array().filter(|p| !p.is_empty())
+            .ok_or_else(|| invalid("each transform must be a list such as [ translate, 1, 2, 3 ]"))?;
+        let op = string(&parts[0], "transform")?;
+        let args = parts[1..].iter()
+            .map(|a| number(a, op))
+            .collect::<Result<Vec<Float>, SceneError>>()?;
+        let expected = match op {
+            "translate" | "scale" => 3,
+            "rotate-x" | "rotate-y" | "rotate-z" => 1,
+            "shear" => 6,
+            "matrix" => 16,
+            other => return Err(invalid(&format!("unsupported transform '{}'", other)))
+        };
+        if args.len() != expected {
+            return Err(invalid(&format!("'{}' takes {} numbers", op, expected)));
+        }
+        let step = match op {
+            "translate" => Matrix4::translate(args[0], args[1], args[2]),
+            "scale" => Matrix4::nuscale(args[0], args[1], args[2]),
+            "rotate-x" => Matrix4::rot_x(args[0]),
+            "rotate-y" => Matrix4::rot_y(args[0]),
+            "rotate-z" => Matrix4::rot_z(args[0]),
+            "shear" => Matrix4::shear(args[0], args[1], args[2], args[3], args[4], args[5]),
+            _ => Matrix4::from_row_slice(&args)
+        };
+        builder.transforms.push(step);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::Tuple;
+    use crate::EPSILON;
+    use crate::scene::world_to_yaml;
+    use crate::consts::PI;
+
+    const SCENE: &str = "
+- add: camera
+  width: 100
+  height: 50
+  field-of-view: 0.785
+  from: [ 0, 0, -5 ]
+  to: [ 0, 0, 0 ]
+  up: [ 0, 1, 0 ]
+
+- add: light
+  at: [ -10, 10, -10 ]
+  intensity: [ 1, 1, 1 ]
+
+- define: white-material
+  value:
+    color: [ 1, 1, 1 ]
+    diffuse: 0.7
+    reflective: 0.1
+
+- define: blue-material
+  extend: white-material
+  value:
+    color: [ 0, 0, 1 ]
+
+- define: standard-transform
+  value:
+    - [ scale, 0.5, 0.5, 0.5 ]
+    - [ translate, 1, -1, 1 ]
+
+- add: sphere
+  material: blue-material
+  transform:
+    - standard-transform
+    - [ translate, 0, 2, 0 ]
+
+- add: plane
+  material:
+    pattern:
+      type: checkers
+      colors: [ [ 1, 1, 1 ], [ 0, 0, 0 ] ]
+  shadow: false
+";
+
+    #[test]
+    fn loading_a_scene() {
+        let scene = Scene::parse(SCENE).unwrap();
+
+        assert_eq!((scene.camera.hsize, scene.camera.vsize), (100, 50));
+        assert_eq!(scene.camera.transform, Matrix4::view_transform(point(0.0, 0.0, -5.0), point(0.0, 0.0, 0.0), vector(0.0, 1.0, 0.0)));
+        assert_eq!(scene.world.lights, vec![PointLight::new(Colour::white(), point(-10.0, 10.0, -10.0))]);
+        assert_eq!(scene.world.objects.len(), 2);
+        assert!(!scene.world.objects[1].umbra);
+    }
+
+    #[test]
+    fn defines_can_be_extended_and_reused() {
+        let scene = Scene::parse(SCENE).unwrap();
+        let sphere = scene.world.objects[0].clone();
+        let expected = Material::default()
+            .with_colour(Colour::blue())
+            .with_diffuse(0.7)
+            .with_reflectivity(0.1);
+
+        assert_eq!(sphere.material, expected);
+        assert_eq!(sphere.transform * point(0.0, 0.0, 0.0), point(1.0, 1.0, 1.0));
+        assert_eq!((sphere.transform * point(2.0, 0.0, 0.0)).to_5dp(), point(2.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn objects_naming_a_material_share_it() {
+        let src = "- define: tile\n  value:\n    color: [ 1, 1, 1 ]\n\
+                   - add: plane\n  material: tile\n\
+                   - add: sphere\n  material: tile\n\
+                   - add: sphere\n  material:\n    color: [ 1, 0, 0 ]";
+        let mut w = parse_world(src).unwrap();
+        let tile = w.find_material("tile").unwrap();
+        w.set_material(tile, Material::default().with_colour(Colour::blue()));
+
+        assert_eq!(w.palette().count(), 1);
+        assert_eq!(w.objects[0].material, Material::default().with_colour(Colour::blue()));
+        assert_eq!(w.objects[1].material_id, Some(tile));
+        assert_eq!(w.objects[2].material_id, None);
+        assert_eq!(parse_world(&world_to_yaml(&w).unwrap().to_string()).unwrap(), w);
+    }
+
+    #[test]
+    fn loaded_scenes_can_be_checked_before_rendering() {
+        assert!(Scene::parse(SCENE).unwrap().validate().is_empty());
+        let dark = Scene::parse("- add: camera\n  width: 10\n  height: 10\n  field-of-view: 1\n  from: [ 0, 0, -5 ]\n  to: [ 0, 0, 0 ]\n  up: [ 0, 1, 0 ]").unwrap();
+
+        assert_eq!(dark.validate()[0].issue, crate::core::Issue::NoLights);
+    }
+
+    #[test]
+    fn rendering_a_loaded_scene() {
+        let scene = Scene::parse(SCENE).unwrap();
+        let image = scene.camera.render(scene.world);
+
+        assert_eq!((image.width, image.height), (100, 50));
+    }
+
+    #[test]
+    fn world_items_set_the_background() {
+        let flat = parse_world("- add: world\n  background: [ 0.1, 0.2, 0.3 ]").unwrap();
+        let sky = parse_world("- add: world\n  background:\n    horizon: [ 1, 1, 1 ]\n    zenith: [ 0, 0, 1 ]").unwrap();
+
+        assert_eq!(flat.background, Background::Solid(Colour::new(0.1, 0.2, 0.3)));
+        assert_eq!(sky.background, Background::gradient(Colour::white(), Colour::blue()));
+    }
+
+    #[test]
+    fn world_items_set_the_ambient_light() {
+        let w = parse_world("- add: world\n  ambient: [ 1, 0.5, 0 ]\n  ambient-strength: 0.2").unwrap();
+
+        assert_eq!(w.ambient, Colour::new(1.0, 0.5, 0.0));
+        assert_eq!(w.ambient_strength, 0.2);
+    }
+
+    #[test]
+    fn world_items_set_the_light_samples() {
+        let w = parse_world("- add: world\n  light-samples: 4").unwrap();
+
+        assert_eq!(w.light_samples, Some(4));
+        assert_eq!(parse_world("- add: world").unwrap().light_samples, None);
+    }
+
+    #[test]
+    #[cfg(feature = "image")]
+    fn patterns_can_be_textures() {
+        let dir = std::env::temp_dir().join("feoray-textures");
+        fs::create_dir_all(&dir).unwrap();
+        crate::core::Canvas::new(2, 2, Colour::green()).export(&dir.join("grass.png").to_string_lossy()).unwrap();
+        let scene = "- add: plane\n  material: { pattern: { type: texture, file: grass.png, wrap: mirror, filter: nearest } }";
+        fs::write(dir.join("lawn.yaml"), scene).unwrap();
+        let w = World::load(dir.join("lawn.yaml")).unwrap();
+        let pattern = w.objects[0].material.pattern;
+        let texture = Textures::find(dir.join("grass.png")).unwrap();
+
+        assert_eq!(pattern, Pattern::new_texture(texture).with_wrap(Wrap::Mirror).with_filter(Filter::Nearest));
+        assert_eq!(pattern.pattern_at_object(&w.objects[0], point(3.3, 0.0, -1.2)), Colour::green());
+        assert_eq!(parse_world(&world_to_yaml(&w).unwrap().to_string()).unwrap(), w);
+        assert!(parse_world("- add: plane\n  material: { pattern: { type: texture, file: missing.png } }").is_err());
+        assert!(parse_world("- add: plane\n  material: { pattern: { type: stripes, colors: [ [ 1, 1, 1 ], [ 0, 0, 0 ] ], wrap: sideways } }").is_err());
+    }
+
+    #[test]
+    fn world_items_set_the_epsilon() {
+        let w = parse_world("- add: world\n  epsilon: 0.01").unwrap();
+
+        assert_eq!(w.epsilon, 0.01);
+        assert_eq!(parse_world("- add: world").unwrap().epsilon, EPSILON);
+        assert!(parse_world("- add: world\n  epsilon: 0").is_err());
+    }
+
+    #[test]
+    fn world_items_set_the_slope_bias() {
+        let w = parse_world("- add: world\n  slope-bias: 0.001").unwrap();
+        let saved = world_to_yaml(&w).unwrap().to_string();
+
+        assert_eq!(w.slope_bias, 0.001);
+        assert_eq!(parse_world(&saved).unwrap().slope_bias, 0.001);
+        assert!(!world_to_yaml(&World::default()).unwrap().to_string().contains("slope-bias"));
+        assert!(parse_world("- add: world\n  slope-bias: -1").is_err());
+    }
+
+    #[test]
+    fn world_items_can_trace_caustics() {
+        let src = "- add: world\n  caustics: { photons: 500, radius: 0.2 }\n\
+                   - add: light\n  at: [ 0, 10, 0 ]\n  intensity: [ 1, 1, 1 ]\n\
+                   - add: plane\n\
+                   - add: sphere\n  material: { transparency: 1, refractive-index: 1.5 }\n  transform:\n    - [ translate, 0, 2, 0 ]";
+        let w = parse_world(src).unwrap();
+        let map = w.caustics.unwrap();
+
+        assert_eq!(map.photons_per_light(), 500);
+        assert_eq!(map.radius(), 0.2);
+        assert!(!map.is_empty());
+        assert!(parse_world("- add: world\n  caustics: { radius: 0 }").is_err());
+    }
+
+    #[test]
+    fn objects_can_be_hidden_from_kinds_of_ray() {
+        let w = parse_world("- add: sphere\n  hidden-from: [ camera, refraction ]").unwrap();
+        let s = w.objects[0].clone();
+
+        assert!(!s.is_visible_to(RayKind::Camera));
+        assert!(!s.is_visible_to(RayKind::Refraction));
+        assert!(s.is_visible_to(RayKind::Reflection));
+        assert!(parse_world("- add: sphere\n  hidden-from: [ x-ray ]").is_err());
+    }
+
+    #[test]
+    fn objects_can_be_filled_with_a_medium() {
+        let w = parse_world("- add: sphere\n  medium: { density: 0.25, absorption: 0.5, color: [ 1, 0, 0 ] }").unwrap();
+        let expected = Medium::new(0.25).with_absorption(0.5).with_colour(Colour::red());
+
+        assert_eq!(w.objects[0].medium, Some(expected));
+        assert!(parse_world("- add: plane\n  medium: { density: 0.5 }").is_err());
+    }
+
+    #[test]
+    fn objects_can_be_given_ids() {
+        let w = parse_world("- add: sphere\n  id: 12").unwrap();
+
+        assert_eq!(w.objects[0].id, Some(12));
+    }
+
+    #[test]
+    fn colours_can_be_hex_strings() {
+        let w = parse_world("- add: sphere\n  material: { color: \"#ff0033\" }").unwrap();
+
+        assert_eq!(w.objects[0].material, Material::default().with_colour(Colour::new(1.0, 0.0, 0.2)));
+        assert!(parse_world("- add: sphere\n  material: { color: reddish }").is_err());
+    }
+
+    #[test]
+    fn colours_can_be_named() {
+        let w = parse_world("- add: sphere\n  material: { color: cornflowerblue }").unwrap();
+
+        assert_eq!(w.objects[0].material, Material::default().with_colour(Colour::from_hex("#6495ed").unwrap()));
+    }
+
+    #[test]
+    fn patterns_can_be_tiled_in_uv_space() {
+        let w = parse_world("- add: plane\n  manifold: true\n  material: { pattern: { type: checkers, colors: [ white, black ], tiling: [ 4, 2 ], offset: [ 0.5, 0 ] } }").unwrap();
+        let pattern = Pattern::new_checkers(Colour::white(), Colour::black()).with_tiling(4.0, 2.0).with_offset(0.5, 0.0);
+
+        assert_eq!(w.objects[0].material.pattern, pattern);
+        assert_eq!(parse_world(&world_to_yaml(&w).unwrap().to_string()).unwrap(), w);
+    }
+
+    #[test]
+    fn patterns_can_be_bricks_or_tiles() {
+        let w = parse_world("- add: plane\n  material: { pattern: { type: bricks, colors: [ red, white ], size: [ 2, 1 ], jitter: [ 4, 0.2 ] } }\n\
+                             - add: plane\n  material: { pattern: { type: tiles, colors: [ black, white ], mortar: 0.1 } }").unwrap();
+        let bricks = Pattern::new_bricks(Colour::red(), Colour::white()).with_bricks((2.0, 1.0), 0.05, 0.5).with_jitter(Jitter::new(4, 0.2));
+
+        assert_eq!(w.objects[0].material.pattern, bricks);
+        assert_eq!(w.objects[1].material.pattern, Pattern::new_tiles(Colour::black(), Colour::white()).with_bricks((1.0, 1.0), 0.1, 0.0));
+        assert_eq!(parse_world(&world_to_yaml(&w).unwrap().to_string()).unwrap(), w);
+        assert!(parse_world("- add: plane\n  material: { pattern: { type: tiles, colors: [ black, white ], size: [ 0, 1 ] } }").is_err());
+    }
+
+    #[test]
+    fn stripes_can_run_in_any_direction() {
+        let w = parse_world("- add: sphere\n  material: { pattern: { type: stripes, colors: [ white, black ], direction: y } }\n\
+                             - add: sphere\n  material: { pattern: { type: stripes, colors: [ white, black ], direction: [ 1, 0, 1 ] } }").unwrap();
+        let stripes = Pattern::new_stripes(Colour::white(), Colour::black());
+
+        assert_eq!(w.objects[0].material.pattern, stripes.clone().with_stripe_direction(0.0, 1.0, 0.0));
+        assert_eq!(w.objects[1].material.pattern, stripes.clone().with_stripe_direction(1.0, 0.0, 1.0));
+        assert_eq!(parse_world(&world_to_yaml(&w).unwrap().to_string()).unwrap(), w);
+        assert!(parse_world("- add: sphere\n  material: { pattern: { type: stripes, colors: [ white, black ], direction: w } }").is_err());
+        assert!(parse_world("- add: sphere\n  material: { pattern: { type: stripes, colors: [ white, black ], direction: [ 0, 0, 0 ] } }").is_err());
+    }
+
+    #[test]
+    fn rings_can_be_given_a_width() {
+        let w = parse_world("- add: sphere\n  material: { pattern: { type: rings, colors: [ white, black ], width: 0.25 } }\n\
+                             - add: sphere\n  material: { pattern: { type: radial-gradient, colors: [ red, blue ] } }").unwrap();
+
+        assert_eq!(w.objects[0].material.pattern, Pattern::new_rings(Colour::white(), Colour::black()).with_ring_width(0.25));
+        assert_eq!(w.objects[1].material.pattern, Pattern::new_radial_gradient(Colour::red(), Colour::blue()));
+        assert_eq!(parse_world(&world_to_yaml(&w).unwrap().to_string()).unwrap(), w);
+        assert!(parse_world("- add: sphere\n  material: { pattern: { type: rings, colors: [ white, black ], width: 0 } }").is_err());
+    }
+
+    #[test]
+    fn patterns_can_be_blended_and_nested() {
+        let src = "- add: sphere\n  material:\n    pattern:\n      type: nested\n      \
+                   mask: { type: stripes, colors: [ black, white ], transform: [ [ scale, 0.5, 1, 1 ] ] }\n      \
+                   patterns:\n        - { type: solid, colors: [ red ] }\n        \
+                   - { type: blend, patterns: [ { type: rings, colors: [ white, black ] }, { type: solid, colors: [ blue ] } ] }";
+        let w = parse_world(src).unwrap();
+        let mask = Pattern::new_stripes(Colour::black(), Colour::white()).with_transform(Matrix4::nuscale(0.5, 1.0, 1.0));
+        let blend = Pattern::new_blend(Pattern::new_rings(Colour::white(), Colour::black()), Pattern::new_solid(Colour::blue()));
+
+        assert_eq!(w.objects[0].material.pattern, Pattern::new_nested(mask, Pattern::new_solid(Colour::red()), blend));
+        assert_eq!(parse_world(&world_to_yaml(&w).unwrap().to_string()).unwrap(), w);
+        assert!(parse_world("- add: sphere\n  material: { pattern: { type: blend, patterns: [ { type: test } ] } }").is_err());
+    }
+
+    #[test]
+    fn scenes_can_include_other_scenes() {
+        let dir = std::env::temp_dir().join("feoray-include");
+        fs::create_dir_all(dir.join("props")).unwrap();
+        fs::write(dir.join("props/lamp.yaml"), "- add: light\n  at: [ 0, 2, 0 ]\n  intensity: [ 1, 1, 1 ]\n\
+                                                - add: sphere\n  name: shade\n  transform:\n    - [ translate, 0, 1, 0 ]").unwrap();
+        fs::write(dir.join("room.yaml"), "- add: include\n  file: props/lamp.yaml\n  transform:\n    - [ translate, 3, 0, 0 ]\n\
+                                         - add: include\n  file: props/lamp.yaml").unwrap();
+        fs::write(dir.join("loop.yaml"), "- add: include\n  file: loop.yaml").unwrap();
+        let w = World::load(dir.join("room.yaml")).unwrap();
+
+        assert_eq!(w.objects.len(), 2);
+        assert_eq!(w.lights[0].position, point(3.0, 2.0, 0.0));
+        assert_eq!(w.get(w.find("shade").unwrap()).unwrap().transform, Matrix4::translate(3.0, 1.0, 0.0));
+        assert!(World::load(dir.join("loop.yaml")).is_err());
+        assert!(parse_world("- add: include\n  file: missing.yaml").is_err());
+    }
+
+    #[test]
+    fn reporting_scene_errors() {
+        let no_camera = Scene::parse("- add: light\n  at: [ 0, 0, 0 ]\n  intensity: [ 1, 1, 1 ]");
+        let cube = Scene::parse("- add: cube");
+        let undefined = Scene::parse("- add: sphere\n  material: missing");
+        let bad_colour = Scene::parse("- add: light\n  at: [ 0, 0, 0 ]\n  intensity: [ 1, 1 ]");
+
+        assert!(matches!(no_camera, Err(SceneError::Invalid(_))));
+        assert!(matches!(cube, Err(SceneError::Invalid(m)) if m.contains("cube")));
+        assert!(matches!(undefined, Err(SceneError::Invalid(m)) if m.contains("missing")));
+        assert!(matches!(bad_colour, Err(SceneError::Invalid(m)) if m.contains("intensity")));
+    }
+
+    #[test]
+    fn missing_files_are_reported() {
+        let result = Scene::load("no/such/scene.yaml");
+
+        assert!(matches!(result, Err(SceneError::Io(_))));
+    }
+
+    #[test]
+    fn singular_transforms_are_reported() {
+        let flat = parse_world("- add: sphere\n  transform: [ [ scale, 0, 0, 0 ] ]");
+        let pattern = parse_world("- add: sphere\n  material: { pattern: { type: stripes, colors: [ [ 1, 1, 1 ], [ 0, 0, 0 ] ], transform: [ [ scale, 0, 1, 1 ] ] } }");
+        let camera = Scene::parse("- add: camera\n  width: 10\n  height: 10\n  field-of-view: 1\n  from: [ 0, 0, 0 ]\n  to: [ 0, 0, 1 ]\n  up: [ 0, 0, 1 ]");
+
+        assert!(matches!(flat, Err(SceneError::Invalid(m)) if m.contains("object")));
+        assert!(matches!(pattern, Err(SceneError::Invalid(m)) if m.contains("pattern")));
+        assert!(matches!(camera, Err(SceneError::Invalid(m)) if m.contains("camera")));
+        let staring = Scene::parse("- add: camera\n  width: 10\n  height: 10\n  field-of-view: 1\n  from: [ 0, 0, -5 ]\n  to: [ 0, 0, -5 ]\n  up: [ 0, 1, 0 ]");
+        assert!(matches!(staring, Err(SceneError::Invalid(m)) if m.contains("'from' and 'to'")));
+    }
+
+    #[test]
+    fn gradients_can_take_a_ramp_of_colours() {
+        let w = parse_world("- add: sphere\n  material:\n    pattern:\n      type: gradient\n      ramp:\n        \
+                             interpolation: smooth\n        stops: [ [ 0, black ], [ 0.5, red ], [ 1, [ 1, 1, 1 ] ] ]").unwrap();
+        let ramp = ColourRamp::new(Colour::black(), Colour::white())
+            .with_stop(0.5, Colour::red())
+            .with_interpolation(Interpolation::Smooth);
+
+        assert_eq!(w.objects[0].material.pattern, Pattern::new_gradient(Colour::black(), Colour::white()).with_ramp(ramp));
+        assert_eq!(parse_world(&world_to_yaml(&w).unwrap().to_string()).unwrap(), w);
+        assert!(parse_world("- add: sphere\n  material: { pattern: { type: gradient, ramp: { stops: [ ] } } }").is_err());
+        assert!(parse_world("- add: sphere\n  material: { pattern: { type: gradient, ramp: { stops: [ [ 0 ] ] } } }").is_err());
+        assert!(parse_world("- add: sphere\n  material: { pattern: { type: gradient, \
+                             ramp: { interpolation: cubic, stops: [ [ 0, red ] ] } } }").is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "image")]
+    fn patterns_can_be_projected() {
+        let dir = std::env::temp_dir().join("feoray-projections");
+        fs::create_dir_all(&dir).unwrap();
+        crate::core::Canvas::new(2, 1, Colour::blue()).export(&dir.join("window.png").to_string_lossy()).unwrap();
+        let scene = "- add: plane\n  material:\n    pattern:\n      type: projection\n      file: window.png\n      \
+                     field-of-view: 0.5\n      from: [ 0, 10, 0 ]\n      to: [ 0, 0, 0 ]\n      up: [ 0, 0, 1 ]\n      \
+                     outside: [ 0.1, 0.1, 0.1 ]";
+        fs::write(dir.join("room.yaml"), scene).unwrap();
+        let w = World::load(dir.join("room.yaml")).unwrap();
+        let texture = Textures::find(dir.join("window.png")).unwrap();
+        let projector = Matrix4::look_at(point(0.0, 10.0, 0.0), point(0.0, 0.0, 0.0), vector(0.0, 0.0, 1.0));
+
+        assert_eq!(w.objects[0].material.pattern,
+                   Pattern::new_projection(texture, 0.5, Colour::grey(0.1)).with_transform(projector));
+        assert_eq!(parse_world(&world_to_yaml(&w).unwrap().to_string()).unwrap(), w);
+        let stretched = scene.replace("field-of-view", "aspect: 1\n      field-of-view");
+        fs::write(dir.join("stretched.yaml"), stretched).unwrap();
+        let w = World::load(dir.join("stretched.yaml")).unwrap();
+        assert_eq!(parse_world(&world_to_yaml(&w).unwrap().to_string()).unwrap(), w);
+        assert!(parse_world("- add: plane\n  material: { pattern: { type: projection, file: window.png } }").is_err());
+    }
+
+    #[test]
+    fn lights_can_be_masked() {
+        let w = parse_world("- add: light\n  at: [ 0, 5, 0 ]\n  intensity: [ 1, 1, 1 ]\n  \
+                             mask: { type: stripes, colors: [ white, black ], transform: [ [ scale, 0.5, 1, 1 ] ] }").unwrap();
+        let blind = Pattern::new_stripes(Colour::white(), Colour::black()).with_transform(Matrix4::nuscale(0.5, 1.0, 1.0));
+
+        assert_eq!(w.lights[0].mask, Some(blind));
+        assert_eq!(parse_world(&world_to_yaml(&w).unwrap().to_string()).unwrap(), w);
+        assert!(parse_world("- add: light\n  at: [ 0, 5, 0 ]\n  intensity: [ 1, 1, 1 ]\n  mask: { type: spots }").is_err());
+    }
+
+    #[test]
+    fn cameras_can_be_given_an_exposure() {
+        let camera = |exposure: &str| Scene::parse(&format!("- add: camera\n  width: 10\n  height: 10\n  field-of-view: 1\n  \
+                                                             from: [ 0, 0, -5 ]\n  to: [ 0, 0, 0 ]\n  up: [ 0, 1, 0 ]\n  {}", exposure));
+
+        assert_eq!(camera("exposure: 1.5").unwrap().camera.exposure, 1.5);
+        assert_eq!(camera("").unwrap().camera.exposure, 0.0);
+        assert_eq!(camera("exposure: { f-number: 2, shutter: 0.25 }").unwrap().camera.exposure,
+                   Camera::new(10, 10, 1.0).with_photographic_exposure(2.0, 0.25, 100.0).exposure);
+        assert!(camera("exposure: { f-number: 2, shutter: 0 }").is_err());
+        assert!(camera("exposure: bright").is_err());
+    }
+
+    #[test]
+    fn cameras_can_have_lens_effects() {
+        let scene = Scene::parse("- add: camera\n  width: 10\n  height: 10\n  field-of-view: 1\n  from: [ 0, 0, -5 ]\n  to: [ 0, 0, 0 ]\n  \
+                                  up: [ 0, 1, 0 ]\n  distortion: [ 0.1, -0.02 ]\n  vignetting: 0.8").unwrap();
+
+        assert_eq!(scene.camera.distortion, (0.1, -0.02));
+        assert_eq!(scene.camera.vignetting, 0.8);
+        let shifted = Scene::parse("- add: camera\n  width: 10\n  height: 10\n  field-of-view: 1\n  from: [ 0, 0, -5 ]\n  to: [ 0, 0, 0 ]\n  \
+                                    up: [ 0, 1, 0 ]\n  shift: [ 0, 0.25 ]\n  tilt: [ 0.1, 0 ]").unwrap();
+        assert_eq!((shifted.camera.shift, shifted.camera.tilt), ((0.0, 0.25), (0.1, 0.0)));
+        assert!(Scene::parse("- add: camera\n  width: 10\n  height: 10\n  field-of-view: 1\n  from: [ 0, 0, -5 ]\n  to: [ 0, 0, 0 ]\n  \
+                              up: [ 0, 1, 0 ]\n  distortion: 0.1").is_err());
+    }
+
+    #[test]
+    fn spheres_can_be_cut_down() {
+        let w = parse_world("- add: sphere\n  latitude: [ 0, 1.5707963267948966 ]\n  longitude: [ 0.5, -0.5 ]").unwrap();
+
+        assert_eq!(w.objects[0].shape, Object::new_sphere().with_latitude(0.0, PI / 2.0).with_longitude(0.5, -0.5).shape);
+        assert_eq!(parse_world(&world_to_yaml(&w).unwrap().to_string()).unwrap(), w);
+        assert!(!world_to_yaml(&parse_world("- add: sphere").unwrap()).unwrap().to_string().contains("latitude"));
+        assert!(parse_world("- add: plane\n  latitude: [ 0, 1 ]").is_err());
+        assert!(parse_world("- add: sphere\n  longitude: 1").is_err());
+    }
+
+    #[test]
+    fn lenses_take_their_radii_and_thickness() {
+        let w = parse_world("- add: lens\n  front: 2\n  back: -4\n  thickness: 0.5\n  radius: 0.8").unwrap();
+
+        assert_eq!(w.objects[0].shape, Object::new_lens(Lens::new(2.0, -4.0, 0.5).with_radius(0.8)).shape);
+        assert_eq!(parse_world(&world_to_yaml(&w).unwrap().to_string()).unwrap(), w);
+        assert!(parse_world("- add: lens\n  front: 2\n  back: 2").is_err());
+        assert!(parse_world("- add: lens\n  front: 2\n  back: 2\n  thickness: 0").is_err());
+        assert!(parse_world("- add: sphere\n  thickness: 0.5").is_err());
+    }
+
+    #[test]
+    fn polygons_take_their_corners() {
+        let w = parse_world("- add: polygon\n  vertices: [ [ 0, 0 ], [ 4, 0 ], [ 4, 2 ], [ 2, 2 ], [ 2, 4 ], [ 0, 4 ] ]").unwrap();
+
+        let Primitive::Polygon(floor) = &w.objects[0].shape else { panic!("not a polygon") };
+        assert_eq!(floor.area(), 12.0);
+        assert_eq!(parse_world(&world_to_yaml(&w).unwrap().to_string()).unwrap(), w);
+        assert!(parse_world("- add: polygon\n  vertices: [ [ 0, 0 ], [ 4, 0 ] ]").is_err());
+        assert!(parse_world("- add: polygon\n  vertices: [ [ 0, 0 ], [ 4, 0 ], [ 4 ] ]").is_err());
+        assert!(parse_world("- add: sphere\n  vertices: [ [ 0, 0 ], [ 4, 0 ], [ 4, 4 ] ]").is_err());
+    }
+
+    #[test]
+    fn too_many_keyframes_are_reported() {
+        let keys: String = (0..=MAX_KEYFRAMES)
+            .map(|i| format!("\n    - {{ time: {}, transform: [ [ translate, 0, 0, 0 ] ] }}", i))
+            .collect();
+
+        assert!(parse_world(&format!("- add: sphere\n  keyframes:{}", keys)).is_err());
+        let fits = &keys[..keys.rfind("\n").unwrap()];
+        assert_eq!(parse_world(&format!("- add: sphere\n  keyframes:{}", fits)).unwrap().objects[0].keyframes.len(), MAX_KEYFRAMES);
+    }
+}
@@ -0,0 +1,259 @@
+use crate::Float;
+use crate::consts::PI;
+use crate::core::{point, vector, Camera, Transform};
+use nalgebra::Matrix4;
+
+/// Builds a Scene from a list of items, the same as chaining the World and
+/// Camera builders. Items are a camera, lights, objects (sphere, plane or
+/// glass_orb) and world settings, each with its properties in braces.
+/// Triples are written as tuples, and numbers can be whole or not.
+///
+/// # Example
+///
+/// ```ignore
+/// let glass = Material::null().with_transparency(1.0).with_ior(1.5);
+/// let Scene { camera, world } = scene! {
+///     camera { width: 700, height: 350, fov: PI / 3.0, from: (0, 1.5, -5), to: (0, 1, 0) }
+///     light { at: (-10, 10, -10), colour: Colour::white() }
+///     plane { material: Material::default().with_specular(0.0) }
+///     sphere { material: glass, uscale: 0.5, translate: (1, 0.5, 0), name: "ball" }
+///     world { recursions: 3 }
+/// };
+/// ```
+///
+/// The camera takes width, height, fov, from, to, up, exposure, distortion,
+/// vignetting, shift and tilt (pairs for distortion, shift and tilt), and
+/// looks from (0, 0, -5) at the origin if they're left out. Lights take at, colour and
+/// name. Objects take material, name, id, shadow, manifold, and latitude and
+/// longitude pairs to cut spheres down, and a transform built up
+/// in the order it's written from translate, uscale, nuscale, rot_x, rot_y,
+/// rot_z and whole transform matrices. The world takes recursions,
+/// background, epsilon, slope_bias and light_samples.
+#[macro_export]
+macro_rules! scene {
+    ($($kind:ident { $($body:tt)* })*) => {{
+        #[allow(unused_mut)]
+        let mut camera = $crate::scene::macros::CameraSpec::default();
+        #[allow(unused_mut)]
+        let mut world = $crate::core::World::default();
+        $($crate::scene!(@item camera, world, $kind { $($body)* });)*
+
+        $crate::scene::Scene { camera: camera.build(), world }
+    }};
+
+    (@item $camera:ident, $world:ident, camera { $($body:tt)* }) => {
+        $crate::scene!(@props camera_set $camera; $($body)*);
+    };
+    (@item $camera:ident, $world:ident, light { $($body:tt)* }) => {
+        let mut light = $crate::lights::PointLight::new($crate::core::Colour::white(), $crate::core::point(0.0, 0.0, 0.0));
+        $crate::scene!(@props light_set light; $($body)*);
+        $world.lights.push(light);
+    };
+    (@item $camera:ident, $world:ident, world { $($body:tt)* }) => {
+        $crate::scene!(@props world_set $world; $($body)*);
+    };
+    (@item $camera:ident, $world:ident, sphere { $($body:tt)* }) => {
+        $crate::scene!(@object $world, $crate::primitives::Object::new_sphere(), $($body)*);
+    };
+    (@item $camera:ident, $world:ident, plane { $($body:tt)* }) => {
+        $crate::scene!(@object $world, $crate::primitives::Object::new_plane(), $($body)*);
+    };
+    (@item $camera:ident, $world:ident, glass_orb { $($body:tt)* }) => {
+        $crate::scene!(@object $world, $crate::primitives::Object::glass_orb(), $($body)*);
+    };
+    (@item $camera:ident, $world:ident, $other:ident { $($body:tt)* }) => {
+        compile_error!(concat!("unknown scene item `", stringify!($other), "`"));
+    };
+
+    (@object $world:ident, $new:expr, $($body:tt)*) => {
+        let mut object = ($new, $crate::core::TransformBuilder::new());
+        $crate::scene!(@props object_set object; $($body)*);
+        let (mut object, transform) = object;
+        if !transform.transforms.is_empty() {
+            object.with_transform(transform.build());
+        }
+        $world.add(object);
+    };
+
+    // Splits properties up, turning triples into tuples of Float.
+    (@props $set:ident $target:ident;) => {};
+    (@props $set:ident $target:ident; $key:ident : ($x:expr, $y:expr, $z:expr) $(, $($rest:tt)*)?) => {
+        $crate::scene!(@$set $target, $key, ($x as $crate::Float, $y as $crate::Float, $z as $crate::Float));
+        $crate::scene!(@props $set $target; $($($rest)*)?);
+    };
+    (@props $set:ident $target:ident; $key:ident : $value:expr $(, $($rest:tt)*)?) => {
+        $crate::scene!(@$set $target, $key, $value);
+        $crate::scene!(@props $set $target; $($($rest)*)?);
+    };
+
+    (@camera_set $c:ident, width, $v:expr) => { $c.width = $v as usize; };
+    (@camera_set $c:ident, height, $v:expr) => { $c.height = $v as usize; };
+    (@camera_set $c:ident, fov, $v:expr) => { $c.fov = $v as $crate::Float; };
+    (@camera_set $c:ident, from, $v:expr) => { $c.from = $v; };
+    (@camera_set $c:ident, to, $v:expr) => { $c.to = $v; };
+    (@camera_set $c:ident, up, $v:expr) => { $c.up = $v; };
+    (@camera_set $c:ident, exposure, $v:expr) => { $c.exposure = $v as $crate::Float; };
+    (@camera_set $c:ident, distortion, $v:expr) => { $c.distortion = $v; };
+    (@camera_set $c:ident, vignetting, $v:expr) => { $c.vignetting = $v as $crate::Float; };
+    (@camera_set $c:ident, shift, $v:expr) => { $c.shift = $v; };
+    (@camera_set $c:ident, tilt, $v:expr) => { $c.tilt = $v; };
+    (@camera_set $c:ident, $other:ident, $v:expr) => {
+        compile_error!(concat!("unknown camera property `", stringify!($other), "`"));
+    };
+
+    (@light_set $l:ident, at, $v:expr) => {
+        let (x, y, z) = $v;
+        $l.position = $crate::core::point(x, y, z);
+    };
+    (@light_set $l:ident, colour, $v:expr) => { $l.colour = $v; };
+    (@light_set $l:ident, name, $v:expr) => { $l.name = Some(String::from($v)); };
+    (@light_set $l:ident, $other:ident, $v:expr) => {
+        compile_error!(concat!("unknown light property `", stringify!($other), "`"));
+    };
+
+    (@world_set $w:ident, recursions, $v:expr) => { $w.rcrs_lim = $v as u8; };
+    (@world_set $w:ident, background, $v:expr) => { $w.background = $v; };
+    (@world_set $w:ident, epsilon, $v:expr) => { $w.epsilon = $v as $crate::Float; };
+    (@world_set $w:ident, slope_bias, $v:expr) => { $w.slope_bias = $v as $crate::Float; };
+    (@world_set $w:ident, light_samples, $v:expr) => { $w.light_samples = Some($v as usize); };
+    (@world_set $w:ident, $other:ident, $v:expr) => {
+        compile_error!(concat!("unknown world property `", stringify!($other), "`"));
+    };
+
+    (@object_set $o:ident, material, $v:expr) => { $o.0.with_material($v); };
+    (@object_set $o:ident, name, $v:expr) => { $o.0.with_name($v); };
+    (@object_set $o:ident, id, $v:expr) => { $o.0.with_id($v as u32); };
+    (@object_set $o:ident, shadow, $v:expr) => {
+        if !$v {
+            $o.0.cast_no_shadow();
+        }
+    };
+    (@object_set $o:ident, manifold, $v:expr) => {
+        if $v {
+            $o.0.use_manifold();
+        }
+    };
+    (@object_set $o:ident, latitude, $v:expr) => {
+        let (min, max) = $v;
+        $o.0.with_latitude(min as $crate::Float, max as $crate::Float);
+    };
+    (@object_set $o:ident, longitude, $v:expr) => {
+        let (min, max) = $v;
+        $o.0.with_longitude(min as $crate::Float, max as $crate::Float);
+    };
+    (@object_set $o:ident, translate, $v:expr) => {
+        let (x, y, z) = $v;
+        $o.1 = $o.1.translate(x, y, z);
+    };
+    (@object_set $o:ident, nuscale, $v:expr) => {
+        let (x, y, z) = $v;
+        $o.1 = $o.1.nuscale(x, y, z);
+    };
+    (@object_set $o:ident, uscale, $v:expr) => { $o.1 = $o.1.uscale($v as $crate::Float); };
+    (@object_set $o:ident, rot_x, $v:expr) => { $o.1 = $o.1.rot_x($v as $crate::Float); };
+    (@object_set $o:ident, rot_y, $v:expr) => { $o.1 = $o.1.rot_y($v as $crate::Float); };
+    (@object_set $o:ident, rot_z, $v:expr) => { $o.1 = $o.1.rot_z($v as $crate::Float); };
+    (@object_set $o:ident, transform, $v:expr) => { $o.1.transforms.push($v.into()); };
+    (@object_set $o:ident, $other:ident, $v:expr) => {
+        compile_error!(concat!("unknown object property `", stringify!($other), "`"));
+    };
+}
+
+/// Camera settings gathered by scene!, which the camera is made from once
+/// they're all known.
+#[doc(hidden)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CameraSpec {
+    pub width: usize,
+    pub height: usize,
+    pub fov: Float,
+    pub from: (Float, Float, Float),
+    pub to: (Float, Float, Float),
+    pub up: (Float, Float, Float),
+    pub exposure: Float,
+    pub distortion: (Float, Float),
+    pub vignetting: Float,
+    pub shift: (Float, Float),
+    pub tilt: (Float, Float)
+}
+
+impl CameraSpec {
+    pub fn build(&self) -> Camera {
+        let ((fx, fy, fz), (tx, ty, tz), (ux, uy, uz)) = (self.from, self.to, self.up);
+        Camera::new(self.width, self.height, self.fov)
+            .with_transform(Matrix4::view_transform(point(fx, fy, fz), point(tx, ty, tz), vector(ux, uy, uz)))
+            .with_exposure(self.exposure)
+            .with_distortion(self.distortion.0, self.distortion.1)
+            .with_vignetting(self.vignetting)
+            .with_shift(self.shift.0, self.shift.1)
+            .with_tilt(self.tilt.0, self.tilt.1)
+    }
+}
+
+impl Default for CameraSpec {
+    fn default() -> Self {
+        CameraSpec {
+            width: 100,
+            height: 100,
+            fov: PI / 3.0,
+            from: (0.0, 0.0, -5.0),
+            to: (0.0, 0.0, 0.0),
+            up: (0.0, 1.0, 0.0),
+            exposure: 0.0,
+            distortion: (0.0, 0.0),
+            vignetting: 0.0,
+            shift: (0.0, 0.0),
+            tilt: (0.0, 0.0)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::{point, vector, Background, Colour, Transform, TransformBuilder, World};
+    use crate::consts::PI;
+    use crate::lights::PointLight;
+    use crate::materials::Material;
+    use crate::primitives::Object;
+    use nalgebra::Matrix4;
+
+    #[test]
+    fn scenes_match_the_builders() {
+        let glass = Material::null().with_transparency(1.0).with_ior(1.5);
+        let scene = crate::scene! {
+            camera { width: 70, height: 35, fov: PI / 3.0, from: (0, 1.5, -5), to: (0, 1, 0), exposure: -0.5, vignetting: 0.5, shift: (0.0, 0.1) }
+            light { at: (-10, 10, -10), colour: Colour::white(), name: "key" }
+            plane { material: Material::default().with_specular(0.0), shadow: false }
+            sphere { material: glass, uscale: 0.5, translate: (1, 0.5, 0), name: "ball", latitude: (0.0, PI / 2.0) }
+            world { recursions: 3, background: Background::Solid(Colour::blue()) }
+        };
+        let ball = Object::new_sphere()
+            .with_material(glass)
+            .with_transform(TransformBuilder::new().uscale(0.5).translate(1.0, 0.5, 0.0))
+            .with_name("ball")
+            .with_latitude(0.0, PI / 2.0);
+        let world = World::default()
+            .with_light(PointLight::new(Colour::white(), point(-10.0, 10.0, -10.0)).with_name("key"))
+            .with_object(Object::new_plane().with_material(Material::default().with_specular(0.0)).cast_no_shadow())
+            .with_object(ball)
+            .with_recursions(3)
+            .with_background(Background::Solid(Colour::blue()));
+        let view = Matrix4::view_transform(point(0.0, 1.5, -5.0), point(0.0, 1.0, 0.0), vector(0.0, 1.0, 0.0));
+
+        assert_eq!(scene.world, world);
+        assert_eq!((scene.camera.hsize, scene.camera.vsize), (70, 35));
+        assert_eq!(scene.camera.transform, view);
+        assert_eq!(scene.camera.exposure, -0.5);
+        assert_eq!(scene.camera.vignetting, 0.5);
+        assert_eq!(scene.camera.shift, (0.0, 0.1));
+    }
+
+    #[test]
+    fn empty_scenes_have_a_default_camera() {
+        let scene = crate::scene! {};
+
+        assert!(scene.world.objects.is_empty());
+        assert_eq!(scene.camera.hsize, 100);
+        assert_eq!(scene.camera.ray_for_pixel(50, 50).origin, point(0.0, 0.0, -5.0));
+    }
+}
@@ -0,0 +1,224 @@
+use crate::{Float, EPSILON};
+use crate::core::{Background, Colour, RayKind, World};
+use crate::materials::{Material, MaterialId, Medium, Pattern};
+use crate::primitives::{Object, Primitive, Sphere};
+use crate::scene::{SceneError, Yaml};
+use nalgebra::{Matrix4, Vector4};
+
+/// Describes a world in the scene file format, so it can be read back with
+/// World::load() or Scene::load(). Transforms are written as whole matrices,
+/// so nothing is lost in the round trip. Materials shared through the world's
+/// palette are written as defines, and come back shared, in the order objects
+/// first use them.
+pub fn world_to_yaml(world: &World) -> Result<Yaml, SceneError> {
+    let mut items = vec![Yaml::Hash(vec![
+        entry("add", "world".into()),
+        entry("recursion-limit", (world.rcrs_lim as Float).into()),
+        entry("background", background_to_yaml(&world.background)?),
+        entry("ambient", colour(world.ambient)),
+        entry("ambient-strength", world.ambient_strength.into())
+    ])];
+    if let (Some(samples), Yaml::Hash(entries)) = (world.light_samples, &mut items[0]) {
+        entries.push(entry("light-samples", (samples as Float).into()));
+    }
+    if let (true, Yaml::Hash(entries)) = (world.epsilon != EPSILON, &mut items[0]) {
+        entries.push(entry("epsilon", world.epsilon.into()));
+    }
+    if let (true, Yaml::Hash(entries)) = (world.slope_bias != 0.0, &mut items[0]) {
+        entries.push(entry("slope-bias", world.slope_bias.into()));
+    }
+    if let (Some(map), Yaml::Hash(entries)) = (&world.caustics, &mut items[0]) {
+        entries.push(entry("caustics", Yaml::Hash(vec![
+            entry("photons", (map.photons_per_light() as Float).into()),
+            entry("radius", map.radius().into())
+        ])));
+    }
+    for light in &world.lights {
+        let mut entries = vec![
+            entry("add", "light".into()),
+            entry("at", tuple(light.position)),
+            entry("intensity", colour(light.colour))
+        ];
+        if let Some(name) = &light.name {
+            entries.push(entry("name", name.as_str().into()));
+        }
+        if let Some(mask) = &light.mask {
+            entries.push(entry("mask", pattern_to_yaml(mask)));
+        }
+        items.push(Yaml::Hash(entries));
+    }
+    let names: Vec<(MaterialId, String, Material)> = world.palette()
+        .map(|(id, name, m)| (id, name.map_or_else(|| format!("material-{}", id.0), String::from), *m))
+        .collect();
+    for (_, name, material) in &names {
+        items.push(Yaml::Hash(vec![
+            entry("define", name.as_str().into()),
+            entry("value", material_to_yaml(material))
+        ]));
+    }
+    for object in &world.objects {
+        let shared = names.iter()
+            .find(|(id, _, m)| object.material_id == Some(*id) && *m == object.material)
+            .map(|(_, name, _)| name.as_str());
+        items.push(object_to_yaml(object, shared)?);
+    }
+
+    Ok(Yaml::Array(items))
+}
+
+fn entry(key: &str, value: Yaml) -> (String, Yaml) {
+    (key.to_string(), value)
+}
+
+fn colour(c: Colour) -> Yaml {
+    Yaml::Array(vec![c.r.into(), c.g.into(), c.b.into()])
+}
+
+fn tuple(v: Vector4<Float>) -> Yaml {
+    Yaml::Array(vec![v.x.into(), v.y.into(), v.z.into()])
+}
+
+// A single [ matrix, ... ] step holding all sixteen values, row by row.
+fn transform(m: &Matrix4<Float>) -> Yaml {
+    let mut step = vec![Yaml::from("matrix")];
+    for row in 0..4 {
+        for col in 0..4 {
+            step.push(m[(row, col)].into());
+        }
+    }
+
+    Yaml::Array(vec![Yaml::Array(step)])
+}
+
+fn background_to_yaml(background: &Background) -> Result<Yaml, SceneError> {
+    match background {
+        Background::Solid(c) => Ok(colour(*c)),
+        Background::Gradient { horizon, zenith } => Ok(Yaml::Hash(vec![
+            entry("horizon", colour(*horizon)),
+            entry("zenith", colour(*zenith))
+        ])),
+        Background::Environment(_) => Err(SceneError::Invalid(String::from("environment maps can't be saved"))),
+        Background::CubeMap(_) => Err(SceneError::Invalid(String::from("cube maps can't be saved")))
+    }
+}
+
+// Shared is the name of the palette material the object uses, if any.
+fn object_to_yaml(object: &Object, shared: Option<&str>) -> Result<Yaml, SceneError> {
+    let shape = match object.shape {
+        Primitive::Sphere(_) => "sphere",
+        Primitive::Plane() => "plane",
+        Primitive::Lens(_) => "lens",
+        Primitive::Polygon(_) => "polygon",
+        Primitive::TestShape(_) => return Err(SceneError::Invalid(String::from("test shapes can't be saved")))
+    };
+    let mut entries = vec![
+        entry("add", shape.into()),
+        entry("material", shared.map_or_else(|| material_to_yaml(&object.material), Yaml::from))
+    ];
+    if object.transform != Matrix4::identity() {
+        entries.push(entry("transform", transform(&object.transform)));
+    }
+    if let Primitive::Sphere(sphere) = object.shape {
+        let whole = Sphere::new();
+        for (key, range, all) in [("latitude", sphere.latitude, whole.latitude), ("longitude", sphere.longitude, whole.longitude)] {
+            if range != all {
+                entries.push(entry(key, Yaml::Array(vec![range.0.into(), range.1.into()])));
+            }
+        }
+    }
+    if let Primitive::Lens(lens) = object.shape {
+        entries.push(entry("front", lens.front.into()));
+        entries.push(entry("back", lens.back.into()));
+        entries.push(entry("thickness", lens.thickness.into()));
+        entries.push(entry("radius", lens.radius.into()));
+    }
+    if let Primitive::Polygon(polygon) = &object.shape {
+        let vertices = polygon.vertices().iter()
+            .map(|&(x, z)| Yaml::Array(vec![x.into(), z.into()]))
+            .collect();
+        entries.push(entry("vertices", Yaml::Array(vertices)));
+    }
+    if !object.umbra {
+        entries.push(entry("shadow", false.into()));
+    }
+    if object.hidden_from != 0 {
+        let kinds = RayKind::ALL.iter()
+            .filter(|kind| **kind != RayKind::Shadow && !object.is_visible_to(**kind))
+            .map(|kind| kind.name().into())
+            .collect();
+        entries.push(entry("hidden-from", Yaml::Array(kinds)));
+    }
+    if let Some(id) = object.id {
+        entries.push(entry("id", (id as Float).into()));
+    }
+    if let Some(name) = &object.name {
+        entries.push(entry("name", name.as_str().into()));
+    }
+    if object.uv_manifold {
+        entries.push(entry("manifold", true.into()));
+    }
+    if let Some(medium) = object.medium {
+        entries.push(entry("medium", medium_to_yaml(&medium)));
+    }
+    if object.is_animated() {
+        let keys = object.keyframes.keys().iter()
+            .map(|(time, m)| Yaml::Hash(vec![entry("time", (*time).into()), entry("transform", transform(m))]))
+            .collect();
+        entries.push(entry("keyframes", Yaml::Array(keys)));
+    }
+
+    Ok(Yaml::Hash(entries))
+}
+
+fn medium_to_yaml(medium: &Medium) -> Yaml {
+    let mut entries = vec![
+        entry("color", colour(medium.colour)),
+        entry("density", medium.density.into()),
+        entry("absorption", medium.absorption.into()),
+        entry("scattering", medium.scattering.into()),
+        entry("steps", (medium.steps as Float).into())
+    ];
+    if let Some(noise) = medium.noise {
+        entries.push(entry("noise", Yaml::Hash(vec![
+            entry("seed", (noise.seed as Float).into()),
+            entry("frequency", noise.frequency.into()),
+            entry("octaves", (noise.octaves as Float).into()),
+            entry("threshold", noise.threshold.into())
+        ])));
+    }
+
+    Yaml::Hash(entries)
+}
+
+// A pattern with its transform, as it's written in a scene file.
+pub(crate) fn pattern_to_yaml(pattern: &Pattern) -> Yaml {
+    let mut value = pattern.to_yaml();
+    if let Yaml::Hash(ref mut entries) = value {
+        if pattern.transform != Matrix4::identity() {
+            entries.push(entry("transform", transform(&pattern.transform)));
+        }
+    }
+
+    value
+}
+
+fn material_to_yaml(material: &Material) -> Yaml {
+    let pattern = &material.pattern;
+    let mut entries = match pattern.solid_colour() {
+        Some(c) if pattern.transform == Matrix4::identity() => {
+            vec![entry("color", colour(c))]
+        },
+        _ => vec![entry("pattern", pattern_to_yaml(pattern))]
+    };
+    entries.extend([
+        entry("ambient", material.ambient.into()),
+        entry("diffuse", material.diffuse.into()),
+        entry("specular", material.specular.into()),
+        entry("shininess", material.smoothness.into()),
+        entry("reflective", material.reflectivity.into()),
+        entry("transparency", material.transparency.into()),
+        entry("refractive-index", material.ior.into())
+    ]);
+
+    Yaml::Hash(entries)
+}
@@ -0,0 +1,657 @@
+// A small YAML reader and writer, covering the subset used by scene files:
+// block sequences and mappings, flow [arrays] and {mappings}, quoted and plain
+// scalars, and comments. Anchors, tags and multi-line strings are not
+// supported.
+use crate::scene::SceneError;
+use std::fmt;
+
+/// Parsed YAML value.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Yaml {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Yaml>),
+    /// Mapping, with its keys kept in file order.
+    Hash(Vec<(String, Yaml)>)
+}
+
+impl Yaml {
+    /// Parses a whole YAML document.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let doc = Yaml::parse("- add: light\n  at: [ -10, 10, -10 ]").unwrap();
+    ///
+    /// assert_eq!(doc.as_array().unwrap().len(), 1);
+    /// ```
+    pub fn parse(src: &str) -> Result<Yaml, SceneError> {
+        let lines = lines(src)?;
+        if lines.is_empty() {
+            return Ok(Yaml::Null);
+        }
+        let mut parser = Parser { lines, pos: 0 };
+        let indent = parser.lines[0].indent;
+        let doc = parser.block(indent)?;
+        match parser.lines.get(parser.pos) {
+            Some(line) => Err(SceneError::syntax(line.number, "unexpected indentation")),
+            None => Ok(doc)
+        }
+    }
+
+    /// Looks up a key in a mapping.
+    pub fn get(&self, key: &str) -> Option<&Yaml> {
+        self.as_hash()?.iter().find(|(k, _)| k == key).map(|(_, v)| v)
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Yaml::Number(n) => Some(*n),
+            _ => None
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Yaml::Bool(b) => Some(*b),
+            _ => None
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Yaml::String(s) => Some(s),
+            _ => None
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Yaml]> {
+        match self {
+            Yaml::Array(items) => Some(items),
+            _ => None
+        }
+    }
+
+    pub fn as_hash(&self) -> Option<&[(String, Yaml)]> {
+        match self {
+            Yaml::Hash(entries) => Some(entries),
+            _ => None
+        }
+    }
+}
+
+impl From<f64> for Yaml {
+    fn from(n: f64) -> Self {
+        Yaml::Number(n)
+    }
+}
+
+// Goes via the shortest decimal form, so 0.1f32 is written as 0.1 rather
+// than 0.10000000149011612, and still reads back as the same f32.
+impl From<f32> for Yaml {
+    fn from(n: f32) -> Self {
+        Yaml::Number(n.to_string().parse().unwrap_or(n as f64))
+    }
+}
+
+impl From<&str> for Yaml {
+    fn from(s: &str) -> Self {
+        Yaml::String(s.to_string())
+    }
+}
+
+impl From<bool> for Yaml {
+    fn from(b: bool) -> Self {
+        Yaml::Bool(b)
+    }
+}
+
+// Writes block style, with lists of scalars and lists kept on one line so
+// colours, points and transforms stay readable. Parses back to the same value.
+impl fmt::Display for Yaml {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut out = String::new();
+        emit(self, 0, &mut out);
+        f.write_str(&out)
+    }
+}
+
+fn emit(value: &Yaml, indent: usize, out: &mut String) {
+    let pad = " ".repeat(indent);
+    match value {
+        Yaml::Hash(entries) if !entries.is_empty() => {
+            for (key, value) in entries {
+                out.push_str(&pad);
+                out.push_str(&inline(&Yaml::String(key.clone())));
+                if is_block(value) {
+                    out.push_str(":\n");
+                    emit(value, indent + 2, out);
+                } else {
+                    out.push_str(": ");
+                    out.push_str(&inline(value));
+                    out.push('\n');
+                }
+            }
+        },
+        Yaml::Array(items) if is_block(value) => {
+            for item in items {
+                let mut nested = String::new();
+                emit(item, indent + 2, &mut nested);
+                if let Yaml::Hash(_) = item {
+                    // Put the first key on the same line as the dash
+                    out.push_str(&pad);
+                    out.push_str("- ");
+                    out.push_str(&nested[indent + 2..]);
+                } else {
+                    out.push_str(&pad);
+                    out.push_str("- ");
+                    out.push_str(nested.trim_start());
+                }
+            }
+        },
+        value => {
+            out.push_str(&pad);
+            out.push_str(&inline(value));
+            out.push('\n');
+        }
+    }
+}
+
+// Non-empty mappings, and lists containing them, are written as blocks.
+fn is_block(value: &Yaml) -> bool {
+    match value {
+        Yaml::Hash(entries) => !entries.is_empty(),
+        Yaml::Array(items) => items.iter().any(|item| matches!(item, Yaml::Hash(e) if !e.is_empty())),
+        _ => false
+    }
+}
+
+fn inline(value: &Yaml) -> String {
+    match value {
+        Yaml::Null => String::from("~"),
+        Yaml::Bool(b) => b.to_string(),
+        Yaml::Number(n) => n.to_string(),
+        Yaml::String(s) => {
+            let plain = !s.is_empty()
+                && s.trim() == s
+                && scalar(s) == Yaml::String(s.clone())
+                && !s.starts_with(['-', '~', '&', '*', '!', '|', '>', '%', '@', '`'])
+                && !s.contains([':', '#', ',', '[', ']', '{', '}', '"', '\'', '\\', '\n', '\t']);
+            if plain {
+                s.clone()
+            } else {
+                let escaped = s.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n").replace('\t', "\\t");
+                format!("\"{}\"", escaped)
+            }
+        },
+        Yaml::Array(items) => {
+            let items: Vec<String> = items.iter().map(inline).collect();
+            format!("[ {} ]", items.join(", "))
+        },
+        Yaml::Hash(entries) => {
+            let entries: Vec<String> = entries.iter()
+                .map(|(k, v)| format!("{}: {}", inline(&Yaml::String(k.clone())), inline(v)))
+                .collect();
+            format!("{{ {} }}", entries.join(", "))
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Line {
+    number: usize,
+    indent: usize,
+    text: String
+}
+
+// Splits the source into meaningful lines, dropping comments and blank lines,
+// and joining flow collections that continue over several lines.
+fn lines(src: &str) -> Result<Vec<Line>, SceneError> {
+    let mut lines: Vec<Line> = vec![];
+    let mut open: Option<usize> = None;
+    for (i, raw) in src.lines().enumerate() {
+        let text = strip_comment(raw).trim_end();
+        let trimmed = text.trim_start();
+        if open.is_none() && (trimmed.is_empty() || trimmed == "---" || trimmed == "...") {
+            continue;
+        }
+        if text.starts_with('\t') {
+            return Err(SceneError::syntax(i + 1, "tabs can't be used for indentation"));
+        }
+        match open {
+            Some(index) => {
+                let line = &mut lines[index];
+                line.text.push(' ');
+                line.text.push_str(trimmed);
+            },
+            None => lines.push(Line {
+                number: i + 1,
+                indent: text.len() - trimmed.len(),
+                text: trimmed.to_string()
+            })
+        }
+        let index = lines.len() - 1;
+        open = if depth(&lines[index].text) > 0 { Some(index) } else { None };
+    }
+    if let Some(index) = open {
+        return Err(SceneError::syntax(lines[index].number, "unclosed bracket"));
+    }
+
+    Ok(lines)
+}
+
+// Removes a trailing comment, i.e. a # at the start or after whitespace that
+// isn't inside quotes.
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut prev = ' ';
+    for (i, c) in line.char_indices() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => {},
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None if c == '#' && prev.is_whitespace() => return &line[..i],
+            None => {}
+        }
+        prev = c;
+    }
+
+    line
+}
+
+// Number of unclosed flow brackets on a line.
+fn depth(text: &str) -> i32 {
+    let mut depth = 0;
+    let mut quote = None;
+    for c in text.chars() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => {},
+            None => match c {
+                '"' | '\'' => quote = Some(c),
+                '[' | '{' => depth += 1,
+                ']' | '}' => depth -= 1,
+                _ => {}
+            }
+        }
+    }
+
+    depth
+}
+
+fn is_item(text: &str) -> bool {
+    text == "-" || text.starts_with("- ")
+}
+
+// Finds the key in "key: value", ignoring colons inside quotes or brackets.
+fn split_key(text: &str) -> Option<(&str, &str)> {
+    let mut quote = None;
+    let mut depth = 0;
+    let bytes = text.as_bytes();
+    for (i, c) in text.char_indices() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => {},
+            None => match c {
+                '"' | '\'' => quote = Some(c),
+                '[' | '{' => depth += 1,
+                ']' | '}' => depth -= 1,
+                ':' if depth == 0 && (i + 1 == text.len() || bytes[i + 1] == b' ') => {
+                    return Some((text[..i].trim(), text[i + 1..].trim()));
+                },
+                _ => {}
+            }
+        }
+    }
+
+    None
+}
+
+struct Parser {
+    lines: Vec<Line>,
+    pos: usize
+}
+
+impl Parser {
+    fn block(&mut self, indent: usize) -> Result<Yaml, SceneError> {
+        if is_item(&self.lines[self.pos].text) {
+            self.sequence(indent)
+        } else {
+            self.mapping(indent)
+        }
+    }
+
+    // Value of a key or item written on the following lines, if there is one.
+    fn nested(&mut self, indent: usize, allow_same_indent_sequence: bool) -> Result<Yaml, SceneError> {
+        match self.lines.get(self.pos) {
+            Some(next) if next.indent > indent => {
+                let indent = next.indent;
+                self.block(indent)
+            },
+            Some(next) if allow_same_indent_sequence && next.indent == indent && is_item(&next.text) => {
+                self.sequence(indent)
+            },
+            _ => Ok(Yaml::Null)
+        }
+    }
+
+    fn sequence(&mut self, indent: usize) -> Result<Yaml, SceneError> {
+        let mut items = vec![];
+        while let Some(line) = self.lines.get(self.pos) {
+            if line.indent < indent || !is_item(&line.text) {
+                break;
+            }
+            if line.indent > indent {
+                return Err(SceneError::syntax(line.number, "unexpected indentation"));
+            }
+            let number = line.number;
+            let rest = line.text[1..].trim_start().to_string();
+            if rest.is_empty() {
+                self.pos += 1;
+                items.push(self.nested(indent, false)?);
+            } else if !rest.starts_with(['[', '{', '"', '\'']) && split_key(&rest).is_some() {
+                // "- key: value" starts a mapping indented to where the key is
+                let offset = line.text.len() - rest.len();
+                let line = &mut self.lines[self.pos];
+                line.indent = indent + offset;
+                line.text = rest;
+                items.push(self.mapping(indent + offset)?);
+            } else {
+                self.pos += 1;
+                items.push(flow(&rest, number)?);
+            }
+        }
+
+        Ok(Yaml::Array(items))
+    }
+
+    fn mapping(&mut self, indent: usize) -> Result<Yaml, SceneError> {
+        let mut entries: Vec<(String, Yaml)> = vec![];
+        while let Some(line) = self.lines.get(self.pos) {
+            if line.indent < indent || (line.indent == indent && is_item(&line.text)) {
+                break;
+            }
+            if line.indent > indent {
+                return Err(SceneError::syntax(line.number, "unexpected indentation"));
+            }
+            let number = line.number;
+            let (key, rest) = split_key(&line.text)
+                .ok_or_else(|| SceneError::syntax(number, "expected 'key: value'"))?;
+            let key = match flow(key, number)? {
+                Yaml::String(key) => key,
+                _ => key.to_string()
+            };
+            if entries.iter().any(|(k, _)| *k == key) {
+                return Err(SceneError::syntax(number, &format!("duplicate key '{}'", key)));
+            }
+            let value = if rest.is_empty() {
+                None
+            } else {
+                Some(flow(rest, number)?)
+            };
+            self.pos += 1;
+            let value = match value {
+                Some(value) => value,
+                None => self.nested(indent, true)?
+            };
+            entries.push((key, value));
+        }
+
+        Ok(Yaml::Hash(entries))
+    }
+}
+
+// Parses a value written on a single line.
+fn flow(text: &str, line: usize) -> Result<Yaml, SceneError> {
+    let mut reader = Flow { chars: text.chars().collect(), pos: 0, line };
+    let value = reader.value(false)?;
+    reader.skip_whitespace();
+    if reader.pos < reader.chars.len() {
+        return Err(SceneError::syntax(line, "unexpected characters after value"));
+    }
+
+    Ok(value)
+}
+
+struct Flow {
+    chars: Vec<char>,
+    pos: usize,
+    line: usize
+}
+
+impl Flow {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().map_or(false, char::is_whitespace) {
+            self.pos += 1;
+        }
+    }
+
+    fn error(&self, message: &str) -> SceneError {
+        SceneError::syntax(self.line, message)
+    }
+
+    fn value(&mut self, nested: bool) -> Result<Yaml, SceneError> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('[') => self.array(),
+            Some('{') => self.hash(),
+            Some(q) if q == '"' || q == '\'' => self.quoted(q),
+            Some(_) => Ok(self.plain(nested)),
+            None => Ok(Yaml::Null)
+        }
+    }
+
+    fn array(&mut self) -> Result<Yaml, SceneError> {
+        self.pos += 1;
+        let mut items = vec![];
+        loop {
+            self.skip_whitespace();
+            match self.peek() {
+                Some(']') => {
+                    self.pos += 1;
+                    return Ok(Yaml::Array(items));
+                },
+                None => return Err(self.error("unclosed '['")),
+                _ => {}
+            }
+            items.push(self.value(true)?);
+            self.separator(']')?;
+        }
+    }
+
+    fn hash(&mut self) -> Result<Yaml, SceneError> {
+        self.pos += 1;
+        let mut entries = vec![];
+        loop {
+            self.skip_whitespace();
+            match self.peek() {
+                Some('}') => {
+                    self.pos += 1;
+                    return Ok(Yaml::Hash(entries));
+                },
+                None => return Err(self.error("unclosed '{'")),
+                _ => {}
+            }
+            let key = match self.value(true)? {
+                Yaml::String(key) => key,
+                Yaml::Number(n) => n.to_string(),
+                _ => return Err(self.error("mapping keys must be strings"))
+            };
+            self.skip_whitespace();
+            if self.peek() != Some(':') {
+                return Err(self.error("expected ':' after key"));
+            }
+            self.pos += 1;
+            entries.push((key, self.value(true)?));
+            self.separator('}')?;
+        }
+    }
+
+    // Consumes the comma between flow items, or checks for the closing bracket.
+    fn separator(&mut self, close: char) -> Result<(), SceneError> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some(',') => {
+                self.pos += 1;
+                Ok(())
+            },
+            Some(c) if c == close => Ok(()),
+            _ => Err(self.error(&format!("expected ',' or '{}'", close)))
+        }
+    }
+
+    fn quoted(&mut self, quote: char) -> Result<Yaml, SceneError> {
+        self.pos += 1;
+        let mut s = String::new();
+        while let Some(c) = self.peek() {
+            self.pos += 1;
+            match c {
+                // Doubled single quotes are an escaped quote
+                '\'' if quote == '\'' && self.peek() == Some('\'') => {
+                    self.pos += 1;
+                    s.push('\'');
+                },
+                c if c == quote => return Ok(Yaml::String(s)),
+                '\\' if quote == '"' => {
+                    let escaped = self.peek().ok_or_else(|| self.error("unfinished escape"))?;
+                    self.pos += 1;
+                    s.push(match escaped {
+                        'n' => '\n',
+                        't' => '\t',
+                        other => other
+                    });
+                },
+                c => s.push(c)
+            }
+        }
+
+        Err(self.error("unclosed quote"))
+    }
+
+    // Unquoted scalar. Inside a flow collection it ends at the next separator.
+    fn plain(&mut self, nested: bool) -> Yaml {
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            if nested && matches!(c, ',' | ']' | '}' | ':') {
+                break;
+            }
+            self.pos += 1;
+        }
+        let text: String = self.chars[start..self.pos].iter().collect();
+
+        scalar(text.trim())
+    }
+}
+
+fn scalar(text: &str) -> Yaml {
+    match text {
+        "" | "~" | "null" | "Null" | "NULL" => Yaml::Null,
+        "true" | "True" | "TRUE" => Yaml::Bool(true),
+        "false" | "False" | "FALSE" => Yaml::Bool(false),
+        _ => match text.parse::<f64>() {
+            Ok(n) if !text.starts_with(['+', 'i', 'I', 'n', 'N']) => Yaml::Number(n),
+            _ => Yaml::String(text.to_string())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn s(text: &str) -> Yaml {
+        Yaml::String(text.to_string())
+    }
+
+    #[test]
+    fn parsing_scalars() {
+        let doc = Yaml::parse("a: 1.5\nb: -2\nc: true\nd: hello world\ne: 'it''s'\nf: \"x # y\"\ng: ~").unwrap();
+
+        assert_eq!(doc.get("a"), Some(&Yaml::Number(1.5)));
+        assert_eq!(doc.get("b"), Some(&Yaml::Number(-2.0)));
+        assert_eq!(doc.get("c"), Some(&Yaml::Bool(true)));
+        assert_eq!(doc.get("d"), Some(&s("hello world")));
+        assert_eq!(doc.get("e"), Some(&s("it's")));
+        assert_eq!(doc.get("f"), Some(&s("x # y")));
+        assert_eq!(doc.get("g"), Some(&Yaml::Null));
+    }
+
+    #[test]
+    fn parsing_flow_collections() {
+        let doc = Yaml::parse("- [ translate, 1, -1, 1 ]\n- { type: stripes, colors: [ [1, 0, 0], [0, 0, 1] ] }").unwrap();
+        let items = doc.as_array().unwrap();
+
+        assert_eq!(items[0], Yaml::Array(vec![s("translate"), Yaml::Number(1.0), Yaml::Number(-1.0), Yaml::Number(1.0)]));
+        assert_eq!(items[1].get("type"), Some(&s("stripes")));
+        assert_eq!(items[1].get("colors").unwrap().as_array().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn parsing_nested_blocks() {
+        let src = "
+# A comment
+- add: sphere   # trailing comment
+  material:
+    color: [ 1, 0.2, 1 ]
+    diffuse: 0.7
+  transform:
+    - [ scale, 0.5, 0.5, 0.5 ]
+    - [ translate, 0, 1,
+        0 ]
+
+- add: plane
+  transform:
+  - [ rotate-x, 1.5 ]
+";
+        let doc = Yaml::parse(src).unwrap();
+        let items = doc.as_array().unwrap();
+
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].get("add"), Some(&s("sphere")));
+        assert_eq!(items[0].get("material").unwrap().get("diffuse"), Some(&Yaml::Number(0.7)));
+        assert_eq!(items[0].get("transform").unwrap().as_array().unwrap()[1].as_array().unwrap().len(), 4);
+        assert_eq!(items[1].get("transform").unwrap().as_array().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn reporting_syntax_errors_with_line_numbers() {
+        let bad_indent = Yaml::parse("a: 1\n   b: 2");
+        let unclosed = Yaml::parse("a: 1\nb: [ 1, 2");
+        let no_key = Yaml::parse("a: 1\njust text");
+
+        assert!(matches!(bad_indent, Err(SceneError::Syntax { line: 2, .. })));
+        assert!(matches!(unclosed, Err(SceneError::Syntax { line: 2, .. })));
+        assert!(matches!(no_key, Err(SceneError::Syntax { line: 2, .. })));
+    }
+
+    #[test]
+    fn writing_and_reading_back() {
+        let doc = Yaml::Array(vec![
+            Yaml::Hash(vec![
+                (String::from("add"), s("sphere")),
+                (String::from("name"), s("it's: \"quoted\"")),
+                (String::from("empty"), s("")),
+                (String::from("number-like"), s("1.5")),
+                (String::from("material"), Yaml::Hash(vec![
+                    (String::from("color"), Yaml::Array(vec![Yaml::Number(0.1), Yaml::Number(1.0), Yaml::Number(-2.5)]))
+                ])),
+                (String::from("keys"), Yaml::Array(vec![
+                    Yaml::Hash(vec![(String::from("time"), Yaml::Number(0.0)), (String::from("shadow"), Yaml::Bool(false))])
+                ])),
+                (String::from("none"), Yaml::Null)
+            ]),
+            Yaml::Hash(vec![(String::from("add"), s("light"))])
+        ]);
+        let text = doc.to_string();
+
+        assert!(text.starts_with("- add: sphere\n  name: "));
+        assert!(text.contains("  material:\n    color: [ 0.1, 1, -2.5 ]\n"));
+        assert_eq!(Yaml::parse(&text).unwrap(), doc);
+    }
+}
@@ -0,0 +1,244 @@
+use crate::{to_f32, Float};
+use crate::consts::PI;
+use crate::core::{point, vector, Camera, Colour, Rng, Transform, World};
+use crate::lights::PointLight;
+use crate::materials::{Material, Pattern};
+use crate::primitives::Object;
+use crate::scene::Scene;
+use nalgebra::Matrix4;
+
+// Directions a sphere flake's children sit in. Each one's opposite is its index
+// with the lowest bit flipped.
+const AXES: [(Float, Float, Float); 6] = [
+    (1.0, 0.0, 0.0), (-1.0, 0.0, 0.0),
+    (0.0, 1.0, 0.0), (0.0, -1.0, 0.0),
+    (0.0, 0.0, 1.0), (0.0, 0.0, -1.0)
+];
+
+/// All the ready made scenes, by name, at their standard sizes. These are
+/// the scenes benchmarked by `feoray bench`.
+pub fn scenes() -> Vec<(&'static str, Scene)> {
+    vec![
+        ("sphere-flake", sphere_flake(3)),
+        ("cornell-box", cornell_box()),
+        ("glass-grid", glass_grid(5)),
+        ("random-spheres", random_spheres(0)),
+        ("material-grid", material_grid(5))
+    ]
+}
+
+/// Fractal of shiny spheres, each with smaller spheres a third of its size on
+/// every side but the one it grew from, to the given depth. Lots of objects
+/// and lots of reflections. A depth of 3 gives 156 spheres.
+pub fn sphere_flake(depth: usize) -> Scene {
+    let mut world = World::default()
+        .with_light(PointLight::new(Colour::white(), point(-10.0, 10.0, -10.0)))
+        .with_object(Object::new_plane()
+            .with_transform(Matrix4::translate(0.0, -1.5, 0.0))
+            .with_material(Material::default().with_specular(0.0)));
+    let chrome = Material::default()
+        .with_colour(Colour::new(0.8, 0.8, 0.9))
+        .with_reflectivity(0.4);
+    flake(&mut world.objects, Matrix4::identity(), chrome, None, depth);
+    let camera = Camera::new(320, 240, PI / 3.0)
+        .with_transform(Matrix4::view_transform(point(2.0, 2.5, -5.0), point(0.0, 0.0, 0.0), vector(0.0, 1.0, 0.0)));
+
+    Scene { camera, world }
+}
+
+fn flake(objects: &mut Vec<Object>, transform: Matrix4<Float>, material: Material, parent: Option<usize>, depth: usize) {
+    objects.push(Object::new_sphere().with_transform(transform).with_material(material));
+    if depth == 0 {
+        return;
+    }
+    for (i, (x, y, z)) in AXES.iter().enumerate() {
+        if parent == Some(i) {
+            continue;
+        }
+        let offset = 1.0 + 1.0 / 3.0;
+        let child = transform
+            * Matrix4::translate(x * offset, y * offset, z * offset)
+            * Matrix4::uscale(1.0 / 3.0);
+        flake(objects, child, material, Some(i ^ 1), depth - 1);
+    }
+}
+
+/// Closed box with a red wall on the left, a green one on the right, a light
+/// just under the ceiling, and a mirror ball and a glass ball on the floor.
+/// Light bounces around everywhere, so it's a good test of the path tracer.
+pub fn cornell_box() -> Scene {
+    let matte = |colour: Colour| Material::default().with_colour(colour).with_specular(0.0);
+    let white = matte(Colour::new(0.73, 0.73, 0.73));
+    let walls = [
+        (Matrix4::identity(), white),
+        (Matrix4::translate(0.0, 5.0, 0.0) * Matrix4::rot_x(PI), white),
+        (Matrix4::translate(0.0, 0.0, 5.0) * Matrix4::rot_x(-PI / 2.0), white),
+        (Matrix4::translate(0.0, 0.0, -7.5) * Matrix4::rot_x(PI / 2.0), white),
+        (Matrix4::translate(-2.5, 0.0, 0.0) * Matrix4::rot_z(-PI / 2.0), matte(Colour::new(0.65, 0.05, 0.05))),
+        (Matrix4::translate(2.5, 0.0, 0.0) * Matrix4::rot_z(PI / 2.0), matte(Colour::new(0.12, 0.45, 0.15)))
+    ];
+    let mut world = World::default()
+        .with_light(PointLight::new(Colour::white(), point(0.0, 4.8, 0.0)));
+    for (transform, material) in walls {
+        world = world.with_object(Object::new_plane().with_transform(transform).with_material(material));
+    }
+    let mirror = Object::new_sphere()
+        .with_transform(Matrix4::translate(-1.1, 1.0, 1.5))
+        .with_material(Material::null().with_reflectivity(0.95).with_specular(0.9));
+    let glass = Object::glass_orb()
+        .with_transform(Matrix4::translate(1.0, 0.75, 0.0) * Matrix4::uscale(0.75));
+    let world = world.with_object(mirror).with_object(glass);
+    let camera = Camera::new(256, 256, PI / 3.0)
+        .with_transform(Matrix4::view_transform(point(0.0, 2.5, -7.0), point(0.0, 2.5, 0.0), vector(0.0, 1.0, 0.0)));
+
+    Scene { camera, world }
+}
+
+/// Grid of n by n glass orbs over a checkered floor. Every camera ray that
+/// hits an orb splits into a reflection and a refraction at each surface. The
+/// orbs cast no shadows, so the floor can be seen through them.
+pub fn glass_grid(n: usize) -> Scene {
+    let checkers = Pattern::new_checkers(Colour::white(), Colour::grey(0.3));
+    let mut world = World::default()
+        .with_light(PointLight::new(Colour::white(), point(-5.0, 10.0, -5.0)))
+        .with_object(Object::new_plane().with_material(Material::default().with_pattern(checkers)));
+    let centre = (n as Float - 1.0) / 2.0;
+    for i in 0..n {
+        for j in 0..n {
+            let (x, z) = (i as Float - centre, j as Float - centre);
+            world = world.with_object(Object::glass_orb()
+                .with_transform(Matrix4::translate(x, 0.4, z) * Matrix4::uscale(0.4))
+                .cast_no_shadow());
+        }
+    }
+    let distance = n as Float + 1.0;
+    let camera = Camera::new(320, 240, PI / 3.0)
+        .with_transform(Matrix4::view_transform(point(0.0, distance, -distance), point(0.0, 0.0, 0.0), vector(0.0, 1.0, 0.0)));
+
+    Scene { camera, world }
+}
+
+/// Field of small spheres scattered over a floor around three big ones, one
+/// matte, one glass and one mirror, after the cover of Ray Tracing in One
+/// Weekend. Most of the small spheres are matte, in random colours, with the
+/// rest mirrors or glass. The same seed always gives the same scene.
+pub fn random_spheres(seed: u64) -> Scene {
+    let mut rng = Rng::new(seed);
+    let mut next = || to_f32(rng.next_float());
+    let mut small = vec![];
+    for a in -11..11 {
+        for b in -11..11 {
+            let (x, z) = (a as Float + 0.9 * next() as Float, b as Float + 0.9 * next() as Float);
+            // Leave room around the big spheres.
+            if (x - 4.0).hypot(z) < 0.9 || x.hypot(z) < 0.9 || (x + 4.0).hypot(z) < 0.9 {
+                continue;
+            }
+            let choice = next();
+            let material = if choice < 0.8 {
+                Material::default()
+                    .with_colour(Colour::new(next() * next(), next() * next(), next() * next()))
+                    .with_specular(0.0)
+            } else if choice < 0.95 {
+                Material::default()
+                    .with_colour(Colour::new(0.5 + next() / 2.0, 0.5 + next() / 2.0, 0.5 + next() / 2.0))
+                    .with_diffuse(0.3)
+                    .with_reflectivity(0.5 + next() / 2.0)
+            } else {
+                Object::glass_orb().material
+            };
+            small.push(Object::new_sphere()
+                .with_transform(Matrix4::translate(x, 0.2, z) * Matrix4::uscale(0.2))
+                .with_material(material));
+        }
+    }
+    let big = [
+        Object::new_sphere()
+            .with_transform(Matrix4::translate(-4.0, 1.0, 0.0))
+            .with_material(Material::default().with_colour(Colour::new(0.4, 0.2, 0.1)).with_specular(0.0)),
+        Object::glass_orb()
+            .with_transform(Matrix4::translate(0.0, 1.0, 0.0)),
+        Object::new_sphere()
+            .with_transform(Matrix4::translate(4.0, 1.0, 0.0))
+            .with_material(Material::null().with_colour(Colour::new(0.7, 0.6, 0.5)).with_reflectivity(0.9).with_specular(0.9))
+    ];
+    let world = World::default()
+        .with_light(PointLight::new(Colour::white(), point(-10.0, 20.0, -10.0)))
+        .with_object(Object::new_plane().with_material(Material::default().with_colour(Colour::grey(0.5)).with_specular(0.0)))
+        .with_objects(big)
+        .with_objects(small);
+    let camera = Camera::new(320, 180, PI / 9.0)
+        .with_transform(Matrix4::view_transform(point(13.0, 2.0, -3.0), point(0.0, 0.0, 0.0), vector(0.0, 1.0, 0.0)));
+
+    Scene { camera, world }
+}
+
+/// Grid of n by n orbs for comparing materials side by side. Reflectivity
+/// goes up from 0.0 to 1.0 from left to right, and shininess from 10 to 300
+/// from front to back.
+pub fn material_grid(n: usize) -> Scene {
+    let steps = (n.max(2) - 1) as f32;
+    let centre = (n as Float - 1.0) / 2.0;
+    let orbs = (0..n * n).map(|k| {
+        let (i, j) = (k % n, k / n);
+        let material = Material::default()
+            .with_colour(Colour::new(0.8, 0.3, 0.2))
+            .with_specular(0.6)
+            .with_reflectivity(i as f32 / steps)
+            .with_smoothness(10.0 + 290.0 * j as f32 / steps);
+        Object::new_sphere()
+            .with_transform(Matrix4::translate(i as Float - centre, 0.4, j as Float - centre) * Matrix4::uscale(0.4))
+            .with_material(material)
+    });
+    let checkers = Pattern::new_checkers(Colour::white(), Colour::grey(0.3));
+    let world = World::default()
+        .with_light(PointLight::new(Colour::white(), point(-5.0, 10.0, -5.0)))
+        .with_object(Object::new_plane().with_material(Material::default().with_pattern(checkers).with_specular(0.0)))
+        .with_objects(orbs);
+    let distance = n as Float + 1.0;
+    let camera = Camera::new(320, 240, PI / 3.0)
+        .with_transform(Matrix4::view_transform(point(0.0, distance, -distance), point(0.0, 0.0, 0.0), vector(0.0, 1.0, 0.0)));
+
+    Scene { camera, world }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sphere_flakes_grow_five_children_per_sphere() {
+        // The floor, then 1 + 6 + 6 * 5 spheres.
+        assert_eq!(sphere_flake(0).world.objects.len(), 2);
+        assert_eq!(sphere_flake(2).world.objects.len(), 38);
+    }
+
+    #[test]
+    fn random_spheres_come_from_the_seed() {
+        let (a, b) = (random_spheres(1).world, random_spheres(1).world);
+
+        assert_eq!(a, b);
+        assert_ne!(a, random_spheres(2).world);
+        // The floor, the three big spheres, and most of the 22 by 22 small ones.
+        assert!(a.objects.len() > 400 && a.objects.len() <= 4 + 22 * 22);
+    }
+
+    #[test]
+    fn material_grids_cover_the_range_of_each_setting() {
+        let w = material_grid(3).world;
+        let orbs = &w.objects[1..];
+
+        assert_eq!(orbs.len(), 9);
+        assert_eq!((orbs[0].material.reflectivity, orbs[2].material.reflectivity), (0.0, 1.0));
+        assert_eq!((orbs[0].material.smoothness, orbs[8].material.smoothness), (10.0, 300.0));
+    }
+
+    #[test]
+    fn every_scene_has_a_light_and_something_to_see() {
+        for (name, scene) in scenes() {
+            let centre = scene.camera.ray_for_pixel(scene.camera.hsize / 2, scene.camera.vsize / 2);
+
+            assert!(!scene.world.lights.is_empty(), "{}", name);
+            assert!(scene.world.intersect(&centre).hit().is_some(), "{}", name);
+        }
+    }
+}